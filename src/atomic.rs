@@ -0,0 +1,193 @@
+use std::io::prelude::*;
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    document::Document,
+    error::{open_error, XRVErr},
+    writer::WriterOptions,
+};
+
+/// A file written under a temporary name next to its destination and only
+/// moved into place by `commit`. Dropping it uncommitted deletes the
+/// temporary file and leaves the destination untouched.
+///
+/// ```
+/// use xrave::{AtomicFile, Writer};
+///
+/// let path = std::env::temp_dir().join("xrave-doc-atomic.xrv");
+/// let mut writer = Writer::new(AtomicFile::create(&path)?);
+/// writer.add_table("users", &[("name", "str")])?;
+/// writer.finish()?.commit()?;
+/// assert!(std::fs::read_to_string(&path)?.starts_with("v:xrave 3\nj:jumps users:"));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct AtomicFile {
+    file: Option<File>,
+    tmp: PathBuf,
+    path: PathBuf,
+}
+
+impl AtomicFile {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<AtomicFile, XRVErr> {
+        let path = path.as_ref().to_path_buf();
+        let mut tmp: OsString = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        match File::create(&tmp) {
+            Err(err) => Err(open_error(&tmp, err)),
+            Ok(file) => Ok(AtomicFile {
+                file: Some(file),
+                tmp,
+                path,
+            }),
+        }
+    }
+
+    /// Flushes the temporary file to disk and renames it over the
+    /// destination. `fs::rename` replaces an existing destination on both
+    /// Unix and Windows.
+    pub fn commit(mut self) -> Result<(), XRVErr> {
+        let file = match self.file.take() {
+            None => return Ok(()),
+            Some(file) => file,
+        };
+        if let Err(err) = file.sync_all() {
+            return Err(XRVErr::FailToWrite(err));
+        }
+        drop(file);
+        if let Err(err) = fs::rename(&self.tmp, &self.path) {
+            return Err(XRVErr::FailToWrite(err));
+        }
+        #[cfg(unix)]
+        if let Some(dir) = self.path.parent().and_then(|dir| File::open(dir).ok()) {
+            let _ = dir.sync_all();
+        }
+        Ok(())
+    }
+
+    fn file(&mut self) -> std::io::Result<&mut File> {
+        match self.file.as_mut() {
+            None => Err(std::io::Error::other("atomic file already committed")),
+            Some(file) => Ok(file),
+        }
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.file()?.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file()?.flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(&self.tmp);
+        }
+    }
+}
+
+/// A document opened for editing; changes made through it reach the file in
+/// one atomic rewrite on `commit`, or not at all if it is dropped.
+///
+/// ```
+/// use xrave::{ColKind, Document, Schema};
+///
+/// let path = std::env::temp_dir().join("xrave-doc-guard.xrv");
+/// Schema::new().table("users", |t| t.col("name", ColKind::String)).build()?.save(&path)?;
+///
+/// let mut guard = Document::edit(&path)?;
+/// guard.add_record("users", "1", &[("name", "Anna")])?;
+/// guard.add_record("users", "2", &[("name", "Bob")])?;
+/// guard.commit()?;
+/// assert_eq!(Document::open(&path)?.tables[0].len(), 2);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct WriteGuard {
+    path: PathBuf,
+    document: Document,
+}
+
+impl WriteGuard {
+    pub fn commit(self) -> Result<(), XRVErr> {
+        self.document.save_atomic(&self.path)
+    }
+}
+
+impl Deref for WriteGuard {
+    type Target = Document;
+
+    fn deref(&self) -> &Document {
+        &self.document
+    }
+}
+
+impl DerefMut for WriteGuard {
+    fn deref_mut(&mut self) -> &mut Document {
+        &mut self.document
+    }
+}
+
+impl Document {
+    /// Loads the file at `path` for a batch of changes saved by
+    /// `WriteGuard::commit`.
+    pub fn edit<P: AsRef<Path>>(path: P) -> Result<WriteGuard, XRVErr> {
+        let path = path.as_ref().to_path_buf();
+        let document = Document::open(&path)?;
+        Ok(WriteGuard { path, document })
+    }
+
+    /// Saves through an `AtomicFile`, so `path` holds either the old
+    /// contents or the complete new ones, never a partial write.
+    pub fn save_atomic<P: AsRef<Path>>(&self, path: P) -> Result<(), XRVErr> {
+        let options = WriterOptions {
+            checksums: self.checksums,
+            ..WriterOptions::default()
+        };
+        self.write(AtomicFile::create(path)?, options)?.commit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn failed_writes_leave_the_destination_alone() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{AtomicFile, Writer};
+        use std::io::Write;
+
+        struct Failing<W>(W);
+        impl<W: Write> Write for Failing<W> {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("disk full"))
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.0.flush()
+            }
+        }
+
+        let path = std::env::temp_dir().join("xrave-test-atomic.xrv");
+        std::fs::write(&path, "j:jumps\n")?;
+
+        let mut writer = Writer::new(Failing(AtomicFile::create(&path)?));
+        writer.add_table("users", &[("name", "str")])?;
+        assert!(writer.finish().is_err());
+        assert_eq!(std::fs::read_to_string(&path)?, "j:jumps\n");
+
+        let mut writer = Writer::new(AtomicFile::create(&path)?);
+        writer.add_table("users", &[("name", "str")])?;
+        writer.finish()?.commit()?;
+        assert!(std::fs::read_to_string(&path)?.starts_with("v:xrave 3\nj:jumps users:"));
+        Ok(())
+    }
+}
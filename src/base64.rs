@@ -0,0 +1,53 @@
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const PAD: u8 = b'=';
+
+/// Encodes `bytes` as standard base64 with `=` padding, the on-disk form of
+/// `bytes` columns.
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | (*byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(group >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push(PAD as char);
+            }
+        }
+    }
+    out
+}
+
+/// Decodes standard base64 with `=` padding. Fails with the byte offset of
+/// the first character that is outside the alphabet, misplaced padding, or
+/// the end of a truncated value.
+pub(crate) fn decode(value: &str) -> Result<Vec<u8>, usize> {
+    let bytes = value.as_bytes();
+    if !bytes.len().is_multiple_of(4) {
+        return Err(bytes.len());
+    }
+    let mut out = Vec::with_capacity(bytes.len() / 4 * 3);
+    for (start, chunk) in (0..).step_by(4).zip(bytes.chunks(4)) {
+        let last = start + 4 == bytes.len();
+        let pad = match chunk {
+            [.., PAD, PAD] if last => 2,
+            [.., PAD] if last => 1,
+            _ => 0,
+        };
+        let mut group = 0u32;
+        for (i, byte) in chunk[..4 - pad].iter().enumerate() {
+            let digit = match ALPHABET.iter().position(|c| c == byte) {
+                None => return Err(start + i),
+                Some(digit) => digit as u32,
+            };
+            group |= digit << (18 - 6 * i);
+        }
+        if group & (0xFFFF >> (16 - 8 * pad)) != 0 {
+            return Err(start + 3 - pad);
+        }
+        out.extend_from_slice(&group.to_be_bytes()[1..4 - pad]);
+    }
+    Ok(out)
+}
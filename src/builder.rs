@@ -0,0 +1,273 @@
+use std::io::Write;
+use std::time::Duration;
+
+use crate::{
+    base64,
+    error::XRVErr,
+    schema::{validate_identifier, validate_table_name},
+    tokenizer::{Dialect, RECORD_ID, STYLE_ID, TABLE_ID},
+    units::{format_duration, format_size},
+    writer::{write_line, Writer},
+};
+
+/// Builds one record line, quoting values that hold spaces or colons.
+/// Field names and values are checked on `build` and `write_to`: a name
+/// that is not an identifier fails with `XRVErr::InvalidIdentifier`, a
+/// value with a control byte other than a newline, carriage return or tab,
+/// which are escaped, with `XRVErr::InvalidValueByte`.
+///
+/// ```
+/// use xrave::RecordBuilder;
+///
+/// let line = RecordBuilder::new("7")
+///     .field("name", "Anna Smith")
+///     .field_i32("age", 33)
+///     .build()?;
+/// assert_eq!(line, "r:7 name:\"Anna Smith\" age:33\n");
+/// # Ok::<(), xrave::XRVErr>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordBuilder {
+    id: String,
+    fields: Vec<(String, String)>,
+}
+
+impl RecordBuilder {
+    pub fn new(id: &str) -> Self {
+        RecordBuilder {
+            id: id.to_owned(),
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn field(mut self, name: &str, value: &str) -> Self {
+        self.fields.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    pub fn field_i32(self, name: &str, value: i32) -> Self {
+        self.field(name, &value.to_string())
+    }
+
+    /// Adds `value` as base64, for a `bytes` column.
+    pub fn field_bytes(self, name: &str, value: &[u8]) -> Self {
+        self.field(name, &base64::encode(value))
+    }
+
+    /// Adds `bytes` in the largest unit that divides it, such as `64MiB`,
+    /// for a `size` column.
+    pub fn field_size(self, name: &str, bytes: u64) -> Self {
+        self.field(name, &format_size(bytes))
+    }
+
+    /// Adds `duration` in the largest unit that divides it, such as `90m`,
+    /// for a `duration` column.
+    pub fn field_duration(self, name: &str, duration: Duration) -> Self {
+        self.field(name, &format_duration(duration))
+    }
+
+    /// Adds one `name` field per value, for a list column.
+    pub fn field_list(self, name: &str, values: &[&str]) -> Self {
+        values
+            .iter()
+            .fold(self, |builder, value| builder.field(name, value))
+    }
+
+    /// Renders the line, ending in `\n`.
+    pub fn build(&self) -> Result<String, XRVErr> {
+        build_line(RECORD_ID, &self.id, &self.fields)
+    }
+
+    /// Adds the record to `table` of `writer`.
+    pub fn write_to<W: Write>(&self, writer: &mut Writer<W>, table: &str) -> Result<(), XRVErr> {
+        check_line(&self.id, &self.fields)?;
+        writer.add_record(table, &self.id, &pairs(&self.fields))
+    }
+}
+
+/// Builds one table header line from column declarations, with the same
+/// checks as `RecordBuilder`. `pos` and `len` are 0 unless set with
+/// `region`; `write_to` leaves them to the writer.
+///
+/// ```
+/// use xrave::{RawLineIter, TableBuilder};
+///
+/// let line = TableBuilder::new("users")
+///     .col("name", "str")
+///     .col("status", "str=in review")
+///     .region(40, 12)
+///     .build()?;
+/// assert_eq!(line, "t:users name:users pos:40 len:12 name:str status:\"str=in review\"\n");
+/// let raw = RawLineIter::new(line.as_bytes()).next().unwrap()?;
+/// let fields: Vec<_> = raw.fields.collect();
+/// assert_eq!(fields[4], (&b"status"[..], &b"str=in review"[..]));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableBuilder {
+    name: String,
+    pos: usize,
+    len: usize,
+    cols: Vec<(String, String)>,
+}
+
+impl TableBuilder {
+    pub fn new(name: &str) -> Self {
+        TableBuilder {
+            name: name.to_owned(),
+            ..TableBuilder::default()
+        }
+    }
+
+    pub fn col(mut self, name: &str, kind: &str) -> Self {
+        self.cols.push((name.to_owned(), kind.to_owned()));
+        self
+    }
+
+    pub fn region(mut self, pos: usize, len: usize) -> Self {
+        self.pos = pos;
+        self.len = len;
+        self
+    }
+
+    /// Renders the line, ending in `\n`. The table name doubles as the
+    /// line id.
+    pub fn build(&self) -> Result<String, XRVErr> {
+        validate_table_name(&self.name)?;
+        let mut fields = vec![
+            ("name".to_owned(), self.name.clone()),
+            ("pos".to_owned(), self.pos.to_string()),
+            ("len".to_owned(), self.len.to_string()),
+        ];
+        fields.extend(self.cols.iter().cloned());
+        build_line(TABLE_ID, &self.name, &fields)
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut Writer<W>) -> Result<(), XRVErr> {
+        validate_table_name(&self.name)?;
+        check_line(&self.name, &self.cols)?;
+        writer.add_table(&self.name, &pairs(&self.cols))
+    }
+}
+
+/// Builds one style line, with the same checks as `RecordBuilder`.
+///
+/// ```
+/// use xrave::{RawLineIter, StyleBuilder};
+///
+/// let line = StyleBuilder::new("bold").field("font", "Open Sans").build()?;
+/// assert_eq!(line, "s:bold font:\"Open Sans\"\n");
+/// let raw = RawLineIter::new(line.as_bytes()).next().unwrap()?;
+/// assert_eq!(raw.name, b"bold");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StyleBuilder {
+    id: String,
+    fields: Vec<(String, String)>,
+}
+
+impl StyleBuilder {
+    pub fn new(id: &str) -> Self {
+        StyleBuilder {
+            id: id.to_owned(),
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn field(mut self, name: &str, value: &str) -> Self {
+        self.fields.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    pub fn build(&self) -> Result<String, XRVErr> {
+        build_line(STYLE_ID, &self.id, &self.fields)
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut Writer<W>) -> Result<(), XRVErr> {
+        check_line(&self.id, &self.fields)?;
+        writer.add_style(&self.id, &pairs(&self.fields))
+    }
+}
+
+fn build_line(kind: u8, id: &str, fields: &[(String, String)]) -> Result<String, XRVErr> {
+    check_line(id, fields)?;
+    let mut out = Vec::new();
+    write_line(&mut out, kind, id, &pairs(fields), Dialect::default());
+    Ok(String::from_utf8_lossy(&out).into_owned())
+}
+
+/// Checks the line id as the value of `id` and every field.
+fn check_line(id: &str, fields: &[(String, String)]) -> Result<(), XRVErr> {
+    check_value("id", id)?;
+    for (name, value) in fields {
+        validate_identifier(name)?;
+        check_value(name, value)?;
+    }
+    Ok(())
+}
+
+fn check_value(field: &str, value: &str) -> Result<(), XRVErr> {
+    let escaped = |byte: u8| matches!(byte, b'\n' | b'\r' | b'\t');
+    if let Some(byte) = value
+        .bytes()
+        .find(|byte| byte.is_ascii_control() && !escaped(*byte))
+    {
+        return Err(XRVErr::InvalidValueByte {
+            byte,
+            field: field.to_owned(),
+        });
+    }
+    Ok(())
+}
+
+fn pairs(fields: &[(String, String)]) -> Vec<(&str, &str)> {
+    fields
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn builder_quotes_and_checks_fields() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{RawLineIter, RecordBuilder, RecordBytes, XRVErr};
+
+        let line = RecordBuilder::new("7")
+            .field("name", "Anna Smith")
+            .field_i32("age", 33)
+            .field("time", "12:30")
+            .field("note", "")
+            .build()?;
+        assert_eq!(
+            line,
+            "r:7 name:\"Anna Smith\" age:33 time:\"12:30\" note:\"\"\n"
+        );
+
+        let raw = RawLineIter::new(line.as_bytes()).next().unwrap()?;
+        let record = RecordBytes::try_from(raw)?;
+        assert_eq!(record.id(), "7");
+        assert_eq!(record.get("name"), Some(&b"Anna Smith"[..]));
+        assert_eq!(record.get("time"), Some(&b"12:30"[..]));
+
+        let line = RecordBuilder::new("9")
+            .field_list("tag", &["a", "b"])
+            .build()?;
+        assert_eq!(line, "r:9 tag:a tag:b\n");
+
+        assert!(matches!(
+            RecordBuilder::new("8").field("name", "a\x07b").build(),
+            Err(XRVErr::InvalidValueByte { byte: 0x07, .. })
+        ));
+        let line = RecordBuilder::new("8")
+            .field("first name", "Anna")
+            .build()?;
+        assert_eq!(line, "r:8 \"first name\":Anna\n");
+        assert!(matches!(
+            RecordBuilder::new("8").field("first\"name", "Anna").build(),
+            Err(XRVErr::InvalidIdentifier(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use crate::line::OwnedRecordLine;
+
+/// Records read again by the offset of their span, the least recently used
+/// dropped first once `capacity` are held. A capacity of 0 holds nothing.
+#[derive(Debug, Default)]
+pub(crate) struct RecordCache {
+    capacity: usize,
+    /// Record at each line offset and the tick it was last used at.
+    entries: HashMap<u64, (Arc<OwnedRecordLine>, u64)>,
+    /// Line offset by the tick it was last used at, oldest first.
+    recency: BTreeMap<u64, u64>,
+    tick: u64,
+}
+
+impl RecordCache {
+    pub(crate) fn new(capacity: usize) -> RecordCache {
+        RecordCache {
+            capacity,
+            ..RecordCache::default()
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    pub(crate) fn get(&mut self, offset: u64) -> Option<Arc<OwnedRecordLine>> {
+        let (record, used) = self.entries.get_mut(&offset)?;
+        self.recency.remove(used);
+        *used = self.tick;
+        self.recency.insert(self.tick, offset);
+        self.tick += 1;
+        Some(Arc::clone(record))
+    }
+
+    pub(crate) fn insert(&mut self, offset: u64, record: Arc<OwnedRecordLine>) {
+        if !self.is_enabled() {
+            return;
+        }
+        if let Some((_, used)) = self.entries.remove(&offset) {
+            self.recency.remove(&used);
+        }
+        while self.entries.len() >= self.capacity {
+            match self.recency.pop_first() {
+                None => break,
+                Some((_, oldest)) => {
+                    self.entries.remove(&oldest);
+                }
+            }
+        }
+        self.entries.insert(offset, (record, self.tick));
+        self.recency.insert(self.tick, offset);
+        self.tick += 1;
+    }
+
+    /// Drops every record, for when the file changed under them.
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+}
@@ -0,0 +1,303 @@
+use std::io::prelude::*;
+use std::{fs::File, io::SeekFrom, path::Path};
+
+use crate::{
+    error::{open_error, XRVErr},
+    reader::{seek_error, ParseOptions, Reader},
+    sha256::Sha256,
+};
+
+/// Opening bytes of a serialized checkpoint, the last one its layout
+/// version.
+const MAGIC: &[u8; 8] = b"xrvckpt\x01";
+/// Bytes at the start of the file hashed to tell whether it changed.
+const HEAD_LEN: u64 = 4096;
+
+/// Where a reader stood in its file, taken by `Reader::checkpoint` and
+/// resumed from by `Reader::restore`, so that a job stopped halfway does
+/// not have to read the file again from the start.
+///
+/// ```
+/// use xrave::{Checkpoint, Reader, Writer};
+///
+/// let path = std::env::temp_dir().join("xrave-doc-checkpoint.xrv");
+/// let mut writer = Writer::new(std::fs::File::create(&path)?);
+/// writer.add_table("users", &[("name", "str")])?;
+/// for id in 0..10 {
+///     writer.add_record("users", &id.to_string(), &[("name", "Anna")])?;
+/// }
+/// writer.finish()?;
+///
+/// let mut reader = Reader::new(&path)?;
+/// let mut records = Vec::new();
+/// reader.read_batch("users", &mut records, 4)?;
+/// let blob = reader.checkpoint()?.to_bytes();
+///
+/// let mut reader = Reader::restore(&path, &Checkpoint::from_bytes(&blob)?)?;
+/// reader.read_batch("users", &mut records, usize::MAX)?;
+/// assert_eq!(records.len(), 10);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    offset: u64,
+    line: usize,
+    /// Table `Reader::read_batch` was reading and the end of its region.
+    batch: Option<(String, u64)>,
+    diagnostics: usize,
+    file_len: u64,
+    head_hash: [u8; 32],
+}
+
+impl Checkpoint {
+    /// Offset of the next line the reader reads.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Number of the last physical line the reader read.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Table whose records consecutive `Reader::read_batch` calls were
+    /// reading, which the restored reader goes on with.
+    pub fn table(&self) -> Option<&str> {
+        self.batch.as_ref().map(|(table, _)| table.as_str())
+    }
+
+    /// Number of diagnostics the reader had kept. A restored reader starts
+    /// without any, so this is the count to add to its own.
+    pub fn diagnostics(&self) -> usize {
+        self.diagnostics
+    }
+
+    /// Serializes the checkpoint to a few dozen bytes, read back with
+    /// `Checkpoint::from_bytes`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(96);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&self.offset.to_le_bytes());
+        out.extend_from_slice(&(self.line as u64).to_le_bytes());
+        out.extend_from_slice(&(self.diagnostics as u64).to_le_bytes());
+        out.extend_from_slice(&self.file_len.to_le_bytes());
+        out.extend_from_slice(&self.head_hash);
+        if let Some((table, end)) = &self.batch {
+            out.extend_from_slice(&end.to_le_bytes());
+            out.extend_from_slice(&(table.len() as u64).to_le_bytes());
+            out.extend_from_slice(table.as_bytes());
+        }
+        out
+    }
+
+    /// Reads a checkpoint written by `Checkpoint::to_bytes`, failing with
+    /// `XRVErr::CantParseCheckpoint` on anything else.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Checkpoint, XRVErr> {
+        let mut rest = match bytes.strip_prefix(MAGIC) {
+            None => return Err(XRVErr::CantParseCheckpoint),
+            Some(rest) => rest,
+        };
+        let offset = take_u64(&mut rest)?;
+        let line = take_u64(&mut rest)? as usize;
+        let diagnostics = take_u64(&mut rest)? as usize;
+        let file_len = take_u64(&mut rest)?;
+        let head_hash = match rest.split_first_chunk::<32>() {
+            None => return Err(XRVErr::CantParseCheckpoint),
+            Some((hash, tail)) => {
+                rest = tail;
+                *hash
+            }
+        };
+        let batch = match rest.is_empty() {
+            true => None,
+            false => {
+                let end = take_u64(&mut rest)?;
+                let len = take_u64(&mut rest)?;
+                match std::str::from_utf8(rest) {
+                    Ok(table) if table.len() as u64 == len => Some((table.to_owned(), end)),
+                    _ => return Err(XRVErr::CantParseCheckpoint),
+                }
+            }
+        };
+        Ok(Checkpoint {
+            offset,
+            line,
+            batch,
+            diagnostics,
+            file_len,
+            head_hash,
+        })
+    }
+}
+
+impl Reader {
+    /// Takes a checkpoint at the reader's current position, together with
+    /// the file's length and a hash of its first 4 KiB to tell later
+    /// whether it changed. The file is opened again for the hash, so a
+    /// reader made with `Reader::from_file` fails with
+    /// `XRVErr::PathUnknown`. Taken while `ParseMode::Lenient` skips
+    /// malformed lines, the skip is reported again after restoring.
+    pub fn checkpoint(&self) -> Result<Checkpoint, XRVErr> {
+        let (file_len, head_hash) = fingerprint(self.reopen_path()?)?;
+        Ok(Checkpoint {
+            offset: self.buffer.offset,
+            line: self.buffer.line + self.buffer.continued.len(),
+            batch: self.batch.clone(),
+            diagnostics: self.diagnostics().len(),
+            file_len,
+            head_hash,
+        })
+    }
+
+    /// Opens `path` with default options and resumes reading at
+    /// `checkpoint`.
+    pub fn restore<P: AsRef<Path>>(path: P, checkpoint: &Checkpoint) -> Result<Reader, XRVErr> {
+        Reader::restore_with_options(path, ParseOptions::default(), checkpoint)
+    }
+
+    /// Opens `path` and resumes reading at `checkpoint`: table and style
+    /// lines before it are read again, and the reader moves on to where the
+    /// checkpoint was taken, keeping its line numbers and any `read_batch`
+    /// in progress. A file whose length or first 4 KiB differ from when the
+    /// checkpoint was taken fails with `XRVErr::CheckpointStale`.
+    pub fn restore_with_options<P: AsRef<Path>>(
+        path: P,
+        options: ParseOptions,
+        checkpoint: &Checkpoint,
+    ) -> Result<Reader, XRVErr> {
+        let path = path.as_ref();
+        if fingerprint(path)? != (checkpoint.file_len, checkpoint.head_hash) {
+            return Err(XRVErr::CheckpointStale {
+                path: path.to_path_buf(),
+            });
+        }
+        let mut reader = Reader::with_options(path, options)?;
+        let first_region = reader
+            .jumps
+            .values()
+            .map(|(seek, _)| *seek as u64)
+            .min()
+            .unwrap_or(u64::MAX)
+            .min(checkpoint.offset);
+        while reader.buffer.offset < first_region {
+            if reader.parse_next()?.is_none() {
+                break;
+            }
+        }
+        if reader.buffer.offset != checkpoint.offset {
+            if let Err(err) = reader.source.seek(SeekFrom::Start(checkpoint.offset)) {
+                return Err(seek_error(err));
+            }
+        }
+        reader.buffer.offset = checkpoint.offset;
+        reader.buffer.line = checkpoint.line;
+        reader.buffer.continued.clear();
+        reader.batch = checkpoint.batch.clone();
+        Ok(reader)
+    }
+}
+
+/// Length of the file at `path` and the SHA-256 of its first `HEAD_LEN`
+/// bytes.
+fn fingerprint(path: &Path) -> Result<(u64, [u8; 32]), XRVErr> {
+    let file = match File::open(path) {
+        Err(err) => return Err(open_error(path, err)),
+        Ok(file) => file,
+    };
+    let len = match file.metadata() {
+        Err(err) => return Err(XRVErr::FailToReadLine(err)),
+        Ok(metadata) => metadata.len(),
+    };
+    let mut head = Vec::with_capacity(HEAD_LEN as usize);
+    if let Err(err) = file.take(HEAD_LEN).read_to_end(&mut head) {
+        return Err(XRVErr::FailToReadLine(err));
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(&head);
+    Ok((len, hasher.finish()))
+}
+
+fn take_u64(bytes: &mut &[u8]) -> Result<u64, XRVErr> {
+    match bytes.split_first_chunk::<8>() {
+        None => Err(XRVErr::CantParseCheckpoint),
+        Some((value, rest)) => {
+            *bytes = rest;
+            Ok(u64::from_le_bytes(*value))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn checkpoints_resume_batches_and_lines() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Checkpoint, ParseMode, ParseOptions, Reader, Writer, XRVErr};
+
+        let path = std::env::temp_dir().join("xrave-test-checkpoint.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("users", &[("name", "str")])?;
+        writer.add_table("events", &[("kind", "str")])?;
+        for id in 0..10 {
+            writer.add_record("users", &id.to_string(), &[("name", "Anna")])?;
+            writer.add_record("events", &id.to_string(), &[("kind", "login")])?;
+        }
+        writer.finish()?;
+        let ids = |records: &[crate::OwnedRecordLine]| -> Vec<String> {
+            records.iter().map(|record| record.id.clone()).collect()
+        };
+
+        let mut full = Vec::new();
+        Reader::new(&path)?.read_batch("users", &mut full, usize::MAX)?;
+
+        let mut first = Reader::new(&path)?;
+        let mut records = Vec::new();
+        first.read_batch("users", &mut records, 4)?;
+        let blob = first.checkpoint()?.to_bytes();
+        drop(first);
+
+        let checkpoint = Checkpoint::from_bytes(&blob)?;
+        assert_eq!(checkpoint.table(), Some("users"));
+        let mut second = Reader::restore(&path, &checkpoint)?;
+        second.read_batch("users", &mut records, usize::MAX)?;
+        assert_eq!(ids(&records), ids(&full));
+        assert_eq!(records, full);
+
+        let mut reader = Reader::new(&path)?;
+        let mut sequential = Vec::new();
+        for _ in 0..12 {
+            sequential.push(reader.next_record()?.unwrap().to_owned());
+        }
+        let checkpoint = reader.checkpoint()?;
+        let mut reader = Reader::restore(&path, &checkpoint)?;
+        while let Some(record) = reader.next_record()? {
+            sequential.push(record.to_owned());
+        }
+        let expected: Vec<String> = (0..10).chain(0..10).map(|id| id.to_string()).collect();
+        assert_eq!(ids(&sequential), expected);
+
+        assert!(matches!(
+            Checkpoint::from_bytes(&blob[..20]),
+            Err(XRVErr::CantParseCheckpoint)
+        ));
+        let text = std::fs::read_to_string(&path)?;
+        std::fs::write(&path, text.replace("Anna", "Anne"))?;
+        assert!(matches!(
+            Reader::restore(&path, &checkpoint),
+            Err(XRVErr::CheckpointStale { .. })
+        ));
+
+        std::fs::write(&path, "j:jumps\nr:1 a:1\nbroken\nr:2 a:2\nr:3 a:3\n")?;
+        let lenient = ParseOptions {
+            mode: ParseMode::Lenient,
+            ..ParseOptions::default()
+        };
+        let mut reader = Reader::with_options(&path, lenient.clone())?;
+        reader.next_record()?;
+        reader.next_record()?;
+        let checkpoint = reader.checkpoint()?;
+        assert_eq!((checkpoint.line(), checkpoint.diagnostics()), (4, 1));
+        let mut reader = Reader::restore_with_options(&path, lenient, &checkpoint)?;
+        assert_eq!(reader.next_record()?.unwrap().id(), "3");
+        Ok(())
+    }
+}
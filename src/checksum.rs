@@ -0,0 +1,169 @@
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut crc = n as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[n] = crc;
+        n += 1;
+    }
+    table
+}
+
+/// Incremental CRC-32 (IEEE 802.3), the checksum used by `c:crc32` lines.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Crc32(u32);
+
+impl Crc32 {
+    pub(crate) fn new() -> Self {
+        Crc32(0xFFFF_FFFF)
+    }
+
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = CRC32_TABLE[((self.0 ^ *byte as u32) & 0xFF) as usize] ^ (self.0 >> 8);
+        }
+    }
+
+    pub(crate) fn finish(self) -> u32 {
+        self.0 ^ 0xFFFF_FFFF
+    }
+}
+
+pub(crate) const CHECKSUM_ALGORITHM: &str = "crc32";
+
+/// Name of the checksum section covering everything before the first table
+/// region: the jumps line, table headers and styles.
+pub(crate) const CHECKSUM_HEADER_SECTION: &str = "header";
+
+#[derive(Debug)]
+pub struct SectionChecksum {
+    pub name: String,
+    pub expected: u32,
+    pub actual: u32,
+}
+
+impl SectionChecksum {
+    pub fn passed(&self) -> bool {
+        self.expected == self.actual
+    }
+}
+
+#[derive(Debug)]
+pub enum ChecksumReport {
+    NotPresent,
+    Sections(Vec<SectionChecksum>),
+}
+
+impl ChecksumReport {
+    pub fn passed(&self) -> bool {
+        match self {
+            ChecksumReport::NotPresent => true,
+            ChecksumReport::Sections(sections) => sections.iter().all(SectionChecksum::passed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, path::PathBuf};
+
+    use super::*;
+    use crate::{
+        reader::Reader,
+        writer::{Writer, WriterOptions},
+    };
+
+    fn fixture(name: &str, checksums: bool) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("xrave-test-checksum-{}.xrv", name));
+        let options = WriterOptions {
+            checksums,
+            ..WriterOptions::default()
+        };
+        let mut writer = Writer::with_options(File::create(&path).unwrap(), options);
+        writer.add_table("users", &[("name", "str")]).unwrap();
+        writer.add_table("events", &[("kind", "str")]).unwrap();
+        writer.add_style("bold", &[("weight", "700")]).unwrap();
+        for id in 0..5 {
+            let id = id.to_string();
+            writer
+                .add_record("users", &id, &[("name", "Anna")])
+                .unwrap();
+            writer
+                .add_record("events", &id, &[("kind", "click")])
+                .unwrap();
+        }
+        writer.finish().unwrap();
+        path
+    }
+
+    fn failed_sections(path: &PathBuf) -> Vec<String> {
+        let mut reader = Reader::new(path).unwrap();
+        match reader.verify_checksums().unwrap() {
+            ChecksumReport::NotPresent => panic!("no checksum line in {:?}", path),
+            ChecksumReport::Sections(sections) => sections
+                .into_iter()
+                .filter(|section| !section.passed())
+                .map(|section| section.name)
+                .collect(),
+        }
+    }
+
+    fn flip_byte_of(path: &PathBuf, needle: &str, nth: usize) {
+        let mut bytes = std::fs::read(path).unwrap();
+        let text = String::from_utf8(bytes.clone()).unwrap();
+        let at = text.match_indices(needle).nth(nth).unwrap().0;
+        bytes[at] ^= 0x01;
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn crc32_matches_the_reference_value() {
+        let mut crc = Crc32::new();
+        crc.update(b"1234");
+        crc.update(b"56789");
+        assert_eq!(crc.finish(), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn untouched_file_passes() {
+        let path = fixture("untouched", true);
+        assert!(failed_sections(&path).is_empty());
+    }
+
+    #[test]
+    fn file_without_checksums_is_not_present() {
+        let path = fixture("absent", false);
+        let report = Reader::new(&path).unwrap().verify_checksums().unwrap();
+        assert!(matches!(report, ChecksumReport::NotPresent));
+        assert!(report.passed());
+    }
+
+    #[test]
+    fn flipped_record_byte_names_its_section() {
+        let path = fixture("flip-record", true);
+        flip_byte_of(&path, "Anna", 3);
+        assert_eq!(failed_sections(&path), ["users"]);
+
+        let path = fixture("flip-other-table", true);
+        flip_byte_of(&path, "click", 0);
+        assert_eq!(failed_sections(&path), ["events"]);
+    }
+
+    #[test]
+    fn flipped_header_byte_names_the_header_section() {
+        let path = fixture("flip-header", true);
+        flip_byte_of(&path, "weight", 0);
+        assert_eq!(failed_sections(&path), [CHECKSUM_HEADER_SECTION]);
+    }
+}
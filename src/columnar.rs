@@ -0,0 +1,336 @@
+use crate::{
+    dictionary::entry,
+    error::XRVErr,
+    line::LineKind,
+    reader::{DuplicateFields, Reader},
+    schema::{split_default, Col, ColKind},
+    tokenizer::{unescape, RawLine},
+};
+
+/// Values of one column of a `ColumnarBatch`, one per record, `None` where
+/// the record holds a null or leaves the column out and it has no default.
+/// Integer kinds are widened to `i64` and `f32` to `f64`; `bytes` stay
+/// base64 text, as does any column whose declaration is not a known kind.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnValues {
+    Int(Vec<Option<i64>>),
+    Float(Vec<Option<f64>>),
+    Bool(Vec<Option<bool>>),
+    Str(Vec<Option<String>>),
+}
+
+impl ColumnValues {
+    fn for_kind(kind: Option<ColKind>) -> ColumnValues {
+        match kind {
+            Some(ColKind::I32 | ColKind::I64 | ColKind::U32 | ColKind::U64) => {
+                ColumnValues::Int(Vec::new())
+            }
+            Some(ColKind::F32 | ColKind::F64) => ColumnValues::Float(Vec::new()),
+            Some(ColKind::Bool) => ColumnValues::Bool(Vec::new()),
+            Some(ColKind::String | ColKind::Bytes | ColKind::Size | ColKind::Duration) | None => {
+                ColumnValues::Str(Vec::new())
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            ColumnValues::Int(values) => values.len(),
+            ColumnValues::Float(values) => values.len(),
+            ColumnValues::Bool(values) => values.len(),
+            ColumnValues::Str(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the value of record `idx` is present.
+    pub fn is_valid(&self, idx: usize) -> bool {
+        match self {
+            ColumnValues::Int(values) => values.get(idx).is_some_and(Option::is_some),
+            ColumnValues::Float(values) => values.get(idx).is_some_and(Option::is_some),
+            ColumnValues::Bool(values) => values.get(idx).is_some_and(Option::is_some),
+            ColumnValues::Str(values) => values.get(idx).is_some_and(Option::is_some),
+        }
+    }
+
+    /// Validity mask of the column: `true` for every record holding a value.
+    pub fn validity(&self) -> Vec<bool> {
+        (0..self.len()).map(|idx| self.is_valid(idx)).collect()
+    }
+
+    pub fn null_count(&self) -> usize {
+        (0..self.len()).filter(|idx| !self.is_valid(*idx)).count()
+    }
+
+    /// Appends `value`, or a null for `None` and for an empty value of a
+    /// column that is not text. Fails with the name of the kind a value
+    /// does not parse as.
+    fn push(&mut self, value: Option<&str>) -> Result<(), &'static str> {
+        match self {
+            ColumnValues::Str(values) => values.push(value.map(str::to_owned)),
+            ColumnValues::Int(values) => match value.filter(|value| !value.is_empty()) {
+                None => values.push(None),
+                Some(value) => values.push(Some(value.parse().map_err(|_| "i64")?)),
+            },
+            ColumnValues::Float(values) => match value.filter(|value| !value.is_empty()) {
+                None => values.push(None),
+                Some(value) => values.push(Some(value.parse().map_err(|_| "f64")?)),
+            },
+            ColumnValues::Bool(values) => match value.filter(|value| !value.is_empty()) {
+                None => values.push(None),
+                Some("true") => values.push(Some(true)),
+                Some("false") => values.push(Some(false)),
+                Some(_) => return Err("bool"),
+            },
+        }
+        Ok(())
+    }
+}
+
+/// One column of a `ColumnarBatch`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchColumn {
+    pub name: String,
+    pub values: ColumnValues,
+}
+
+/// Columns of a table read out record by record, as returned by
+/// `Reader::read_columnar`. Every column holds `len` values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnarBatch {
+    pub len: usize,
+    pub columns: Vec<BatchColumn>,
+}
+
+impl ColumnarBatch {
+    pub fn column(&self, name: &str) -> Option<&ColumnValues> {
+        self.columns
+            .iter()
+            .find(|column| column.name == name)
+            .map(|column| &column.values)
+    }
+}
+
+impl Reader {
+    /// Reads the columns `cols` of every record of `table` in one pass over
+    /// its region, straight from the tokenized lines into one typed vector
+    /// per column, without building a record per line. A record that leaves
+    /// a column out gets its declared default, or a null. Columns not
+    /// declared in the table header fail with `XRVErr::UnknownColumn`, and
+    /// list columns with `XRVErr::ListColumn`, before any record is read; a
+    /// value that does not parse as its column kind fails with
+    /// `XRVErr::InvalidTypedValue` at its line.
+    ///
+    /// ```
+    /// use xrave::{ColumnValues, Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-columnar.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("readings", &[("station", "str"), ("temp", "f64?")])?;
+    /// writer.add_record("readings", "1", &[("station", "north"), ("temp", "-3.5")])?;
+    /// writer.add_record("readings", "2", &[("station", "south")])?;
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(&path)?;
+    /// let batch = reader.read_columnar("readings", &["temp"])?;
+    /// assert_eq!(batch.column("temp"), Some(&ColumnValues::Float(vec![Some(-3.5), None])));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn read_columnar(&mut self, table: &str, cols: &[&str]) -> Result<ColumnarBatch, XRVErr> {
+        self.load_table(table)?;
+        let header = match self.table(table) {
+            None => return Err(XRVErr::UnknownTable(table.to_owned())),
+            Some(header) => header,
+        };
+        let mut defaults: Vec<Option<String>> = Vec::with_capacity(cols.len());
+        let mut columns: Vec<BatchColumn> = Vec::with_capacity(cols.len());
+        for name in cols {
+            let decl = match header.cols.iter().find(|col| &*col.name == *name) {
+                None => {
+                    return Err(XRVErr::UnknownColumn {
+                        table: table.to_owned(),
+                        column: (*name).to_owned(),
+                    })
+                }
+                Some(col) => col.value.as_str(),
+            };
+            let col = Col::parse(decl).ok();
+            if col.as_ref().is_some_and(|col| col.list) {
+                return Err(XRVErr::ListColumn {
+                    table: table.to_owned(),
+                    column: (*name).to_owned(),
+                });
+            }
+            defaults.push(split_default(decl).1.map(str::to_owned));
+            columns.push(BatchColumn {
+                name: (*name).to_owned(),
+                values: ColumnValues::for_kind(col.map(|col| col.kind)),
+            });
+        }
+
+        self.seek_to(table)?;
+        let dictionary = self.dictionaries.get(table).cloned();
+        let end = match self.jumps.get(table) {
+            None => return Err(XRVErr::UnknownTable(table.to_owned())),
+            Some((seek, len)) => (*seek + *len) as u64,
+        };
+        let mut len = 0;
+        // The fields of the line, last first under `DuplicateFields::KeepLast`,
+        // so the value kept of a repeated name is always the first one met.
+        let mut links = Vec::new();
+        while self.buffer.offset < end {
+            match self.parse_next()? {
+                None => break,
+                // A lenient resync can carry past the end of the region.
+                Some(_) if self.buffer.start >= end => break,
+                Some(LineKind::Record) => {}
+                Some(_) => continue,
+            }
+            let raw_line: RawLine = (&self.buffer).try_into()?;
+            let line = raw_line.fields.buffer;
+            links.clear();
+            links.extend(raw_line.fields.links());
+            if self.options.duplicate_fields == DuplicateFields::KeepLast {
+                links.reverse();
+            }
+            for link in links.iter() {
+                let name = &line[link.name_start..link.name_end];
+                let idx = match cols.iter().position(|col| col.as_bytes() == name) {
+                    None => continue,
+                    Some(idx) => idx,
+                };
+                let values = &mut columns[idx].values;
+                if values.len() > len {
+                    continue;
+                }
+                if link.null {
+                    // A null parses as every kind.
+                    let _ = values.push(None);
+                    continue;
+                }
+                let value = match std::str::from_utf8(&line[link.value_start..link.value_end]) {
+                    Err(_) => {
+                        let err = XRVErr::CantParseFieldStrValue {
+                            line: self.buffer.line,
+                            col: link.value_start + 1,
+                        };
+                        return Err(self.buffer.diagnose(link.value_start + 1, err));
+                    }
+                    Ok(value) if link.escaped => unescape(value, self.options.dialect.quote),
+                    Ok(value) => value.into(),
+                };
+                let value = match dictionary.as_ref().map(|d| entry(d, table, &value)) {
+                    None | Some(Ok(None)) => value,
+                    Some(Ok(Some(full))) => full.to_owned().into(),
+                    Some(Err(err)) => return Err(self.buffer.diagnose(link.value_start + 1, err)),
+                };
+                if let Err(expected) = values.push(Some(&value)) {
+                    let err = XRVErr::InvalidTypedValue {
+                        table: table.to_owned(),
+                        column: cols[idx].to_owned(),
+                        value: value.into_owned(),
+                        expected,
+                    };
+                    return Err(self.buffer.diagnose(link.value_start + 1, err));
+                }
+            }
+            for (column, default) in columns.iter_mut().zip(defaults.iter()) {
+                if column.values.len() > len {
+                    continue;
+                }
+                if let Err(expected) = column.values.push(default.as_deref()) {
+                    return Err(XRVErr::InvalidTypedValue {
+                        table: table.to_owned(),
+                        column: column.name.clone(),
+                        value: default.clone().unwrap_or_default(),
+                        expected,
+                    });
+                }
+            }
+            len += 1;
+        }
+        Ok(ColumnarBatch { len, columns })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn columns_fill_defaults_and_nulls() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{ColumnValues, Reader, Writer, XRVErr};
+
+        let path = std::env::temp_dir().join("xrave-test-columnar.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        let cols = [
+            ("station", "str"),
+            ("temp", "f64?"),
+            ("count", "i32=0"),
+            ("ok", "bool?"),
+            ("tags", "[str]"),
+        ];
+        writer.add_table("readings", &cols)?;
+        writer.add_record(
+            "readings",
+            "1",
+            &[("station", "north"), ("temp", "-3.5"), ("count", "4")],
+        )?;
+        writer.add_record(
+            "readings",
+            "2",
+            &[("ok", "true"), ("station", "south \"b\"")],
+        )?;
+        writer.add_record(
+            "readings",
+            "3",
+            &[("station", "east"), ("temp", ""), ("count", "7")],
+        )?;
+        writer.finish()?;
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        let batch = reader.read_columnar("readings", &["temp", "station", "count", "ok"])?;
+        assert_eq!(batch.len, 3);
+        let names: Vec<_> = batch
+            .columns
+            .iter()
+            .map(|column| column.name.as_str())
+            .collect();
+        assert_eq!(names, ["temp", "station", "count", "ok"]);
+
+        let temp = batch.column("temp").unwrap();
+        assert_eq!(*temp, ColumnValues::Float(vec![Some(-3.5), None, None]));
+        assert_eq!(
+            (temp.validity(), temp.null_count()),
+            (vec![true, false, false], 2)
+        );
+        assert_eq!(
+            batch.column("station"),
+            Some(&ColumnValues::Str(vec![
+                Some("north".to_owned()),
+                Some("south \"b\"".to_owned()),
+                Some("east".to_owned()),
+            ]))
+        );
+        let count = batch.column("count");
+        assert_eq!(
+            count,
+            Some(&ColumnValues::Int(vec![Some(4), Some(0), Some(7)]))
+        );
+        assert_eq!(
+            batch.column("ok"),
+            Some(&ColumnValues::Bool(vec![None, Some(true), None]))
+        );
+
+        assert!(matches!(
+            reader.read_columnar("readings", &["station", "humidity"]),
+            Err(XRVErr::UnknownColumn { .. })
+        ));
+        assert!(matches!(
+            reader.read_columnar("readings", &["tags"]),
+            Err(XRVErr::ListColumn { .. })
+        ));
+        Ok(())
+    }
+}
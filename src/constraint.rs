@@ -0,0 +1,239 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    error::XRVErr,
+    line::{Span, RECORD_KEY_FIELD},
+    reader::Reader,
+    schema::{key_column, Col},
+};
+
+/// A primary key held by more than one record, with the span of each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyDuplicates {
+    pub key: String,
+    pub spans: Vec<Span>,
+}
+
+/// A record whose reference column holds a key missing from the table the
+/// column points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefError {
+    pub table: String,
+    /// Primary key of the referencing record.
+    pub record_key: String,
+    pub column: String,
+    pub missing_key: String,
+}
+
+impl Reader {
+    /// Scans `table` for primary keys used more than once, in order of
+    /// first use. The key is the column declared with `!` in the table
+    /// header, or the record id when the table declares none.
+    ///
+    /// ```
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-unique.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("email", "str!")])?;
+    /// writer.add_record("users", "1", &[("email", "a@x")])?;
+    /// writer.add_record("users", "2", &[("email", "a@x")])?;
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(&path)?;
+    /// assert_eq!(reader.check_unique("users")?[0].key, "a@x");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn check_unique(&mut self, table: &str) -> Result<Vec<KeyDuplicates>, XRVErr> {
+        self.load_table(table)?;
+        let col = self.primary_key_column(table);
+
+        let mut order: Vec<String> = Vec::new();
+        let mut spans: HashMap<String, Vec<Span>> = HashMap::new();
+        self.for_each_key(table, &col, |key, span| match spans.get_mut(&key) {
+            Some(seen) => seen.push(span),
+            None => {
+                order.push(key.clone());
+                spans.insert(key, vec![span]);
+            }
+        })?;
+
+        Ok(order
+            .into_iter()
+            .filter_map(|key| {
+                let spans = spans.remove(&key)?;
+                (spans.len() > 1).then_some(KeyDuplicates { key, spans })
+            })
+            .collect())
+    }
+
+    /// Checks every column declared with `->table` against the primary keys
+    /// of the table it points at. Only the key sets of referenced tables are
+    /// held in memory; referencing records are streamed. Empty values are
+    /// not checked. A reference to a table the file does not have fails with
+    /// `XRVErr::UnknownReferencedTable`.
+    ///
+    /// ```
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-references.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("name", "str")])?;
+    /// writer.add_table("orders", &[("user", "i32->users")])?;
+    /// writer.add_record("users", "42", &[("name", "Anna")])?;
+    /// writer.add_record("orders", "o1", &[("user", "7")])?;
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(&path)?;
+    /// let errors = reader.check_references()?;
+    /// assert_eq!((errors[0].record_key.as_str(), errors[0].missing_key.as_str()), ("o1", "7"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn check_references(&mut self) -> Result<Vec<RefError>, XRVErr> {
+        self.load_headers()?;
+        let mut tables: Vec<(usize, String)> = self
+            .jumps
+            .iter()
+            .map(|(name, (seek, _))| (*seek, name.clone()))
+            .collect();
+        tables.sort();
+
+        let mut checks: Vec<(String, Vec<(String, String)>)> = Vec::new();
+        for (_, table) in tables {
+            let header = match self.table(&table) {
+                None => continue,
+                Some(header) => header,
+            };
+            let refs: Vec<(String, String)> = header
+                .cols
+                .iter()
+                .filter_map(|col| {
+                    let target = Col::parse(&col.value).ok()?.references?;
+                    Some((col.name.to_string(), target))
+                })
+                .collect();
+            if !refs.is_empty() {
+                checks.push((table, refs));
+            }
+        }
+
+        let mut keys: HashMap<String, HashSet<String>> = HashMap::new();
+        for (table, refs) in checks.iter() {
+            for (column, target) in refs {
+                if keys.contains_key(target) {
+                    continue;
+                }
+                if !self.jumps.contains_key(target) {
+                    return Err(XRVErr::UnknownReferencedTable {
+                        table: table.clone(),
+                        column: column.clone(),
+                        target: target.clone(),
+                    });
+                }
+                let col = self.primary_key_column(target);
+                let mut set = HashSet::new();
+                self.for_each_key(target, &col, |key, _| {
+                    set.insert(key);
+                })?;
+                keys.insert(target.clone(), set);
+            }
+        }
+
+        let mut errors = Vec::new();
+        for (table, refs) in checks.iter() {
+            let key_col = self.primary_key_column(table);
+            for record in self.records(table)? {
+                let record = record?;
+                for (column, target) in refs {
+                    let value = match record.cols.iter().find(|field| *field.name == **column) {
+                        None => continue,
+                        Some(field) if field.value.is_empty() => continue,
+                        Some(field) => &field.value,
+                    };
+                    if !keys[target].contains(value) {
+                        errors.push(RefError {
+                            table: table.clone(),
+                            record_key: record.key(&key_col).unwrap_or(&record.id).to_owned(),
+                            column: column.clone(),
+                            missing_key: value.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(errors)
+    }
+
+    /// The column declared with `!` in the header of `table`, or `id` for
+    /// the record id.
+    fn primary_key_column(&self, table: &str) -> String {
+        self.table(table)
+            .and_then(|header| key_column(&header.cols))
+            .unwrap_or(RECORD_KEY_FIELD)
+            .to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn references_are_checked_against_keys() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Reader, RefError, Writer, XRVErr};
+
+        let path = std::env::temp_dir().join("xrave-test-references.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("users", &[("name", "str")])?;
+        writer.add_table("orders", &[("user", "i32->users"), ("total", "f64")])?;
+        writer.add_record("users", "42", &[("name", "Anna")])?;
+        writer.add_record("orders", "o1", &[("user", "42"), ("total", "9.5")])?;
+        writer.add_record("orders", "o2", &[("user", "7"), ("total", "3")])?;
+        writer.finish()?;
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        assert_eq!(
+            reader.check_references()?,
+            [RefError {
+                table: "orders".to_owned(),
+                record_key: "o2".to_owned(),
+                column: "user".to_owned(),
+                missing_key: "7".to_owned(),
+            }]
+        );
+
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("orders", &[("user", "i32->people")])?;
+        writer.add_record("orders", "o1", &[("user", "42")])?;
+        writer.finish()?;
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        assert!(matches!(
+            reader.check_references(),
+            Err(XRVErr::UnknownReferencedTable { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn check_unique_reports_duplicate_keys_in_order() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Reader, Writer};
+
+        let path = std::env::temp_dir().join("xrave-test-unique.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("users", &[("email", "str!"), ("name", "str")])?;
+        writer.add_table("tags", &[("label", "str")])?;
+        writer.add_record("users", "1", &[("email", "a@x"), ("name", "Anna")])?;
+        writer.add_record("users", "2", &[("email", "b@x"), ("name", "Bob")])?;
+        writer.add_record("users", "3", &[("email", "a@x"), ("name", "Ann")])?;
+        writer.add_record("users", "4", &[("email", "b@x"), ("name", "Bo")])?;
+        writer.add_record("tags", "t1", &[("label", "admin")])?;
+        writer.add_record("tags", "t2", &[("label", "admin")])?;
+        writer.finish()?;
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        assert!(reader.check_unique("tags")?.is_empty());
+        let duplicates = reader.check_unique("users")?;
+        let keys: Vec<_> = duplicates.iter().map(|dup| dup.key.as_str()).collect();
+        assert_eq!(keys, ["a@x", "b@x"]);
+        assert_eq!(reader.record_at(duplicates[0].spans[1])?.id, "3");
+        Ok(())
+    }
+}
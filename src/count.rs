@@ -0,0 +1,330 @@
+use std::io::prelude::*;
+use std::{
+    fs::File,
+    io::{BufReader, SeekFrom},
+};
+
+use crate::{
+    error::{open_error, XRVErr},
+    reader::{ParseOptions, Reader, Source},
+    tokenizer::{
+        XraveBuffer, CHECKSUM_ID, COLON_CHAR, CR_CHAR, DICTIONARY_ID, ESCAPE_CHAR, JUMP_ID,
+        NL_CHAR, RECORD_ID, STYLE_ID, TABLE_ID, TOMBSTONE_ID, VERSION_ID,
+    },
+    version::{parse_version_line, EXTENDED_SYNTAX_VERSION},
+};
+
+/// Bytes read at a time by `Reader::fast_count`.
+const FAST_COUNT_BLOCK_LEN: usize = 64 * 1024;
+
+/// Lines of a file by kind, as counted by `count_lines`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineCounts {
+    /// Version lines; only one on the first line is meaningful.
+    pub versions: u64,
+    pub jumps: u64,
+    pub tables: u64,
+    pub styles: u64,
+    pub records: u64,
+    /// Deleted records, whose kind byte was replaced by `x`.
+    pub tombstones: u64,
+    pub checksums: u64,
+    pub dictionaries: u64,
+    /// Lines whose first non-blank byte is `#`.
+    pub comments: u64,
+    /// Empty lines and lines holding only spaces and tabs.
+    pub blank: u64,
+    pub unknown: u64,
+}
+
+/// Counts the lines of `input` by kind from their first bytes only, without
+/// tokenizing fields, so it runs at close to read speed. A line is never
+/// rejected: anything that does not start with a known `kind:` is counted
+/// as a comment, a blank line or unknown.
+///
+/// ```
+/// use xrave::count_lines;
+///
+/// let input = "r:1 name:Anna\n# imported\nx:2 name:Bob\n";
+/// let counts = count_lines(input.as_bytes())?;
+/// assert_eq!((counts.records, counts.comments, counts.tombstones), (1, 1, 1));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn count_lines<R: Read>(input: R) -> Result<LineCounts, XRVErr> {
+    scan(
+        &mut BufReader::new(input),
+        ParseOptions::default().max_line_len,
+    )
+}
+
+impl Reader {
+    /// Counts the lines of the whole file by kind, like `count_lines`. The
+    /// file is read through a handle of its own, so this works on gzip input
+    /// and leaves the reader's position where it was.
+    ///
+    /// ```
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-count.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("name", "str")])?;
+    /// writer.add_style("bold", &[("weight", "700")])?;
+    /// for id in 0..3 {
+    ///     writer.add_record("users", &id.to_string(), &[("name", "Anna")])?;
+    /// }
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+    /// let first = reader.records("users")?.next().unwrap()?;
+    /// let counts = reader.count_lines()?;
+    /// assert_eq!((counts.versions, counts.tables, counts.styles, counts.records), (1, 1, 1, 3));
+    /// assert_eq!(reader.record_at(first.span)?.id, "0");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn count_lines(&mut self) -> Result<LineCounts, XRVErr> {
+        let path = self.reopen_path()?;
+        let file = match File::open(path) {
+            Err(err) => return Err(open_error(path, err)),
+            Ok(file) => file,
+        };
+        scan(
+            &mut BufReader::new(Source::open(file)?),
+            self.options.max_line_len,
+        )
+    }
+
+    /// Counts the record lines of `table`, or of the whole file for `None`,
+    /// from their first two bytes only: no line is tokenized and nothing is
+    /// allocated past one read buffer, so it runs at close to read speed.
+    /// Tombstones, comments and the following lines of a continued line are
+    /// not counted. Lines are not checked either, so a malformed `r:` line
+    /// counts where a full read would fail on it or, in
+    /// `ParseMode::Lenient`, skip it. Like `count_lines`, the file is read
+    /// through a handle of its own.
+    ///
+    /// ```
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-fast-count.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("name", "str")])?;
+    /// for id in 0..3 {
+    ///     writer.add_record("users", &id.to_string(), &[("name", "Anna")])?;
+    /// }
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(&path)?;
+    /// assert_eq!(reader.fast_count(Some("users"))?, 3);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn fast_count(&mut self, table: Option<&str>) -> Result<u64, XRVErr> {
+        let (seek, len) = match table {
+            None => (0, u64::MAX),
+            Some(name) => match self.jumps.get(name) {
+                None => return Err(XRVErr::UnknownTable(name.to_owned())),
+                Some((seek, len)) => (*seek as u64, *len as u64),
+            },
+        };
+        let path = self.reopen_path()?;
+        let file = match File::open(path) {
+            Err(err) => return Err(open_error(path, err)),
+            Ok(file) => file,
+        };
+        let mut source = Source::open(file)?;
+        match source.seek(SeekFrom::Start(seek)) {
+            Ok(_) => {}
+            Err(err) if err.kind() != std::io::ErrorKind::Unsupported => {
+                return Err(XRVErr::FailToSeek(err))
+            }
+            Err(_) => match std::io::copy(&mut (&mut source).take(seek), &mut std::io::sink()) {
+                Err(err) => return Err(XRVErr::FailToReadLine(err)),
+                Ok(skipped) if skipped < seek => return Err(XRVErr::UnexpectedEof),
+                Ok(_) => {}
+            },
+        }
+        count_records(source.take(len), self.version >= EXTENDED_SYNTAX_VERSION)
+    }
+}
+
+/// Counts the lines of `input` starting with `r:`, scanning it block by
+/// block. With `continuations` on, a line following one that ends in a
+/// backslash is part of it and not counted.
+fn count_records<R: Read>(mut input: R, continuations: bool) -> Result<u64, XRVErr> {
+    let mut block = [0u8; FAST_COUNT_BLOCK_LEN];
+    let mut records: u64 = 0;
+    // Bytes seen of the current physical line, up to 2.
+    let mut col: u8 = 0;
+    let mut first: u8 = 0;
+    let mut last: u8 = 0;
+    let mut after_cr = false;
+    let mut continued = false;
+    loop {
+        let read = match input.read(&mut block) {
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(XRVErr::FailToReadLine(err)),
+            Ok(0) => return Ok(records),
+            Ok(read) => read,
+        };
+        for byte in block[..read].iter().copied() {
+            match byte {
+                NL_CHAR if after_cr && col == 0 => after_cr = false,
+                NL_CHAR | CR_CHAR => {
+                    continued = continuations && col > 0 && last == ESCAPE_CHAR;
+                    col = 0;
+                    after_cr = byte == CR_CHAR;
+                }
+                _ => {
+                    match col {
+                        0 => first = byte,
+                        1 if first == RECORD_ID && byte == COLON_CHAR && !continued => records += 1,
+                        _ => {}
+                    }
+                    col = col.saturating_add(1).min(2);
+                    last = byte;
+                    after_cr = false;
+                }
+            }
+        }
+    }
+}
+
+fn scan<R: BufRead>(source: &mut R, max_line_len: usize) -> Result<LineCounts, XRVErr> {
+    let mut counts = LineCounts::default();
+    let mut buffer = XraveBuffer::new();
+    loop {
+        if buffer.read_line(source, max_line_len)? == 0 {
+            break;
+        }
+        if buffer.line == 1 {
+            buffer.strip_bom();
+            if let Some(Ok(version)) = parse_version_line(&buffer.buffer) {
+                buffer.set_version(version);
+            }
+        }
+        let count = match buffer.buffer.as_slice() {
+            [kind, COLON_CHAR, ..] => match *kind {
+                VERSION_ID => &mut counts.versions,
+                JUMP_ID => &mut counts.jumps,
+                TABLE_ID => &mut counts.tables,
+                STYLE_ID => &mut counts.styles,
+                RECORD_ID => &mut counts.records,
+                TOMBSTONE_ID => &mut counts.tombstones,
+                CHECKSUM_ID => &mut counts.checksums,
+                DICTIONARY_ID => &mut counts.dictionaries,
+                _ => &mut counts.unknown,
+            },
+            line => match line.iter().find(|byte| **byte != b' ' && **byte != b'\t') {
+                None => &mut counts.blank,
+                Some(b'#') => &mut counts.comments,
+                Some(_) => &mut counts.unknown,
+            },
+        };
+        *count += 1;
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Instant};
+
+    use super::*;
+    use crate::writer::Writer;
+
+    fn temp(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("xrave-test-count-{}.xrv", name))
+    }
+
+    #[test]
+    fn fast_count_matches_full_iteration() {
+        let path = temp("fast-count");
+        let mut writer = Writer::new(File::create(&path).unwrap());
+        writer.add_table("users", &[("name", "str")]).unwrap();
+        writer.add_table("events", &[("note", "str")]).unwrap();
+        for id in 0..500 {
+            let id = id.to_string();
+            writer
+                .add_record("users", &id, &[("name", "Anna")])
+                .unwrap();
+            if id.len() == 3 {
+                // An escaped newline followed by `r:` must not count twice.
+                writer
+                    .add_record("events", &id, &[("note", "line\nbreak r:x")])
+                    .unwrap();
+            }
+        }
+        writer.finish().unwrap();
+
+        let mut reader = Reader::new(&path).unwrap();
+        let offsets: Vec<u64> = reader
+            .records("users")
+            .unwrap()
+            .map(|record| record.unwrap().span.offset)
+            .collect();
+        for offset in offsets.iter().step_by(7) {
+            reader.delete_record(*offset).unwrap();
+        }
+
+        let mut total = 0;
+        for table in ["users", "events"] {
+            let full = reader.records(table).unwrap().count() as u64;
+            assert_eq!(reader.fast_count(Some(table)).unwrap(), full, "{}", table);
+            total += full;
+        }
+        assert_eq!(total, 500 - 72 + 400);
+        assert_eq!(reader.fast_count(None).unwrap(), total);
+        assert!(matches!(
+            reader.fast_count(Some("orders")),
+            Err(XRVErr::UnknownTable(_))
+        ));
+    }
+
+    #[test]
+    #[ignore = "timing run; use `cargo test --release -- --ignored --nocapture`"]
+    fn fast_count_million_records() {
+        let path = temp("fast-count-perf");
+        let mut file = std::io::BufWriter::new(File::create(&path).unwrap());
+        writeln!(file, "j:jumps").unwrap();
+        for id in 0..1_000_000 {
+            writeln!(file, "r:{} name:\"user {}\" score:{}", id, id, id % 97).unwrap();
+        }
+        file.into_inner().unwrap().sync_all().unwrap();
+
+        let mut reader = Reader::new(&path).unwrap();
+        let start = Instant::now();
+        assert_eq!(reader.fast_count(None).unwrap(), 1_000_000);
+        let fast = start.elapsed();
+        let start = Instant::now();
+        let mut full = 0;
+        while reader.next_record().unwrap().is_some() {
+            full += 1;
+        }
+        assert_eq!(full, 1_000_000);
+        println!("fast_count {:?}, next_record {:?}", fast, start.elapsed());
+    }
+
+    #[test]
+    fn count_lines_counts_every_kind() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{count_lines, LineCounts};
+
+        let input = "j:jumps users:58-20\n\
+            t:1 name:users pos:58 len:20 name:str\n\n# imported\n\
+            r:1 name:Anna\n\
+            x:2 name:Bob\n\
+            q:3\n";
+        let counts = count_lines(input.as_bytes())?;
+        let expected = LineCounts {
+            jumps: 1,
+            tables: 1,
+            records: 1,
+            tombstones: 1,
+            comments: 1,
+            blank: 1,
+            unknown: 1,
+            ..LineCounts::default()
+        };
+        assert_eq!(counts, expected);
+        assert_eq!(count_lines(&b""[..])?, LineCounts::default());
+        Ok(())
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{
+    error::XRVErr,
+    line::{LineKind, OwnedRecordLine, Span},
+    reader::{Reader, Source},
+};
+
+/// Cursor over one table's records that moves both ways, returned by
+/// `Reader::cursor`. It stands between two records: `next` returns the one
+/// after it and `prev` the one before, like a text cursor between
+/// characters. The span of every record met is kept, so going back reads
+/// just that line again instead of the region from its start.
+#[derive(Debug)]
+pub struct RecordCursor<'r> {
+    reader: &'r mut Reader,
+    table: String,
+    end: u64,
+    /// Spans of the records met so far, in region order.
+    spans: Vec<Span>,
+    position: usize,
+    /// Whether the scan reached the end of the region or failed.
+    exhausted: bool,
+}
+
+impl Reader {
+    /// Opens a cursor before the first record of `table`. The reader must
+    /// be able to seek back, so gzip input fails with
+    /// `XRVErr::SourceNotSeekable`.
+    ///
+    /// ```
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-cursor.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("name", "str")])?;
+    /// for id in 0..3 {
+    ///     writer.add_record("users", &id.to_string(), &[("name", "Anna")])?;
+    /// }
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(&path)?;
+    /// let mut cursor = reader.cursor("users")?;
+    /// assert_eq!(cursor.next().unwrap()?.id, "0");
+    /// assert_eq!(cursor.next().unwrap()?.id, "1");
+    /// assert_eq!(cursor.prev().unwrap()?.id, "1");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn cursor(&mut self, table: &str) -> Result<RecordCursor<'_>, XRVErr> {
+        if !matches!(self.source.get_ref(), Source::File(_)) {
+            return Err(XRVErr::SourceNotSeekable);
+        }
+        self.seek_to(table)?;
+        let end = match self.jumps.get(table) {
+            None => return Err(XRVErr::UnknownTable(table.to_owned())),
+            Some((seek, len)) => (*seek + *len) as u64,
+        };
+        Ok(RecordCursor {
+            reader: self,
+            table: table.to_owned(),
+            end,
+            spans: Vec::new(),
+            position: 0,
+            exhausted: false,
+        })
+    }
+}
+
+impl<'r> RecordCursor<'r> {
+    /// Number of records before the cursor.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Moves back over the record before the cursor and returns it, `None`
+    /// at the start of the table.
+    pub fn prev(&mut self) -> Option<Result<OwnedRecordLine, XRVErr>> {
+        let index = self.position.checked_sub(1)?;
+        let record = self.read_visited(index);
+        if record.is_ok() {
+            self.position = index;
+        }
+        Some(record)
+    }
+
+    /// Moves the cursor to stand before the record at `index`, scanning on
+    /// through records not met yet. Returns `false`, leaving the cursor at
+    /// the end, when the table holds fewer than `index` records.
+    pub fn seek_to_index(&mut self, index: usize) -> Result<bool, XRVErr> {
+        while self.spans.len() < index && self.advance()? {}
+        self.position = index.min(self.spans.len());
+        Ok(self.position == index)
+    }
+
+    /// Reads on to the next record line of the region, leaving it in the
+    /// reader's buffer and noting its span. Returns `false` at the end of
+    /// the region.
+    fn advance(&mut self) -> Result<bool, XRVErr> {
+        if self.exhausted {
+            return Ok(false);
+        }
+        let found = self.scan();
+        self.exhausted = !matches!(found, Ok(true));
+        if let Ok(true) = found {
+            self.spans.push(self.reader.buffer.span());
+        }
+        found
+    }
+
+    fn scan(&mut self) -> Result<bool, XRVErr> {
+        loop {
+            if self.reader.buffer.offset >= self.end {
+                return Ok(false);
+            }
+            match self.reader.parse_next()? {
+                None => return Ok(false),
+                // A lenient resync can carry past the end of the region.
+                Some(_) if self.reader.buffer.start >= self.end => continue,
+                Some(_) if !self.reader.within_region(&self.table, self.end)? => return Ok(false),
+                Some(LineKind::Tombstone) => continue,
+                Some(_) => return Ok(true),
+            }
+        }
+    }
+
+    /// Reads the record met at `index` again from its span.
+    fn read_visited(&mut self, index: usize) -> Result<OwnedRecordLine, XRVErr> {
+        self.reader.record_at(self.spans[index])
+    }
+}
+
+impl<'r> Iterator for RecordCursor<'r> {
+    type Item = Result<OwnedRecordLine, XRVErr>;
+
+    /// Moves on over the record after the cursor and returns it, `None` at
+    /// the end of the table.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position < self.spans.len() {
+            let record = self.read_visited(self.position);
+            if record.is_ok() {
+                self.position += 1;
+            }
+            return Some(record);
+        }
+        match self.advance() {
+            Ok(false) => None,
+            Ok(true) => {
+                let record = self.reader.owned_record();
+                if record.is_ok() {
+                    self.position += 1;
+                }
+                Some(record)
+            }
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn cursor_walks_both_ways_and_seeks() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Reader, Writer};
+
+        let path = std::env::temp_dir().join("xrave-test-cursor.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("users", &[("name", "str")])?;
+        writer.add_table("events", &[("kind", "str")])?;
+        for id in 0..30 {
+            let name = format!("user {}", id);
+            writer.add_record("users", &id.to_string(), &[("name", name.as_str())])?;
+            writer.add_record("events", &id.to_string(), &[("kind", "login")])?;
+        }
+        writer.finish()?;
+
+        let mut reader = Reader::new(&path)?;
+        let all = reader.records("users")?.collect::<Result<Vec<_>, _>>()?;
+
+        let mut cursor = reader.cursor("users")?;
+        for expected in &all[..10] {
+            assert_eq!(cursor.next().unwrap()?, *expected);
+        }
+        for expected in all[5..10].iter().rev() {
+            assert_eq!(cursor.prev().unwrap()?, *expected);
+        }
+        assert_eq!(cursor.position(), 5);
+        for expected in &all[5..12] {
+            assert_eq!(cursor.next().unwrap()?, *expected);
+        }
+        assert_eq!(cursor.position(), 12);
+
+        assert!(cursor.seek_to_index(25)?);
+        assert_eq!(cursor.next().unwrap()?, all[25]);
+        assert!(cursor.seek_to_index(3)?);
+        assert_eq!(cursor.prev().unwrap()?, all[2]);
+
+        assert!(!cursor.seek_to_index(31)?);
+        assert_eq!(cursor.position(), 30);
+        assert!(cursor.next().is_none());
+        assert_eq!(cursor.prev().unwrap()?, all[29]);
+
+        let mut cursor = reader.cursor("users")?;
+        assert!(cursor.prev().is_none());
+        assert_eq!(cursor.by_ref().map(Result::unwrap).collect::<Vec<_>>(), all);
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+use crate::{
+    error::XRVErr,
+    line::{Field, FieldBytes, LineField, OwnedRecordLine},
+    ordered::OrderedMap,
+};
+
+/// Opens a value that stands for a dictionary entry, `^2` for token `2`.
+pub(crate) const DICTIONARY_MARKER: char = '^';
+
+/// Table and entries of a `d:` line, full values by token.
+pub(crate) fn parse_dictionary(line: LineField) -> (String, OrderedMap<String>) {
+    let mut entries = OrderedMap::new();
+    for field in line.fields.iter() {
+        entries.insert(field.name.to_owned(), field.value_unescaped().into_owned());
+    }
+    (line.name.to_owned(), entries)
+}
+
+/// Entry of the token `value` holds after the marker, `None` when it holds
+/// a literal. A token `dictionary` lacks fails with
+/// `XRVErr::UnknownDictionaryToken`.
+pub(crate) fn entry<'d>(
+    dictionary: &'d OrderedMap<String>,
+    table: &str,
+    value: &str,
+) -> Result<Option<&'d str>, XRVErr> {
+    let token = match value.strip_prefix(DICTIONARY_MARKER) {
+        None => return Ok(None),
+        Some(token) => token,
+    };
+    match dictionary.get(token) {
+        None => Err(XRVErr::UnknownDictionaryToken {
+            table: table.to_owned(),
+            token: token.to_owned(),
+        }),
+        Some(value) => Ok(Some(value)),
+    }
+}
+
+/// Replaces the tokens among the values of `fields` by their entries.
+pub(crate) fn expand_fields<'b>(
+    dictionary: &'b OrderedMap<String>,
+    table: &str,
+    fields: &mut [Field<'b>],
+) -> Result<(), XRVErr> {
+    for field in fields.iter_mut() {
+        if let Some(value) = entry(dictionary, table, field.value)? {
+            field.value = value;
+            field.escaped = false;
+        }
+    }
+    Ok(())
+}
+
+/// Like `expand_fields`, for values left as bytes.
+pub(crate) fn expand_field_bytes<'b>(
+    dictionary: &'b OrderedMap<String>,
+    table: &str,
+    fields: &mut [FieldBytes<'b>],
+) -> Result<(), XRVErr> {
+    for field in fields.iter_mut() {
+        if let Some(value) = entry(dictionary, table, &String::from_utf8_lossy(field.value))? {
+            field.value = value.as_bytes();
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the tokens among the values of `record` by their entries.
+pub(crate) fn expand_record(
+    dictionary: &OrderedMap<String>,
+    table: &str,
+    record: &mut OwnedRecordLine,
+) -> Result<(), XRVErr> {
+    for field in record.cols.iter_mut() {
+        if let Some(value) = entry(dictionary, table, &field.value)? {
+            field.value = value.to_owned();
+        }
+    }
+    Ok(())
+}
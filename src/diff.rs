@@ -0,0 +1,401 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::Path,
+};
+
+use crate::{
+    document::{Document, DocumentTable},
+    error::XRVErr,
+    line::{OwnedField, OwnedRecordLine},
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    /// Field that identifies a record across both files. `None` keys records
+    /// by their `r:` id, as does naming a field the records do not have.
+    pub key: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffStatus {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl DiffStatus {
+    fn sigil(self) -> char {
+        match self {
+            DiffStatus::Added => '+',
+            DiffStatus::Removed => '-',
+            DiffStatus::Changed => '~',
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            DiffStatus::Added => "added",
+            DiffStatus::Removed => "removed",
+            DiffStatus::Changed => "changed",
+        }
+    }
+}
+
+/// One field that differs: `old` is `None` for an added field, `new` is
+/// `None` for a removed one. For columns the values are the declared kinds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub name: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordDiff {
+    pub key: String,
+    pub status: DiffStatus,
+    /// Field changes of a `Changed` record; empty for added and removed ones.
+    pub fields: Vec<FieldChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableDiff {
+    pub name: String,
+    pub status: DiffStatus,
+    pub columns: Vec<FieldChange>,
+    pub records: Vec<RecordDiff>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StyleDiff {
+    pub id: String,
+    pub status: DiffStatus,
+    pub fields: Vec<FieldChange>,
+}
+
+/// Differences between two files, ignoring layout: offsets, jumps, quoting
+/// and field spacing never show up here.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiffReport {
+    pub tables: Vec<TableDiff>,
+    pub styles: Vec<StyleDiff>,
+}
+
+/// Compares the files at `a` and `b` table by table and record by record.
+///
+/// ```
+/// use xrave::{diff, DiffOptions, Writer};
+///
+/// let dir = std::env::temp_dir();
+/// let (a, b) = (dir.join("xrave-doc-diff-a.xrv"), dir.join("xrave-doc-diff-b.xrv"));
+/// for (path, name) in [(&a, "Anna"), (&b, "Ann")] {
+///     let mut writer = Writer::new(std::fs::File::create(path)?);
+///     writer.add_table("users", &[("name", "str")])?;
+///     writer.add_record("users", "1", &[("name", name)])?;
+///     writer.finish()?;
+/// }
+///
+/// let report = diff(&a, &b, DiffOptions::default())?;
+/// assert_eq!(report.to_string(), "~ table users\n    ~ record 1\n        ~ name: Anna -> Ann\n");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn diff(a: &Path, b: &Path, options: DiffOptions) -> Result<DiffReport, XRVErr> {
+    let (a, b) = (Document::open(a)?, Document::open(b)?);
+    Ok(diff_documents(&a, &b, &options))
+}
+
+pub(crate) fn diff_documents(a: &Document, b: &Document, options: &DiffOptions) -> DiffReport {
+    let mut report = DiffReport::default();
+    for old in a.tables.iter() {
+        match b.table(&old.name) {
+            None => report.tables.push(TableDiff {
+                name: old.name.clone(),
+                status: DiffStatus::Removed,
+                columns: Vec::new(),
+                records: Vec::new(),
+            }),
+            Some(new) => {
+                let table = diff_table(old, new, options);
+                if !table.columns.is_empty() || !table.records.is_empty() {
+                    report.tables.push(table);
+                }
+            }
+        }
+    }
+    for new in b.tables.iter().filter(|new| a.table(&new.name).is_none()) {
+        report.tables.push(TableDiff {
+            name: new.name.clone(),
+            status: DiffStatus::Added,
+            columns: Vec::new(),
+            records: Vec::new(),
+        });
+    }
+
+    for old in a.styles.iter() {
+        let (status, fields) = match b.style(&old.id) {
+            None => (DiffStatus::Removed, Vec::new()),
+            Some(new) => (DiffStatus::Changed, diff_fields(&old.cols, &new.cols)),
+        };
+        if status == DiffStatus::Removed || !fields.is_empty() {
+            report.styles.push(StyleDiff {
+                id: old.id.clone(),
+                status,
+                fields,
+            });
+        }
+    }
+    for new in b.styles.iter().filter(|new| a.style(&new.id).is_none()) {
+        report.styles.push(StyleDiff {
+            id: new.id.clone(),
+            status: DiffStatus::Added,
+            fields: Vec::new(),
+        });
+    }
+    report
+}
+
+fn diff_table(old: &DocumentTable, new: &DocumentTable, options: &DiffOptions) -> TableDiff {
+    let key_of = |record: &OwnedRecordLine| -> String {
+        options
+            .key
+            .as_deref()
+            .and_then(|key| record.cols.iter().find(|field| &*field.name == key))
+            .map(|field| field.value.clone())
+            .unwrap_or_else(|| record.id.clone())
+    };
+    let new_records: HashMap<String, &OwnedRecordLine> = new
+        .records
+        .iter()
+        .map(|record| (key_of(record), record))
+        .collect();
+    let old_keys: HashSet<String> = old.records.iter().map(key_of).collect();
+
+    let mut records = Vec::new();
+    for record in old.records.iter() {
+        let key = key_of(record);
+        let (status, fields) = match new_records.get(&key) {
+            None => (DiffStatus::Removed, Vec::new()),
+            Some(new) => (DiffStatus::Changed, diff_fields(&record.cols, &new.cols)),
+        };
+        if status == DiffStatus::Removed || !fields.is_empty() {
+            records.push(RecordDiff {
+                key,
+                status,
+                fields,
+            });
+        }
+    }
+    for record in new.records.iter() {
+        let key = key_of(record);
+        if !old_keys.contains(&key) {
+            records.push(RecordDiff {
+                key,
+                status: DiffStatus::Added,
+                fields: Vec::new(),
+            });
+        }
+    }
+
+    TableDiff {
+        name: old.name.clone(),
+        status: DiffStatus::Changed,
+        columns: diff_fields(&old.cols, &new.cols),
+        records,
+    }
+}
+
+/// Compares fields by name, old fields first in their order, then fields
+/// only `new` has.
+pub(crate) fn diff_fields(old: &[OwnedField], new: &[OwnedField]) -> Vec<FieldChange> {
+    let find = |fields: &[OwnedField], name: &str| {
+        fields
+            .iter()
+            .find(|field| &*field.name == name)
+            .map(|field| field.value.clone())
+    };
+    let mut changes = Vec::new();
+    for field in old.iter() {
+        let value = find(new, &field.name);
+        if value.as_deref() != Some(field.value.as_str()) {
+            changes.push(FieldChange {
+                name: field.name.to_string(),
+                old: Some(field.value.clone()),
+                new: value,
+            });
+        }
+    }
+    for field in new.iter().filter(|field| find(old, &field.name).is_none()) {
+        changes.push(FieldChange {
+            name: field.name.to_string(),
+            old: None,
+            new: Some(field.value.clone()),
+        });
+    }
+    changes
+}
+
+impl DiffReport {
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty() && self.styles.is_empty()
+    }
+
+    /// Renders the report as a JSON object with `tables` and `styles`
+    /// arrays; missing old/new values are `null`.
+    pub fn to_json(&self) -> String {
+        let tables: Vec<String> = self
+            .tables
+            .iter()
+            .map(|table| {
+                let records: Vec<String> = table
+                    .records
+                    .iter()
+                    .map(|record| {
+                        format!(
+                            "{{\"key\":{},\"status\":\"{}\",\"fields\":{}}}",
+                            json_string(&record.key),
+                            record.status.as_str(),
+                            json_changes(&record.fields)
+                        )
+                    })
+                    .collect();
+                format!(
+                    "{{\"name\":{},\"status\":\"{}\",\"columns\":{},\"records\":[{}]}}",
+                    json_string(&table.name),
+                    table.status.as_str(),
+                    json_changes(&table.columns),
+                    records.join(",")
+                )
+            })
+            .collect();
+        let styles: Vec<String> = self
+            .styles
+            .iter()
+            .map(|style| {
+                format!(
+                    "{{\"id\":{},\"status\":\"{}\",\"fields\":{}}}",
+                    json_string(&style.id),
+                    style.status.as_str(),
+                    json_changes(&style.fields)
+                )
+            })
+            .collect();
+        format!(
+            "{{\"tables\":[{}],\"styles\":[{}]}}",
+            tables.join(","),
+            styles.join(",")
+        )
+    }
+}
+
+fn json_changes(changes: &[FieldChange]) -> String {
+    let value = |value: &Option<String>| match value {
+        None => "null".to_owned(),
+        Some(value) => json_string(value),
+    };
+    let changes: Vec<String> = changes
+        .iter()
+        .map(|change| {
+            format!(
+                "{{\"name\":{},\"old\":{},\"new\":{}}}",
+                json_string(&change.name),
+                value(&change.old),
+                value(&change.new)
+            )
+        })
+        .collect();
+    format!("[{}]", changes.join(","))
+}
+
+pub(crate) fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn write_changes(
+    f: &mut fmt::Formatter<'_>,
+    indent: &str,
+    label: &str,
+    changes: &[FieldChange],
+) -> fmt::Result {
+    for change in changes {
+        match (&change.old, &change.new) {
+            (Some(old), Some(new)) => writeln!(
+                f,
+                "{}~ {}{}: {} -> {}",
+                indent, label, change.name, old, new
+            )?,
+            (None, Some(new)) => writeln!(f, "{}+ {}{}: {}", indent, label, change.name, new)?,
+            (Some(old), None) => writeln!(f, "{}- {}{}: {}", indent, label, change.name, old)?,
+            (None, None) => {}
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for table in self.tables.iter() {
+            writeln!(f, "{} table {}", table.status.sigil(), table.name)?;
+            write_changes(f, "    ", "column ", &table.columns)?;
+            for record in table.records.iter() {
+                writeln!(f, "    {} record {}", record.status.sigil(), record.key)?;
+                write_changes(f, "        ", "", &record.fields)?;
+            }
+        }
+        for style in self.styles.iter() {
+            writeln!(f, "{} style {}", style.status.sigil(), style.id)?;
+            write_changes(f, "    ", "", &style.fields)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn diff_reports_tables_columns_and_records() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{diff, DiffOptions, DiffStatus, Writer};
+
+        let dir = std::env::temp_dir();
+        let (a, b) = (
+            dir.join("xrave-test-diff-a.xrv"),
+            dir.join("xrave-test-diff-b.xrv"),
+        );
+        let mut writer = Writer::new(std::fs::File::create(&a)?);
+        writer.add_table("users", &[("name", "str"), ("age", "int")])?;
+        writer.add_record("users", "1", &[("name", "Anna"), ("age", "31")])?;
+        writer.finish()?;
+        let mut writer = Writer::new(std::fs::File::create(&b)?);
+        writer.add_table("users", &[("name", "str"), ("age", "str")])?;
+        writer.add_record("users", "1", &[("name", "Ann"), ("age", "31")])?;
+        writer.add_record("users", "2", &[("name", "Bob"), ("age", "4")])?;
+        writer.finish()?;
+
+        let report = diff(&a, &b, DiffOptions::default())?;
+        let users = &report.tables[0];
+        assert_eq!(users.status, DiffStatus::Changed);
+        assert_eq!(users.columns[0].name, "age");
+        assert_eq!(users.records[0].fields[0].new.as_deref(), Some("Ann"));
+        assert_eq!(users.records[1].status, DiffStatus::Added);
+        assert_eq!(
+            report.to_string(),
+            "~ table users\n    ~ column age: int -> str\n    ~ record 1\n        \
+             ~ name: Anna -> Ann\n    + record 2\n",
+        );
+        Ok(())
+    }
+}
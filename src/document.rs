@@ -0,0 +1,483 @@
+use std::io::prelude::*;
+use std::{borrow::Cow, fs::File, path::Path, sync::Arc};
+
+use crate::{
+    dictionary::{expand_fields, parse_dictionary},
+    error::{open_error, XRVErr},
+    ids::{assign_id, IdStrategy},
+    line::{
+        LineField, LineJump, LineKind, OwnedField, OwnedRecordLine, OwnedStyleLine, RecordLine,
+        Span, StyleLine, TableLine, Value,
+    },
+    ordered::OrderedMap,
+    reader::{ParseMode, ParseOptions},
+    schema::{check_fields, key_column},
+    spill::{fill, Backing, Budget},
+    tokenizer::{RawLine, XraveBuffer},
+    version::read_first_line,
+    writer::{Writer, WriterOptions},
+};
+
+/// A whole `.xrv` file held in memory: table declarations, styles and each
+/// table's records in file order. Offsets are not kept; `write` lays the
+/// file out again and regenerates the jumps line and every `pos`/`len`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Document {
+    pub tables: Vec<DocumentTable>,
+    pub styles: Vec<OwnedStyleLine>,
+    /// Set when the source carried a `c:crc32` line.
+    pub checksums: bool,
+    /// Lets `add_record` accept an empty value for a required column.
+    pub allow_empty_required: bool,
+    /// How `add_record` comes by the id of a record added with an empty
+    /// one; see `IdStrategy`. The default, `Provided`, does not check that
+    /// ids are unique.
+    pub ids: IdStrategy,
+}
+
+/// A table of a `Document`. Its records are reached through `records`,
+/// `record` and `records_mut`, which read the fields of spilled records
+/// from the file again.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocumentTable {
+    pub name: String,
+    /// Column declarations following the `name`, `pos` and `len` fields.
+    pub cols: Vec<OwnedField>,
+    /// Records in file order; spilled ones hold only their id and span.
+    pub(crate) records: Vec<OwnedRecordLine>,
+    /// Next id `IdStrategy::Sequential` gives out, kept on the header as
+    /// `next_id`.
+    pub next_id: Option<u64>,
+    /// File to read spilled records from, for a table loaded over its
+    /// `LoadOptions` budget.
+    pub(crate) backing: Option<Backing>,
+}
+
+impl DocumentTable {
+    pub fn new(name: &str, cols: Vec<OwnedField>, records: Vec<OwnedRecordLine>) -> Self {
+        DocumentTable {
+            name: name.to_owned(),
+            cols,
+            records,
+            ..DocumentTable::default()
+        }
+    }
+
+    /// Number of records, spilled ones included.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Record ids in order, which stay in memory when records spill.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.records.iter().map(|record| record.id.as_str())
+    }
+
+    /// Record at `index`, read from the file again when spilled.
+    pub fn record(&self, index: usize) -> Result<Option<Cow<'_, OwnedRecordLine>>, XRVErr> {
+        match self.records.get(index) {
+            None => Ok(None),
+            Some(record) => fill(self.backing.as_ref(), record).map(Some),
+        }
+    }
+
+    /// Every record in order, each read from the file again when spilled.
+    pub fn records(&self) -> impl Iterator<Item = Result<Cow<'_, OwnedRecordLine>, XRVErr>> {
+        let backing = self.backing.as_ref();
+        self.records.iter().map(move |record| fill(backing, record))
+    }
+
+    /// The records to change in place, read back into memory first when
+    /// spilled.
+    pub fn records_mut(&mut self) -> Result<&mut Vec<OwnedRecordLine>, XRVErr> {
+        self.unspill()?;
+        Ok(&mut self.records)
+    }
+}
+
+impl Document {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Document, XRVErr> {
+        let path = path.as_ref();
+        match File::open(path) {
+            Err(err) => Err(open_error(path, err)),
+            Ok(file) => Document::read(file),
+        }
+    }
+
+    /// Parses a complete file from `input`. Records are assigned to the
+    /// table whose `pos`/`len` region contains them. Malformed input fails
+    /// with an error rather than a panic, including regions whose end
+    /// does not fit in a `usize`.
+    pub fn read<R: Read>(mut input: R) -> Result<Document, XRVErr> {
+        let mut bytes = Vec::new();
+        if let Err(err) = input.read_to_end(&mut bytes) {
+            return Err(XRVErr::FailToReadLine(err));
+        }
+        Document::from_bytes(&bytes)
+    }
+
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Document, XRVErr> {
+        Document::load(bytes, None)
+    }
+
+    /// Parses a complete file from `source`, counting records against
+    /// `budget` when given.
+    pub(crate) fn load<R: BufRead>(
+        mut source: R,
+        mut budget: Option<&mut Budget>,
+    ) -> Result<Document, XRVErr> {
+        let max_line_len = ParseOptions::default().max_line_len;
+        let mut buffer = XraveBuffer::new();
+        read_first_line(
+            &mut buffer,
+            &mut source,
+            max_line_len,
+            ParseMode::Strict,
+            &mut Vec::new(),
+        )?;
+        let raw_line: RawLine = (&buffer).try_into()?;
+        if let Err(err) = LineJump::try_from(raw_line) {
+            return Err(buffer.diagnose(err.position().1, err));
+        }
+
+        let mut document = Document::default();
+        let mut regions: Vec<(usize, usize)> = Vec::new();
+        let mut dictionaries: OrderedMap<OrderedMap<String>> = OrderedMap::new();
+        loop {
+            let start = buffer.offset as usize;
+            if buffer.read_line(&mut source, max_line_len)? == 0 {
+                break;
+            }
+            let span = Span {
+                offset: start as u64,
+                len: buffer.offset as usize - start,
+            };
+            if buffer.is_filler(&mut source)? {
+                continue;
+            }
+            let raw_line: RawLine = (&buffer).try_into()?;
+            let line_field = match LineField::try_from(raw_line) {
+                Err(err) => return Err(buffer.diagnose(err.position().1, err)),
+                Ok(line_field) => line_field,
+            };
+            match line_field.kind {
+                LineKind::Jump => return Err(buffer.diagnose(1, XRVErr::ItsNotAJumpsLine)),
+                LineKind::Checksum => document.checksums = true,
+                LineKind::Tombstone => {}
+                LineKind::Table => {
+                    let table = match TableLine::try_from(line_field) {
+                        Err(err) => return Err(buffer.diagnose(err.position().1, err)),
+                        Ok(table) => table,
+                    };
+                    if document.table(table.name).is_some() {
+                        return Err(XRVErr::DuplicateTable(table.name.to_owned()));
+                    }
+                    regions.push((table.pos, table.pos + table.len));
+                    let cols = table.cols.iter().map(|field| field.to_owned()).collect();
+                    let mut new = DocumentTable::new(table.name, cols, Vec::new());
+                    new.next_id = table.next_id;
+                    document.tables.push(new);
+                }
+                LineKind::Style => {
+                    let style = StyleLine::try_from(line_field)?;
+                    if document.style(style.id).is_some() {
+                        return Err(XRVErr::DuplicateStyle(style.id.to_owned()));
+                    }
+                    document.styles.push(style.to_owned());
+                }
+                LineKind::Dictionary => {
+                    let (table, entries) = parse_dictionary(line_field);
+                    if dictionaries.insert(table.clone(), entries).is_some() {
+                        return Err(XRVErr::DuplicateDictionary(table));
+                    }
+                }
+                LineKind::Record => {
+                    let mut record = RecordLine::try_from(line_field)?;
+                    let idx = regions
+                        .iter()
+                        .position(|(pos, end)| (*pos..*end).contains(&start));
+                    let idx = match idx {
+                        None => return Err(XRVErr::RecordOutsideTable { line: buffer.line }),
+                        Some(idx) => idx,
+                    };
+                    let name = &document.tables[idx].name;
+                    if let Some(dictionary) = dictionaries.get(name) {
+                        let expanded = expand_fields(dictionary, name, &mut record.cols);
+                        if let Err(err) = expanded {
+                            return Err(buffer.diagnose(1, err));
+                        }
+                    }
+                    let mut record = record.to_owned();
+                    if let Some(budget) = budget.as_deref_mut() {
+                        record.span = span;
+                        budget.admit(&mut document.tables, &mut record)?;
+                    }
+                    document.tables[idx].records.push(record);
+                }
+            }
+        }
+        Ok(document)
+    }
+
+    pub fn table(&self, name: &str) -> Option<&DocumentTable> {
+        self.tables.iter().find(|table| table.name == name)
+    }
+
+    pub fn table_mut(&mut self, name: &str) -> Option<&mut DocumentTable> {
+        self.tables.iter_mut().find(|table| table.name == name)
+    }
+
+    pub fn style(&self, id: &str) -> Option<&OwnedStyleLine> {
+        self.styles.iter().find(|style| style.id == id)
+    }
+
+    /// Appends a record to `table` after checking it against the table's
+    /// column declarations: a missing required column fails with
+    /// `XRVErr::MissingColumn`, an empty one with `XRVErr::EmptyColumn` and a
+    /// value outside declared bounds such as `i32[0..150]` with
+    /// `XRVErr::ValueOutOfRange`, and a repeated value of the `!` key column
+    /// with `XRVErr::DuplicateKey`. An empty `id` is given one under
+    /// `Document::ids`. Nothing is added on failure.
+    ///
+    /// ```
+    /// use xrave::{ColKind, Schema, XRVErr};
+    ///
+    /// let mut document = Schema::new().table("users", |t| t.col("name", ColKind::String)).build()?;
+    /// document.add_record("users", "1", &[("name", "Anna")])?;
+    /// assert!(matches!(document.add_record("users", "2", &[]), Err(XRVErr::MissingColumn { .. })));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn add_record(
+        &mut self,
+        table: &str,
+        id: &str,
+        fields: &[(&str, &str)],
+    ) -> Result<(), XRVErr> {
+        let (allow_empty, strategy) = (self.allow_empty_required, self.ids);
+        match self.table_mut(table) {
+            None => Err(XRVErr::UnknownTable(table.to_owned())),
+            Some(table) => {
+                if key_column(&table.cols).is_some() {
+                    table.unspill()?;
+                }
+                check_fields(&table.name, &table.cols, fields, allow_empty)?;
+                if let Some(col) = key_column(&table.cols) {
+                    let key = fields
+                        .iter()
+                        .find(|(name, _)| *name == col)
+                        .map(|(_, value)| *value);
+                    if let Some(key) = key {
+                        if table
+                            .records
+                            .iter()
+                            .any(|record| record.key(col) == Some(key))
+                        {
+                            return Err(XRVErr::DuplicateKey {
+                                table: table.name.clone(),
+                                key: key.to_owned(),
+                            });
+                        }
+                    }
+                }
+                let mut next_id = table.next_id;
+                let id = assign_id(strategy, &table.name, id, &mut next_id)?;
+                if strategy != IdStrategy::Provided
+                    && table.records.iter().any(|record| record.id == id)
+                {
+                    return Err(XRVErr::DuplicateRecordId {
+                        table: table.name.clone(),
+                        id: id.into_owned(),
+                    });
+                }
+                table.next_id = next_id;
+                table.records.push(OwnedRecordLine {
+                    id: id.into_owned(),
+                    cols: fields
+                        .iter()
+                        .map(|(name, value)| OwnedField {
+                            name: Arc::from(*name),
+                            value: (*value).to_owned(),
+                            null: false,
+                        })
+                        .collect(),
+                    span: Span::default(),
+                });
+                Ok(())
+            }
+        }
+    }
+
+    /// Orders tables by name instead of the order they were declared in.
+    pub fn sort_tables_by_name(&mut self) {
+        self.tables.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    /// Orders the records of every table by their `col` value, or by record
+    /// id for `id`. The sort is stable, and records without `col` go last.
+    /// Spilled records are read back first.
+    ///
+    /// Tables, styles and records otherwise keep the order they were read
+    /// or added in, so a document saved unchanged reproduces its source.
+    ///
+    /// ```
+    /// use xrave::{Document, Writer};
+    ///
+    /// let mut writer = Writer::new(Vec::new());
+    /// writer.add_table("users", &[("rank", "str")])?;
+    /// for (id, rank) in [("3", "c"), ("1", "a"), ("2", "b")] {
+    ///     writer.add_record("users", id, &[("rank", rank)])?;
+    /// }
+    /// let mut document = Document::read(&writer.finish()?[..])?;
+    /// document.sort_records_by("rank")?;
+    /// assert_eq!(document.tables[0].ids().collect::<Vec<_>>(), ["1", "2", "3"]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn sort_records_by(&mut self, col: &str) -> Result<(), XRVErr> {
+        self.unspill()?;
+        for table in self.tables.iter_mut() {
+            table
+                .records
+                .sort_by(|a, b| match (a.key(col), b.key(col)) {
+                    (Some(a), Some(b)) => a.cmp(b),
+                    (Some(_), None) => std::cmp::Ordering::Less,
+                    (None, Some(_)) => std::cmp::Ordering::Greater,
+                    (None, None) => std::cmp::Ordering::Equal,
+                });
+        }
+        Ok(())
+    }
+
+    /// Writes the document to `path`, replacing any existing file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), XRVErr> {
+        let path = path.as_ref();
+        let file = match File::create(path) {
+            Err(err) => return Err(open_error(path, err)),
+            Ok(file) => file,
+        };
+        let options = WriterOptions {
+            checksums: self.checksums,
+            ..WriterOptions::default()
+        };
+        self.write(file, options)?;
+        Ok(())
+    }
+
+    /// Lays the document out through a `Writer`, tables and styles in
+    /// document order.
+    pub fn write<W: Write>(&self, out: W, options: WriterOptions) -> Result<W, XRVErr> {
+        let mut writer = Writer::with_options(out, options);
+        for table in self.tables.iter() {
+            writer.add_table(&table.name, &owned_pairs(&table.cols))?;
+            if let Some(next_id) = table.next_id {
+                writer.set_next_id(&table.name, next_id)?;
+            }
+        }
+        for style in self.styles.iter() {
+            writer.add_style(&style.id, &owned_pairs(&style.cols))?;
+        }
+        for table in self.tables.iter() {
+            for record in table.records() {
+                let record = record?;
+                let fields: Vec<(&str, Value)> = record
+                    .cols
+                    .iter()
+                    .map(|field| (&*field.name, field.as_value()))
+                    .collect();
+                writer.add_record_values(&table.name, &record.id, &fields)?;
+            }
+        }
+        writer.finish()
+    }
+}
+
+fn owned_pairs(fields: &[OwnedField]) -> Vec<(&str, &str)> {
+    fields
+        .iter()
+        .map(|field| (&*field.name, field.value.as_str()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn add_record_checks_declarations() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{ColKind, Schema, XRVErr};
+
+        let mut document = Schema::new()
+            .table("users", |t| {
+                t.col("name", ColKind::String)
+                    .col("email", ColKind::String.optional())
+            })
+            .build()?;
+        document.add_record("users", "1", &[("name", "Anna")])?;
+        document.add_record("users", "2", &[("name", "Bob"), ("email", "")])?;
+        assert!(matches!(
+            document.add_record("users", "3", &[("email", "c@example.com")]),
+            Err(XRVErr::MissingColumn { .. })
+        ));
+        assert!(matches!(
+            document.add_record("users", "4", &[("name", "")]),
+            Err(XRVErr::EmptyColumn { .. })
+        ));
+        document.allow_empty_required = true;
+        document.add_record("users", "4", &[("name", "")])?;
+
+        let table = document.table("users").unwrap();
+        let records = table.typed_records().collect::<Result<Vec<_>, _>>()?;
+        let emails: Vec<_> = records.iter().map(|r| r.get_opt_str("email")).collect();
+        assert_eq!(emails, [None, Some(""), None]);
+
+        let mut tags = Schema::new()
+            .table("tags", |t| t.col("label", ColKind::String.key()))
+            .build()?;
+        tags.add_record("tags", "t1", &[("label", "admin")])?;
+        assert!(matches!(
+            tags.add_record("tags", "t2", &[("label", "admin")]),
+            Err(XRVErr::DuplicateKey { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn documents_keep_source_order_until_sorted() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Document, Reader, Writer};
+
+        let path = std::env::temp_dir().join("xrave-test-order.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        for table in ["zeta", "alpha", "mid"] {
+            writer.add_table(table, &[("rank", "str")])?;
+        }
+        writer.add_style("plain", &[("weight", "400")])?;
+        writer.add_style("bold", &[("weight", "700")])?;
+        for (id, rank) in [("3", "c"), ("1", "a"), ("4", ""), ("2", "b")] {
+            writer.add_record("zeta", id, &[("rank", rank)])?;
+        }
+        writer.add_record("zeta", "5", &[])?;
+        writer.add_record("mid", "9", &[("rank", "x")])?;
+        writer.finish()?;
+
+        let original = std::fs::read(&path)?;
+        let mut document = Document::read(&original[..])?;
+        assert_eq!(document.write(Vec::new(), Default::default())?, original);
+
+        let reader = Reader::new(path.to_string_lossy().into_owned())?;
+        let tables: Vec<_> = reader.jumps().map(|jump| jump.name().to_owned()).collect();
+        assert_eq!(tables, ["zeta", "alpha", "mid"]);
+
+        document.sort_tables_by_name();
+        document.sort_records_by("rank")?;
+        let tables: Vec<_> = document
+            .tables
+            .iter()
+            .map(|table| table.name.as_str())
+            .collect();
+        assert_eq!(tables, ["alpha", "mid", "zeta"]);
+        let ids: Vec<_> = document.tables[2].ids().collect();
+        assert_eq!(ids, ["4", "1", "2", "3", "5"]);
+        Ok(())
+    }
+}
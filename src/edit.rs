@@ -0,0 +1,620 @@
+use std::io::prelude::*;
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter, SeekFrom},
+    ops::Range,
+    path::Path,
+};
+
+use crate::{
+    atomic::AtomicFile,
+    checksum::{Crc32, CHECKSUM_HEADER_SECTION},
+    error::{open_error, XRVErr},
+    line::LineKind,
+    reader::{seek_error, Reader, Source},
+    tokenizer::{
+        RawLine, XraveBuffer, CHECKSUM_ID, COLON_CHAR, CR_CHAR, JUMP_ID, NL_CHAR, TABLE_ID,
+        TOMBSTONE_ID, UTF8_BOM,
+    },
+    writer::{checksum_line, write_value},
+};
+
+impl Reader {
+    /// Overwrites the value of `column` on the record line starting at byte
+    /// `offset`, padding with the dialect's separator after the value when
+    /// the new one is shorter. Quotes and escapes are added or dropped as `new_value`
+    /// needs, and the
+    /// rendered value must fit in the bytes of the old one, quotes included;
+    /// otherwise `XRVErr::ValueTooLongForInPlaceUpdate` is returned and
+    /// nothing is written. Checksum lines are not updated.
+    ///
+    /// ```
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-update.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("age", "int"), ("name", "str")])?;
+    /// writer.add_record("users", "1", &[("age", "1031"), ("name", "Anna Smith")])?;
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+    /// let offset = reader.jumps().next().unwrap().seek() as u64;
+    /// reader.update_record_field(offset, "age", "32")?;
+    /// reader.update_record_field(offset, "name", "Anna")?;
+    /// assert!(reader.update_record_field(offset, "age", "100000").is_err());
+    ///
+    /// let record = reader.records("users")?.next().unwrap()?;
+    /// assert_eq!(record.cols[0].value, "32");
+    /// assert_eq!(record.cols[1].value, "Anna");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn update_record_field(
+        &mut self,
+        offset: u64,
+        column: &str,
+        new_value: &str,
+    ) -> Result<(), XRVErr> {
+        let (at, start, end) = {
+            let line = self.read_line_at(offset)?;
+            let raw_line: RawLine = (&line).try_into()?;
+            if raw_line.kind != LineKind::Record {
+                return Err(XRVErr::NotRecordLine);
+            }
+            let link = raw_line
+                .fields
+                .links()
+                .find(|link| &line.buffer[link.name_start..link.name_end] == column.as_bytes());
+            let (start, end) = match link {
+                None => {
+                    return Err(XRVErr::UnknownColumn {
+                        table: self.table_at(offset).unwrap_or_default(),
+                        column: column.to_owned(),
+                    })
+                }
+                Some(link) if link.quoted => (link.value_start - 1, link.value_end + 1),
+                Some(link) => (link.value_start, link.value_end),
+            };
+            if line
+                .continued
+                .iter()
+                .any(|(at, _)| start < *at && *at < end)
+            {
+                return Err(XRVErr::ValueSpansLines(column.to_owned()));
+            }
+            (offset + line.line_offset(start) as u64, start, end)
+        };
+
+        let mut rendered = Vec::with_capacity(end - start);
+        write_value(&mut rendered, new_value, self.options.dialect);
+        if rendered.len() > end - start {
+            return Err(XRVErr::ValueTooLongForInPlaceUpdate {
+                len: rendered.len(),
+                max: end - start,
+            });
+        }
+        rendered.resize(end - start, self.options.dialect.separator);
+        self.write_at(at, &rendered)
+    }
+
+    /// Marks the record line starting at byte `offset` as deleted by
+    /// overwriting its kind byte with a tombstone. Readers skip tombstoned
+    /// lines; `compact` removes them for good.
+    ///
+    /// ```
+    /// use xrave::{compact, Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-tombstone.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("name", "str")])?;
+    /// writer.add_record("users", "1", &[("name", "Anna")])?;
+    /// writer.add_record("users", "2", &[("name", "Bob")])?;
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(&path)?;
+    /// let first = reader.records("users")?.next().unwrap()?.span.offset;
+    /// reader.delete_record(first)?;
+    /// assert_eq!(reader.records("users")?.count(), 1);
+    /// compact(&path)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn delete_record(&mut self, offset: u64) -> Result<(), XRVErr> {
+        let line = self.read_line_at(offset)?;
+        let raw_line: RawLine = (&line).try_into()?;
+        match raw_line.kind {
+            LineKind::Record => self.write_at(offset, &[TOMBSTONE_ID]),
+            LineKind::Tombstone => Ok(()),
+            _ => Err(XRVErr::NotRecordLine),
+        }
+    }
+
+    /// Name of the table whose region holds byte `offset`.
+    pub(crate) fn table_at(&self, offset: u64) -> Option<String> {
+        self.jumps
+            .iter()
+            .find(|(_, (seek, len))| (*seek as u64..(*seek + *len) as u64).contains(&offset))
+            .map(|(name, _)| name.clone())
+    }
+
+    /// Reads the line starting at `offset` and puts the stream back where it
+    /// was. Only plain files can be edited in place.
+    pub(crate) fn read_line_at(&mut self, offset: u64) -> Result<XraveBuffer, XRVErr> {
+        if !matches!(self.source.get_ref(), Source::File(_)) {
+            return Err(XRVErr::SourceNotSeekable);
+        }
+        let resume = self.buffer.offset;
+        let mut line = XraveBuffer::new();
+        line.limits = self.options.line_limits();
+        line.set_version(self.version);
+        let read = match self.source.seek(SeekFrom::Start(offset)) {
+            Err(err) => Err(seek_error(err)),
+            Ok(_) => line.read_line(&mut self.source, self.options.max_line_len),
+        };
+        if let Err(err) = self.source.seek(SeekFrom::Start(resume)) {
+            return Err(seek_error(err));
+        }
+        match read? {
+            0 => Err(XRVErr::UnexpectedEof),
+            _ => Ok(line),
+        }
+    }
+
+    /// Writes `bytes` at `offset` through a second, writable handle and
+    /// drops whatever the reader had buffered or cached so it sees the new
+    /// bytes.
+    pub(crate) fn write_at(&mut self, offset: u64, bytes: &[u8]) -> Result<(), XRVErr> {
+        self.cache.clear();
+        let path = self.reopen_path()?;
+        let mut file = match OpenOptions::new().write(true).open(path) {
+            Err(err) => return Err(open_error(path, err)),
+            Ok(file) => file,
+        };
+        let written = file
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| file.write_all(bytes))
+            .and_then(|_| file.sync_data());
+        if let Err(err) = written {
+            return Err(XRVErr::FailToWrite(err));
+        }
+        match self.source.seek(SeekFrom::Start(self.buffer.offset)) {
+            Err(err) => Err(seek_error(err)),
+            Ok(_) => Ok(()),
+        }
+    }
+}
+
+/// Rewrites the file at `path` without its tombstoned lines, recomputing
+/// the jumps line, every table's `pos`/`len` and any checksums. The file is
+/// streamed region by region and every other line is copied byte for byte,
+/// so spacing and quoting come out as they went in. Only plain files can
+/// be compacted.
+pub fn compact<P: AsRef<Path>>(path: P) -> Result<(), XRVErr> {
+    let path = path.as_ref();
+    let reader = Reader::new(path)?;
+    let file_len = match reader.source.get_ref() {
+        Source::File(file) => match file.metadata() {
+            Err(err) => return Err(XRVErr::FailToReadLine(err)),
+            Ok(metadata) => metadata.len(),
+        },
+        _ => return Err(XRVErr::SourceNotSeekable),
+    };
+    let (version, max_len) = (reader.version, reader.options.max_line_len);
+    let dialect = reader.options.dialect;
+
+    // The header runs up to the first region; each region is followed by
+    // the bytes up to the next one, the checksum line among them.
+    let mut regions: Vec<(String, u64, u64)> = reader
+        .jumps
+        .iter()
+        .map(|(name, (seek, len))| (name.clone(), *seek as u64, (*seek + *len) as u64))
+        .collect();
+    drop(reader);
+    regions.sort_by_key(|(_, start, _)| *start);
+    let header_end = regions.first().map_or(file_len, |(_, start, _)| *start);
+    let mut segments = Vec::new();
+    for (idx, (name, start, end)) in regions.iter().enumerate() {
+        let next = regions
+            .get(idx + 1)
+            .map_or(file_len, |(_, start, _)| *start);
+        segments.push((Some(name.as_str()), *start, *end));
+        segments.push((None, *end, next.max(*end)));
+    }
+
+    let header = read_header(path, header_end, version, max_len)?;
+    let mut checksums = header.checksums;
+    let mut kept = Vec::with_capacity(segments.len());
+    for (table, start, end) in segments.iter() {
+        let dropped = kinds_dropped(table.is_some());
+        let filtered = filter_lines(path, *start, *end, version, max_len, dropped, |_| Ok(()))?;
+        checksums |= filtered.checksums;
+        kept.push(filtered.kept);
+    }
+
+    // Positions depend on the header length and the header on the
+    // positions, so lay it out until the two agree.
+    let mut layout: HashMap<String, (usize, usize)> = HashMap::new();
+    let bytes = loop {
+        let bytes = header.render(&layout);
+        let mut pos = bytes.len();
+        let mut next = HashMap::new();
+        for ((table, _, _), len) in segments.iter().zip(kept.iter()) {
+            if let Some(name) = table {
+                next.insert(name.to_string(), (pos, *len));
+            }
+            pos += len;
+        }
+        if next == layout {
+            break bytes;
+        }
+        layout = next;
+    };
+
+    let mut out = BufWriter::new(AtomicFile::create(path)?);
+    let write = |out: &mut BufWriter<AtomicFile>, bytes: &[u8]| match out.write_all(bytes) {
+        Err(err) => Err(XRVErr::FailToWrite(err)),
+        Ok(_) => Ok(()),
+    };
+    write(&mut out, &bytes)?;
+    let mut crc = Crc32::new();
+    crc.update(&bytes);
+    let mut crcs = vec![(CHECKSUM_HEADER_SECTION, crc.finish())];
+    for (table, start, end) in segments.iter() {
+        let mut crc = Crc32::new();
+        let dropped = kinds_dropped(table.is_some());
+        filter_lines(path, *start, *end, version, max_len, dropped, |bytes| {
+            crc.update(bytes);
+            write(&mut out, bytes)
+        })?;
+        if let Some(name) = table {
+            crcs.push((name, crc.finish()));
+        }
+    }
+    if checksums {
+        write(&mut out, &checksum_line(&crcs, dialect))?;
+    }
+    match out.into_inner() {
+        Err(err) => Err(XRVErr::FailToWrite(err.into_error())),
+        Ok(file) => file.commit(),
+    }
+}
+
+/// Kinds of line `compact` leaves out: tombstones, and outside the table
+/// regions the checksum line it writes anew.
+fn kinds_dropped(region: bool) -> &'static [u8] {
+    match region {
+        true => &[TOMBSTONE_ID],
+        false => &[TOMBSTONE_ID, CHECKSUM_ID],
+    }
+}
+
+/// Bytes `filter_lines` kept of a stretch of the file, and whether it
+/// dropped a checksum line.
+struct Filtered {
+    kept: usize,
+    checksums: bool,
+}
+
+/// Reads the lines between `start` and `end` and hands the bytes of each
+/// one not of a kind in `kinds` to `emit`, terminator included. One handle
+/// tokenizes the lines while a second reads their bytes as they are.
+fn filter_lines<F>(
+    path: &Path,
+    start: u64,
+    end: u64,
+    version: u32,
+    max_len: usize,
+    kinds: &[u8],
+    mut emit: F,
+) -> Result<Filtered, XRVErr>
+where
+    F: FnMut(&[u8]) -> Result<(), XRVErr>,
+{
+    let open = || -> Result<BufReader<File>, XRVErr> {
+        let mut file = match File::open(path) {
+            Err(err) => return Err(open_error(path, err)),
+            Ok(file) => file,
+        };
+        match file.seek(SeekFrom::Start(start)) {
+            Err(err) => Err(seek_error(err)),
+            Ok(_) => Ok(BufReader::new(file)),
+        }
+    };
+    let mut lines = open()?.take(end - start);
+    let mut raw = open()?;
+    let mut buffer = XraveBuffer::new();
+    buffer.set_version(version);
+    let mut bytes = Vec::new();
+    let mut filtered = Filtered {
+        kept: 0,
+        checksums: false,
+    };
+    loop {
+        let read = buffer.read_line(&mut lines, max_len)?;
+        if read == 0 {
+            return Ok(filtered);
+        }
+        bytes.clear();
+        if let Err(err) = (&mut raw).take(read as u64).read_to_end(&mut bytes) {
+            return Err(XRVErr::FailToReadLine(err));
+        }
+        match buffer.buffer[..] {
+            [kind, COLON_CHAR, ..] if kinds.contains(&kind) => {
+                filtered.checksums |= kind == CHECKSUM_ID;
+                continue;
+            }
+            _ => {}
+        }
+        if !bytes.ends_with(&[NL_CHAR]) && !bytes.ends_with(&[CR_CHAR]) {
+            bytes.push(NL_CHAR);
+        }
+        emit(&bytes)?;
+        filtered.kept += bytes.len();
+    }
+}
+
+/// A value on the jumps line or a table line that `compact` recomputes.
+enum Slot {
+    Jump(String),
+    Pos(String),
+    Len(String),
+}
+
+/// A header line as written and the byte ranges of the values in it to
+/// recompute.
+type HeaderLine = (Vec<u8>, Vec<(Range<usize>, Slot)>);
+
+/// Lines before the first table region.
+struct Header {
+    lines: Vec<HeaderLine>,
+    /// Whether a checksum line was left out.
+    checksums: bool,
+}
+
+impl Header {
+    /// The header with each table's region taken from `layout`; values of
+    /// tables missing from it are copied as they are.
+    fn render(&self, layout: &HashMap<String, (usize, usize)>) -> Vec<u8> {
+        let mut out = Vec::new();
+        for (line, slots) in self.lines.iter() {
+            let mut copied = 0;
+            for (range, slot) in slots {
+                let value = match slot {
+                    Slot::Jump(name) => layout
+                        .get(name)
+                        .map(|(pos, len)| format!("{}-{}", pos, len)),
+                    Slot::Pos(name) => layout.get(name).map(|(pos, _)| pos.to_string()),
+                    Slot::Len(name) => layout.get(name).map(|(_, len)| len.to_string()),
+                };
+                if let Some(value) = value {
+                    out.extend_from_slice(&line[copied..range.start]);
+                    out.extend_from_slice(value.as_bytes());
+                    copied = range.end;
+                }
+            }
+            out.extend_from_slice(&line[copied..]);
+        }
+        out
+    }
+}
+
+/// Splits the first `end` bytes of the file into lines, noting where the
+/// jumps line and the table lines hold the regions.
+fn read_header(path: &Path, end: u64, version: u32, max_len: usize) -> Result<Header, XRVErr> {
+    let mut bytes = Vec::new();
+    filter_lines(path, 0, end, version, max_len, &[], |line| {
+        bytes.extend_from_slice(line);
+        Ok(())
+    })?;
+    let mut header = Header {
+        lines: Vec::new(),
+        checksums: false,
+    };
+    let bom = match bytes.starts_with(&UTF8_BOM) {
+        true => UTF8_BOM.len(),
+        false => 0,
+    };
+    header.lines.push((bytes[..bom].to_vec(), Vec::new()));
+    let mut source = &bytes[bom..];
+    let mut buffer = XraveBuffer::new();
+    buffer.set_version(version);
+    loop {
+        let start = buffer.offset as usize + bom;
+        let read = buffer.read_line(&mut source, max_len)?;
+        if read == 0 {
+            return Ok(header);
+        }
+        let line = bytes[start..start + read].to_vec();
+        let kind = match buffer.buffer[..] {
+            [kind, COLON_CHAR, ..] => kind,
+            _ => {
+                header.lines.push((line, Vec::new()));
+                continue;
+            }
+        };
+        if kind == TOMBSTONE_ID || kind == CHECKSUM_ID {
+            header.checksums |= kind == CHECKSUM_ID;
+            continue;
+        }
+        if kind != JUMP_ID && kind != TABLE_ID {
+            header.lines.push((line, Vec::new()));
+            continue;
+        }
+        let raw_line: RawLine = (&buffer).try_into()?;
+        let text =
+            |range: Range<usize>| String::from_utf8_lossy(&buffer.buffer[range]).into_owned();
+        let mut slots = Vec::new();
+        let mut table = None;
+        for link in raw_line.fields.links() {
+            let (start, end) = match link.quoted {
+                true => (link.value_start - 1, link.value_end + 1),
+                false => (link.value_start, link.value_end),
+            };
+            let range = buffer.line_offset(start)..buffer.line_offset(end);
+            let name = text(link.name_start..link.name_end);
+            match (kind, name.as_str()) {
+                (JUMP_ID, _) => slots.push((range, Slot::Jump(name))),
+                (_, "name") if table.is_none() => {
+                    table = Some(text(link.value_start..link.value_end))
+                }
+                (_, "pos") => slots.push((range, Slot::Pos(String::new()))),
+                (_, "len") => slots.push((range, Slot::Len(String::new()))),
+                _ => {}
+            }
+        }
+        for (_, slot) in slots.iter_mut() {
+            if let (Slot::Pos(name) | Slot::Len(name), Some(table)) = (slot, &table) {
+                name.clone_from(table);
+            }
+        }
+        header.lines.push((line, slots));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn tombstones_hide_records_until_compacted() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{compact, Reader, Writer, WriterOptions};
+
+        let path = std::env::temp_dir().join("xrave-test-tombstone.xrv");
+        let options = WriterOptions {
+            checksums: true,
+            ..WriterOptions::default()
+        };
+        let mut writer = Writer::with_options(std::fs::File::create(&path)?, options);
+        writer.add_table("users", &[("name", "str")])?;
+        for id in 0..10 {
+            writer.add_record("users", &id.to_string(), &[("name", "Anna")])?;
+        }
+        writer.finish()?;
+        let size = std::fs::metadata(&path)?.len();
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        let start = reader.jumps().next().unwrap().seek() as u64;
+        let line = "r:0 name:Anna\n".len() as u64;
+        reader.delete_record(start)?;
+        reader.delete_record(start + 3 * line)?;
+        assert_eq!(reader.records("users")?.count(), 8);
+
+        compact(&path)?;
+        assert!(std::fs::metadata(&path)?.len() < size);
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        assert_eq!(reader.records("users")?.count(), 8);
+        assert!(reader.verify_checksums()?.passed());
+        Ok(())
+    }
+
+    #[test]
+    fn updates_pad_with_the_dialect_separator() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Dialect, ParseOptions, Reader, Writer, WriterOptions};
+
+        let dialect = Dialect {
+            separator: b';',
+            quote: b'|',
+            null: b'-',
+        };
+        let path = std::env::temp_dir().join("xrave-test-update-dialect.xrv");
+        let options = WriterOptions {
+            dialect,
+            ..WriterOptions::default()
+        };
+        let mut writer = Writer::with_options(std::fs::File::create(&path)?, options);
+        writer.add_table("users", &[("age", "int"), ("name", "str")])?;
+        writer.add_record("users", "1", &[("age", "1031"), ("name", "Anna Smith")])?;
+        writer.finish()?;
+
+        let options = ParseOptions {
+            dialect,
+            ..ParseOptions::default()
+        };
+        let mut reader = Reader::with_options(&path, options.clone())?;
+        let start = reader.jumps().next().unwrap().seek() as u64;
+        reader.update_record_field(start, "age", "32")?;
+        reader.update_record_field(start, "name", "Bob")?;
+        let text = std::fs::read_to_string(&path)?;
+        assert!(text.contains("r:1;age:32;;;name:Bob;;;;;;;\n"), "{}", text);
+
+        let mut reader = Reader::with_options(&path, options)?;
+        let record = reader.records("users")?.next().unwrap()?;
+        assert_eq!(record.cols[0].value, "32");
+        assert_eq!(record.cols[1].value, "Bob");
+        Ok(())
+    }
+
+    #[test]
+    fn compact_keeps_lines_as_written() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{compact, Reader};
+
+        let path = std::env::temp_dir().join("xrave-test-compact-as-written.xrv");
+        let records = "r:1   name:\"Anna\"  note:\"x y\"\n\
+                       x:2 name:Bob\n\
+                       r:3 name:Carl    note:~\n";
+        let header = |pos: usize, len: usize| {
+            format!(
+                "v:xrave 3\n\
+                 j:jumps  users:{pos:05}-{len:05}\n\
+                 t:1 name:\"users\"  pos:{pos:05} len:{len:05}   name:str note:\"str?\"\n"
+            )
+        };
+        let pos = header(0, 0).len();
+        let text = format!(
+            "{}{}c:crc32 header:00000000 users:00000000\n",
+            header(pos, records.len()),
+            records
+        );
+        std::fs::write(&path, text)?;
+
+        compact(&path)?;
+        let records = "r:1   name:\"Anna\"  note:\"x y\"\nr:3 name:Carl    note:~\n";
+        let header = "v:xrave 3\n\
+                      j:jumps  users:86-54\n\
+                      t:1 name:\"users\"  pos:86 len:54   name:str note:\"str?\"\n";
+        let text = std::fs::read_to_string(&path)?;
+        assert_eq!((header.len(), records.len()), (86, 54));
+        assert!(text.starts_with(&format!("{}{}c:crc32 header:", header, records)));
+
+        let mut reader = Reader::new(&path)?;
+        assert!(reader.verify_checksums()?.passed());
+        let ids: Vec<_> = reader
+            .records("users")?
+            .map(|record| record.map(|record| record.id))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(ids, ["1", "3"]);
+        Ok(())
+    }
+
+    #[test]
+    fn compact_moves_every_region() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{compact, Document, Reader, Writer, WriterOptions};
+
+        let path = std::env::temp_dir().join("xrave-test-compact-regions.xrv");
+        let options = WriterOptions {
+            checksums: true,
+            dictionary_threshold: Some(1),
+            ..WriterOptions::default()
+        };
+        let mut writer = Writer::with_options(std::fs::File::create(&path)?, options);
+        writer.add_table("users", &[("name", "str"), ("status", "str")])?;
+        writer.add_table("tags", &[("label", "str")])?;
+        writer.add_style("bold", &[("weight", "700")])?;
+        for id in 0..4 {
+            let fields = [("name", "Anna"), ("status", "active")];
+            writer.add_record("users", &id.to_string(), &fields)?;
+            writer.add_record("tags", &format!("t{}", id), &[("label", "admin")])?;
+        }
+        writer.finish()?;
+
+        let mut reader = Reader::new(&path)?;
+        for table in ["users", "tags"] {
+            let offset = reader.records(table)?.nth(1).unwrap()?.span.offset;
+            reader.delete_record(offset)?;
+        }
+        let before = Document::open(&path)?;
+        compact(&path)?;
+        let mut reader = Reader::new(&path)?;
+        assert!(reader.verify_checksums()?.passed());
+        assert_eq!(reader.records("tags")?.count(), 3);
+        assert_eq!(Document::open(&path)?, before);
+        Ok(())
+    }
+}
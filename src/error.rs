@@ -0,0 +1,1512 @@
+use std::path::{Path, PathBuf};
+
+use crate::{reader::Limit, schema::SchemaDiff};
+
+impl std::fmt::Display for XRVErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XRVErr::FailToOpenFile { path, source } => {
+                write!(f, "failed to open {}: {}", path.display(), source)
+            }
+            XRVErr::PathUnknown => write!(
+                f,
+                "the reader was opened from a file handle and cannot open its file again"
+            ),
+            XRVErr::FailToReadLine(err) => write!(f, "failed to read line: {}", err),
+            XRVErr::FailToSeek(err) => write!(f, "failed to seek: {}", err),
+            XRVErr::SourceNotSeekable => write!(f, "source is not seekable"),
+            XRVErr::FileTruncated { len, offset } => write!(
+                f,
+                "file shrank to {} bytes, below the last read offset {}",
+                len, offset
+            ),
+            XRVErr::CheckpointStale { path } => write!(
+                f,
+                "{} changed since the checkpoint was taken",
+                path.display()
+            ),
+            XRVErr::CantParseCheckpoint => write!(f, "checkpoint is malformed"),
+            XRVErr::FileLocked => write!(f, "file is locked by another handle"),
+            XRVErr::LimitExceeded(limit) => match limit {
+                Limit::FieldsPerLine(max) => write!(f, "line has more than {} fields", max),
+                Limit::ValueLen(max) => write!(f, "value is longer than {} bytes", max),
+                Limit::Tables(max) => write!(f, "file has more than {} tables", max),
+                Limit::RecordsPerTable(max) => {
+                    write!(f, "table has more than {} records", max)
+                }
+            },
+            XRVErr::MemoryBudgetExceeded { used, budget } => write!(
+                f,
+                "records need about {} bytes, more than the memory budget of {}",
+                used, budget
+            ),
+            XRVErr::UnexpectedEof => write!(f, "unexpected end of file"),
+            XRVErr::FailToWrite(err) => write!(f, "failed to write: {}", err),
+            XRVErr::LineTooLong { line, len, max } => write!(
+                f,
+                "line {} is {} bytes long, more than the maximum of {}",
+                line, len, max
+            ),
+            XRVErr::NameMustFolowedByColon => write!(f, "field name must be followed by ':'"),
+            XRVErr::NameMustNotContainQoutes => write!(f, "field name must not contain '\"'"),
+            XRVErr::ExpectSpaceOrAlpha => write!(f, "expected a space or a field name"),
+            XRVErr::ExpectAlpha => write!(f, "expected a value after ':'"),
+            XRVErr::ExpectingSpaceOrNewline => {
+                write!(f, "expected a space or end of line after value")
+            }
+            XRVErr::ExpectingQouteNotNewline => write!(f, "unterminated quoted value"),
+            XRVErr::FailedToConsumePairs => write!(f, "field is missing its value"),
+            XRVErr::FailToGetLineKind => write!(f, "line has no kind"),
+            XRVErr::FailToGetLineName => write!(f, "line has no name"),
+            XRVErr::NotTableLine => write!(f, "not a table line"),
+            XRVErr::CantParseFieldUsizeValue => write!(f, "value is not an unsigned integer"),
+            XRVErr::CantParseFieldStrName { line, col } => {
+                write!(f, "field name is not valid UTF-8 at {}:{}", line, col)
+            }
+            XRVErr::CantParseFieldStrValue { line, col } => {
+                write!(f, "field value is not valid UTF-8 at {}:{}", line, col)
+            }
+            XRVErr::CantParseFieldName => write!(f, "line name is not valid UTF-8"),
+            XRVErr::RegionOverflow { pos, len } => {
+                write!(
+                    f,
+                    "region of {} bytes at {} ends past the largest offset",
+                    len, pos
+                )
+            }
+            XRVErr::SectionOutOfBounds {
+                name,
+                seek,
+                len,
+                file_len,
+            } => write!(
+                f,
+                "region of '{}' at {}-{} runs past the end of the {}-byte file",
+                name, seek, len, file_len
+            ),
+            XRVErr::RegionStartsMidLine { table, offset } => write!(
+                f,
+                "region of '{}' starts inside a line at byte {}",
+                table, offset
+            ),
+            XRVErr::RegionEndsMidLine { table, offset } => write!(
+                f,
+                "region of '{}' ends inside a line at byte {}",
+                table, offset
+            ),
+            XRVErr::MissingTableFields(fields) => {
+                write!(f, "table line is missing {}", fields.join(", "))
+            }
+            XRVErr::TableLineTooShort {
+                expected,
+                got,
+                line,
+            } => write!(
+                f,
+                "table line {} has {} fields, expected at least {}",
+                line, got, expected
+            ),
+            XRVErr::ItsNotAJumpsLine => write!(f, "first line is not a jumps line"),
+            XRVErr::DuplicateJump(name) => write!(f, "duplicate jump '{}'", name),
+            XRVErr::DuplicateTable(name) => write!(f, "duplicate table '{}'", name),
+            XRVErr::DuplicateStyle(name) => write!(f, "duplicate style '{}'", name),
+            XRVErr::DuplicateDictionary(name) => {
+                write!(f, "duplicate dictionary for table '{}'", name)
+            }
+            XRVErr::UnknownDictionaryToken { table, token } => write!(
+                f,
+                "dictionary of table '{}' has no token '{}'",
+                table, token
+            ),
+            XRVErr::UnknownTable(name) => write!(f, "unknown table '{}'", name),
+            XRVErr::InvalidIdentifier(name) => write!(f, "invalid identifier '{}'", name),
+            XRVErr::DuplicateColumn { table, column } => {
+                write!(f, "duplicate column '{}' in table '{}'", column, table)
+            }
+            XRVErr::ColumnGroupCollision { table, column } => write!(
+                f,
+                "column '{}' in table '{}' is also the group of dotted columns",
+                column, table
+            ),
+            XRVErr::DuplicateField { line, name } => {
+                write!(f, "duplicate field '{}' on line {}", name, line)
+            }
+            XRVErr::UnrepresentableValue(value) => {
+                write!(f, "value {:?} cannot be written to a line", value)
+            }
+            XRVErr::InvalidValueByte { byte, field } => write!(
+                f,
+                "value of field '{}' contains byte 0x{:02x}, which cannot be written",
+                field, byte
+            ),
+            XRVErr::InvalidNameByte { byte, name } => write!(
+                f,
+                "name {:?} contains byte 0x{:02x}, which cannot be read back",
+                name, byte
+            ),
+            XRVErr::ValueTooLongForInPlaceUpdate { len, max } => write!(
+                f,
+                "new value takes {} bytes, only {} are available in place",
+                len, max
+            ),
+            XRVErr::ValueSpansLines(column) => write!(
+                f,
+                "value of column '{}' continues on the next line and cannot be updated in place",
+                column
+            ),
+            XRVErr::UnknownReferencedTable {
+                table,
+                column,
+                target,
+            } => write!(
+                f,
+                "column '{}' in table '{}' references unknown table '{}'",
+                column, table, target
+            ),
+            XRVErr::DuplicateKey { table, key } => {
+                write!(f, "duplicate key '{}' in table '{}'", key, table)
+            }
+            XRVErr::DuplicateRecordId { table, id } => {
+                write!(f, "duplicate record id '{}' in table '{}'", id, table)
+            }
+            XRVErr::MissingRecordId { table } => {
+                write!(f, "record added to table '{}' without an id", table)
+            }
+            XRVErr::IdsExhausted { table } => {
+                write!(f, "no sequential ids left in table '{}'", table)
+            }
+            XRVErr::MissingColumn { table, column } => {
+                write!(
+                    f,
+                    "required column '{}' missing in table '{}'",
+                    column, table
+                )
+            }
+            XRVErr::RepeatedColumn { table, column } => write!(
+                f,
+                "column '{}' in table '{}' is not a list but repeats",
+                column, table
+            ),
+            XRVErr::EmptyColumn { table, column } => {
+                write!(
+                    f,
+                    "required column '{}' is empty in table '{}'",
+                    column, table
+                )
+            }
+            XRVErr::InvalidColumnKind(kind) => write!(f, "invalid column kind '{}'", kind),
+            XRVErr::InvalidVersionLine(line) => {
+                write!(f, "malformed version line '{}', expected 'v:xrave N'", line)
+            }
+            XRVErr::UnsupportedVersion {
+                found,
+                max_supported,
+            } => write!(
+                f,
+                "file is format version {}, only versions up to {} are supported",
+                found, max_supported
+            ),
+            XRVErr::InvalidBase64 { column, pos } => write!(
+                f,
+                "value of column '{}' is not valid base64 at byte {}",
+                column, pos
+            ),
+            #[cfg(feature = "sqlite")]
+            XRVErr::Sqlite(err) => write!(f, "sqlite: {}", err),
+            #[cfg(feature = "sqlite")]
+            XRVErr::SqliteType { table, column } => write!(
+                f,
+                "column '{}' of table '{}' has no SQLite counterpart",
+                column, table
+            ),
+            #[cfg(feature = "testkit")]
+            XRVErr::InvalidGenSpec(reason) => write!(f, "invalid generator spec: {}", reason),
+            XRVErr::InvalidTypedValue {
+                table,
+                column,
+                value,
+                expected,
+            } => write!(
+                f,
+                "value '{}' of column '{}' in table '{}' is not a valid {}",
+                value, column, table, expected
+            ),
+            XRVErr::FractionalSize {
+                table,
+                column,
+                value,
+            } => write!(
+                f,
+                "value '{}' of column '{}' in table '{}' is not a whole number of bytes",
+                value, column, table
+            ),
+            XRVErr::NegativeDuration {
+                table,
+                column,
+                value,
+            } => write!(
+                f,
+                "value '{}' of column '{}' in table '{}' is a negative duration",
+                value, column, table
+            ),
+            XRVErr::ZeroSampleInterval => write!(f, "sampling interval must be at least 1"),
+            XRVErr::InvalidDialect {
+                separator,
+                quote,
+                null,
+            } => write!(
+                f,
+                "invalid dialect: separator 0x{:02x}, quote 0x{:02x} and null 0x{:02x} must be \
+                 distinct ASCII symbols other than ':', '\\' and line breaks",
+                separator, quote, null
+            ),
+            XRVErr::ValueOutOfRange {
+                table,
+                column,
+                value,
+                min,
+                max,
+            } => write!(
+                f,
+                "value '{}' of column '{}' in table '{}' is outside {}..{}",
+                value, column, table, min, max
+            ),
+            XRVErr::UnknownColumn { table, column } => {
+                write!(f, "unknown column '{}' in table '{}'", column, table)
+            }
+            XRVErr::ListColumn { table, column } => write!(
+                f,
+                "column '{}' in table '{}' is a list, not one value per record",
+                column, table
+            ),
+            XRVErr::FieldExists { table, id, field } => write!(
+                f,
+                "record '{}' in table '{}' already has a field '{}'",
+                id, table, field
+            ),
+            XRVErr::SchemaMismatch { table, columns } => write!(
+                f,
+                "table '{}' differs in columns {}",
+                table,
+                columns.join(", ")
+            ),
+            XRVErr::SchemaDeviations(diffs) => {
+                write!(f, "file deviates from the schema: ")?;
+                for (idx, diff) in diffs.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", diff)?;
+                }
+                Ok(())
+            }
+            XRVErr::ShardSchemaMismatch { first, shards } => {
+                write!(f, "shards deviate from {}:", first.display())?;
+                for (idx, (path, diffs)) in shards.iter().enumerate() {
+                    let sep = if idx == 0 { " " } else { "; " };
+                    write!(f, "{}{}:", sep, path.display())?;
+                    for (idx, diff) in diffs.iter().enumerate() {
+                        let sep = if idx == 0 { " " } else { ", " };
+                        write!(f, "{}{}", sep, diff)?;
+                    }
+                }
+                Ok(())
+            }
+            XRVErr::MergeConflict { table, id } => {
+                write!(f, "conflicting record '{}' in table '{}'", id, table)
+            }
+            XRVErr::RecordOutsideTable { line } => {
+                write!(f, "record on line {} lies outside every table region", line)
+            }
+            XRVErr::InTable { table, err } => write!(f, "in table '{}': {}", table, err),
+            XRVErr::Resynced { skipped, err } => {
+                write!(f, "{}\n(skipped {} bytes to the next line)", err, skipped)
+            }
+            XRVErr::AtLine { snippet, .. } => write!(f, "{}", self.render(snippet)),
+            XRVErr::WorkerPanicked => write!(f, "worker thread panicked"),
+            XRVErr::CantParseChecksum => write!(f, "checksum is not a hexadecimal u32"),
+            XRVErr::NotStyleLine => write!(f, "not a style line"),
+            XRVErr::NotRecordLine => write!(f, "not a record line"),
+            XRVErr::UnkwnownLineKind => write!(f, "unknown line kind"),
+        }
+    }
+}
+
+impl XRVErr {
+    /// Line number and 1-based byte column the error points at. The column
+    /// falls back to 1 for errors that only know their line, or none at all.
+    pub(crate) fn position(&self) -> (Option<usize>, usize) {
+        match self {
+            XRVErr::AtLine { line, col, .. }
+            | XRVErr::CantParseFieldStrName { line, col }
+            | XRVErr::CantParseFieldStrValue { line, col } => (Some(*line), *col),
+            XRVErr::LineTooLong { line, .. }
+            | XRVErr::TableLineTooShort { line, .. }
+            | XRVErr::RecordOutsideTable { line }
+            | XRVErr::DuplicateField { line, .. } => (Some(*line), 1),
+            XRVErr::Resynced { err, .. } => err.position(),
+            _ => (None, 1),
+        }
+    }
+
+    /// A stable code for the error, such as `E_QUOTE_UNTERMINATED`, for
+    /// callers that store or match on errors. Codes never change once
+    /// given, even when the variant is renamed. Errors that wrap another,
+    /// to place it on a line or in a table, have the code of the one they
+    /// wrap.
+    ///
+    /// ```
+    /// use xrave::{parse_line, ErrorClass, XRVErr};
+    ///
+    /// let err = parse_line(b"r:1 name:\"Anna").unwrap_err();
+    /// assert!(matches!(err, XRVErr::AtLine { .. }));
+    /// assert_eq!(err.code(), "E_QUOTE_UNTERMINATED");
+    /// assert_eq!(err.class(), ErrorClass::Syntax);
+    /// assert!(err.is_syntax() && !err.is_io());
+    ///
+    /// let missing = std::env::temp_dir().join("xrave-doc-no-such-file.xrv");
+    /// let err = xrave::Reader::new(&missing).unwrap_err();
+    /// assert_eq!((err.code(), err.is_io()), ("E_OPEN_FAILED", true));
+    ///
+    /// let wrapped = XRVErr::InTable { table: "users".into(), err: Box::new(XRVErr::NotRecordLine) };
+    /// assert_eq!(wrapped.code(), XRVErr::NotRecordLine.code());
+    /// assert_eq!(XRVErr::UnknownTable("users".into()).class(), ErrorClass::Schema);
+    /// ```
+    pub fn code(&self) -> &'static str {
+        match self {
+            XRVErr::InTable { err, .. }
+            | XRVErr::Resynced { err, .. }
+            | XRVErr::AtLine { err, .. } => err.code(),
+            XRVErr::FailToOpenFile { .. } => "E_OPEN_FAILED",
+            XRVErr::PathUnknown => "E_PATH_UNKNOWN",
+            XRVErr::FailToReadLine(_) => "E_READ_FAILED",
+            XRVErr::FailToSeek(_) => "E_SEEK_FAILED",
+            XRVErr::SourceNotSeekable => "E_SOURCE_NOT_SEEKABLE",
+            XRVErr::FileTruncated { .. } => "E_FILE_TRUNCATED",
+            XRVErr::CheckpointStale { .. } => "E_CHECKPOINT_STALE",
+            XRVErr::CantParseCheckpoint => "E_INVALID_CHECKPOINT",
+            XRVErr::FileLocked => "E_FILE_LOCKED",
+            XRVErr::LimitExceeded(_) => "E_LIMIT_EXCEEDED",
+            XRVErr::MemoryBudgetExceeded { .. } => "E_MEMORY_BUDGET_EXCEEDED",
+            XRVErr::UnexpectedEof => "E_UNEXPECTED_EOF",
+            XRVErr::FailToWrite(_) => "E_WRITE_FAILED",
+            XRVErr::LineTooLong { .. } => "E_LINE_TOO_LONG",
+            XRVErr::NameMustFolowedByColon => "E_NAME_WITHOUT_COLON",
+            XRVErr::NameMustNotContainQoutes => "E_QUOTE_IN_NAME",
+            XRVErr::ExpectSpaceOrAlpha => "E_EXPECTED_FIELD",
+            XRVErr::ExpectAlpha => "E_EXPECTED_VALUE",
+            XRVErr::ExpectingSpaceOrNewline => "E_EXPECTED_SEPARATOR",
+            XRVErr::ExpectingQouteNotNewline => "E_QUOTE_UNTERMINATED",
+            XRVErr::FailedToConsumePairs => "E_FIELD_WITHOUT_VALUE",
+            XRVErr::FailToGetLineKind => "E_LINE_WITHOUT_KIND",
+            XRVErr::FailToGetLineName => "E_LINE_WITHOUT_NAME",
+            XRVErr::NotTableLine => "E_NOT_TABLE_LINE",
+            XRVErr::CantParseFieldUsizeValue => "E_NOT_UNSIGNED",
+            XRVErr::CantParseFieldStrName { .. } => "E_FIELD_NAME_NOT_UTF8",
+            XRVErr::CantParseFieldStrValue { .. } => "E_FIELD_VALUE_NOT_UTF8",
+            XRVErr::CantParseFieldName => "E_LINE_NAME_NOT_UTF8",
+            XRVErr::RegionOverflow { .. } => "E_REGION_OVERFLOW",
+            XRVErr::SectionOutOfBounds { .. } => "E_REGION_OUT_OF_BOUNDS",
+            XRVErr::RegionStartsMidLine { .. } => "E_REGION_STARTS_MID_LINE",
+            XRVErr::RegionEndsMidLine { .. } => "E_REGION_ENDS_MID_LINE",
+            XRVErr::MissingTableFields(_) => "E_TABLE_FIELDS_MISSING",
+            XRVErr::TableLineTooShort { .. } => "E_TABLE_LINE_TOO_SHORT",
+            XRVErr::ItsNotAJumpsLine => "E_NOT_JUMPS_LINE",
+            XRVErr::DuplicateJump(_) => "E_DUPLICATE_JUMP",
+            XRVErr::DuplicateTable(_) => "E_DUPLICATE_TABLE",
+            XRVErr::DuplicateStyle(_) => "E_DUPLICATE_STYLE",
+            XRVErr::DuplicateDictionary(_) => "E_DUPLICATE_DICTIONARY",
+            XRVErr::UnknownDictionaryToken { .. } => "E_UNKNOWN_DICTIONARY_TOKEN",
+            XRVErr::UnknownTable(_) => "E_UNKNOWN_TABLE",
+            XRVErr::RecordOutsideTable { .. } => "E_RECORD_OUTSIDE_TABLE",
+            XRVErr::InvalidIdentifier(_) => "E_INVALID_IDENTIFIER",
+            XRVErr::DuplicateColumn { .. } => "E_DUPLICATE_COLUMN",
+            XRVErr::ColumnGroupCollision { .. } => "E_COLUMN_GROUP_COLLISION",
+            XRVErr::DuplicateField { .. } => "E_DUPLICATE_FIELD",
+            XRVErr::UnrepresentableValue(_) => "E_UNREPRESENTABLE_VALUE",
+            XRVErr::InvalidValueByte { .. } => "E_INVALID_VALUE_BYTE",
+            XRVErr::InvalidNameByte { .. } => "E_INVALID_NAME_BYTE",
+            XRVErr::ValueTooLongForInPlaceUpdate { .. } => "E_VALUE_TOO_LONG_IN_PLACE",
+            XRVErr::ValueSpansLines(_) => "E_VALUE_SPANS_LINES",
+            XRVErr::UnknownColumn { .. } => "E_UNKNOWN_COLUMN",
+            XRVErr::ListColumn { .. } => "E_LIST_COLUMN",
+            XRVErr::MissingColumn { .. } => "E_MISSING_COLUMN",
+            XRVErr::DuplicateKey { .. } => "E_DUPLICATE_KEY",
+            XRVErr::DuplicateRecordId { .. } => "E_DUPLICATE_RECORD_ID",
+            XRVErr::MissingRecordId { .. } => "E_MISSING_RECORD_ID",
+            XRVErr::IdsExhausted { .. } => "E_IDS_EXHAUSTED",
+            XRVErr::UnknownReferencedTable { .. } => "E_UNKNOWN_REFERENCED_TABLE",
+            XRVErr::EmptyColumn { .. } => "E_EMPTY_COLUMN",
+            XRVErr::RepeatedColumn { .. } => "E_REPEATED_COLUMN",
+            XRVErr::InvalidColumnKind(_) => "E_INVALID_COLUMN_KIND",
+            XRVErr::InvalidTypedValue { .. } => "E_INVALID_TYPED_VALUE",
+            XRVErr::FractionalSize { .. } => "E_FRACTIONAL_SIZE",
+            XRVErr::NegativeDuration { .. } => "E_NEGATIVE_DURATION",
+            XRVErr::ZeroSampleInterval => "E_ZERO_SAMPLE_INTERVAL",
+            XRVErr::InvalidDialect { .. } => "E_INVALID_DIALECT",
+            XRVErr::InvalidVersionLine(_) => "E_INVALID_VERSION_LINE",
+            XRVErr::UnsupportedVersion { .. } => "E_UNSUPPORTED_VERSION",
+            XRVErr::InvalidBase64 { .. } => "E_INVALID_BASE64",
+            #[cfg(feature = "sqlite")]
+            XRVErr::Sqlite(_) => "E_SQLITE",
+            #[cfg(feature = "sqlite")]
+            XRVErr::SqliteType { .. } => "E_SQLITE_TYPE",
+            #[cfg(feature = "testkit")]
+            XRVErr::InvalidGenSpec(_) => "E_INVALID_GEN_SPEC",
+            XRVErr::ValueOutOfRange { .. } => "E_VALUE_OUT_OF_RANGE",
+            XRVErr::FieldExists { .. } => "E_FIELD_EXISTS",
+            XRVErr::SchemaMismatch { .. } => "E_SCHEMA_MISMATCH",
+            XRVErr::SchemaDeviations(_) => "E_SCHEMA_DEVIATIONS",
+            XRVErr::ShardSchemaMismatch { .. } => "E_SHARD_SCHEMA_MISMATCH",
+            XRVErr::MergeConflict { .. } => "E_MERGE_CONFLICT",
+            XRVErr::WorkerPanicked => "E_WORKER_PANICKED",
+            XRVErr::CantParseChecksum => "E_INVALID_CHECKSUM",
+            XRVErr::NotStyleLine => "E_NOT_STYLE_LINE",
+            XRVErr::NotRecordLine => "E_NOT_RECORD_LINE",
+            XRVErr::UnkwnownLineKind => "E_UNKNOWN_LINE_KIND",
+        }
+    }
+
+    /// What kind of failure the error is; wrapping errors take the class of
+    /// the one they wrap.
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            XRVErr::InTable { err, .. }
+            | XRVErr::Resynced { err, .. }
+            | XRVErr::AtLine { err, .. } => err.class(),
+            XRVErr::FailToOpenFile { .. } => ErrorClass::Io,
+            XRVErr::PathUnknown => ErrorClass::Internal,
+            XRVErr::FailToReadLine(_) => ErrorClass::Io,
+            XRVErr::FailToSeek(_) => ErrorClass::Io,
+            XRVErr::SourceNotSeekable => ErrorClass::Io,
+            XRVErr::FileTruncated { .. } => ErrorClass::Io,
+            XRVErr::CheckpointStale { .. } => ErrorClass::Io,
+            XRVErr::CantParseCheckpoint => ErrorClass::Internal,
+            XRVErr::FileLocked => ErrorClass::Io,
+            XRVErr::LimitExceeded(_) => ErrorClass::Limit,
+            XRVErr::MemoryBudgetExceeded { .. } => ErrorClass::Limit,
+            XRVErr::UnexpectedEof => ErrorClass::Syntax,
+            XRVErr::FailToWrite(_) => ErrorClass::Io,
+            XRVErr::LineTooLong { .. } => ErrorClass::Limit,
+            XRVErr::NameMustFolowedByColon => ErrorClass::Syntax,
+            XRVErr::NameMustNotContainQoutes => ErrorClass::Syntax,
+            XRVErr::ExpectSpaceOrAlpha => ErrorClass::Syntax,
+            XRVErr::ExpectAlpha => ErrorClass::Syntax,
+            XRVErr::ExpectingSpaceOrNewline => ErrorClass::Syntax,
+            XRVErr::ExpectingQouteNotNewline => ErrorClass::Syntax,
+            XRVErr::FailedToConsumePairs => ErrorClass::Syntax,
+            XRVErr::FailToGetLineKind => ErrorClass::Syntax,
+            XRVErr::FailToGetLineName => ErrorClass::Syntax,
+            XRVErr::NotTableLine => ErrorClass::Syntax,
+            XRVErr::CantParseFieldUsizeValue => ErrorClass::Syntax,
+            XRVErr::CantParseFieldStrName { .. } => ErrorClass::Syntax,
+            XRVErr::CantParseFieldStrValue { .. } => ErrorClass::Syntax,
+            XRVErr::CantParseFieldName => ErrorClass::Syntax,
+            XRVErr::RegionOverflow { .. } => ErrorClass::Syntax,
+            XRVErr::SectionOutOfBounds { .. } => ErrorClass::Syntax,
+            XRVErr::RegionStartsMidLine { .. } => ErrorClass::Syntax,
+            XRVErr::RegionEndsMidLine { .. } => ErrorClass::Syntax,
+            XRVErr::MissingTableFields(_) => ErrorClass::Syntax,
+            XRVErr::TableLineTooShort { .. } => ErrorClass::Syntax,
+            XRVErr::ItsNotAJumpsLine => ErrorClass::Syntax,
+            XRVErr::DuplicateJump(_) => ErrorClass::Syntax,
+            XRVErr::DuplicateTable(_) => ErrorClass::Syntax,
+            XRVErr::DuplicateStyle(_) => ErrorClass::Syntax,
+            XRVErr::DuplicateDictionary(_) => ErrorClass::Syntax,
+            XRVErr::UnknownDictionaryToken { .. } => ErrorClass::Schema,
+            XRVErr::UnknownTable(_) => ErrorClass::Schema,
+            XRVErr::RecordOutsideTable { .. } => ErrorClass::Syntax,
+            XRVErr::InvalidIdentifier(_) => ErrorClass::Syntax,
+            XRVErr::DuplicateColumn { .. } => ErrorClass::Schema,
+            XRVErr::ColumnGroupCollision { .. } => ErrorClass::Schema,
+            XRVErr::DuplicateField { .. } => ErrorClass::Syntax,
+            XRVErr::UnrepresentableValue(_) => ErrorClass::Syntax,
+            XRVErr::InvalidValueByte { .. } => ErrorClass::Syntax,
+            XRVErr::InvalidNameByte { .. } => ErrorClass::Syntax,
+            XRVErr::ValueTooLongForInPlaceUpdate { .. } => ErrorClass::Limit,
+            XRVErr::ValueSpansLines(_) => ErrorClass::Syntax,
+            XRVErr::UnknownColumn { .. } => ErrorClass::Schema,
+            XRVErr::ListColumn { .. } => ErrorClass::Schema,
+            XRVErr::MissingColumn { .. } => ErrorClass::Schema,
+            XRVErr::DuplicateKey { .. } => ErrorClass::Schema,
+            XRVErr::DuplicateRecordId { .. } => ErrorClass::Schema,
+            XRVErr::MissingRecordId { .. } => ErrorClass::Schema,
+            XRVErr::IdsExhausted { .. } => ErrorClass::Schema,
+            XRVErr::UnknownReferencedTable { .. } => ErrorClass::Schema,
+            XRVErr::EmptyColumn { .. } => ErrorClass::Schema,
+            XRVErr::RepeatedColumn { .. } => ErrorClass::Schema,
+            XRVErr::InvalidColumnKind(_) => ErrorClass::Schema,
+            XRVErr::InvalidTypedValue { .. } => ErrorClass::Schema,
+            XRVErr::FractionalSize { .. } => ErrorClass::Schema,
+            XRVErr::NegativeDuration { .. } => ErrorClass::Schema,
+            XRVErr::ZeroSampleInterval => ErrorClass::Internal,
+            XRVErr::InvalidDialect { .. } => ErrorClass::Internal,
+            XRVErr::InvalidVersionLine(_) => ErrorClass::Syntax,
+            XRVErr::UnsupportedVersion { .. } => ErrorClass::Syntax,
+            XRVErr::InvalidBase64 { .. } => ErrorClass::Schema,
+            #[cfg(feature = "sqlite")]
+            XRVErr::Sqlite(_) => ErrorClass::Io,
+            #[cfg(feature = "sqlite")]
+            XRVErr::SqliteType { .. } => ErrorClass::Schema,
+            #[cfg(feature = "testkit")]
+            XRVErr::InvalidGenSpec(_) => ErrorClass::Internal,
+            XRVErr::ValueOutOfRange { .. } => ErrorClass::Schema,
+            XRVErr::FieldExists { .. } => ErrorClass::Schema,
+            XRVErr::SchemaMismatch { .. } => ErrorClass::Schema,
+            XRVErr::SchemaDeviations(_) => ErrorClass::Schema,
+            XRVErr::ShardSchemaMismatch { .. } => ErrorClass::Schema,
+            XRVErr::MergeConflict { .. } => ErrorClass::Schema,
+            XRVErr::WorkerPanicked => ErrorClass::Internal,
+            XRVErr::CantParseChecksum => ErrorClass::Syntax,
+            XRVErr::NotStyleLine => ErrorClass::Syntax,
+            XRVErr::NotRecordLine => ErrorClass::Syntax,
+            XRVErr::UnkwnownLineKind => ErrorClass::Syntax,
+        }
+    }
+
+    /// Whether reading or writing the underlying file or database failed.
+    pub fn is_io(&self) -> bool {
+        self.class() == ErrorClass::Io
+    }
+
+    /// Whether the input is not well-formed xrave.
+    pub fn is_syntax(&self) -> bool {
+        self.class() == ErrorClass::Syntax
+    }
+
+    /// Formats the error like a compiler diagnostic: the error name and
+    /// message, the line number, `source_line` and a caret under the failing
+    /// column.
+    ///
+    /// ```
+    /// use xrave::{Reader, XRVErr};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-render.xrv");
+    /// std::fs::write(&path, "j:jumps\nr:1 name:\"Anna\"Smith\n")?;
+    ///
+    /// let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+    /// let err = reader.next_record().unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "error[ExpectingSpaceOrNewline]: expected a space or end of line after value\n \
+    ///      --> line 2:16\n  |\n2 | r:1 name:\"Anna\"Smith\n  |                ^",
+    /// );
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn render(&self, source_line: &str) -> String {
+        let (line, col) = self.position();
+        let err = match self {
+            XRVErr::AtLine { err, .. } => err.as_ref(),
+            _ => self,
+        };
+
+        let debug = format!("{:?}", err);
+        let name: String = debug
+            .chars()
+            .take_while(char::is_ascii_alphanumeric)
+            .collect();
+        let gutter = line.map(|line| line.to_string()).unwrap_or_default();
+        let pad = " ".repeat(gutter.len());
+        let caret = source_line
+            .char_indices()
+            .take_while(|(idx, _)| *idx + 1 < col)
+            .count();
+
+        let mut out = format!("error[{}]: {}\n", name, err);
+        if let Some(line) = line {
+            out.push_str(&format!("{}--> line {}:{}\n", pad, line, col));
+        }
+        out.push_str(&format!("{} |\n", pad));
+        out.push_str(&format!("{} | {}\n", gutter, source_line));
+        out.push_str(&format!("{} | {}^", pad, " ".repeat(caret)));
+        out
+    }
+}
+
+impl std::error::Error for XRVErr {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            XRVErr::FailToOpenFile { source: err, .. }
+            | XRVErr::FailToReadLine(err)
+            | XRVErr::FailToSeek(err)
+            | XRVErr::FailToWrite(err) => Some(err),
+            #[cfg(feature = "sqlite")]
+            XRVErr::Sqlite(err) => Some(err),
+            XRVErr::InTable { err, .. }
+            | XRVErr::AtLine { err, .. }
+            | XRVErr::Resynced { err, .. } => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Broad kind of an `XRVErr`, returned by `XRVErr::class`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorClass {
+    /// Opening, reading, seeking, locking or writing a file or database
+    /// failed.
+    Io,
+    /// The input is not well-formed xrave, or a value cannot be written as
+    /// such.
+    Syntax,
+    /// The lines are well-formed but disagree with their declared or
+    /// expected tables, columns, kinds or keys.
+    Schema,
+    /// A limit set in `ParseOptions` or by the format was exceeded.
+    Limit,
+    /// An argument or option the call cannot work with, or a failure inside
+    /// the library itself.
+    Internal,
+}
+
+#[derive(Debug)]
+pub enum XRVErr {
+    FailToOpenFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// Work that needs a handle of its own on a reader made with
+    /// `Reader::from_file`, which has no path to open again.
+    PathUnknown,
+    FailToReadLine(std::io::Error),
+    FailToSeek(std::io::Error),
+    SourceNotSeekable,
+    FileTruncated {
+        len: u64,
+        offset: u64,
+    },
+    /// The file given to `Reader::restore` is not the one the checkpoint
+    /// was taken on.
+    CheckpointStale {
+        path: PathBuf,
+    },
+    CantParseCheckpoint,
+    FileLocked,
+    LimitExceeded(Limit),
+    /// Records of a document outgrowing `LoadOptions::max_memory_bytes`, by
+    /// the estimate of `Document::open_with_options`.
+    MemoryBudgetExceeded {
+        used: usize,
+        budget: usize,
+    },
+    UnexpectedEof,
+    FailToWrite(std::io::Error),
+    LineTooLong {
+        line: usize,
+        len: usize,
+        max: usize,
+    },
+    NameMustFolowedByColon,
+    NameMustNotContainQoutes,
+    ExpectSpaceOrAlpha,
+    ExpectAlpha,
+    ExpectingSpaceOrNewline,
+    ExpectingQouteNotNewline,
+    FailedToConsumePairs,
+    FailToGetLineKind,
+    FailToGetLineName,
+    NotTableLine,
+    CantParseFieldUsizeValue,
+    CantParseFieldStrName {
+        line: usize,
+        col: usize,
+    },
+    CantParseFieldStrValue {
+        line: usize,
+        col: usize,
+    },
+    CantParseFieldName,
+    RegionOverflow {
+        pos: usize,
+        len: usize,
+    },
+    /// A jump or table region that runs past the end of the file.
+    SectionOutOfBounds {
+        name: String,
+        seek: usize,
+        len: usize,
+        file_len: u64,
+    },
+    /// A table region whose first byte does not start a line.
+    RegionStartsMidLine {
+        table: String,
+        offset: u64,
+    },
+    /// A table region whose end falls inside a line rather than after its
+    /// terminator.
+    RegionEndsMidLine {
+        table: String,
+        offset: u64,
+    },
+    MissingTableFields(Vec<String>),
+    TableLineTooShort {
+        expected: usize,
+        got: usize,
+        line: usize,
+    },
+    ItsNotAJumpsLine,
+    DuplicateJump(String),
+    DuplicateTable(String),
+    DuplicateStyle(String),
+    DuplicateDictionary(String),
+    /// A `^token` value in a table whose dictionary lacks the token.
+    UnknownDictionaryToken {
+        table: String,
+        token: String,
+    },
+    UnknownTable(String),
+    RecordOutsideTable {
+        line: usize,
+    },
+    InvalidIdentifier(String),
+    DuplicateColumn {
+        table: String,
+        column: String,
+    },
+    /// A column named like the group of dotted columns such as `addr` next
+    /// to `addr.city`, which cannot both be nested.
+    ColumnGroupCollision {
+        table: String,
+        column: String,
+    },
+    /// A field name used more than once on a line, under
+    /// `DuplicateFields::Error`.
+    DuplicateField {
+        line: usize,
+        name: String,
+    },
+    UnrepresentableValue(String),
+    InvalidValueByte {
+        byte: u8,
+        field: String,
+    },
+    /// A table, column or field name holding a byte it cannot be read back
+    /// with, refused by the `Writer`.
+    InvalidNameByte {
+        byte: u8,
+        name: String,
+    },
+    ValueTooLongForInPlaceUpdate {
+        len: usize,
+        max: usize,
+    },
+    ValueSpansLines(String),
+    UnknownColumn {
+        table: String,
+        column: String,
+    },
+    ListColumn {
+        table: String,
+        column: String,
+    },
+    MissingColumn {
+        table: String,
+        column: String,
+    },
+    DuplicateKey {
+        table: String,
+        key: String,
+    },
+    /// A record id given twice in one table under an `IdStrategy` other
+    /// than `IdStrategy::Provided`.
+    DuplicateRecordId {
+        table: String,
+        id: String,
+    },
+    /// A record added with an empty id under `IdStrategy::Provided`.
+    MissingRecordId {
+        table: String,
+    },
+    /// A record added with an empty id under `IdStrategy::Sequential` once
+    /// the table's count has reached `u64::MAX`.
+    IdsExhausted {
+        table: String,
+    },
+    UnknownReferencedTable {
+        table: String,
+        column: String,
+        target: String,
+    },
+    EmptyColumn {
+        table: String,
+        column: String,
+    },
+    RepeatedColumn {
+        table: String,
+        column: String,
+    },
+    InvalidColumnKind(String),
+    InvalidTypedValue {
+        table: String,
+        column: String,
+        value: String,
+        expected: &'static str,
+    },
+    /// A `size` value that comes to a fraction of a byte, such as `1.5`.
+    FractionalSize {
+        table: String,
+        column: String,
+        value: String,
+    },
+    /// A `duration` value below zero, such as `-5s`.
+    NegativeDuration {
+        table: String,
+        column: String,
+        value: String,
+    },
+    ZeroSampleInterval,
+    InvalidDialect {
+        separator: u8,
+        quote: u8,
+        null: u8,
+    },
+    InvalidVersionLine(String),
+    UnsupportedVersion {
+        found: u32,
+        max_supported: u32,
+    },
+    InvalidBase64 {
+        column: String,
+        pos: usize,
+    },
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Error),
+    /// A column or value with no counterpart on the other side of a SQLite
+    /// conversion, such as a list column.
+    #[cfg(feature = "sqlite")]
+    SqliteType {
+        table: String,
+        column: String,
+    },
+    /// A `testkit::GenSpec` that cannot be generated from, and why.
+    #[cfg(feature = "testkit")]
+    InvalidGenSpec(String),
+    ValueOutOfRange {
+        table: String,
+        column: String,
+        value: String,
+        min: f64,
+        max: f64,
+    },
+    FieldExists {
+        table: String,
+        id: String,
+        field: String,
+    },
+    SchemaMismatch {
+        table: String,
+        columns: Vec<String>,
+    },
+    /// Every deviation of a file from the schema it was opened against.
+    SchemaDeviations(Vec<SchemaDiff>),
+    /// Shards of a `MultiReader` whose table headers deviate from those of
+    /// the `first`, each with its deviations.
+    ShardSchemaMismatch {
+        first: PathBuf,
+        shards: Vec<(PathBuf, Vec<SchemaDiff>)>,
+    },
+    MergeConflict {
+        table: String,
+        id: String,
+    },
+    InTable {
+        table: String,
+        err: Box<XRVErr>,
+    },
+    /// A line skipped in `ParseMode::Lenient` together with the following
+    /// `skipped` bytes passed over before the next line with a line kind.
+    Resynced {
+        skipped: u64,
+        err: Box<XRVErr>,
+    },
+    AtLine {
+        line: usize,
+        col: usize,
+        snippet: String,
+        err: Box<XRVErr>,
+    },
+    WorkerPanicked,
+    CantParseChecksum,
+    NotStyleLine,
+    NotRecordLine,
+    UnkwnownLineKind,
+}
+
+/// `XRVErr::FailToOpenFile` for `path`.
+pub(crate) fn open_error(path: &Path, source: std::io::Error) -> XRVErr {
+    XRVErr::FailToOpenFile {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn io() -> std::io::Error {
+        std::io::Error::other("test")
+    }
+
+    /// One of every error that does not wrap another, with the code and
+    /// class it was released with. Codes are stored by callers, so an entry
+    /// here may be added but never changed.
+    fn released() -> Vec<(XRVErr, &'static str, ErrorClass)> {
+        vec![
+            (
+                XRVErr::FailToOpenFile {
+                    path: "x".into(),
+                    source: io(),
+                },
+                "E_OPEN_FAILED",
+                ErrorClass::Io,
+            ),
+            (XRVErr::PathUnknown, "E_PATH_UNKNOWN", ErrorClass::Internal),
+            (
+                XRVErr::FailToReadLine(io()),
+                "E_READ_FAILED",
+                ErrorClass::Io,
+            ),
+            (XRVErr::FailToSeek(io()), "E_SEEK_FAILED", ErrorClass::Io),
+            (
+                XRVErr::SourceNotSeekable,
+                "E_SOURCE_NOT_SEEKABLE",
+                ErrorClass::Io,
+            ),
+            (
+                XRVErr::FileTruncated { len: 1, offset: 1 },
+                "E_FILE_TRUNCATED",
+                ErrorClass::Io,
+            ),
+            (
+                XRVErr::CheckpointStale { path: "x".into() },
+                "E_CHECKPOINT_STALE",
+                ErrorClass::Io,
+            ),
+            (
+                XRVErr::CantParseCheckpoint,
+                "E_INVALID_CHECKPOINT",
+                ErrorClass::Internal,
+            ),
+            (XRVErr::FileLocked, "E_FILE_LOCKED", ErrorClass::Io),
+            (
+                XRVErr::LimitExceeded(Limit::Tables(1)),
+                "E_LIMIT_EXCEEDED",
+                ErrorClass::Limit,
+            ),
+            (
+                XRVErr::MemoryBudgetExceeded { used: 1, budget: 1 },
+                "E_MEMORY_BUDGET_EXCEEDED",
+                ErrorClass::Limit,
+            ),
+            (
+                XRVErr::UnexpectedEof,
+                "E_UNEXPECTED_EOF",
+                ErrorClass::Syntax,
+            ),
+            (XRVErr::FailToWrite(io()), "E_WRITE_FAILED", ErrorClass::Io),
+            (
+                XRVErr::LineTooLong {
+                    line: 1,
+                    len: 1,
+                    max: 1,
+                },
+                "E_LINE_TOO_LONG",
+                ErrorClass::Limit,
+            ),
+            (
+                XRVErr::NameMustFolowedByColon,
+                "E_NAME_WITHOUT_COLON",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::NameMustNotContainQoutes,
+                "E_QUOTE_IN_NAME",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::ExpectSpaceOrAlpha,
+                "E_EXPECTED_FIELD",
+                ErrorClass::Syntax,
+            ),
+            (XRVErr::ExpectAlpha, "E_EXPECTED_VALUE", ErrorClass::Syntax),
+            (
+                XRVErr::ExpectingSpaceOrNewline,
+                "E_EXPECTED_SEPARATOR",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::ExpectingQouteNotNewline,
+                "E_QUOTE_UNTERMINATED",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::FailedToConsumePairs,
+                "E_FIELD_WITHOUT_VALUE",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::FailToGetLineKind,
+                "E_LINE_WITHOUT_KIND",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::FailToGetLineName,
+                "E_LINE_WITHOUT_NAME",
+                ErrorClass::Syntax,
+            ),
+            (XRVErr::NotTableLine, "E_NOT_TABLE_LINE", ErrorClass::Syntax),
+            (
+                XRVErr::CantParseFieldUsizeValue,
+                "E_NOT_UNSIGNED",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::CantParseFieldStrName { line: 1, col: 1 },
+                "E_FIELD_NAME_NOT_UTF8",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::CantParseFieldStrValue { line: 1, col: 1 },
+                "E_FIELD_VALUE_NOT_UTF8",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::CantParseFieldName,
+                "E_LINE_NAME_NOT_UTF8",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::RegionOverflow { pos: 1, len: 1 },
+                "E_REGION_OVERFLOW",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::SectionOutOfBounds {
+                    name: "x".into(),
+                    seek: 1,
+                    len: 1,
+                    file_len: 1,
+                },
+                "E_REGION_OUT_OF_BOUNDS",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::RegionStartsMidLine {
+                    table: "x".into(),
+                    offset: 1,
+                },
+                "E_REGION_STARTS_MID_LINE",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::RegionEndsMidLine {
+                    table: "x".into(),
+                    offset: 1,
+                },
+                "E_REGION_ENDS_MID_LINE",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::MissingTableFields(vec![]),
+                "E_TABLE_FIELDS_MISSING",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::TableLineTooShort {
+                    expected: 1,
+                    got: 1,
+                    line: 1,
+                },
+                "E_TABLE_LINE_TOO_SHORT",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::ItsNotAJumpsLine,
+                "E_NOT_JUMPS_LINE",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::DuplicateJump("x".into()),
+                "E_DUPLICATE_JUMP",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::DuplicateTable("x".into()),
+                "E_DUPLICATE_TABLE",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::DuplicateStyle("x".into()),
+                "E_DUPLICATE_STYLE",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::DuplicateDictionary("x".into()),
+                "E_DUPLICATE_DICTIONARY",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::UnknownDictionaryToken {
+                    table: "x".into(),
+                    token: "x".into(),
+                },
+                "E_UNKNOWN_DICTIONARY_TOKEN",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::UnknownTable("x".into()),
+                "E_UNKNOWN_TABLE",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::RecordOutsideTable { line: 1 },
+                "E_RECORD_OUTSIDE_TABLE",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::InvalidIdentifier("x".into()),
+                "E_INVALID_IDENTIFIER",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::DuplicateColumn {
+                    table: "x".into(),
+                    column: "x".into(),
+                },
+                "E_DUPLICATE_COLUMN",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::ColumnGroupCollision {
+                    table: "x".into(),
+                    column: "x".into(),
+                },
+                "E_COLUMN_GROUP_COLLISION",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::DuplicateField {
+                    line: 1,
+                    name: "x".into(),
+                },
+                "E_DUPLICATE_FIELD",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::UnrepresentableValue("x".into()),
+                "E_UNREPRESENTABLE_VALUE",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::InvalidValueByte {
+                    byte: b'x',
+                    field: "x".into(),
+                },
+                "E_INVALID_VALUE_BYTE",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::InvalidNameByte {
+                    byte: b'x',
+                    name: "x".into(),
+                },
+                "E_INVALID_NAME_BYTE",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::ValueTooLongForInPlaceUpdate { len: 1, max: 1 },
+                "E_VALUE_TOO_LONG_IN_PLACE",
+                ErrorClass::Limit,
+            ),
+            (
+                XRVErr::ValueSpansLines("x".into()),
+                "E_VALUE_SPANS_LINES",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::UnknownColumn {
+                    table: "x".into(),
+                    column: "x".into(),
+                },
+                "E_UNKNOWN_COLUMN",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::ListColumn {
+                    table: "x".into(),
+                    column: "x".into(),
+                },
+                "E_LIST_COLUMN",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::MissingColumn {
+                    table: "x".into(),
+                    column: "x".into(),
+                },
+                "E_MISSING_COLUMN",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::DuplicateKey {
+                    table: "x".into(),
+                    key: "x".into(),
+                },
+                "E_DUPLICATE_KEY",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::DuplicateRecordId {
+                    table: "x".into(),
+                    id: "x".into(),
+                },
+                "E_DUPLICATE_RECORD_ID",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::MissingRecordId { table: "x".into() },
+                "E_MISSING_RECORD_ID",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::IdsExhausted { table: "x".into() },
+                "E_IDS_EXHAUSTED",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::UnknownReferencedTable {
+                    table: "x".into(),
+                    column: "x".into(),
+                    target: "x".into(),
+                },
+                "E_UNKNOWN_REFERENCED_TABLE",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::EmptyColumn {
+                    table: "x".into(),
+                    column: "x".into(),
+                },
+                "E_EMPTY_COLUMN",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::RepeatedColumn {
+                    table: "x".into(),
+                    column: "x".into(),
+                },
+                "E_REPEATED_COLUMN",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::InvalidColumnKind("x".into()),
+                "E_INVALID_COLUMN_KIND",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::InvalidTypedValue {
+                    table: "x".into(),
+                    column: "x".into(),
+                    value: "x".into(),
+                    expected: "i32",
+                },
+                "E_INVALID_TYPED_VALUE",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::FractionalSize {
+                    table: "x".into(),
+                    column: "x".into(),
+                    value: "x".into(),
+                },
+                "E_FRACTIONAL_SIZE",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::NegativeDuration {
+                    table: "x".into(),
+                    column: "x".into(),
+                    value: "x".into(),
+                },
+                "E_NEGATIVE_DURATION",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::ZeroSampleInterval,
+                "E_ZERO_SAMPLE_INTERVAL",
+                ErrorClass::Internal,
+            ),
+            (
+                XRVErr::InvalidDialect {
+                    separator: b'x',
+                    quote: b'x',
+                    null: b'x',
+                },
+                "E_INVALID_DIALECT",
+                ErrorClass::Internal,
+            ),
+            (
+                XRVErr::InvalidVersionLine("x".into()),
+                "E_INVALID_VERSION_LINE",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::UnsupportedVersion {
+                    found: 1,
+                    max_supported: 1,
+                },
+                "E_UNSUPPORTED_VERSION",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::InvalidBase64 {
+                    column: "x".into(),
+                    pos: 1,
+                },
+                "E_INVALID_BASE64",
+                ErrorClass::Schema,
+            ),
+            #[cfg(feature = "sqlite")]
+            (
+                XRVErr::Sqlite(rusqlite::Error::QueryReturnedNoRows),
+                "E_SQLITE",
+                ErrorClass::Io,
+            ),
+            #[cfg(feature = "sqlite")]
+            (
+                XRVErr::SqliteType {
+                    table: "x".into(),
+                    column: "x".into(),
+                },
+                "E_SQLITE_TYPE",
+                ErrorClass::Schema,
+            ),
+            #[cfg(feature = "testkit")]
+            (
+                XRVErr::InvalidGenSpec("x".into()),
+                "E_INVALID_GEN_SPEC",
+                ErrorClass::Internal,
+            ),
+            (
+                XRVErr::ValueOutOfRange {
+                    table: "x".into(),
+                    column: "x".into(),
+                    value: "x".into(),
+                    min: 0.0,
+                    max: 0.0,
+                },
+                "E_VALUE_OUT_OF_RANGE",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::FieldExists {
+                    table: "x".into(),
+                    id: "x".into(),
+                    field: "x".into(),
+                },
+                "E_FIELD_EXISTS",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::SchemaMismatch {
+                    table: "x".into(),
+                    columns: vec![],
+                },
+                "E_SCHEMA_MISMATCH",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::SchemaDeviations(vec![]),
+                "E_SCHEMA_DEVIATIONS",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::ShardSchemaMismatch {
+                    first: "x".into(),
+                    shards: vec![],
+                },
+                "E_SHARD_SCHEMA_MISMATCH",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::MergeConflict {
+                    table: "x".into(),
+                    id: "x".into(),
+                },
+                "E_MERGE_CONFLICT",
+                ErrorClass::Schema,
+            ),
+            (
+                XRVErr::WorkerPanicked,
+                "E_WORKER_PANICKED",
+                ErrorClass::Internal,
+            ),
+            (
+                XRVErr::CantParseChecksum,
+                "E_INVALID_CHECKSUM",
+                ErrorClass::Syntax,
+            ),
+            (XRVErr::NotStyleLine, "E_NOT_STYLE_LINE", ErrorClass::Syntax),
+            (
+                XRVErr::NotRecordLine,
+                "E_NOT_RECORD_LINE",
+                ErrorClass::Syntax,
+            ),
+            (
+                XRVErr::UnkwnownLineKind,
+                "E_UNKNOWN_LINE_KIND",
+                ErrorClass::Syntax,
+            ),
+        ]
+    }
+
+    #[test]
+    fn codes_are_unique() {
+        let mut seen = HashSet::new();
+        for (err, _, _) in released() {
+            assert!(seen.insert(err.code()), "{} is used twice", err.code());
+        }
+    }
+
+    #[test]
+    fn codes_and_classes_are_stable() {
+        for (err, code, class) in released() {
+            assert_eq!((err.code(), err.class()), (code, class), "{:?}", err);
+            assert!(
+                code.starts_with("E_")
+                    && code
+                        .bytes()
+                        .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b'_')
+            );
+        }
+    }
+
+    #[test]
+    fn wrapping_errors_take_the_code_and_class_of_the_wrapped_one() {
+        let inner = || Box::new(XRVErr::DuplicateTable("users".into()));
+        for err in [
+            XRVErr::InTable {
+                table: "users".into(),
+                err: inner(),
+            },
+            XRVErr::Resynced {
+                skipped: 3,
+                err: inner(),
+            },
+            XRVErr::AtLine {
+                line: 1,
+                col: 1,
+                snippet: String::new(),
+                err: inner(),
+            },
+        ] {
+            assert_eq!(
+                (err.code(), err.class()),
+                ("E_DUPLICATE_TABLE", ErrorClass::Syntax)
+            );
+        }
+    }
+}
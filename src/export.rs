@@ -0,0 +1,194 @@
+use crate::{
+    diff::json_string,
+    document::{Document, DocumentTable},
+    error::XRVErr,
+    line::Value,
+    schema::GROUP_SEPARATOR,
+};
+
+/// How `Document::to_json` and `DocumentTable::to_json` render records.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExportOptions {
+    /// Nest dotted fields into objects, `addr.city:Delft` as
+    /// `"addr":{"city":"Delft"}`, rather than keeping the dotted names as
+    /// keys.
+    pub nest_dotted: bool,
+}
+
+/// A JSON value being assembled from the fields of a record.
+enum Node<'v> {
+    /// Values of a field, an array when it repeats.
+    Values(Vec<Value<'v>>),
+    Object(Vec<(&'v str, Node<'v>)>),
+}
+
+impl Document {
+    /// Renders every table as by `DocumentTable::to_json`, in one JSON
+    /// object keyed by table name.
+    pub fn to_json(&self, options: ExportOptions) -> Result<String, XRVErr> {
+        let mut tables = Vec::with_capacity(self.tables.len());
+        for table in &self.tables {
+            tables.push(format!(
+                "{}:{}",
+                json_string(&table.name),
+                table.to_json(options)?
+            ));
+        }
+        Ok(format!("{{{}}}", tables.join(",")))
+    }
+}
+
+impl DocumentTable {
+    /// Renders the records as a JSON array of objects holding the record
+    /// `id` and every field as a string, or `null` for a null, repeated
+    /// fields as arrays. With `ExportOptions::nest_dotted`, a field named like the
+    /// group of dotted ones on the same record fails with
+    /// `XRVErr::ColumnGroupCollision`.
+    ///
+    /// ```
+    /// use xrave::{ColKind, ExportOptions, Schema};
+    ///
+    /// let mut document = Schema::new()
+    ///     .table("users", |t| t.group("addr", |g| g.col("city", ColKind::String)))
+    ///     .build()?;
+    /// document.add_record("users", "1", &[("addr.city", "Delft")])?;
+    /// let table = document.table("users").unwrap();
+    /// let nested = ExportOptions { nest_dotted: true };
+    /// assert_eq!(table.to_json(nested)?, r#"[{"id":"1","addr":{"city":"Delft"}}]"#);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn to_json(&self, options: ExportOptions) -> Result<String, XRVErr> {
+        let mut records = Vec::with_capacity(self.len());
+        for record in self.records() {
+            let record = record?;
+            let mut object = vec![("id", Node::Values(vec![Value::Str(&record.id)]))];
+            for field in &record.cols {
+                insert(
+                    &mut object,
+                    &field.name,
+                    0,
+                    field.as_value(),
+                    options.nest_dotted,
+                )
+                .map_err(|column| XRVErr::ColumnGroupCollision {
+                    table: self.name.clone(),
+                    column: column.to_owned(),
+                })?;
+            }
+            let mut out = String::new();
+            write_object(&mut out, &object);
+            records.push(out);
+        }
+        Ok(format!("[{}]", records.join(",")))
+    }
+}
+
+/// Adds `value` under the part of `name` from byte `at` on, split at dots
+/// when `nest`. Fails with the name of the field or group that is both.
+fn insert<'v>(
+    object: &mut Vec<(&'v str, Node<'v>)>,
+    name: &'v str,
+    at: usize,
+    value: Value<'v>,
+    nest: bool,
+) -> Result<(), &'v str> {
+    let rest = &name[at..];
+    let split = match nest {
+        true => rest.find(GROUP_SEPARATOR),
+        false => None,
+    };
+    let key = split.map_or(rest, |end| &rest[..end]);
+    let index = match object.iter().position(|(entry, _)| *entry == key) {
+        Some(index) => index,
+        None => {
+            let node = match split {
+                None => Node::Values(Vec::new()),
+                Some(_) => Node::Object(Vec::new()),
+            };
+            object.push((key, node));
+            object.len() - 1
+        }
+    };
+    match (&mut object[index].1, split) {
+        (Node::Values(values), None) => {
+            values.push(value);
+            Ok(())
+        }
+        (Node::Object(inner), Some(end)) => insert(inner, name, at + end + 1, value, nest),
+        (Node::Object(_), None) => Err(name),
+        (Node::Values(_), Some(end)) => Err(&name[..at + end]),
+    }
+}
+
+fn write_object(out: &mut String, object: &[(&str, Node<'_>)]) {
+    out.push('{');
+    for (idx, (key, node)) in object.iter().enumerate() {
+        if idx > 0 {
+            out.push(',');
+        }
+        out.push_str(&json_string(key));
+        out.push(':');
+        match node {
+            Node::Values(values) if values.len() == 1 => out.push_str(&json_value(values[0])),
+            Node::Values(values) => {
+                let values: Vec<String> = values.iter().map(|value| json_value(*value)).collect();
+                out.push('[');
+                out.push_str(&values.join(","));
+                out.push(']');
+            }
+            Node::Object(inner) => write_object(out, inner),
+        }
+    }
+    out.push('}');
+}
+
+fn json_value(value: Value<'_>) -> String {
+    match value {
+        Value::Null => "null".to_owned(),
+        Value::Str(value) => json_string(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn json_nests_dotted_groups_on_request() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{ColKind, Document, ExportOptions, Schema, XRVErr};
+
+        let mut document = Schema::new()
+            .table("users", |t| {
+                t.col("name", ColKind::String).group("addr", |g| {
+                    g.col("city", ColKind::String).col("zip", ColKind::String)
+                })
+            })
+            .build()?;
+        let fields = [
+            ("name", "Anna"),
+            ("addr.city", "Delft"),
+            ("addr.zip", "2611"),
+        ];
+        document.add_record("users", "1", &fields)?;
+        let table = document.table("users").unwrap();
+
+        assert_eq!(
+            table.to_json(ExportOptions::default())?,
+            r#"[{"id":"1","name":"Anna","addr.city":"Delft","addr.zip":"2611"}]"#
+        );
+        let nested = ExportOptions { nest_dotted: true };
+        assert_eq!(
+            table.to_json(nested)?,
+            r#"[{"id":"1","name":"Anna","addr":{"city":"Delft","zip":"2611"}}]"#
+        );
+
+        let document = Document::read(
+            &b"j:jumps\nt:1 name:users pos:37 len:27\nr:1 addr:x addr.city:Delft\n"[..],
+        )?;
+        let table = document.table("users").unwrap();
+        assert!(table.to_json(ExportOptions::default()).is_ok());
+        assert!(matches!(
+            table.to_json(nested),
+            Err(XRVErr::ColumnGroupCollision { ref column, .. }) if column == "addr"
+        ));
+        Ok(())
+    }
+}
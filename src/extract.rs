@@ -0,0 +1,189 @@
+use std::io::prelude::*;
+use std::{fs::File, io::SeekFrom};
+
+use crate::{
+    dictionary::entry,
+    error::{open_error, XRVErr},
+    intern::Interner,
+    line::{decode_record, LineKind, StyleLine, TableLine},
+    reader::Reader,
+    tokenizer::{RawLine, XraveBuffer, ESCAPE_CHAR, NL_CHAR},
+    version::{EXTENDED_SYNTAX_VERSION, NULL_VALUE_VERSION},
+    writer::{Writer, WriterOptions},
+};
+
+/// Record field naming the style a record uses.
+pub(crate) const STYLE_FIELD: &str = "style";
+
+impl Reader {
+    /// Writes a standalone file holding only `name`: its header line, the
+    /// styles its records name in a `style:` field, and its records copied
+    /// byte for byte from the table's region. The jumps line and `pos`/`len`
+    /// are computed for the new layout.
+    ///
+    /// ```
+    /// use xrave::{Document, Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-extract.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("name", "str")])?;
+    /// writer.add_table("tags", &[("label", "str")])?;
+    /// writer.add_record("users", "1", &[("name", "Anna")])?;
+    /// writer.add_record("tags", "t1", &[("label", "admin")])?;
+    /// writer.finish()?;
+    ///
+    /// let mut out = Vec::new();
+    /// Reader::new(&path)?.extract_table("tags", &mut out)?;
+    /// let extracted = Document::read(&out[..])?;
+    /// assert_eq!(extracted.tables.len(), 1);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn extract_table<W: Write>(&mut self, name: &str, out: W) -> Result<(), XRVErr> {
+        let (seek, len) = match self.jumps.get(name) {
+            None => return Err(XRVErr::UnknownTable(name.to_owned())),
+            Some(region) => *region,
+        };
+        if self.load_table(name)?.is_none() {
+            return Err(XRVErr::UnknownTable(name.to_owned()));
+        }
+        let dictionary = self.dictionaries.get(name).cloned();
+
+        let path = self.reopen_path()?;
+        let mut file = match File::open(path) {
+            Err(err) => return Err(open_error(path, err)),
+            Ok(file) => file,
+        };
+        if let Err(err) = file.seek(SeekFrom::Start(seek as u64)) {
+            return Err(XRVErr::FailToSeek(err));
+        }
+        let mut region = Vec::new();
+        if let Err(err) = file.take(len as u64).read_to_end(&mut region) {
+            return Err(XRVErr::FailToReadLine(err));
+        }
+        if region.len() < len {
+            return Err(XRVErr::UnexpectedEof);
+        }
+        if !region.is_empty() && !region.ends_with(&[NL_CHAR]) {
+            region.push(NL_CHAR);
+        }
+
+        // Copied as they are, records of a version 1 file would gain escape
+        // sequences and continuations, and those of an older file nulls, in
+        // the current version of the output, so those holding a backslash
+        // or the null byte are written out again instead.
+        let null = self.options.dialect.null;
+        let reencode = self.version < EXTENDED_SYNTAX_VERSION && region.contains(&ESCAPE_CHAR)
+            || self.version < NULL_VALUE_VERSION && region.contains(&null);
+        let mut records = Vec::new();
+        let mut names = Interner::new(false);
+        let mut styles: Vec<String> = Vec::new();
+        let mut source = &region[..];
+        let mut buffer = XraveBuffer::new();
+        buffer.set_version(self.version);
+        while buffer.read_line(&mut source, self.options.max_line_len)? > 0 {
+            if buffer.is_filler(&mut source)? {
+                continue;
+            }
+            let raw_line: RawLine = (&buffer).try_into()?;
+            if raw_line.kind != LineKind::Record {
+                continue;
+            }
+            if reencode {
+                records.push(decode_record(
+                    raw_line.clone(),
+                    self.options.encoding,
+                    &mut names,
+                )?);
+            }
+            for (field, value) in raw_line.fields {
+                if field != STYLE_FIELD.as_bytes() {
+                    continue;
+                }
+                let mut value = String::from_utf8_lossy(value);
+                if let Some(dictionary) = &dictionary {
+                    if let Some(style) = entry(dictionary, name, &value)? {
+                        value = style.to_owned().into();
+                    }
+                }
+                if !styles.iter().any(|id| *id == value) {
+                    styles.push(value.into_owned());
+                }
+            }
+        }
+
+        for id in styles.iter() {
+            self.load_style(id)?;
+        }
+        let header = match self.table(name) {
+            None => return Err(XRVErr::UnknownTable(name.to_owned())),
+            Some(header) => header,
+        };
+
+        let options = WriterOptions {
+            dialect: self.options.dialect,
+            ..WriterOptions::default()
+        };
+        let mut writer = Writer::with_options(out, options);
+        writer.add_table_line(&TableLine::from(header))?;
+        // Records are copied with their tokens, so the table keeps its
+        // dictionary.
+        if let Some(dictionary) = &dictionary {
+            writer.set_dictionary(name, dictionary)?;
+        }
+        for id in styles.iter() {
+            if let Some(style) = self.styles.get(id) {
+                writer.add_style_line(&StyleLine::from(style))?;
+            }
+        }
+        if reencode {
+            for record in records.iter() {
+                let fields: Vec<(&str, &str)> = record
+                    .cols
+                    .iter()
+                    .map(|field| (&*field.name, field.value.as_str()))
+                    .collect();
+                writer.add_record(name, &record.id, &fields)?;
+            }
+        } else {
+            writer.add_raw_records(name, &region)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn extracted_tables_take_only_their_styles() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Document, Reader, Writer};
+
+        let path = std::env::temp_dir().join("xrave-test-extract.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("users", &[("name", "str")])?;
+        writer.add_table("orders", &[("user", "i32"), ("style", "str")])?;
+        writer.add_table("tags", &[("label", "str")])?;
+        writer.add_style("bold", &[("weight", "700")])?;
+        writer.add_style("dim", &[("opacity", "0.5")])?;
+        writer.add_record("users", "1", &[("name", "Anna")])?;
+        writer.add_record("orders", "o1", &[("user", "1"), ("style", "bold")])?;
+        writer.add_record("orders", "o2", &[("user", "1"), ("style", "bold")])?;
+        writer.add_record("tags", "t1", &[("label", "admin")])?;
+        writer.finish()?;
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        let mut out = Vec::new();
+        reader.extract_table("orders", &mut out)?;
+
+        let extracted = Document::read(&out[..])?;
+        let original = Document::open(&path)?;
+        assert_eq!(extracted.tables.len(), 1);
+        assert_eq!(extracted.table("orders"), original.table("orders"));
+        assert!(extracted.style("bold").is_some());
+        assert!(extracted.style("dim").is_none());
+        assert!(
+            String::from_utf8(out)?.ends_with("r:o1 user:1 style:bold\nr:o2 user:1 style:bold\n")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use std::io::prelude::*;
+
+use crate::{document::Document, error::XRVErr, writer::WriterOptions};
+
+#[derive(Debug, Clone, Default)]
+pub struct FormatOptions {
+    /// Pad record fields into aligned columns, see
+    /// `WriterOptions::align_columns`.
+    pub align_columns: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatReport {
+    pub tables: usize,
+    pub styles: usize,
+    pub records: usize,
+    /// Whether the canonical output differs from the input bytes.
+    pub changed: bool,
+}
+
+/// Re-emits `input` in canonical form: single spaces between fields, values
+/// quoted only when needed, LF line endings, table header fields in
+/// `name`/`pos`/`len` order, the current version line and regenerated
+/// jumps, offsets and checksums.
+/// Formatting canonical output again yields the same bytes.
+///
+/// ```
+/// use xrave::{format, Document, FormatOptions};
+///
+/// let messy = "j:jumps  users:65-25\r\nt:users len:25 POS:65 name:users  age:int\r\n\
+///              r:1  name:\"Anna\" age:31\r\n";
+/// let mut canonical = Vec::new();
+/// let report = format(messy.as_bytes(), &mut canonical, FormatOptions::default())?;
+/// assert!(report.changed);
+/// assert_eq!(
+///     String::from_utf8(canonical.clone())?,
+///     "v:xrave 3\nj:jumps users:67-21\n\
+///      t:1 name:users pos:67 len:21 age:int\nr:1 name:Anna age:31\n",
+/// );
+///
+/// let mut again = Vec::new();
+/// let report = format(&canonical[..], &mut again, FormatOptions::default())?;
+/// assert!(!report.changed);
+/// assert_eq!(again, canonical);
+/// assert_eq!(Document::read(messy.as_bytes())?, Document::read(&canonical[..])?);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn format<R: Read, W: Write>(
+    mut input: R,
+    mut output: W,
+    options: FormatOptions,
+) -> Result<FormatReport, XRVErr> {
+    let mut bytes = Vec::new();
+    if let Err(err) = input.read_to_end(&mut bytes) {
+        return Err(XRVErr::FailToReadLine(err));
+    }
+    let document = Document::from_bytes(&bytes)?;
+    let writer_options = WriterOptions {
+        checksums: document.checksums,
+        align_columns: options.align_columns,
+        ..WriterOptions::default()
+    };
+    let canonical = document.write(Vec::new(), writer_options)?;
+
+    if let Err(err) = output.write_all(&canonical).and_then(|_| output.flush()) {
+        return Err(XRVErr::FailToWrite(err));
+    }
+    Ok(FormatReport {
+        tables: document.tables.len(),
+        styles: document.styles.len(),
+        records: document
+            .tables
+            .iter()
+            .map(|table| table.records.len())
+            .sum(),
+        changed: canonical != bytes,
+    })
+}
@@ -0,0 +1,174 @@
+use crate::{
+    error::XRVErr,
+    line::{OwnedField, OwnedTableLine},
+    reader::Reader,
+    schema::Col,
+    sha256::Sha256,
+};
+
+/// Tags opening each item of the canonical serialization.
+const STYLE_TAG: u8 = b's';
+const TABLE_TAG: u8 = b't';
+const COLUMN_TAG: u8 = b'c';
+const RECORD_TAG: u8 = b'r';
+const FIELD_TAG: u8 = b'f';
+/// Stands in for the length of a null value, which no string has.
+const NULL_LEN: u64 = u64::MAX;
+
+/// Feeds `bytes` to `hasher` after their length, so that no two sequences
+/// of strings serialize alike.
+fn feed(hasher: &mut Sha256, bytes: &[u8]) {
+    hasher.update(&(bytes.len() as u64).to_le_bytes());
+    hasher.update(bytes);
+}
+
+/// Feeds `fields` sorted by name. The sort is stable, keeping the values of
+/// a list column in their order.
+fn feed_fields(hasher: &mut Sha256, fields: &[OwnedField]) {
+    let mut sorted: Vec<&OwnedField> = fields.iter().collect();
+    sorted.sort_by(|a, b| a.name.cmp(&b.name));
+    hasher.update(&(sorted.len() as u64).to_le_bytes());
+    for field in sorted {
+        hasher.update(&[FIELD_TAG]);
+        feed(hasher, field.name.as_bytes());
+        match field.null {
+            true => hasher.update(&NULL_LEN.to_le_bytes()),
+            false => feed(hasher, field.value.as_bytes()),
+        }
+    }
+}
+
+/// Feeds the header of `table`: its name and its columns sorted by name,
+/// each declaration in the form `Col` prints it when it parses.
+fn feed_header(hasher: &mut Sha256, table: &OwnedTableLine) {
+    hasher.update(&[TABLE_TAG]);
+    feed(hasher, table.name.as_bytes());
+    let mut cols: Vec<(&str, String)> = table
+        .cols
+        .iter()
+        .map(|col| {
+            let decl = Col::parse(&col.value).map_or_else(|_| col.value.clone(), |c| c.to_string());
+            (&*col.name, decl)
+        })
+        .collect();
+    cols.sort();
+    hasher.update(&(cols.len() as u64).to_le_bytes());
+    for (name, decl) in cols {
+        hasher.update(&[COLUMN_TAG]);
+        feed(hasher, name.as_bytes());
+        feed(hasher, decl.as_bytes());
+    }
+}
+
+impl Reader {
+    /// SHA-256 of the logical content of the file, equal for files that
+    /// differ only in layout: line order of tables and styles, field order
+    /// within a line, quoting and escapes, line terminators and wrapping,
+    /// region offsets, tombstones and checksum lines. Tables and styles are
+    /// taken sorted by name, and records in file order within their table.
+    /// Tables are visited by name, so a gzip file only hashes when its
+    /// tables happen to be stored in that order.
+    ///
+    /// ```
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let dir = std::env::temp_dir();
+    /// let (a, b) = (dir.join("xrave-doc-hash-a.xrv"), dir.join("xrave-doc-hash-b.xrv"));
+    /// let mut writer = Writer::new(Vec::new());
+    /// writer.add_table("users", &[("name", "str"), ("age", "i32")])?;
+    /// writer.add_record("users", "1", &[("name", "Anna"), ("age", "31")])?;
+    /// let text = String::from_utf8(writer.finish()?)?;
+    /// std::fs::write(&a, &text)?;
+    /// std::fs::write(&b, text.replace("name:Anna age:31", "age:31 name:Anna"))?;
+    /// assert_eq!(Reader::new(&a)?.content_hash()?, Reader::new(&b)?.content_hash()?);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn content_hash(&mut self) -> Result<[u8; 32], XRVErr> {
+        self.load_headers()?;
+        let mut hasher = Sha256::new();
+
+        let mut styles: Vec<_> = self.styles.values().collect();
+        styles.sort_by(|a, b| a.id.cmp(&b.id));
+        hasher.update(&(styles.len() as u64).to_le_bytes());
+        for style in styles {
+            hasher.update(&[STYLE_TAG]);
+            feed(&mut hasher, style.id.as_bytes());
+            feed_fields(&mut hasher, &style.cols);
+        }
+
+        let mut names: Vec<String> = self.tables.keys().cloned().collect();
+        names.sort();
+        hasher.update(&(names.len() as u64).to_le_bytes());
+        for name in names {
+            self.feed_table(&mut hasher, &name)?;
+        }
+        Ok(hasher.finish())
+    }
+
+    /// SHA-256 of the logical content of one table, its header and records,
+    /// in the canonical form `content_hash` uses.
+    pub fn table_content_hash(&mut self, name: &str) -> Result<[u8; 32], XRVErr> {
+        self.load_headers()?;
+        let mut hasher = Sha256::new();
+        self.feed_table(&mut hasher, name)?;
+        Ok(hasher.finish())
+    }
+
+    fn feed_table(&mut self, hasher: &mut Sha256, name: &str) -> Result<(), XRVErr> {
+        match self.tables.get(name) {
+            None => return Err(XRVErr::UnknownTable(name.to_owned())),
+            Some(table) => feed_header(hasher, table),
+        }
+        if !self.jumps.contains_key(name) {
+            return Ok(());
+        }
+        for record in self.records(name)? {
+            let record = record?;
+            hasher.update(&[RECORD_TAG]);
+            feed(hasher, record.id.as_bytes());
+            feed_fields(hasher, &record.cols);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn content_hash_ignores_layout() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Reader, Writer};
+
+        let path = std::env::temp_dir().join("xrave-test-content-hash.xrv");
+        let hash = |text: &str| -> Result<[u8; 32], crate::XRVErr> {
+            std::fs::write(&path, text).unwrap();
+            Reader::new(path.to_string_lossy().into_owned())?.content_hash()
+        };
+
+        let mut writer = Writer::new(Vec::new());
+        writer.add_table("users", &[("name", "str"), ("age", "i32?")])?;
+        writer.add_table("notes", &[("text", "str")])?;
+        writer.add_record("users", "1", &[("name", "Anna Smith"), ("age", "31")])?;
+        writer.add_record("users", "2", &[("name", "Bob")])?;
+        writer.add_record("notes", "n1", &[("text", "a\tb")])?;
+        let written = String::from_utf8(writer.finish()?)?;
+
+        let reordered = "v:xrave 2\r\n\
+            j:jumps notes:136-18 users:154-46\r\n\
+            t:2 name:notes pos:136 len:18 text:str\r\n\
+            t:1  name:users pos:154 len:46 age:i32? name:str\r\n\
+            r:n1 text:\"a\\tb\"\r\n\
+            r:1 age:31 name:\"Anna Smith\"\r\n\
+            r:2 name:\"Bob\"\r\n";
+        let expected = hash(&written)?;
+        assert_eq!(hash(reordered)?, expected);
+        assert_ne!(hash(&reordered.replace("age:31", "age:32"))?, expected);
+        let renamed = reordered.replace("Bob", "Bobby").replace("46", "48");
+        assert_ne!(hash(&renamed)?, expected);
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        let notes = reader.table_content_hash("notes")?;
+        assert_ne!(notes, reader.table_content_hash("users")?);
+        assert_eq!(reader.table_content_hash("notes")?, notes);
+        Ok(())
+    }
+}
@@ -0,0 +1,303 @@
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+use crate::{
+    error::XRVErr,
+    line::{LineKind, OwnedStyleLine, OwnedTableLine},
+    ordered::OrderedMap,
+    reader::{seek_error, ParseMode, Reader, Source},
+    tokenizer::{
+        starts_with_kind, Dialect, RawLine, XraveBuffer, COLON_CHAR, DICTIONARY_ID, ESCAPE_CHAR,
+        STYLE_ID, TABLE_ID,
+    },
+};
+
+/// Where each table, style and dictionary line before the first table
+/// region starts, and the number of the line before it, found by reading
+/// the lines without parsing them.
+#[derive(Debug)]
+pub(crate) struct HeaderIndex {
+    tables: OrderedMap<(u64, usize)>,
+    styles: OrderedMap<(u64, usize)>,
+    dictionaries: OrderedMap<(u64, usize)>,
+    /// Whether every header line could be named without parsing it; when
+    /// not, a name missing from the index may still be declared.
+    complete: bool,
+}
+
+impl Reader {
+    /// Header of the table `name`, with its dictionary, parsing just those
+    /// lines the first time it is asked for. The lines before the first
+    /// table region are read once to find where each header starts, without
+    /// parsing them, so a file with thousands of styles opens and reads one
+    /// table as fast as one without. Headers parsed so far are kept, and
+    /// `Reader::table` returns them; `ParseOptions::eager_headers` parses
+    /// them all when the file is opened instead.
+    ///
+    /// ```
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-lazy-headers.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("name", "str")])?;
+    /// writer.add_table("events", &[("kind", "str")])?;
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(&path)?;
+    /// assert!(reader.table("users").is_none());
+    /// assert_eq!(reader.load_table("users")?.unwrap().cols.len(), 1);
+    /// assert!(reader.table("users").is_some() && reader.table("events").is_none());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn load_table(&mut self, name: &str) -> Result<Option<&OwnedTableLine>, XRVErr> {
+        if !self.tables.contains_key(name) {
+            self.load_header(LineKind::Table, name)?;
+        }
+        if !self.dictionaries.contains_key(name) {
+            self.load_header(LineKind::Dictionary, name)?;
+        }
+        Ok(self.tables.get(name))
+    }
+
+    /// Style `id`, parsing just its line the first time it is asked for,
+    /// like `Reader::load_table`.
+    pub fn load_style(&mut self, id: &str) -> Result<Option<&OwnedStyleLine>, XRVErr> {
+        if !self.styles.contains_key(id) {
+            self.load_header(LineKind::Style, id)?;
+        }
+        Ok(self.styles.get(id))
+    }
+
+    /// Parses the header line of `kind` declaring `name`, unless every
+    /// header was parsed already. Sources that cannot seek, files without
+    /// table regions and headers the index could not name fall back to
+    /// parsing all of them.
+    fn load_header(&mut self, kind: LineKind, name: &str) -> Result<(), XRVErr> {
+        if self.headers_loaded {
+            return Ok(());
+        }
+        if self.jumps.is_empty() || !matches!(self.source.get_ref(), Source::File(_)) {
+            return self.load_headers();
+        }
+        let index = match self.header_index.take() {
+            Some(index) => index,
+            None => self.index_headers()?,
+        };
+        let found = match kind {
+            LineKind::Table => index.tables.get(name),
+            LineKind::Style => index.styles.get(name),
+            _ => index.dictionaries.get(name),
+        };
+        // Headers before this were read in order, or skipped in
+        // `ParseMode::Lenient`.
+        let read_to = match self.left_headers {
+            None => self.buffer.offset,
+            Some((offset, _)) => offset,
+        };
+        let result = match found {
+            None if index.complete => Ok(()),
+            None => self.load_headers(),
+            Some((start, _)) if *start < read_to => Ok(()),
+            Some(at) => self.parse_header_at(*at),
+        };
+        self.header_index = Some(index);
+        result
+    }
+
+    /// Reads the lines between the jumps line and the first table region,
+    /// noting where each header starts. Style and dictionary lines are
+    /// named by the id at their head, and only table lines are tokenized,
+    /// for their `name` field.
+    fn index_headers(&mut self) -> Result<HeaderIndex, XRVErr> {
+        let first_region = self
+            .jumps
+            .values()
+            .map(|(seek, _)| *seek as u64)
+            .min()
+            .unwrap_or(u64::MAX);
+        self.detour(self.headers_start, |reader| {
+            let mut index = HeaderIndex {
+                tables: OrderedMap::new(),
+                styles: OrderedMap::new(),
+                dictionaries: OrderedMap::new(),
+                complete: true,
+            };
+            while reader.buffer.offset < first_region {
+                match reader.read_next_line() {
+                    Err(XRVErr::LineTooLong { .. }) => {
+                        index.complete = false;
+                        continue;
+                    }
+                    Err(err) => return Err(err),
+                    Ok(0) => break,
+                    Ok(_) => {}
+                }
+                if reader.buffer.is_filler(&mut reader.source)? {
+                    continue;
+                }
+                let buffer = &reader.buffer;
+                let at = (buffer.start, buffer.line - 1);
+                let map = match buffer.buffer.as_slice() {
+                    [TABLE_ID, COLON_CHAR, ..] => &mut index.tables,
+                    [STYLE_ID, COLON_CHAR, ..] => &mut index.styles,
+                    [DICTIONARY_ID, COLON_CHAR, ..] => &mut index.dictionaries,
+                    line if starts_with_kind(line) => continue,
+                    _ => {
+                        index.complete = false;
+                        continue;
+                    }
+                };
+                match header_name(buffer, reader.options.dialect) {
+                    None => index.complete = false,
+                    Some(name) => {
+                        if !map.contains_key(&name) {
+                            map.insert(name, at);
+                        }
+                    }
+                }
+            }
+            Ok(index)
+        })
+    }
+
+    /// Parses the header line starting at byte `start` after line `line`,
+    /// and notes it so that reading on does not parse it again.
+    fn parse_header_at(&mut self, (start, line): (u64, usize)) -> Result<(), XRVErr> {
+        self.loaded_headers.insert(start);
+        self.detour((start, line), |reader| {
+            if let Err(err) = reader.read_next_line() {
+                return reader.skip_header(err);
+            }
+            match reader.register_line() {
+                Err(err) => reader.skip_header(err),
+                Ok(kind) => {
+                    reader.stats.lines_parsed += 1;
+                    reader.call_line_hook(kind);
+                    Ok(())
+                }
+            }
+        })
+    }
+
+    /// Fails with the error of a header line, or in `ParseMode::Lenient`
+    /// keeps it among the diagnostics.
+    fn skip_header(&mut self, err: XRVErr) -> Result<(), XRVErr> {
+        self.stats.errors += 1;
+        match self.options.mode {
+            ParseMode::Strict => Err(err),
+            ParseMode::Lenient => {
+                self.diagnostics.push(err);
+                Ok(())
+            }
+        }
+    }
+
+    /// Runs `read` on the lines from byte `start` on, numbered after line
+    /// `line`, in a line buffer of its own, then puts the source back where
+    /// the reader stood.
+    pub(crate) fn detour<T>(
+        &mut self,
+        (start, line): (u64, usize),
+        read: impl FnOnce(&mut Reader) -> Result<T, XRVErr>,
+    ) -> Result<T, XRVErr> {
+        let mut buffer = XraveBuffer::new();
+        buffer.limits = self.buffer.limits;
+        buffer.continuations = self.buffer.continuations;
+        buffer.offset = start;
+        buffer.line = line;
+        let saved = std::mem::replace(&mut self.buffer, buffer);
+        let resync = self.resync.take();
+        let result = match self.source.seek(SeekFrom::Start(start)) {
+            Err(err) => Err(seek_error(err)),
+            Ok(_) => read(self),
+        };
+        self.buffer = saved;
+        self.resync = resync;
+        if let Err(err) = self.source.seek(SeekFrom::Start(self.buffer.offset)) {
+            return Err(seek_error(err));
+        }
+        result
+    }
+}
+
+/// Name a header line in `buffer` declares, `None` when it is quoted or
+/// escaped and would have to be parsed to be read.
+fn header_name(buffer: &XraveBuffer, dialect: Dialect) -> Option<String> {
+    let name = match buffer.buffer[0] {
+        TABLE_ID => {
+            let raw_line: RawLine = buffer.try_into().ok()?;
+            let (_, value) = raw_line
+                .fields
+                .into_iter()
+                .find(|(field, _)| *field == b"name")?;
+            value
+        }
+        _ => {
+            let head = &buffer.buffer[2..];
+            match head.iter().position(|byte| *byte == dialect.separator) {
+                None => head,
+                Some(end) => &head[..end],
+            }
+        }
+    };
+    if name
+        .iter()
+        .any(|byte| *byte == dialect.quote || *byte == ESCAPE_CHAR)
+    {
+        return None;
+    }
+    std::str::from_utf8(name).ok().map(str::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn headers_load_lazily_one_table_at_a_time() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{ParseOptions, Reader, Writer};
+
+        let path = std::env::temp_dir().join("xrave-test-lazy-headers.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("users", &[("name", "str"), ("style", "str")])?;
+        writer.add_table("events", &[("kind", "str")])?;
+        for id in 0..5_000 {
+            writer.add_style(&format!("s{}", id), &[("color", "red")])?;
+        }
+        for id in 0..10 {
+            writer.add_record(
+                "users",
+                &id.to_string(),
+                &[("name", "Anna"), ("style", "s42")],
+            )?;
+            writer.add_record("events", &id.to_string(), &[("kind", "login")])?;
+        }
+        writer.finish()?;
+
+        let mut reader = Reader::new(&path)?;
+        assert!(reader.table("users").is_none());
+        assert_eq!(reader.load_table("users")?.unwrap().cols.len(), 2);
+        assert_eq!(reader.records("users")?.count(), 10);
+        // The version and jumps lines, the `users` header and its records.
+        assert_eq!(reader.stats().lines_parsed, 2 + 1 + 10);
+        assert!(reader.table("events").is_none() && reader.styles().is_empty());
+
+        assert_eq!(reader.load_style("s42")?.unwrap().get("color"), Some("red"));
+        assert!(reader.load_style("s5000")?.is_none());
+        assert!(reader.load_table("missing")?.is_none());
+        assert_eq!(reader.stats().lines_parsed, 2 + 1 + 10 + 1);
+        assert_eq!(reader.records("events")?.count(), 10);
+
+        // Reading on from the start skips the headers parsed already.
+        let mut reader = Reader::new(&path)?;
+        reader.load_table("events")?;
+        reader.load_headers()?;
+        assert_eq!((reader.tables().len(), reader.styles().len()), (2, 5_000));
+
+        let options = ParseOptions {
+            eager_headers: true,
+            ..ParseOptions::default()
+        };
+        let reader = Reader::with_options(&path, options)?;
+        assert_eq!((reader.tables().len(), reader.styles().len()), (2, 5_000));
+        Ok(())
+    }
+}
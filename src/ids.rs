@@ -0,0 +1,225 @@
+use std::{
+    borrow::Cow,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::error::XRVErr;
+
+/// Table header field holding the next id `IdStrategy::Sequential` gives
+/// out, so that records added after the file is opened again go on
+/// counting.
+pub(crate) const NEXT_ID_FIELD: &str = "next_id";
+
+/// How `Writer::add_record` and `Document::add_record` come by the id of a
+/// record added with an empty one. Under `Sequential` and `Uuid` ids are
+/// also checked to be unique within their table, failing with
+/// `XRVErr::DuplicateRecordId`. The default, `Provided`, does no such
+/// check, so a repeated id is added as given.
+///
+/// ```
+/// use xrave::{IdStrategy, Writer, WriterOptions};
+///
+/// let options = WriterOptions { ids: IdStrategy::Sequential(1), ..WriterOptions::default() };
+/// let mut writer = Writer::with_options(Vec::new(), options);
+/// writer.add_table("users", &[("name", "str")])?;
+/// writer.add_record("users", "", &[("name", "Anna")])?;
+/// writer.add_record("users", "", &[("name", "Bob")])?;
+/// let text = String::from_utf8(writer.finish()?)?;
+/// assert!(text.ends_with("\nr:1 name:Anna\nr:2 name:Bob\n"));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IdStrategy {
+    /// Numbers counting up per table from the one given, or from the
+    /// `next_id` the table header was read with. The count is written on
+    /// the header, and a numeric id given by the caller moves it past
+    /// itself. Once the count reaches `u64::MAX`, a record added without an
+    /// id fails with `XRVErr::IdsExhausted`.
+    Sequential(u64),
+    /// Random version 4 UUIDs.
+    Uuid,
+    /// The caller gives every id; an empty one fails with
+    /// `XRVErr::MissingRecordId`. Ids are not checked for duplicates.
+    #[default]
+    Provided,
+}
+
+/// Id of a record added to `table` with `id`, generating one under
+/// `strategy` when it is empty. `next_id` is the table's sequential count,
+/// moved on past the id returned.
+pub(crate) fn assign_id<'i>(
+    strategy: IdStrategy,
+    table: &str,
+    id: &'i str,
+    next_id: &mut Option<u64>,
+) -> Result<Cow<'i, str>, XRVErr> {
+    let start = match strategy {
+        IdStrategy::Provided if id.is_empty() => {
+            return Err(XRVErr::MissingRecordId {
+                table: table.to_owned(),
+            })
+        }
+        IdStrategy::Provided => return Ok(Cow::Borrowed(id)),
+        IdStrategy::Uuid if id.is_empty() => return Ok(Cow::Owned(uuid_v4())),
+        IdStrategy::Uuid => return Ok(Cow::Borrowed(id)),
+        IdStrategy::Sequential(start) => start,
+    };
+    let next = next_id.unwrap_or(start);
+    if id.is_empty() {
+        let after = next.checked_add(1).ok_or_else(|| XRVErr::IdsExhausted {
+            table: table.to_owned(),
+        })?;
+        *next_id = Some(after);
+        return Ok(Cow::Owned(next.to_string()));
+    }
+    if let Ok(given) = id.parse::<u64>() {
+        *next_id = Some(next.max(given.saturating_add(1)));
+    }
+    Ok(Cow::Borrowed(id))
+}
+
+/// Counter mixed into every UUID so that two taken in the same instant
+/// differ.
+static UUID_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A random version 4 UUID, `xxxxxxxx-xxxx-4xxx-yxxx-xxxxxxxxxxxx`. The
+/// bits come from the randomly keyed hasher of `RandomState`, fed the
+/// time and a counter.
+fn uuid_v4() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or(0);
+    let mut bytes = [0u8; 16];
+    for chunk in bytes.chunks_mut(8) {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(UUID_COUNTER.fetch_add(1, Ordering::Relaxed));
+        hasher.write_u128(nanos);
+        chunk.copy_from_slice(&hasher.finish().to_le_bytes());
+    }
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn id_strategies_fill_in_and_check_ids() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Document, IdStrategy, Writer, WriterOptions, XRVErr};
+
+        let path = std::env::temp_dir().join("xrave-test-ids.xrv");
+        let options = WriterOptions {
+            ids: IdStrategy::Sequential(1),
+            ..WriterOptions::default()
+        };
+        let mut writer = Writer::with_options(std::fs::File::create(&path)?, options);
+        writer.add_table("users", &[("name", "str")])?;
+        writer.add_record("users", "", &[("name", "Anna")])?;
+        writer.add_record("users", "", &[("name", "Bob")])?;
+        writer.add_record("users", "10", &[("name", "Carl")])?;
+        writer.add_record("users", "", &[("name", "Dana")])?;
+        assert!(matches!(
+            writer.add_record("users", "2", &[("name", "Eve")]),
+            Err(XRVErr::DuplicateRecordId { ref id, .. }) if id == "2"
+        ));
+        writer.finish()?;
+        let text = std::fs::read_to_string(&path)?;
+        assert!(text.contains(" next_id:12 "));
+
+        // Records added after opening the file again go on counting.
+        let mut document = Document::open(&path)?;
+        document.ids = IdStrategy::Sequential(1);
+        document.add_record("users", "", &[("name", "Finn")])?;
+        document.save(&path)?;
+        let mut document = Document::open(&path)?;
+        document.ids = IdStrategy::Sequential(1);
+        document.add_record("users", "", &[("name", "Gus")])?;
+        let ids: Vec<_> = document.table("users").unwrap().ids().collect();
+        assert_eq!(ids, ["1", "2", "10", "11", "12", "13"]);
+        assert_eq!(document.table("users").unwrap().next_id, Some(14));
+
+        document.ids = IdStrategy::Uuid;
+        document.add_record("users", "", &[("name", "Hana")])?;
+        let uuid = document.table("users").unwrap().ids().nth(6).unwrap();
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(
+            uuid.split('-').map(str::len).collect::<Vec<_>>(),
+            [8, 4, 4, 4, 12]
+        );
+        assert_eq!(&uuid[14..15], "4");
+        assert!(matches!(&uuid[19..20], "8" | "9" | "a" | "b"));
+        assert!(matches!(
+            document.add_record("users", "1", &[("name", "Ivan")]),
+            Err(XRVErr::DuplicateRecordId { .. })
+        ));
+
+        document.ids = IdStrategy::Provided;
+        assert!(matches!(
+            document.add_record("users", "", &[("name", "Jo")]),
+            Err(XRVErr::MissingRecordId { ref table }) if table == "users"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn rejected_records_use_up_no_id() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Document, IdStrategy, Writer, WriterOptions, XRVErr};
+
+        let options = WriterOptions {
+            ids: IdStrategy::Sequential(1),
+            ..WriterOptions::default()
+        };
+        let mut writer = Writer::with_options(Vec::new(), options);
+        writer.add_table("users", &[("name", "str")])?;
+        assert!(matches!(
+            writer.add_record("users", "", &[("na\nme", "Anna")]),
+            Err(XRVErr::InvalidNameByte { .. })
+        ));
+        writer.add_record("users", "", &[("name", "Anna")])?;
+        assert!(writer
+            .add_record("users", "7", &[("na\nme", "Bob")])
+            .is_err());
+        writer.add_record("users", "7", &[("name", "Bob")])?;
+        writer.add_record("users", "", &[("name", "Carl")])?;
+        let text = String::from_utf8(writer.finish()?)?;
+        assert!(text.contains(" next_id:9 "));
+        assert!(text.ends_with("\nr:1 name:Anna\nr:7 name:Bob\nr:8 name:Carl\n"));
+
+        let mut document = Document::read(text.as_bytes())?;
+        document.ids = IdStrategy::Sequential(1);
+        assert!(document.add_record("users", "", &[]).is_err());
+        document.add_record("users", "", &[("name", "Dana")])?;
+        let ids: Vec<_> = document.table("users").unwrap().ids().collect();
+        assert_eq!(ids, ["1", "7", "8", "9"]);
+        Ok(())
+    }
+
+    #[test]
+    fn sequential_ids_run_out_at_the_top_of_u64() {
+        use super::*;
+
+        let mut next_id = Some(u64::MAX - 1);
+        let strategy = IdStrategy::Sequential(1);
+        let id = assign_id(strategy, "users", "", &mut next_id).unwrap();
+        assert_eq!(id, (u64::MAX - 1).to_string());
+        assert_eq!(next_id, Some(u64::MAX));
+        assert!(matches!(
+            assign_id(strategy, "users", "", &mut next_id),
+            Err(XRVErr::IdsExhausted { ref table }) if table == "users"
+        ));
+        assert_eq!(next_id, Some(u64::MAX));
+        assert!(assign_id(strategy, "users", &u64::MAX.to_string(), &mut next_id).is_ok());
+    }
+}
@@ -0,0 +1,45 @@
+/// A vector that keeps its first `N` items inline and moves to the heap only
+/// past them, for per-line scratch lists that are short on almost every
+/// line.
+#[derive(Debug)]
+pub(crate) struct InlineVec<T, const N: usize> {
+    inline: [T; N],
+    len: usize,
+    spill: Vec<T>,
+}
+
+impl<T: Copy + Default, const N: usize> InlineVec<T, N> {
+    pub(crate) fn new() -> Self {
+        InlineVec {
+            inline: [T::default(); N],
+            len: 0,
+            spill: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, item: T) {
+        if self.len < N {
+            self.inline[self.len] = item;
+            self.len += 1;
+            return;
+        }
+        if self.spill.is_empty() {
+            self.spill.reserve(N * 2);
+            self.spill.extend_from_slice(&self.inline);
+        }
+        self.spill.push(item);
+        self.len += 1;
+    }
+
+    pub(crate) fn as_slice(&self) -> &[T] {
+        match self.spilled() {
+            false => &self.inline[..self.len],
+            true => &self.spill,
+        }
+    }
+
+    /// Whether the items outgrew the inline storage and had to allocate.
+    pub(crate) fn spilled(&self) -> bool {
+        self.len > N
+    }
+}
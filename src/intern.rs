@@ -0,0 +1,35 @@
+use std::{collections::HashSet, sync::Arc};
+
+/// Pool of field names shared between owned lines, so a column name repeated
+/// on every record is allocated once. A disabled interner hands out a fresh
+/// `Arc<str>` per call.
+#[derive(Debug, Default)]
+pub(crate) struct Interner {
+    enabled: bool,
+    names: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    pub(crate) fn new(enabled: bool) -> Self {
+        Interner {
+            enabled,
+            names: HashSet::new(),
+        }
+    }
+
+    pub(crate) fn intern(&mut self, name: &str) -> Arc<str> {
+        if !self.enabled {
+            return Arc::from(name);
+        }
+        if let Some(interned) = self.names.get(name) {
+            return Arc::clone(interned);
+        }
+        let interned: Arc<str> = Arc::from(name);
+        self.names.insert(Arc::clone(&interned));
+        interned
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.names.len()
+    }
+}
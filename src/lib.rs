@@ -0,0 +1,108 @@
+mod atomic;
+mod base64;
+mod builder;
+mod cache;
+mod checkpoint;
+mod checksum;
+mod columnar;
+mod constraint;
+mod count;
+mod cursor;
+mod dictionary;
+mod diff;
+mod document;
+mod edit;
+mod error;
+mod export;
+mod extract;
+mod format;
+mod hash;
+mod headers;
+mod ids;
+mod inline;
+mod intern;
+mod line;
+mod lint;
+mod lock;
+mod merge;
+mod migrate;
+mod multi;
+mod namespace;
+mod ordered;
+mod parse;
+mod reader;
+mod redact;
+mod region;
+mod sample;
+mod schema;
+mod search;
+mod sha256;
+mod sharded;
+mod shared;
+mod spill;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod stats;
+mod tail;
+#[cfg(feature = "testkit")]
+pub mod testkit;
+mod tokenizer;
+mod transform;
+mod typed;
+mod units;
+mod usage;
+mod version;
+mod watch;
+mod writer;
+
+pub use atomic::{AtomicFile, WriteGuard};
+pub use builder::{RecordBuilder, StyleBuilder, TableBuilder};
+pub use checkpoint::Checkpoint;
+pub use checksum::{ChecksumReport, SectionChecksum};
+pub use columnar::{BatchColumn, ColumnValues, ColumnarBatch};
+pub use constraint::{KeyDuplicates, RefError};
+pub use count::{count_lines, LineCounts};
+pub use cursor::RecordCursor;
+pub use diff::{
+    diff, DiffOptions, DiffReport, DiffStatus, FieldChange, RecordDiff, StyleDiff, TableDiff,
+};
+pub use document::{Document, DocumentTable};
+pub use edit::compact;
+pub use error::{ErrorClass, XRVErr};
+pub use export::ExportOptions;
+pub use format::{format, FormatOptions, FormatReport};
+pub use ids::IdStrategy;
+pub use line::{
+    Field, FieldBytes, Jump, LineKind, OwnedField, OwnedRecordLine, OwnedStyleLine, OwnedTableLine,
+    RecordBytes, RecordLine, Span, StyleLine, TableLine, Value,
+};
+pub use lint::{Lint, Severity};
+pub use lock::LockMode;
+pub use merge::{MergePolicy, MergeReport};
+pub use migrate::{migrate, Migration, MigrationOp};
+pub use multi::{MultiReader, MultiRecords};
+pub use ordered::OrderedMap;
+pub use parse::{parse_line, parse_line_owned, OwnedJump, OwnedParsedLine, ParsedLine};
+pub use reader::{
+    DuplicateFields, Encoding, Limit, ParseMode, ParseOptions, Progress, Reader, ReaderStats,
+    Records,
+};
+pub use redact::{redact, RedactReport, RedactSpec, RedactedColumn, Redaction};
+pub use region::TableRegionReader;
+pub use sample::SampleSpec;
+pub use schema::{Col, ColKind, Schema, SchemaDiff, TableSchema};
+pub use search::{Match, Search, SearchOptions};
+pub use sharded::{ShardBy, ShardFile, ShardedWriter};
+pub use shared::SharedReader;
+pub use spill::LoadOptions;
+#[cfg(feature = "sqlite")]
+pub use sqlite::{import_sqlite, import_sqlite_from};
+pub use stats::{ColStats, NumericStats};
+pub use tokenizer::{
+    extract_key, Dialect, FieldSpans, LineTokenizer, RawLine, RawLineIter, TokenizerEvent,
+};
+pub use transform::{transform, Transform};
+pub use typed::{CoercionOptions, NumericLocale, TypedRecord};
+pub use usage::StyleUsage;
+pub use watch::{watch, watch_with_options, ChangeEvent, StopHandle, WatchOptions};
+pub use writer::{Writer, WriterOptions};
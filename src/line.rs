@@ -0,0 +1,1235 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::Arc,
+};
+
+use crate::{
+    error::XRVErr,
+    ids::NEXT_ID_FIELD,
+    inline::InlineVec,
+    intern::Interner,
+    reader::{DuplicateFields, Encoding},
+    schema::Col,
+    tokenizer::{unescape, Dialect, RawLine, QUOTE_CHAR, RECORD_ID, STYLE_ID, TABLE_ID},
+    writer::write_line,
+};
+
+/// Fields kept inline by per-line scratch lists before they allocate.
+const INLINE_FIELDS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Jump,
+    Table,
+    Style,
+    Record,
+    Checksum,
+    /// A record deleted in place; readers skip it.
+    Tombstone,
+    /// Full values of a table's `^token` values, `d:users 1:active`.
+    Dictionary,
+}
+
+#[derive(Debug)]
+pub(crate) struct LineField<'b> {
+    pub(crate) line: usize,
+    pub(crate) kind: LineKind,
+    pub(crate) name: &'b str,
+    pub(crate) fields: Vec<Field<'b>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Field<'b> {
+    pub(crate) name: &'b str,
+    pub(crate) value: &'b str,
+    /// Whether `value` is quoted text that may hold escape sequences.
+    pub(crate) escaped: bool,
+    /// Quote byte of the dialect the line was read in, which a backslash
+    /// escapes.
+    pub(crate) quote: u8,
+    /// Whether the value is a null, `value` then being empty.
+    pub(crate) null: bool,
+}
+
+#[derive(Debug)]
+pub struct Jump<'b> {
+    pub(crate) name: &'b str,
+    pub(crate) seek: usize,
+    pub(crate) len: usize,
+}
+
+impl<'b> Jump<'b> {
+    pub fn name(&self) -> &'b str {
+        self.name
+    }
+
+    /// Byte offset of the region the jump points at.
+    pub fn seek(&self) -> usize {
+        self.seek
+    }
+
+    /// Byte length of the region the jump points at.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct LineJump<'b> {
+    pub(crate) jumps: Vec<Jump<'b>>,
+}
+
+impl<'b> TryFrom<RawLine<'b>> for LineJump<'b> {
+    type Error = XRVErr;
+    fn try_from(value: RawLine<'b>) -> Result<Self, Self::Error> {
+        match std::str::from_utf8(value.name) {
+            Err(_) => Err(XRVErr::CantParseFieldName),
+            Ok(s) => match s {
+                "jumps" => {
+                    let mut jumps: Vec<Jump<'b>> = Vec::new();
+                    for link in value.fields.clone().links() {
+                        let name: &'b str =
+                            match utf8_at(value.fields.buffer, link.name_start, link.name_end) {
+                                Err(col) => {
+                                    return Err(XRVErr::CantParseFieldStrName {
+                                        line: value.line,
+                                        col,
+                                    })
+                                }
+                                Ok(s) => s,
+                            };
+
+                        let value_str: &'b str =
+                            match utf8_at(value.fields.buffer, link.value_start, link.value_end) {
+                                Err(col) => {
+                                    return Err(XRVErr::CantParseFieldStrValue {
+                                        line: value.line,
+                                        col,
+                                    })
+                                }
+                                Ok(s) => s,
+                            };
+
+                        let (seek, len) = match value_str.split_once('-') {
+                            None => return Err(XRVErr::CantParseFieldUsizeValue),
+                            Some((seek, len)) => (seek.parse::<usize>(), len.parse::<usize>()),
+                        };
+                        let (seek, len) = match (seek, len) {
+                            (Ok(seek), Ok(len)) => (seek, len),
+                            _ => return Err(XRVErr::CantParseFieldUsizeValue),
+                        };
+                        if seek.checked_add(len).is_none() {
+                            return Err(XRVErr::RegionOverflow { pos: seek, len });
+                        }
+
+                        jumps.push(Jump { name, seek, len });
+                    }
+                    Ok(LineJump { jumps })
+                }
+                _ => Err(XRVErr::ItsNotAJumpsLine),
+            },
+        }
+    }
+}
+
+impl<'b> TryFrom<RawLine<'b>> for LineField<'b> {
+    type Error = XRVErr;
+    fn try_from(value: RawLine<'b>) -> Result<Self, Self::Error> {
+        let mut fields: Vec<Field<'b>> = Vec::new();
+        let linename: &str = match std::str::from_utf8(value.name) {
+            Err(_) => return Err(XRVErr::CantParseFieldName),
+            Ok(s) => s,
+        };
+        let quote = value.fields.dialect.quote;
+        for link in value.fields.clone().links() {
+            let name: &'b str = match utf8_at(value.fields.buffer, link.name_start, link.name_end) {
+                Err(col) => {
+                    return Err(XRVErr::CantParseFieldStrName {
+                        line: value.line,
+                        col,
+                    })
+                }
+                Ok(s) => s,
+            };
+            let value: &'b str =
+                match utf8_at(value.fields.buffer, link.value_start, link.value_end) {
+                    Err(col) => {
+                        return Err(XRVErr::CantParseFieldStrValue {
+                            line: value.line,
+                            col,
+                        })
+                    }
+                    Ok(s) => s,
+                };
+            fields.push(Field {
+                name,
+                value: if link.null { "" } else { value },
+                escaped: link.escaped,
+                quote,
+                null: link.null,
+            });
+        }
+        let (line, duplicates) = (value.line, value.fields.duplicates);
+        keep_duplicates(&mut fields, |field| field.name, duplicates, line)?;
+
+        Ok(Self {
+            line: value.line,
+            kind: value.kind,
+            name: linename,
+            fields,
+        })
+    }
+}
+
+/// Drops the fields of line `line` that `policy` does not keep of the names
+/// used more than once, or fails under `DuplicateFields::Error`.
+pub(crate) fn keep_duplicates<T>(
+    fields: &mut Vec<T>,
+    name: impl Fn(&T) -> &str,
+    policy: DuplicateFields,
+    line: usize,
+) -> Result<(), XRVErr> {
+    if policy == DuplicateFields::KeepAll {
+        return Ok(());
+    }
+    let mut keep = vec![true; fields.len()];
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for (idx, field) in fields.iter().enumerate() {
+        let name = name(field);
+        let earlier = match seen.get(name) {
+            None => {
+                seen.insert(name, idx);
+                continue;
+            }
+            Some(earlier) => *earlier,
+        };
+        match policy {
+            DuplicateFields::KeepFirst | DuplicateFields::KeepAll => keep[idx] = false,
+            DuplicateFields::KeepLast => {
+                keep[earlier] = false;
+                seen.insert(name, idx);
+            }
+            DuplicateFields::Error => {
+                return Err(XRVErr::DuplicateField {
+                    line,
+                    name: name.to_owned(),
+                })
+            }
+        }
+    }
+    let mut keep = keep.into_iter();
+    fields.retain(|_| keep.next().unwrap_or(true));
+    Ok(())
+}
+
+/// Converts `buffer[start..end]` to a `&str`, reporting the 1-based column of
+/// the first byte that is not valid UTF-8 on failure.
+fn utf8_at(buffer: &[u8], start: usize, end: usize) -> Result<&str, usize> {
+    std::str::from_utf8(&buffer[start..end]).map_err(|err| start + err.valid_up_to() + 1)
+}
+
+impl<'b> Field<'b> {
+    pub fn name(&self) -> &'b str {
+        self.name
+    }
+
+    /// The value as written, with any escape sequences left in; empty for
+    /// a null.
+    pub fn value(&self) -> &'b str {
+        self.value
+    }
+
+    /// Whether the value is a null rather than a string.
+    pub fn is_null(&self) -> bool {
+        self.null
+    }
+
+    /// The value with the escape sequences of a quoted value decoded:
+    /// `\n`, `\r`, `\t`, `\\` and `\"`. Unquoted values have no escapes.
+    ///
+    /// ```
+    /// use xrave::{parse_line, ParsedLine};
+    ///
+    /// if let ParsedLine::Record(record) = parse_line(b"r:1 a:\"x\\ty\"\n")? {
+    ///     assert_eq!(record.cols()[0].value(), "x\\ty");
+    ///     assert_eq!(record.cols()[0].value_unescaped(), "x\ty");
+    /// }
+    /// # Ok::<(), xrave::XRVErr>(())
+    /// ```
+    pub fn value_unescaped(&self) -> Cow<'b, str> {
+        match self.escaped {
+            false => Cow::Borrowed(self.value),
+            true => unescape(self.value, self.quote),
+        }
+    }
+
+    pub fn to_owned(&self) -> OwnedField {
+        self.to_owned_in(&mut Interner::new(false))
+    }
+
+    pub(crate) fn to_owned_in(&self, names: &mut Interner) -> OwnedField {
+        OwnedField {
+            name: names.intern(self.name),
+            value: self.value_unescaped().into_owned(),
+            null: self.null,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedField {
+    pub name: Arc<str>,
+    /// The value, empty for a null.
+    pub value: String,
+    /// Whether the value is a null, written as the bare null byte of the
+    /// dialect, rather than a string.
+    pub null: bool,
+}
+
+impl OwnedField {
+    /// The value, or `Value::Null`.
+    pub fn as_value(&self) -> Value<'_> {
+        match self.null {
+            true => Value::Null,
+            false => Value::Str(&self.value),
+        }
+    }
+}
+
+/// A field value: a string, possibly empty, or a null.
+///
+/// ```
+/// use xrave::{Reader, Value, Writer};
+///
+/// let path = std::env::temp_dir().join("xrave-doc-null.xrv");
+/// let mut writer = Writer::new(std::fs::File::create(&path)?);
+/// writer.add_table("users", &[("email", "str?")])?;
+/// writer.add_record_values("users", "1", &[("email", Value::Null)])?;
+/// writer.add_record_values("users", "2", &[("email", "".into())])?;
+/// writer.finish()?;
+///
+/// let mut reader = Reader::new(&path)?;
+/// let records = reader.records("users")?.collect::<Result<Vec<_>, _>>()?;
+/// let emails: Vec<_> = records.iter().map(|record| record.cols[0].as_value()).collect();
+/// assert_eq!(emails, [Value::Null, Value::Str("")]);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Value<'a> {
+    Null,
+    Str(&'a str),
+}
+
+impl<'a> From<&'a str> for Value<'a> {
+    fn from(value: &'a str) -> Self {
+        Value::Str(value)
+    }
+}
+
+impl<'b> From<&'b OwnedField> for Field<'b> {
+    fn from(value: &'b OwnedField) -> Self {
+        Field {
+            name: &value.name,
+            value: &value.value,
+            escaped: false,
+            quote: QUOTE_CHAR,
+            null: value.null,
+        }
+    }
+}
+
+/// Where a line lies in its file: the byte offset of its first byte and its
+/// length including the line terminator.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub offset: u64,
+    pub len: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedRecordLine {
+    pub id: String,
+    pub cols: Vec<OwnedField>,
+    /// Location of the line the record was read from; zero for records
+    /// that were not read by a `Reader` or `SharedReader`.
+    pub span: Span,
+}
+
+impl OwnedRecordLine {
+    /// Values of every field called `name`, in line order.
+    pub fn get_all<'s>(&'s self, name: &'s str) -> impl Iterator<Item = &'s str> {
+        self.cols
+            .iter()
+            .filter(move |field| &*field.name == name)
+            .map(|field| field.value.as_str())
+    }
+
+    /// Value of the first field called `col`, falling back to the id for
+    /// `id`, like `extract_key`.
+    pub(crate) fn key(&self, col: &str) -> Option<&str> {
+        match self.cols.iter().find(|field| &*field.name == col) {
+            None if col == RECORD_KEY_FIELD => Some(&self.id),
+            field => field.map(|field| field.value.as_str()),
+        }
+    }
+}
+
+impl<'b> From<&'b OwnedRecordLine> for RecordLine<'b> {
+    fn from(value: &'b OwnedRecordLine) -> Self {
+        RecordLine {
+            id: &value.id,
+            cols: value.cols.iter().map(Field::from).collect(),
+        }
+    }
+}
+
+impl<'b> TryInto<usize> for Field<'b> {
+    type Error = XRVErr;
+    fn try_into(self) -> Result<usize, Self::Error> {
+        match self.value.parse::<usize>() {
+            Err(_) => Err(XRVErr::CantParseFieldUsizeValue),
+            Ok(u) => Ok(u),
+        }
+    }
+}
+
+pub struct TableLine<'b> {
+    pub(crate) id: &'b str,
+    pub(crate) name: &'b str,
+    pub(crate) pos: usize,
+    pub(crate) len: usize,
+    pub(crate) next_id: Option<u64>,
+    pub(crate) cols: Vec<Field<'b>>,
+}
+
+impl<'b> TableLine<'b> {
+    pub fn id(&self) -> &'b str {
+        self.id
+    }
+
+    pub fn name(&self) -> &'b str {
+        self.name
+    }
+
+    /// Byte offset of the table's first record line.
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Byte length of the table's record region.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Next id `IdStrategy::Sequential` gives out in the table, from the
+    /// optional `next_id` field.
+    pub fn next_id(&self) -> Option<u64> {
+        self.next_id
+    }
+
+    /// Column declarations following the `name`, `pos` and `len` fields.
+    pub fn cols(&self) -> &[Field<'b>] {
+        &self.cols
+    }
+
+    pub fn to_owned(&self) -> OwnedTableLine {
+        self.to_owned_in(&mut Interner::new(false))
+    }
+
+    pub(crate) fn to_owned_in(&self, names: &mut Interner) -> OwnedTableLine {
+        OwnedTableLine {
+            id: self.id.to_owned(),
+            name: self.name.to_owned(),
+            pos: self.pos,
+            len: self.len,
+            next_id: self.next_id,
+            cols: self
+                .cols
+                .iter()
+                .map(|field| field.to_owned_in(names))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedTableLine {
+    pub id: String,
+    pub name: String,
+    pub pos: usize,
+    pub len: usize,
+    /// See `TableLine::next_id`.
+    pub next_id: Option<u64>,
+    pub cols: Vec<OwnedField>,
+}
+
+impl OwnedTableLine {
+    /// Declaration of the column `name`, when the header declares it as a
+    /// known kind.
+    pub fn col(&self, name: &str) -> Option<Col> {
+        self.cols
+            .iter()
+            .find(|col| &*col.name == name)
+            .and_then(|col| Col::parse(&col.value).ok())
+    }
+
+    /// Every column in header order with its declaration, or `None` for a
+    /// kind `Col` does not know, which is kept in `cols` as written.
+    ///
+    /// ```
+    /// use xrave::{ColKind, Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-typed-cols.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("email", "str!"), ("photo", "bin")])?;
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(&path)?;
+    /// reader.load_headers()?;
+    /// let cols: Vec<_> = reader.table("users").unwrap().typed_cols().collect();
+    /// assert_eq!(cols, [("email", Some(ColKind::String.key())), ("photo", None)]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn typed_cols(&self) -> impl Iterator<Item = (&str, Option<Col>)> {
+        self.cols
+            .iter()
+            .map(|col| (&*col.name, Col::parse(&col.value).ok()))
+    }
+}
+
+impl<'b> From<&'b OwnedTableLine> for TableLine<'b> {
+    fn from(value: &'b OwnedTableLine) -> Self {
+        TableLine {
+            id: &value.id,
+            name: &value.name,
+            pos: value.pos,
+            len: value.len,
+            next_id: value.next_id,
+            cols: value.cols.iter().map(Field::from).collect(),
+        }
+    }
+}
+
+/// Required table header fields with the spellings accepted for each,
+/// compared case-insensitively.
+const TABLE_HEADER_FIELDS: [(&str, &[&str]); 3] = [
+    ("name", &["name"]),
+    ("pos", &["pos", "position", "seek"]),
+    ("len", &["len", "length"]),
+];
+
+impl<'b> TryFrom<LineField<'b>> for TableLine<'b> {
+    type Error = XRVErr;
+    fn try_from(value: LineField<'b>) -> Result<Self, Self::Error> {
+        match value.kind {
+            LineKind::Table => {
+                let got = value.fields.len();
+                let mut header: HashMap<&str, Field<'b>> = HashMap::new();
+                let mut next_id: Option<u64> = None;
+                let mut cols: Vec<Field<'b>> = Vec::new();
+                for field in value.fields {
+                    if next_id.is_none() && field.name.eq_ignore_ascii_case(NEXT_ID_FIELD) {
+                        match field.value.parse::<u64>() {
+                            Err(_) => return Err(XRVErr::CantParseFieldUsizeValue),
+                            Ok(id) => next_id = Some(id),
+                        }
+                        continue;
+                    }
+                    let required = TABLE_HEADER_FIELDS.iter().find(|(_, aliases)| {
+                        aliases
+                            .iter()
+                            .any(|alias| field.name.eq_ignore_ascii_case(alias))
+                    });
+                    match required {
+                        Some((key, _)) if !header.contains_key(key) => {
+                            header.insert(key, field);
+                        }
+                        _ => cols.push(field),
+                    }
+                }
+
+                let missing: Vec<String> = TABLE_HEADER_FIELDS
+                    .iter()
+                    .filter(|(key, _)| !header.contains_key(key))
+                    .map(|(key, _)| key.to_string())
+                    .collect();
+                // A line cut short before any header field is reported as
+                // such; one naming some of them lists the rest.
+                if header.is_empty() && got < TABLE_HEADER_FIELDS.len() {
+                    return Err(XRVErr::TableLineTooShort {
+                        expected: TABLE_HEADER_FIELDS.len(),
+                        got,
+                        line: value.line,
+                    });
+                }
+                if !missing.is_empty() {
+                    return Err(XRVErr::MissingTableFields(missing));
+                }
+
+                let name: &'b str = header["name"].value;
+                let pos: usize = header["pos"].clone().try_into()?;
+                let len: usize = header["len"].clone().try_into()?;
+                if pos.checked_add(len).is_none() {
+                    return Err(XRVErr::RegionOverflow { pos, len });
+                }
+
+                Ok(TableLine {
+                    id: value.name,
+                    name,
+                    pos,
+                    len,
+                    next_id,
+                    cols,
+                })
+            }
+            _ => Err(XRVErr::NotTableLine),
+        }
+    }
+}
+
+pub struct StyleLine<'b> {
+    pub(crate) id: &'b str,
+    pub(crate) cols: Vec<Field<'b>>,
+}
+
+impl<'b> StyleLine<'b> {
+    pub fn id(&self) -> &'b str {
+        self.id
+    }
+
+    pub fn cols(&self) -> &[Field<'b>] {
+        &self.cols
+    }
+
+    pub fn to_owned(&self) -> OwnedStyleLine {
+        self.to_owned_in(&mut Interner::new(false))
+    }
+
+    pub(crate) fn to_owned_in(&self, names: &mut Interner) -> OwnedStyleLine {
+        OwnedStyleLine {
+            id: self.id.to_owned(),
+            cols: self
+                .cols
+                .iter()
+                .map(|field| field.to_owned_in(names))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedStyleLine {
+    pub id: String,
+    pub cols: Vec<OwnedField>,
+}
+
+impl OwnedStyleLine {
+    /// Value of the first field called `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.cols
+            .iter()
+            .find(|field| &*field.name == name)
+            .map(|field| field.value.as_str())
+    }
+}
+
+impl<'b> From<&'b OwnedStyleLine> for StyleLine<'b> {
+    fn from(value: &'b OwnedStyleLine) -> Self {
+        StyleLine {
+            id: &value.id,
+            cols: value.cols.iter().map(Field::from).collect(),
+        }
+    }
+}
+
+impl<'b> TryFrom<LineField<'b>> for StyleLine<'b> {
+    type Error = XRVErr;
+    fn try_from(value: LineField<'b>) -> Result<Self, Self::Error> {
+        match value.kind {
+            LineKind::Style => Ok(StyleLine {
+                id: value.name,
+                cols: value.fields,
+            }),
+            _ => Err(XRVErr::NotStyleLine),
+        }
+    }
+}
+
+/// Field name that refers to a record's `r:` id when no field of that name
+/// exists.
+pub(crate) const RECORD_KEY_FIELD: &str = "id";
+
+pub struct RecordLine<'b> {
+    pub(crate) id: &'b str,
+    pub(crate) cols: Vec<Field<'b>>,
+}
+
+impl<'b> RecordLine<'b> {
+    pub fn id(&self) -> &'b str {
+        self.id
+    }
+
+    pub fn cols(&self) -> &[Field<'b>] {
+        &self.cols
+    }
+
+    /// Value of the first field called `name`.
+    pub fn get(&self, name: &str) -> Option<&'b str> {
+        self.cols
+            .iter()
+            .find(|field| field.name == name)
+            .map(|field| field.value)
+    }
+
+    /// Values of every field called `name`, in line order.
+    pub fn get_all<'s>(&'s self, name: &'s str) -> impl Iterator<Item = &'b str> + 's {
+        self.cols
+            .iter()
+            .filter(move |field| field.name == name)
+            .map(|field| field.value)
+    }
+
+    pub fn to_owned(&self) -> OwnedRecordLine {
+        self.to_owned_in(&mut Interner::new(false))
+    }
+
+    pub(crate) fn to_owned_in(&self, names: &mut Interner) -> OwnedRecordLine {
+        OwnedRecordLine {
+            id: self.id.to_owned(),
+            cols: self
+                .cols
+                .iter()
+                .map(|field| field.to_owned_in(names))
+                .collect(),
+            span: Span::default(),
+        }
+    }
+}
+
+/// Writes a line in canonical form, as the `Writer` lays it out in the
+/// default dialect, without its terminator.
+fn fmt_line(f: &mut fmt::Formatter<'_>, kind: u8, id: &str, fields: &[Field]) -> fmt::Result {
+    let values: Vec<Cow<str>> = fields.iter().map(Field::value_unescaped).collect();
+    let pairs: Vec<(&str, Value)> = fields
+        .iter()
+        .zip(values.iter())
+        .map(|(field, value)| match field.null {
+            true => (field.name, Value::Null),
+            false => (field.name, Value::Str(value)),
+        })
+        .collect();
+    let mut line = Vec::new();
+    write_line(&mut line, kind, id, &pairs, Dialect::default());
+    line.pop();
+    f.write_str(&String::from_utf8_lossy(&line))
+}
+
+/// Renders the header back to canonical xrv text, its `pos` and `len` as
+/// parsed. The other line kinds render the same way; their `Debug` output
+/// only names the line and counts its fields.
+///
+/// ```
+/// use xrave::{parse_line, ParsedLine};
+///
+/// if let ParsedLine::Table(table) = parse_line(b"t:1  pos:120 name:users len:40 id:i32")? {
+///     assert_eq!(table.to_string(), "t:1 name:users pos:120 len:40 id:i32");
+/// }
+/// # Ok::<(), xrave::XRVErr>(())
+/// ```
+impl<'b> fmt::Display for TableLine<'b> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (pos, len) = (self.pos.to_string(), self.len.to_string());
+        let plain = |name, value| Field {
+            name,
+            value,
+            escaped: false,
+            quote: QUOTE_CHAR,
+            null: false,
+        };
+        let head = [
+            plain("name", self.name),
+            plain("pos", &pos),
+            plain("len", &len),
+        ];
+        let fields: Vec<Field> = head.into_iter().chain(self.cols.iter().cloned()).collect();
+        fmt_line(f, TABLE_ID, self.id, &fields)
+    }
+}
+
+impl<'b> fmt::Debug for TableLine<'b> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TableLine")
+            .field("name", &self.name)
+            .field("pos", &self.pos)
+            .field("len", &self.len)
+            .field("fields", &self.cols.len())
+            .finish()
+    }
+}
+
+impl<'b> fmt::Display for StyleLine<'b> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_line(f, STYLE_ID, self.id, &self.cols)
+    }
+}
+
+impl<'b> fmt::Debug for StyleLine<'b> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StyleLine")
+            .field("id", &self.id)
+            .field("fields", &self.cols.len())
+            .finish()
+    }
+}
+
+impl<'b> fmt::Display for RecordLine<'b> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_line(f, RECORD_ID, self.id, &self.cols)
+    }
+}
+
+impl<'b> fmt::Debug for RecordLine<'b> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordLine")
+            .field("id", &self.id)
+            .field("fields", &self.cols.len())
+            .finish()
+    }
+}
+
+/// Converts a record line to an owned record, decoding its values as
+/// `encoding` asks. The id and field names must be UTF-8 in every mode.
+pub(crate) fn decode_record(
+    raw: RawLine,
+    encoding: Encoding,
+    names: &mut Interner,
+) -> Result<OwnedRecordLine, XRVErr> {
+    let id = match std::str::from_utf8(raw.name) {
+        Err(_) => return Err(XRVErr::CantParseFieldName),
+        Ok(id) => id.to_owned(),
+    };
+    if raw.kind != LineKind::Record {
+        return Err(XRVErr::NotRecordLine);
+    }
+    let (line, buffer, quote) = (raw.line, raw.fields.buffer, raw.fields.dialect.quote);
+    let duplicates = raw.fields.duplicates;
+    let mut cols = Vec::new();
+    for link in raw.fields.links() {
+        let name = match utf8_at(buffer, link.name_start, link.name_end) {
+            Err(col) => return Err(XRVErr::CantParseFieldStrName { line, col }),
+            Ok(name) => name,
+        };
+        let value = match encoding {
+            Encoding::Utf8Strict => match utf8_at(buffer, link.value_start, link.value_end) {
+                Err(col) => return Err(XRVErr::CantParseFieldStrValue { line, col }),
+                Ok(value) => Cow::Borrowed(value),
+            },
+            _ => String::from_utf8_lossy(&buffer[link.value_start..link.value_end]),
+        };
+        cols.push(OwnedField {
+            name: names.intern(name),
+            value: match (link.null, link.escaped) {
+                (true, _) => String::new(),
+                (false, false) => value.into_owned(),
+                (false, true) => unescape(&value, quote).into_owned(),
+            },
+            null: link.null,
+        });
+    }
+    keep_duplicates(&mut cols, |field| &field.name, duplicates, line)?;
+    Ok(OwnedRecordLine {
+        id,
+        cols,
+        span: Span::default(),
+    })
+}
+
+/// Checks that the line name and every field name of `raw` are UTF-8, and
+/// the values too when `values` is set, and under `DuplicateFields::Error`
+/// that no name is used twice, without building the line's fields. Errors
+/// come in the order converting to a `LineField` would raise them. Names
+/// are gathered in an inline scratch list; `spills` counts the lines whose
+/// names outgrew it.
+pub(crate) fn check_fields(raw: &RawLine, values: bool, spills: &mut usize) -> Result<(), XRVErr> {
+    if std::str::from_utf8(raw.name).is_err() {
+        return Err(XRVErr::CantParseFieldName);
+    }
+    let (line, buffer) = (raw.line, raw.fields.buffer);
+    let unique = raw.fields.duplicates == DuplicateFields::Error;
+    let mut names: InlineVec<&str, INLINE_FIELDS> = InlineVec::new();
+    for link in raw.fields.clone().links() {
+        let name = match utf8_at(buffer, link.name_start, link.name_end) {
+            Err(col) => return Err(XRVErr::CantParseFieldStrName { line, col }),
+            Ok(name) => name,
+        };
+        if values {
+            if let Err(col) = utf8_at(buffer, link.value_start, link.value_end) {
+                return Err(XRVErr::CantParseFieldStrValue { line, col });
+            }
+        }
+        if unique {
+            names.push(name);
+        }
+    }
+    if names.spilled() {
+        *spills += 1;
+    }
+    match first_duplicate(names.as_slice()) {
+        None => Ok(()),
+        Some(name) => Err(XRVErr::DuplicateField {
+            line,
+            name: name.to_owned(),
+        }),
+    }
+}
+
+/// The first name in `names` that an earlier one already used.
+fn first_duplicate<'n>(names: &[&'n str]) -> Option<&'n str> {
+    if names.len() <= INLINE_FIELDS {
+        return (1..names.len())
+            .find(|idx| names[..*idx].contains(&names[*idx]))
+            .map(|idx| names[idx]);
+    }
+    let mut seen = HashSet::new();
+    names.iter().copied().find(|name| !seen.insert(*name))
+}
+
+/// A record whose values are left as bytes, returned by
+/// `Reader::next_record_bytes`.
+#[derive(Debug)]
+pub struct RecordBytes<'b> {
+    pub(crate) id: &'b str,
+    pub(crate) cols: Vec<FieldBytes<'b>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldBytes<'b> {
+    pub(crate) name: &'b str,
+    pub(crate) value: &'b [u8],
+}
+
+impl<'b> FieldBytes<'b> {
+    pub fn name(&self) -> &'b str {
+        self.name
+    }
+
+    pub fn value(&self) -> &'b [u8] {
+        self.value
+    }
+}
+
+impl<'b> RecordBytes<'b> {
+    pub fn id(&self) -> &'b str {
+        self.id
+    }
+
+    pub fn cols(&self) -> &[FieldBytes<'b>] {
+        &self.cols
+    }
+
+    /// Value of the first field called `name`.
+    pub fn get(&self, name: &str) -> Option<&'b [u8]> {
+        self.cols
+            .iter()
+            .find(|field| field.name == name)
+            .map(|field| field.value)
+    }
+}
+
+impl<'b> TryFrom<RawLine<'b>> for RecordBytes<'b> {
+    type Error = XRVErr;
+    fn try_from(value: RawLine<'b>) -> Result<Self, Self::Error> {
+        if value.kind != LineKind::Record {
+            return Err(XRVErr::NotRecordLine);
+        }
+        let id = match std::str::from_utf8(value.name) {
+            Err(_) => return Err(XRVErr::CantParseFieldName),
+            Ok(id) => id,
+        };
+        let (line, buffer) = (value.line, value.fields.buffer);
+        let duplicates = value.fields.duplicates;
+        let mut cols = Vec::new();
+        for link in value.fields.links() {
+            let name = match utf8_at(buffer, link.name_start, link.name_end) {
+                Err(col) => return Err(XRVErr::CantParseFieldStrName { line, col }),
+                Ok(name) => name,
+            };
+            cols.push(FieldBytes {
+                name,
+                value: &buffer[link.value_start..link.value_end],
+            });
+        }
+        keep_duplicates(&mut cols, |field| field.name, duplicates, line)?;
+        Ok(RecordBytes { id, cols })
+    }
+}
+
+impl<'b> TryFrom<LineField<'b>> for RecordLine<'b> {
+    type Error = XRVErr;
+    fn try_from(value: LineField<'b>) -> Result<Self, Self::Error> {
+        match value.kind {
+            LineKind::Record => Ok(RecordLine {
+                id: value.name,
+                cols: value.fields,
+            }),
+            _ => Err(XRVErr::NotRecordLine),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table_line(bytes: &[u8]) -> Result<OwnedTableLine, XRVErr> {
+        let line_field = LineField::try_from(RawLine::parse(bytes)?)?;
+        TableLine::try_from(line_field).map(|table| table.to_owned())
+    }
+
+    #[test]
+    fn truncated_table_lines_fail_without_panicking() {
+        for (bytes, got) in [(&b"t:1"[..], 0), (b"t:1 a:x", 1), (b"t:1 a:x b:y", 2)] {
+            match table_line(bytes) {
+                Err(XRVErr::TableLineTooShort {
+                    expected: 3,
+                    got: found,
+                    line: 1,
+                }) => assert_eq!(found, got, "{:?}", bytes),
+                other => panic!("unexpected {:?} for {:?}", other, bytes),
+            }
+        }
+    }
+
+    #[test]
+    fn partial_table_headers_list_missing_fields() {
+        let missing = |bytes: &[u8]| match table_line(bytes) {
+            Err(XRVErr::MissingTableFields(fields)) => fields,
+            other => panic!("unexpected {:?} for {:?}", other, bytes),
+        };
+        assert_eq!(missing(b"t:1 name:users"), ["pos", "len"]);
+        assert_eq!(missing(b"t:1 name:users pos:0"), ["len"]);
+        assert_eq!(missing(b"t:1 a:x b:y c:z"), ["name", "pos", "len"]);
+        assert_eq!(missing(b"t:1 name:users a:x b:y"), ["pos", "len"]);
+    }
+
+    #[test]
+    fn shuffled_header_fields_parse_alike() {
+        let expected = table_line(b"t:1 name:users pos:10 len:5 age:i32").unwrap();
+        for bytes in [
+            &b"t:1 len:5 pos:10 name:users age:i32"[..],
+            b"t:1 age:i32 len:5 name:users pos:10",
+            b"t:1 pos:10 age:i32 name:users len:5",
+        ] {
+            assert_eq!(table_line(bytes).unwrap(), expected, "{:?}", bytes);
+        }
+    }
+
+    #[test]
+    fn header_field_names_ignore_case_and_accept_aliases() {
+        let expected = table_line(b"t:1 name:users pos:10 len:5").unwrap();
+        for bytes in [
+            &b"t:1 NAME:users Pos:10 LEN:5"[..],
+            b"t:1 name:users position:10 length:5",
+            b"t:1 Name:users SEEK:10 Length:5",
+        ] {
+            assert_eq!(table_line(bytes).unwrap(), expected, "{:?}", bytes);
+        }
+    }
+
+    #[test]
+    fn header_without_len_lists_it_as_missing() {
+        for bytes in [
+            &b"t:1 name:users pos:10 age:i32"[..],
+            b"t:1 POS:10 Name:users age:i32",
+            b"t:1 age:i32 seek:10 name:users",
+        ] {
+            match table_line(bytes) {
+                Err(XRVErr::MissingTableFields(fields)) => assert_eq!(fields, ["len"]),
+                other => panic!("unexpected {:?} for {:?}", other, bytes),
+            }
+        }
+    }
+
+    #[test]
+    fn complete_table_header_parses() {
+        let table = table_line(b"t:1 name:users pos:0 len:5").unwrap();
+        assert_eq!((table.name.as_str(), table.pos, table.len), ("users", 0, 5));
+        assert!(table.cols.is_empty());
+    }
+
+    #[test]
+    fn escapes_decode_only_in_quoted_values() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Document, RawLineIter, Reader, Writer, XRVErr};
+
+        let note = "first\nsecond\tcol \\ \"quoted\" and a literal \\n";
+        let mut out = Vec::new();
+        let mut writer = Writer::new(&mut out);
+        writer.add_table("notes", &[("text", "str"), ("path", "str")])?;
+        writer.add_record("notes", "1", &[("text", note), ("path", "C:\\n")])?;
+        writer.finish()?;
+
+        let text = String::from_utf8(out)?;
+        assert_eq!(text.lines().count(), 4);
+        assert!(text.contains(r#" text:"first\nsecond\tcol \\ \"quoted\" and a literal \\n" "#));
+
+        let document = Document::read(text.as_bytes())?;
+        let record = document.table("notes").unwrap().record(0)?.unwrap();
+        assert_eq!(record.cols[0].value, note);
+        assert_eq!(record.cols[1].value, "C:\\n");
+
+        let path = std::env::temp_dir().join("xrave-test-unescape.xrv");
+        std::fs::write(&path, "v:xrave 2\nj:jumps\nr:1 a:\"x\\ty\" b:x\\ty\n")?;
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        let record = reader.next_record()?.unwrap();
+        let values: Vec<_> = record.cols().iter().map(|f| f.value_unescaped()).collect();
+        assert_eq!(values, ["x\ty", "x\\ty"]);
+        assert_eq!(record.cols()[0].value(), "x\\ty");
+
+        let raw_newline = RawLineIter::new(b"r:1 a:\"x\ny\"\n").next().unwrap();
+        assert!(matches!(raw_newline, Err(XRVErr::AtLine { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn nulls_and_empty_strings_stay_apart() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Document, ExportOptions, Reader, Value, Writer};
+
+        let path = std::env::temp_dir().join("xrave-test-null.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("users", &[("name", "str"), ("email", "str?")])?;
+        writer.add_record_values(
+            "users",
+            "1",
+            &[("name", "Anna".into()), ("email", Value::Null)],
+        )?;
+        writer.add_record_values(
+            "users",
+            "2",
+            &[("name", "Bob".into()), ("email", "".into())],
+        )?;
+        writer.add_record("users", "3", &[("name", "~"), ("email", "~@x")])?;
+        writer.finish()?;
+
+        let text = std::fs::read_to_string(&path)?;
+        assert!(text.contains("r:1 name:Anna email:~\n"));
+        assert!(text.contains("r:2 name:Bob email:\"\"\n"));
+        assert!(text.contains("r:3 name:\"~\" email:~@x\n"));
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        let records = reader.records("users")?.collect::<Result<Vec<_>, _>>()?;
+        let emails: Vec<_> = records.iter().map(|r| r.cols[1].as_value()).collect();
+        assert_eq!(emails, [Value::Null, Value::Str(""), Value::Str("~@x")]);
+        assert_eq!(records[2].cols[0].as_value(), Value::Str("~"));
+
+        let document = Document::open(&path)?;
+        let table = document.table("users").unwrap();
+        let typed = table.typed_records().collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(typed[0].value("email"), Some(Value::Null));
+        assert_eq!(typed[0].get_opt_str("email"), None);
+        assert_eq!(typed[1].get_opt_str("email"), Some(""));
+        assert_eq!(typed[2].get_opt_str("name"), Some("~"));
+        assert_eq!(
+            table.to_json(ExportOptions::default())?,
+            r#"[{"id":"1","name":"Anna","email":null},{"id":"2","name":"Bob","email":""},{"id":"3","name":"~","email":"~@x"}]"#
+        );
+
+        let mut out = Vec::new();
+        document.write(&mut out, Default::default())?;
+        assert_eq!(String::from_utf8(out)?, text);
+
+        let old = Document::read(
+            &b"v:xrave 2\nj:jumps\nt:1 name:users pos:56 len:11 name:str\nr:1 name:~\n"[..],
+        )?;
+        assert_eq!(
+            old.tables[0].record(0)?.unwrap().cols[0].as_value(),
+            Value::Str("~")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn typed_cols_parse_every_declaration() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{ColKind, Reader, Writer};
+
+        let path = std::env::temp_dir().join("xrave-test-typed-cols.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table(
+            "users",
+            &[("email", "str!"), ("age", "i32[0..150]"), ("photo", "bin")],
+        )?;
+        writer.add_table("orders", &[("user", "str->users"), ("total", "f64=0")])?;
+        writer.add_style("bold", &[("weight", "700")])?;
+        writer.add_record(
+            "users",
+            "1",
+            &[("email", "a@x"), ("age", "31"), ("photo", "")],
+        )?;
+        writer.add_record("orders", "o1", &[("user", "a@x")])?;
+        writer.finish()?;
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        reader.load_headers()?;
+        let users = reader.table("users").unwrap();
+        let orders = reader.table("orders").unwrap();
+        assert_eq!((users.pos + users.len, orders.len), (orders.pos, 14));
+        assert_eq!(
+            users.typed_cols().collect::<Vec<_>>(),
+            [
+                ("email", Some(ColKind::String.key())),
+                ("age", Some(ColKind::I32.bounded(0.0, 150.0))),
+                ("photo", None),
+            ]
+        );
+        assert_eq!(
+            orders.col("user"),
+            Some(ColKind::String.references("users"))
+        );
+        assert_eq!(orders.col("total").unwrap().default.as_deref(), Some("0"));
+        assert_eq!(orders.col("missing"), None);
+        assert_eq!(reader.style("bold").unwrap().get("weight"), Some("700"));
+        Ok(())
+    }
+
+    #[test]
+    fn header_lines_render_canonically() -> Result<(), crate::XRVErr> {
+        use crate::{parse_line, ParsedLine};
+
+        let golden = [
+            (
+                "t:1  pos:120 name:users len:4096 id:i32 \"full name\":str",
+                "t:1 name:users pos:120 len:4096 id:i32 \"full name\":str",
+                "TableLine { name: \"users\", pos: 120, len: 4096, fields: 2 }",
+            ),
+            (
+                "s:bold weight:700  color:\"dark red\"",
+                "s:bold weight:700 color:\"dark red\"",
+                "StyleLine { id: \"bold\", fields: 2 }",
+            ),
+            (
+                "r:7 name:\"Anna\" note:\"a \\\"b\\\"\\tc\" tag:x",
+                "r:7 name:Anna note:\"a \\\"b\\\"\\tc\" tag:x",
+                "RecordLine { id: \"7\", fields: 3 }",
+            ),
+        ];
+        for (input, display, debug) in golden {
+            let (shown, debugged) = match parse_line(input.as_bytes())? {
+                ParsedLine::Table(line) => (line.to_string(), format!("{:?}", line)),
+                ParsedLine::Style(line) => (line.to_string(), format!("{:?}", line)),
+                ParsedLine::Record(line) => (line.to_string(), format!("{:?}", line)),
+                other => panic!("unexpected {:?}", other),
+            };
+            assert_eq!((shown.as_str(), debugged.as_str()), (display, debug));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,321 @@
+use std::io::prelude::*;
+use std::{fmt, fs::File};
+
+use crate::{
+    error::{open_error, XRVErr},
+    line::{LineField, LineJump, LineKind, TableLine},
+    reader::{ParseMode, Reader, Source},
+    tokenizer::{RawLine, XraveBuffer, CR_CHAR, NL_CHAR, SPACE_CHAR, TAB_CHAR},
+    version::read_first_line,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Info => write!(f, "info"),
+        }
+    }
+}
+
+/// One problem found by `Reader::lint`. Codes stay the same across
+/// releases:
+///
+/// - `X001` error: the file or a line cannot be read or parsed
+/// - `X002` error: a record before any table header
+/// - `X003` error: a record outside every table's `pos`/`len` region
+/// - `X004` error: a table region that starts or ends inside a line
+/// - `X005` warning: spaces or tabs at the end of a line
+/// - `X006` warning: line endings that differ from the first line's
+/// - `X007` info: record fields in another order than the table declares
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    pub code: &'static str,
+    pub severity: Severity,
+    /// 1-based physical line the problem is on, 0 for the whole file.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}: {} {}: {}",
+            self.line, self.code, self.severity, self.message
+        )
+    }
+}
+
+impl Lint {
+    fn new(code: &'static str, severity: Severity, line: usize, message: String) -> Lint {
+        Lint {
+            code,
+            severity,
+            line,
+            message,
+        }
+    }
+}
+
+/// A table line met by the scan: its name, region, declared column order
+/// and the line it is on.
+struct LintTable {
+    name: String,
+    pos: usize,
+    end: usize,
+    cols: Vec<String>,
+    line: usize,
+}
+
+impl Reader {
+    /// Reads the whole file again and reports every structural problem in
+    /// it, in line order, instead of stopping at the first one. See `Lint`
+    /// for the codes.
+    ///
+    /// ```
+    /// use xrave::Reader;
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-lint.xrv");
+    /// std::fs::write(&path, "j:jumps\nr:1 a:\"open\n")?;
+    /// let mut reader = Reader::new(&path)?;
+    /// let lints = reader.lint();
+    /// assert!(lints.iter().any(|lint| lint.line == 2));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn lint(&mut self) -> Vec<Lint> {
+        let mut bytes = Vec::new();
+        let read = match self.reopen_path().map(|path| (path, File::open(path))) {
+            Err(err) => Err(err),
+            Ok((path, Err(err))) => Err(open_error(path, err)),
+            Ok((_, Ok(file))) => Source::open(file).and_then(|mut source| {
+                source
+                    .read_to_end(&mut bytes)
+                    .map_err(XRVErr::FailToReadLine)
+            }),
+        };
+        if let Err(err) = read {
+            return vec![Lint::new("X001", Severity::Error, 0, err.to_string())];
+        }
+
+        let mut lints = physical_lints(&bytes);
+        lints.extend(self.logical_lints(&bytes));
+        lints.sort_by_key(|lint| lint.line);
+        lints
+    }
+
+    /// Lints of the lines as parsed: structure, regions and field order.
+    fn logical_lints(&self, bytes: &[u8]) -> Vec<Lint> {
+        let mut lints = Vec::new();
+        let max_line_len = self.options.max_line_len;
+        let mut source = bytes;
+        let mut buffer = XraveBuffer::new();
+        buffer.limits = self.options.line_limits();
+        let first = read_first_line(
+            &mut buffer,
+            &mut source,
+            max_line_len,
+            ParseMode::Lenient,
+            &mut Vec::new(),
+        );
+        let jumps = first.and_then(|_| {
+            let raw_line: RawLine = (&buffer).try_into()?;
+            LineJump::try_from(raw_line)
+                .map(|_| ())
+                .map_err(|err| buffer.diagnose(err.position().1, err))
+        });
+        if let Err(err) = jumps {
+            lints.push(Lint::new(
+                "X001",
+                Severity::Error,
+                buffer.line,
+                err.to_string(),
+            ));
+            return lints;
+        }
+
+        let mut tables: Vec<LintTable> = Vec::new();
+        let mut line_starts = vec![buffer.start as usize];
+        loop {
+            let start = buffer.offset as usize;
+            match buffer.read_line(&mut source, max_line_len) {
+                Err(err) => {
+                    lints.push(Lint::new(
+                        "X001",
+                        Severity::Error,
+                        buffer.line,
+                        err.to_string(),
+                    ));
+                    continue;
+                }
+                Ok(0) => break,
+                Ok(_) => line_starts.push(start),
+            }
+            if buffer.is_filler(&mut source).unwrap_or(false) {
+                continue;
+            }
+            let line = buffer.line;
+            let parsed = RawLine::try_from(&buffer).and_then(|raw_line| {
+                LineField::try_from(raw_line).map_err(|err| buffer.diagnose(err.position().1, err))
+            });
+            let line_field = match parsed {
+                Err(err) => {
+                    lints.push(Lint::new("X001", Severity::Error, line, err.to_string()));
+                    continue;
+                }
+                Ok(line_field) => line_field,
+            };
+            match line_field.kind {
+                LineKind::Table => match TableLine::try_from(line_field) {
+                    Err(err) => {
+                        let err = buffer.diagnose(err.position().1, err);
+                        lints.push(Lint::new("X001", Severity::Error, line, err.to_string()));
+                    }
+                    Ok(table) => tables.push(LintTable {
+                        name: table.name.to_owned(),
+                        pos: table.pos,
+                        end: table.pos.saturating_add(table.len),
+                        cols: table.cols.iter().map(|col| col.name.to_owned()).collect(),
+                        line,
+                    }),
+                },
+                LineKind::Record => {
+                    if tables.is_empty() {
+                        let message =
+                            format!("record '{}' before any table header", line_field.name);
+                        lints.push(Lint::new("X002", Severity::Error, line, message));
+                        continue;
+                    }
+                    let table = tables
+                        .iter()
+                        .find(|table| (table.pos..table.end).contains(&start));
+                    let table = match table {
+                        None => {
+                            let message =
+                                format!("record '{}' outside every table region", line_field.name);
+                            lints.push(Lint::new("X003", Severity::Error, line, message));
+                            continue;
+                        }
+                        Some(table) => table,
+                    };
+                    let order: Vec<usize> = line_field
+                        .fields
+                        .iter()
+                        .filter_map(|field| table.cols.iter().position(|col| col == field.name))
+                        .collect();
+                    if order.windows(2).any(|pair| pair[0] > pair[1]) {
+                        let message = format!(
+                            "fields of record '{}' are not in the order table '{}' declares",
+                            line_field.name, table.name
+                        );
+                        lints.push(Lint::new("X007", Severity::Info, line, message));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        line_starts.push(bytes.len());
+        for table in tables.iter() {
+            for (edge, offset) in [("starts", table.pos), ("ends", table.end)] {
+                if line_starts.binary_search(&offset).is_err() {
+                    let message = format!(
+                        "region of table '{}' {} inside a line, at byte {}",
+                        table.name, edge, offset
+                    );
+                    lints.push(Lint::new("X004", Severity::Error, table.line, message));
+                }
+            }
+        }
+        lints
+    }
+}
+
+/// Lints of the physical lines as written: trailing whitespace and line
+/// endings.
+fn physical_lints(bytes: &[u8]) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    let mut first_ending: Option<&str> = None;
+    let mut mixed = false;
+    let (mut line, mut pos) = (1, 0);
+    while pos < bytes.len() {
+        let (content_end, ending, next) = match bytes[pos..]
+            .iter()
+            .position(|byte| *byte == NL_CHAR || *byte == CR_CHAR)
+        {
+            None => (bytes.len(), None, bytes.len()),
+            Some(at) if bytes[pos + at] == NL_CHAR => (pos + at, Some("LF"), pos + at + 1),
+            Some(at) if bytes.get(pos + at + 1) == Some(&NL_CHAR) => {
+                (pos + at, Some("CRLF"), pos + at + 2)
+            }
+            Some(at) => (pos + at, Some("CR"), pos + at + 1),
+        };
+        let content = &bytes[pos..content_end];
+        if content
+            .iter()
+            .any(|byte| !matches!(*byte, SPACE_CHAR | TAB_CHAR))
+            && matches!(content.last(), Some(&SPACE_CHAR) | Some(&TAB_CHAR))
+        {
+            let message = "trailing whitespace".to_owned();
+            lints.push(Lint::new("X005", Severity::Warning, line, message));
+        }
+        match (first_ending, ending) {
+            (None, Some(ending)) => first_ending = Some(ending),
+            (Some(first), Some(ending)) if !mixed && first != ending => {
+                mixed = true;
+                let message = format!(
+                    "line ends with {} where the first line ends with {}",
+                    ending, first
+                );
+                lints.push(Lint::new("X006", Severity::Warning, line, message));
+            }
+            _ => {}
+        }
+        line += 1;
+        pos = next;
+    }
+    lints
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn lint_reports_every_problem_in_line_order() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Reader, Severity, Writer};
+
+        let mut out = Vec::new();
+        let mut writer = Writer::new(&mut out);
+        writer.add_table("users", &[("a", "str"), ("b", "str")])?;
+        writer.add_record("users", "1", &[("a", "x"), ("b", "y")])?;
+        writer.add_record("users", "2", &[("a", "x"), ("b", "y")])?;
+        writer.finish()?;
+        let text = String::from_utf8(out)?
+            .replacen("t:1", "r:0 a:q\nt:1", 1)
+            .replace("r:2 a:x b:y\n", "r:2 b:y a:x \r\n")
+            + "r:3 a:z\nr:4 a:\"open\n";
+
+        let path = std::env::temp_dir().join("xrave-test-lint.xrv");
+        std::fs::write(&path, text)?;
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        let lints = reader.lint();
+        let codes: Vec<_> = lints.iter().map(|lint| lint.code).collect();
+        for code in ["X001", "X002", "X003", "X004", "X005", "X006", "X007"] {
+            assert!(codes.contains(&code), "{} missing from {:?}", code, codes);
+        }
+        let record_zero = lints.iter().find(|lint| lint.code == "X002").unwrap();
+        assert_eq!(
+            (record_zero.line, record_zero.severity),
+            (3, Severity::Error)
+        );
+        assert!(lints.windows(2).all(|w| w[0].line <= w[1].line));
+        Ok(())
+    }
+}
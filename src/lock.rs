@@ -0,0 +1,40 @@
+use std::{
+    fs::{File, TryLockError},
+    path::Path,
+};
+
+use crate::error::{open_error, XRVErr};
+
+/// How to take an advisory file lock held by another handle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    /// Fail at once with `XRVErr::FileLocked`.
+    Try,
+    /// Wait until the lock is released.
+    Blocking,
+}
+
+/// Takes an exclusive lock on `file`, opened from `path`, released when the
+/// file is closed.
+pub(crate) fn lock_exclusive(file: &File, path: &Path, mode: LockMode) -> Result<(), XRVErr> {
+    match mode {
+        LockMode::Blocking => file.lock().map_err(|err| open_error(path, err)),
+        LockMode::Try => file.try_lock().map_err(|err| lock_error(path, err)),
+    }
+}
+
+/// Takes a shared lock on `file`, opened from `path`, released when the file
+/// is closed.
+pub(crate) fn lock_shared(file: &File, path: &Path, mode: LockMode) -> Result<(), XRVErr> {
+    match mode {
+        LockMode::Blocking => file.lock_shared().map_err(|err| open_error(path, err)),
+        LockMode::Try => file.try_lock_shared().map_err(|err| lock_error(path, err)),
+    }
+}
+
+fn lock_error(path: &Path, err: TryLockError) -> XRVErr {
+    match err {
+        TryLockError::WouldBlock => XRVErr::FileLocked,
+        TryLockError::Error(err) => open_error(path, err),
+    }
+}
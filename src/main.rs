@@ -1,29 +1,319 @@
-mod newxrv;
+use std::{env, fs::File, io, path::Path, process::ExitCode};
 
-use std::{fs::File, io::Read, time::Instant};
+use xrave::{
+    diff, format, parse_line, DiffOptions, Document, DocumentTable, ExportOptions, FormatOptions,
+    ParsedLine, RawLine, Reader, RecordLine, SearchOptions, Severity, XRVErr,
+};
+#[cfg(feature = "testkit")]
+use xrave::{
+    testkit::{generate as generate_file, GenSpec, GenTable, GenValues},
+    ColKind,
+};
 
-fn main() -> Result<(), std::io::Error> {
-    // let mut reader = newxrv::Reader::new("test.xrv".into())?;
+const PROGRESS_EVERY: u64 = 1024 * 1024;
+const CLEAR_PROGRESS: &str = "\r    \r";
+const PREVIEW_BUDGET: usize = 4 * 1024;
 
-    // let line_link = reader.next();
-    // dbg!(&line_link);
+fn count(path: String) -> Result<(), XRVErr> {
+    let mut reader = Reader::new(path)?;
+    reader.set_progress_callback(PROGRESS_EVERY, |progress| {
+        if let Some(total) = progress.total_bytes.filter(|total| *total > 0) {
+            eprint!("\r{:>3}%", progress.bytes_read * 100 / total);
+        }
+    });
+    reader.load_headers()?;
 
-    // reader.parse_next()?;
-    // reader.parse_next()?;
-    // reader.parse_next()?;
+    let mut names: Vec<String> = reader.tables().keys().cloned().collect();
+    names.sort();
+    for name in names {
+        let mut count = 0;
+        for record in reader.records(&name)? {
+            record?;
+            count += 1;
+        }
+        eprint!("{}", CLEAR_PROGRESS);
+        println!("{}: {} records", name, count);
+    }
+    eprint!("{}", CLEAR_PROGRESS);
+    Ok(())
+}
+
+/// Prints per-column statistics of every table.
+fn inspect_stats(path: String) -> Result<(), XRVErr> {
+    let mut reader = Reader::new(path)?;
+    reader.load_headers()?;
+
+    let mut names: Vec<String> = reader.tables().keys().cloned().collect();
+    names.sort();
+    for name in names {
+        println!("{}", name);
+        for stats in reader.column_stats(&name)? {
+            println!("    {}", stats);
+        }
+    }
+    Ok(())
+}
+
+/// Prints the first records of every table, up to `PREVIEW_BUDGET` bytes
+/// of lines each.
+fn inspect_preview(path: String) -> Result<(), XRVErr> {
+    let mut reader = Reader::new(path)?;
+    reader.load_headers()?;
 
-    // dbg!(&reader.tables);
-    // dbg!(&reader.styles);
+    let names: Vec<String> = reader.tables().keys().cloned().collect();
+    for name in names {
+        println!("{}", name);
+        let (records, truncated) = reader.load_table_prefix(&name, PREVIEW_BUDGET)?;
+        for record in records.iter() {
+            println!("    {}", RecordLine::from(record));
+        }
+        if truncated {
+            println!("    ...");
+        }
+    }
+    Ok(())
+}
 
-    let mut file = File::open("big.txt")?;
+/// Prints every table of the file at `path`, or of stdin for `-`, as one
+/// JSON object. Tables are read in the order of their regions, so that
+/// piped input is read in one pass.
+fn export(path: String, nested: bool) -> Result<(), XRVErr> {
+    let mut reader = match path.as_str() {
+        "-" => Reader::from_unseekable(io::stdin())?,
+        _ => Reader::new(path)?,
+    };
+    reader.load_headers()?;
+
+    let mut jumps: Vec<(usize, String)> = reader
+        .jumps()
+        .map(|jump| (jump.seek(), jump.name().to_owned()))
+        .collect();
+    jumps.sort();
+    let mut document = Document::default();
+    for (_, name) in jumps {
+        let records = reader.records(&name)?.collect::<Result<Vec<_>, _>>()?;
+        let (cols, next_id) = match reader.table(&name) {
+            None => (Vec::new(), None),
+            Some(table) => (table.cols.clone(), table.next_id),
+        };
+        let mut table = DocumentTable::new(&name, cols, records);
+        table.next_id = next_id;
+        document.tables.push(table);
+    }
+    let options = ExportOptions {
+        nest_dotted: nested,
+    };
+    println!("{}", document.to_json(options)?);
+    Ok(())
+}
+
+/// Writes the canonical form of the file at `path` to stdout.
+fn fmt(path: String) -> Result<(), XRVErr> {
+    let file = match File::open(&path) {
+        Err(source) => {
+            let path = path.into();
+            return Err(XRVErr::FailToOpenFile { path, source });
+        }
+        Ok(file) => file,
+    };
+    format(file, io::stdout().lock(), FormatOptions::default())?;
+    Ok(())
+}
 
-    let now = Instant::now();
-    let mut buf: [u8; 4096] = [0; 4096];
-    // let r = file.read_exact(&mut buf);
-    let r = file.read(&mut buf);
-    let e = now.elapsed();
+/// Prints the record-level differences between two files, as JSON with
+/// `--json`.
+fn diff_files(a: &str, b: &str, json: bool) -> Result<(), XRVErr> {
+    let report = diff(Path::new(a), Path::new(b), DiffOptions::default())?;
+    if json {
+        println!("{}", report.to_json());
+    } else {
+        print!("{}", report);
+    }
+    Ok(())
+}
 
-    dbg!(e, buf.len(), r);
+/// Prints every value holding `needle` as `table:key:column:value`.
+fn grep(needle: &str, path: String, case_insensitive: bool) -> Result<(), XRVErr> {
+    let mut reader = Reader::new(path)?;
+    let options = SearchOptions {
+        case_insensitive,
+        ..SearchOptions::default()
+    };
+    for hit in reader.search(needle, options)? {
+        let hit = hit?;
+        println!(
+            "{}:{}:{}:{}",
+            hit.table, hit.record_key, hit.column, hit.value
+        );
+    }
+    Ok(())
+}
 
+/// Parses `line` on its own and prints its kind, then each field as its
+/// name, value and their byte ranges within the line.
+fn check_line(line: &str) -> Result<(), XRVErr> {
+    let bytes = line.as_bytes();
+    let kind = match parse_line(bytes)? {
+        ParsedLine::Version(version) => {
+            println!("version {}", version);
+            return Ok(());
+        }
+        ParsedLine::Jumps(_) => "jumps",
+        ParsedLine::Table(_) => "table",
+        ParsedLine::Style(_) => "style",
+        ParsedLine::Record(_) => "record",
+        ParsedLine::Checksum { .. } => "checksum",
+        ParsedLine::Tombstone(_) => "tombstone",
+        ParsedLine::Dictionary { .. } => "dictionary",
+    };
+    let raw = RawLine::parse(bytes)?;
+    println!("{} {}", kind, String::from_utf8_lossy(raw.name));
+    for (name, value) in raw.fields.ranges() {
+        println!(
+            "    {} {} {:?} {:?}",
+            String::from_utf8_lossy(&bytes[name.clone()]),
+            String::from_utf8_lossy(&bytes[value.clone()]),
+            name,
+            value
+        );
+    }
     Ok(())
 }
+
+/// Prints every lint of the file; fails when any of them is an error.
+fn lint(path: String) -> Result<bool, XRVErr> {
+    let mut reader = Reader::new(path)?;
+    let lints = reader.lint();
+    for lint in lints.iter() {
+        println!("{}", lint);
+    }
+    Ok(lints.iter().all(|lint| lint.severity != Severity::Error))
+}
+
+/// Writes a generated file to stdout. `args` take `--seed N` and
+/// `--errors RATE`, then each table as `name:rows` followed by its columns
+/// as `name=seq[:start]`, `name=int:min..max`, `name=str:min..max` or
+/// `name=choice:a,b,c`.
+#[cfg(feature = "testkit")]
+fn generate(args: &[String]) -> Result<(), XRVErr> {
+    let invalid = |arg: &str| XRVErr::InvalidGenSpec(format!("cannot read '{}'", arg));
+    let mut spec = GenSpec::new(0);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => {
+                let seed = args.next().and_then(|seed| seed.parse().ok());
+                spec.seed = seed.ok_or_else(|| invalid(arg))?;
+            }
+            "--errors" => {
+                let rate = args.next().and_then(|rate| rate.parse().ok());
+                spec.error_rate = rate.ok_or_else(|| invalid(arg))?;
+            }
+            _ => match arg.split_once('=') {
+                None => {
+                    let (name, rows) = arg.split_once(':').ok_or_else(|| invalid(arg))?;
+                    let rows = rows.parse().map_err(|_| invalid(arg))?;
+                    spec.tables.push(GenTable::new(name, rows));
+                }
+                Some((name, values)) => {
+                    let (kind, values) = gen_values(values).ok_or_else(|| invalid(arg))?;
+                    let table = spec.tables.pop().ok_or_else(|| invalid(arg))?;
+                    spec.tables.push(table.col(name, kind, values));
+                }
+            },
+        }
+    }
+    generate_file(spec, io::stdout().lock())
+}
+
+/// Reads a column of the `generate` command, such as `str:3..12`.
+#[cfg(feature = "testkit")]
+fn gen_values(arg: &str) -> Option<(ColKind, GenValues)> {
+    let (how, rest) = arg.split_once(':').unwrap_or((arg, ""));
+    let range = || {
+        let (min, max) = rest.split_once("..")?;
+        Some((min.parse().ok()?, max.parse().ok()?))
+    };
+    match how {
+        "seq" => {
+            let start = match rest {
+                "" => 0,
+                start => start.parse().ok()?,
+            };
+            Some((ColKind::I64, GenValues::Sequential { start }))
+        }
+        "int" => {
+            let (min, max) = range()?;
+            Some((ColKind::I64, GenValues::RandomInt { min, max }))
+        }
+        "str" => {
+            let (min, max): (i64, i64) = range()?;
+            let (min_len, max_len) = (min.try_into().ok()?, max.try_into().ok()?);
+            Some((
+                ColKind::String,
+                GenValues::RandomString { min_len, max_len },
+            ))
+        }
+        "choice" => {
+            let choices = rest.split(',').map(str::to_owned).collect();
+            Some((ColKind::String, GenValues::Choice(choices)))
+        }
+        _ => None,
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.as_slice() {
+        [cmd, path] if cmd == "fmt" => fmt(path.clone()),
+        [cmd, flag, path] if cmd == "inspect" && flag == "--stats" => inspect_stats(path.clone()),
+        [cmd, flag, path] if cmd == "inspect" && flag == "--preview" => {
+            inspect_preview(path.clone())
+        }
+        [cmd, path] if cmd == "export" => export(path.clone(), false),
+        [cmd, flag, path] if cmd == "export" && flag == "--nested" => export(path.clone(), true),
+        [cmd, a, b] if cmd == "diff" => diff_files(a, b, false),
+        [cmd, flag, a, b] if cmd == "diff" && flag == "--json" => diff_files(a, b, true),
+        [cmd, needle, path] if cmd == "grep" => grep(needle, path.clone(), false),
+        [cmd, flag, needle, path] if cmd == "grep" && flag == "-i" => {
+            grep(needle, path.clone(), true)
+        }
+        [cmd, line] if cmd == "check-line" => check_line(line),
+        [cmd, path] if cmd == "lint" => match lint(path.clone()) {
+            Ok(false) => return ExitCode::FAILURE,
+            result => result.map(|_| ()),
+        },
+        #[cfg(feature = "testkit")]
+        [cmd, rest @ ..] if cmd == "generate" => generate(rest),
+        [path] => count(path.clone()),
+        _ => {
+            eprintln!("usage: xrave <file.xrv>");
+            eprintln!("       xrave fmt <file.xrv>");
+            eprintln!("       xrave inspect --stats <file.xrv>");
+            eprintln!("       xrave inspect --preview <file.xrv>");
+            eprintln!("       xrave export [--nested] <file.xrv|->");
+            eprintln!("       xrave diff [--json] <a.xrv> <b.xrv>");
+            eprintln!("       xrave grep [-i] <needle> <file.xrv>");
+            eprintln!("       xrave check-line <line>");
+            eprintln!("       xrave lint <file.xrv>");
+            #[cfg(feature = "testkit")]
+            eprintln!(
+                "       xrave generate [--seed <n>] [--errors <rate>] \
+                 (<table>:<rows> <col>=<seq[:start]|int:a..b|str:a..b|choice:x,y>...)..."
+            );
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match result {
+        Err(err @ XRVErr::AtLine { .. }) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+        Ok(_) => ExitCode::SUCCESS,
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{diff::diff_fields, document::Document, error::XRVErr};
+
+/// Which side wins when both documents hold a record or style with the same
+/// id and different contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    PreferSelf,
+    PreferOther,
+    Error,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    pub tables_added: usize,
+    pub records_added: usize,
+    pub styles_added: usize,
+    /// Same-id records and styles with different contents, however the
+    /// policy resolved them.
+    pub conflicts: usize,
+}
+
+impl Document {
+    /// Merges `other` into this document. Tables are matched by name and
+    /// must declare the same columns; their records are matched by id.
+    /// Tables, records and styles only `other` has are appended. On error
+    /// the document is left unchanged.
+    ///
+    /// ```
+    /// use xrave::{Document, MergePolicy, XRVErr};
+    ///
+    /// let mut ours = Document::read(&b"j:jumps users:49-11\n\
+    ///     t:1 name:users pos:49 len:11\nr:1 age:31\n"[..])?;
+    /// let theirs = Document::read(&b"j:jumps users:49-21\n\
+    ///     t:1 name:users pos:49 len:21\nr:1 age:32\nr:2 age:4\n"[..])?;
+    ///
+    /// let err = ours.clone().merge(theirs.clone(), MergePolicy::Error).unwrap_err();
+    /// assert!(matches!(err, XRVErr::MergeConflict { .. }));
+    ///
+    /// let report = ours.merge(theirs, MergePolicy::PreferSelf)?;
+    /// assert_eq!((report.records_added, report.conflicts), (1, 1));
+    /// let users = ours.table("users").unwrap();
+    /// assert_eq!(users.record(0)?.unwrap().cols[0].value, "31");
+    /// assert_eq!(users.ids().nth(1), Some("2"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn merge(&mut self, other: Document, policy: MergePolicy) -> Result<MergeReport, XRVErr> {
+        let mut other = other;
+        other.unspill()?;
+        self.unspill()?;
+        let mut merged = self.clone();
+        let mut report = MergeReport::default();
+
+        for table in other.tables {
+            let ours = match merged.table_mut(&table.name) {
+                None => {
+                    report.tables_added += 1;
+                    report.records_added += table.records.len();
+                    merged.tables.push(table);
+                    continue;
+                }
+                Some(ours) => ours,
+            };
+            let columns: Vec<String> = diff_fields(&ours.cols, &table.cols)
+                .into_iter()
+                .map(|change| change.name)
+                .collect();
+            if !columns.is_empty() {
+                return Err(XRVErr::SchemaMismatch {
+                    table: table.name,
+                    columns,
+                });
+            }
+
+            for record in table.records {
+                match ours.records.iter_mut().find(|ours| ours.id == record.id) {
+                    None => {
+                        report.records_added += 1;
+                        ours.records.push(record);
+                    }
+                    Some(existing) if *existing == record => {}
+                    Some(existing) => {
+                        report.conflicts += 1;
+                        match policy {
+                            MergePolicy::PreferSelf => {}
+                            MergePolicy::PreferOther => *existing = record,
+                            MergePolicy::Error => {
+                                return Err(XRVErr::MergeConflict {
+                                    table: table.name,
+                                    id: record.id,
+                                })
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for style in other.styles {
+            match merged.styles.iter_mut().find(|ours| ours.id == style.id) {
+                None => {
+                    report.styles_added += 1;
+                    merged.styles.push(style);
+                }
+                Some(existing) if *existing == style => {}
+                Some(existing) => {
+                    report.conflicts += 1;
+                    match policy {
+                        MergePolicy::PreferSelf => {}
+                        MergePolicy::PreferOther => *existing = style,
+                        MergePolicy::Error => return Err(XRVErr::DuplicateStyle(style.id)),
+                    }
+                }
+            }
+        }
+
+        merged.checksums |= other.checksums;
+        *self = merged;
+        Ok(report)
+    }
+}
@@ -0,0 +1,247 @@
+use std::{path::Path, sync::Arc};
+
+use crate::{
+    document::{Document, DocumentTable},
+    error::XRVErr,
+    line::OwnedField,
+    schema::{validate_identifier, ColKind},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MigrationOp {
+    /// Declares a column and gives every record `default` for it.
+    AddColumn {
+        table: String,
+        name: String,
+        kind: ColKind,
+        default: String,
+    },
+    /// Removes a column from the header and from every record that has it.
+    DropColumn { table: String, name: String },
+    /// Renames a column in the header and in every record; values are kept.
+    RenameColumn {
+        table: String,
+        from: String,
+        to: String,
+    },
+}
+
+/// Column changes applied in order by `migrate` or `Document::migrate`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Migration {
+    pub ops: Vec<MigrationOp>,
+    /// When a record already has a field named like an added column, replace
+    /// its value with the default instead of failing with
+    /// `XRVErr::FieldExists`.
+    pub overwrite_existing: bool,
+}
+
+impl Migration {
+    pub fn new() -> Migration {
+        Migration::default()
+    }
+
+    pub fn add_column(mut self, table: &str, name: &str, kind: ColKind, default: &str) -> Self {
+        self.ops.push(MigrationOp::AddColumn {
+            table: table.to_owned(),
+            name: name.to_owned(),
+            kind,
+            default: default.to_owned(),
+        });
+        self
+    }
+
+    pub fn drop_column(mut self, table: &str, name: &str) -> Self {
+        self.ops.push(MigrationOp::DropColumn {
+            table: table.to_owned(),
+            name: name.to_owned(),
+        });
+        self
+    }
+
+    pub fn rename_column(mut self, table: &str, from: &str, to: &str) -> Self {
+        self.ops.push(MigrationOp::RenameColumn {
+            table: table.to_owned(),
+            from: from.to_owned(),
+            to: to.to_owned(),
+        });
+        self
+    }
+}
+
+/// Applies `migration` to the file at `path`. The result is written next to
+/// it and renamed over the original, which stays untouched on any error.
+///
+/// ```
+/// use xrave::{migrate, ColKind, Document, Migration, Schema};
+///
+/// let path = std::env::temp_dir().join("xrave-doc-migrate.xrv");
+/// let mut document = Schema::new().table("users", |t| t.col("name", ColKind::String)).build()?;
+/// document.add_record("users", "1", &[("name", "Anna")])?;
+/// document.save(&path)?;
+///
+/// migrate(&path, &Migration::new().rename_column("users", "name", "full_name"))?;
+/// assert_eq!(&*Document::open(&path)?.tables[0].cols[0].name, "full_name");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn migrate<P: AsRef<Path>>(path: P, migration: &Migration) -> Result<(), XRVErr> {
+    let path = path.as_ref();
+    let mut document = Document::open(path)?;
+    document.migrate(migration)?;
+    document.save_atomic(path)
+}
+
+impl Document {
+    /// Applies `migration` in memory; on error the document is unchanged
+    /// but for spilled records, which are read back first.
+    pub fn migrate(&mut self, migration: &Migration) -> Result<(), XRVErr> {
+        self.unspill()?;
+        let mut migrated = self.clone();
+        for op in migration.ops.iter() {
+            apply(&mut migrated, op, migration.overwrite_existing)?;
+        }
+        *self = migrated;
+        Ok(())
+    }
+}
+
+fn apply(document: &mut Document, op: &MigrationOp, overwrite: bool) -> Result<(), XRVErr> {
+    let table_name = match op {
+        MigrationOp::AddColumn { table, .. }
+        | MigrationOp::DropColumn { table, .. }
+        | MigrationOp::RenameColumn { table, .. } => table,
+    };
+    let table = match document.table_mut(table_name) {
+        None => return Err(XRVErr::UnknownTable(table_name.to_owned())),
+        Some(table) => table,
+    };
+
+    match op {
+        MigrationOp::AddColumn {
+            name,
+            kind,
+            default,
+            ..
+        } => {
+            validate_identifier(name)?;
+            if has_col(table, name) {
+                return Err(duplicate_column(table, name));
+            }
+            let name: Arc<str> = Arc::from(name.as_str());
+            table.cols.push(OwnedField {
+                name: Arc::clone(&name),
+                value: kind.as_str().to_owned(),
+                null: false,
+            });
+            for record in table.records.iter_mut() {
+                match record.cols.iter_mut().find(|field| field.name == name) {
+                    Some(_) if !overwrite => {
+                        return Err(XRVErr::FieldExists {
+                            table: table.name.clone(),
+                            id: record.id.clone(),
+                            field: name.to_string(),
+                        })
+                    }
+                    Some(field) => {
+                        field.value = default.clone();
+                        field.null = false;
+                    }
+                    None => record.cols.push(OwnedField {
+                        name: Arc::clone(&name),
+                        value: default.clone(),
+                        null: false,
+                    }),
+                }
+            }
+        }
+        MigrationOp::DropColumn { name, .. } => {
+            if !has_col(table, name) {
+                return Err(unknown_column(table, name));
+            }
+            table.cols.retain(|col| *col.name != **name);
+            for record in table.records.iter_mut() {
+                record.cols.retain(|field| *field.name != **name);
+            }
+        }
+        MigrationOp::RenameColumn { from, to, .. } => {
+            validate_identifier(to)?;
+            if !has_col(table, from) {
+                return Err(unknown_column(table, from));
+            }
+            if has_col(table, to) {
+                return Err(duplicate_column(table, to));
+            }
+            let to: Arc<str> = Arc::from(to.as_str());
+            let fields = table.cols.iter_mut().chain(
+                table
+                    .records
+                    .iter_mut()
+                    .flat_map(|record| record.cols.iter_mut()),
+            );
+            for field in fields.filter(|field| *field.name == **from) {
+                field.name = Arc::clone(&to);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn has_col(table: &DocumentTable, name: &str) -> bool {
+    table.cols.iter().any(|col| &*col.name == name)
+}
+
+fn duplicate_column(table: &DocumentTable, name: &str) -> XRVErr {
+    XRVErr::DuplicateColumn {
+        table: table.name.clone(),
+        column: name.to_owned(),
+    }
+}
+
+fn unknown_column(table: &DocumentTable, name: &str) -> XRVErr {
+    XRVErr::UnknownColumn {
+        table: table.name.clone(),
+        column: name.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn migration_adds_drops_and_renames_columns() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{migrate, ColKind, Document, Migration, Schema};
+
+        let path = std::env::temp_dir().join("xrave-test-migrate.xrv");
+        let mut document = Schema::new()
+            .table("users", |t| {
+                t.col("name", ColKind::String)
+                    .col("tmp", ColKind::Bool.optional())
+            })
+            .build()?;
+        document.add_record("users", "1", &[("name", "Anna"), ("tmp", "true")])?;
+        document.add_record("users", "2", &[("name", "Bob")])?;
+        document.save(&path)?;
+
+        let migration = Migration::new()
+            .add_column("users", "age", ColKind::I32, "0")
+            .drop_column("users", "tmp")
+            .rename_column("users", "name", "full_name");
+        migrate(&path, &migration)?;
+
+        let users = Document::open(&path)?.tables.remove(0);
+        let cols: Vec<_> = users
+            .cols
+            .iter()
+            .map(|col| (&*col.name, col.value.as_str()))
+            .collect();
+        assert_eq!(cols, [("full_name", "str"), ("age", "i32")]);
+        let records = users.records().collect::<Result<Vec<_>, _>>()?;
+        let first: Vec<_> = records[0]
+            .cols
+            .iter()
+            .map(|f| (&*f.name, f.value.as_str()))
+            .collect();
+        assert_eq!(first, [("full_name", "Anna"), ("age", "0")]);
+        assert_eq!(records[1].cols.len(), 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,294 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    error::{open_error, XRVErr},
+    line::{OwnedRecordLine, OwnedTableLine},
+    ordered::OrderedMap,
+    reader::{Reader, Records},
+    schema::{Col, SchemaDiff},
+};
+
+/// Shards of one dataset, such as `data-0001.xrv`, `data-0002.xrv`, ...,
+/// read as a single file. Every shard must declare the same tables and
+/// columns; records of a table are read shard by shard in file name order.
+///
+/// ```
+/// use xrave::{MultiReader, Writer, XRVErr};
+///
+/// let dir = std::env::temp_dir().join("xrave-doc-multi");
+/// let _ = std::fs::remove_dir_all(&dir);
+/// std::fs::create_dir_all(&dir)?;
+/// for (name, id) in [("data-0001.xrv", "1"), ("data-0002.xrv", "2")] {
+///     let mut writer = Writer::new(std::fs::File::create(dir.join(name))?);
+///     writer.add_table("users", &[("name", "str")])?;
+///     writer.add_record("users", id, &[("name", "Anna")])?;
+///     writer.finish()?;
+/// }
+///
+/// let mut shards = MultiReader::open_glob(&dir, "data-*.xrv")?;
+/// let ids = shards.records("users")?.map(|record| Ok(record?.id)).collect::<Result<Vec<_>, XRVErr>>()?;
+/// assert_eq!(ids, ["1", "2"]);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct MultiReader {
+    shards: Vec<Reader>,
+}
+
+impl MultiReader {
+    /// Opens every file in `dir` whose name matches `pattern`, where `*`
+    /// stands for any run of bytes and `?` for any one byte, and checks
+    /// their table headers against those of the first. Shards that differ
+    /// fail with `XRVErr::ShardSchemaMismatch` listing each of them with its
+    /// deviations; a column declared without a kind matches any declaration.
+    /// A pattern that matches no file fails with `XRVErr::FailToOpenFile`
+    /// on `dir`.
+    pub fn open_glob(dir: &Path, pattern: &str) -> Result<MultiReader, XRVErr> {
+        let entries = match std::fs::read_dir(dir) {
+            Err(err) => return Err(open_error(dir, err)),
+            Ok(entries) => entries,
+        };
+        let mut paths: Vec<PathBuf> = Vec::new();
+        for entry in entries {
+            let entry = match entry {
+                Err(err) => return Err(open_error(dir, err)),
+                Ok(entry) => entry,
+            };
+            let name = entry.file_name();
+            if glob_match(pattern.as_bytes(), name.as_encoded_bytes()) && entry.path().is_file() {
+                paths.push(entry.path());
+            }
+        }
+        if paths.is_empty() {
+            let err = std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no file matches '{}'", pattern),
+            );
+            return Err(open_error(dir, err));
+        }
+        paths.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+
+        let mut shards = Vec::with_capacity(paths.len());
+        for path in paths {
+            let mut reader = Reader::new(&path)?;
+            reader.load_headers()?;
+            shards.push(reader);
+        }
+        let mismatched: Vec<(PathBuf, Vec<SchemaDiff>)> = shards[1..]
+            .iter()
+            .filter_map(|shard| {
+                let diffs = header_diffs(&shards[0].tables, &shard.tables);
+                (!diffs.is_empty()).then(|| (shard_path(shard), diffs))
+            })
+            .collect();
+        if !mismatched.is_empty() {
+            return Err(XRVErr::ShardSchemaMismatch {
+                first: shard_path(&shards[0]),
+                shards: mismatched,
+            });
+        }
+        Ok(MultiReader { shards })
+    }
+
+    /// Paths of the shards, in the order they are read.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.shards.iter().filter_map(|shard| shard.path.as_deref())
+    }
+
+    /// Header of the table `name`, as every shard declares it.
+    pub fn table(&self, name: &str) -> Option<&OwnedTableLine> {
+        self.shards[0].table(name)
+    }
+
+    /// Iterates the records of `table` in every shard, one shard after the
+    /// other.
+    pub fn records(&mut self, table: &str) -> Result<MultiRecords<'_>, XRVErr> {
+        if self.table(table).is_none() {
+            return Err(XRVErr::UnknownTable(table.to_owned()));
+        }
+        Ok(MultiRecords {
+            shards: self.shards.iter_mut(),
+            table: table.to_owned(),
+            current: None,
+        })
+    }
+
+    /// Sums `Reader::fast_count` over the shards.
+    pub fn fast_count(&mut self, table: Option<&str>) -> Result<u64, XRVErr> {
+        let mut total = 0;
+        for shard in self.shards.iter_mut() {
+            total += shard.fast_count(table)?;
+        }
+        Ok(total)
+    }
+}
+
+/// Iterator over one table's records across shards, returned by
+/// `MultiReader::records`.
+#[derive(Debug)]
+pub struct MultiRecords<'r> {
+    shards: std::slice::IterMut<'r, Reader>,
+    table: String,
+    current: Option<Records<'r>>,
+}
+
+impl<'r> Iterator for MultiRecords<'r> {
+    type Item = Result<OwnedRecordLine, XRVErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.current.as_mut().and_then(Iterator::next) {
+                return Some(record);
+            }
+            let shard = self.shards.next()?;
+            match shard.records(&self.table) {
+                Err(err) => {
+                    self.shards = [].iter_mut();
+                    self.current = None;
+                    return Some(Err(err));
+                }
+                Ok(records) => self.current = Some(records),
+            }
+        }
+    }
+}
+
+fn shard_path(shard: &Reader) -> PathBuf {
+    shard.path.clone().unwrap_or_default()
+}
+
+/// How the table headers in `found` deviate from those in `expected`.
+fn header_diffs(
+    expected: &OrderedMap<OwnedTableLine>,
+    found: &OrderedMap<OwnedTableLine>,
+) -> Vec<SchemaDiff> {
+    let mut diffs = Vec::new();
+    for (name, table) in expected.iter() {
+        let other = match found.get(name) {
+            None => {
+                diffs.push(SchemaDiff::MissingTable(name.clone()));
+                continue;
+            }
+            Some(other) => other,
+        };
+        for col in table.cols.iter() {
+            let field = match other.cols.iter().find(|field| field.name == col.name) {
+                None => {
+                    diffs.push(SchemaDiff::MissingColumn {
+                        table: name.clone(),
+                        column: col.name.to_string(),
+                    });
+                    continue;
+                }
+                Some(field) => field,
+            };
+            if let (Ok(expected), Ok(found)) = (Col::parse(&col.value), Col::parse(&field.value)) {
+                if expected != found {
+                    diffs.push(SchemaDiff::KindChanged {
+                        table: name.clone(),
+                        column: col.name.to_string(),
+                        expected,
+                        found,
+                    });
+                }
+            }
+        }
+        for field in other.cols.iter() {
+            if !table.cols.iter().any(|col| col.name == field.name) {
+                diffs.push(SchemaDiff::ExtraColumn {
+                    table: name.clone(),
+                    column: field.name.to_string(),
+                });
+            }
+        }
+    }
+    for name in found.keys() {
+        if expected.get(name).is_none() {
+            diffs.push(SchemaDiff::ExtraTable(name.clone()));
+        }
+    }
+    diffs
+}
+
+/// Whether `name` matches `pattern`, where `*` matches any run of bytes and
+/// `?` any one byte.
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*` and the name byte it was tried against.
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(b'?') => (p, n) = (p + 1, n + 1),
+            Some(byte) if *byte == name[n] => (p, n) = (p + 1, n + 1),
+            _ => match star {
+                None => return false,
+                Some((after, tried)) => {
+                    star = Some((after, tried + 1));
+                    (p, n) = (after, tried + 1);
+                }
+            },
+        }
+    }
+    pattern[p..].iter().all(|byte| *byte == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn shards_read_in_order_and_must_agree() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{MultiReader, SchemaDiff, Writer, XRVErr};
+
+        let dir = std::env::temp_dir().join("xrave-test-multi");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+        let shard = |name: &str, age: &str, ids: &[&str]| -> Result<(), XRVErr> {
+            let mut writer = Writer::new(std::fs::File::create(dir.join(name)).unwrap());
+            writer.add_table("users", &[("name", "str"), ("age", age)])?;
+            for id in ids {
+                writer.add_record("users", id, &[("name", "Anna"), ("age", "31")])?;
+            }
+            writer.finish().map(drop)
+        };
+        shard("data-0002.xrv", "i32", &[])?;
+        shard("data-0001.xrv", "i32", &["1", "2"])?;
+        shard("data-0003.xrv", "i32", &["3"])?;
+        std::fs::write(dir.join("notes.txt"), "not a shard")?;
+
+        let mut shards = MultiReader::open_glob(&dir, "data-*.xrv")?;
+        let names: Vec<_> = shards
+            .paths()
+            .map(|path| path.file_name().unwrap().to_owned())
+            .collect();
+        assert_eq!(names, ["data-0001.xrv", "data-0002.xrv", "data-0003.xrv"]);
+        let ids = shards
+            .records("users")?
+            .map(|record| Ok(record?.id))
+            .collect::<Result<Vec<_>, XRVErr>>()?;
+        assert_eq!(ids, ["1", "2", "3"]);
+        assert_eq!(shards.fast_count(Some("users"))?, 3);
+        assert_eq!(shards.fast_count(None)?, 3);
+
+        shard("data-0004.xrv", "i64", &["4"])?;
+        match MultiReader::open_glob(&dir, "data-????.xrv") {
+            Err(XRVErr::ShardSchemaMismatch { first, shards }) => {
+                assert!(first.ends_with("data-0001.xrv"));
+                assert_eq!(shards.len(), 1);
+                assert!(shards[0].0.ends_with("data-0004.xrv"));
+                assert!(
+                    matches!(&shards[0].1[..], [SchemaDiff::KindChanged { column, .. }] if column == "age")
+                );
+            }
+            other => panic!("unexpected {:?}", other.map(drop)),
+        }
+        let err = MultiReader::open_glob(&dir, "data-*").unwrap_err();
+        assert!(err.to_string().ends_with(
+            "data-0004.xrv: column 'age' in table 'users' is declared 'i64', expected 'i32'"
+        ));
+        assert!(MultiReader::open_glob(&dir, "*.csv").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,200 @@
+use crate::{
+    document::Document,
+    error::XRVErr,
+    reader::Reader,
+    schema::{split_default, validate_table_name},
+};
+
+/// Separates the namespace segments of a table name such as `sales.2024`.
+pub(crate) const NAMESPACE_SEPARATOR: char = '.';
+
+/// Reference in a column declaration, as in `i32->users`.
+const REFERENCE_MARKER: &str = "->";
+
+/// Whether `name` lies in `namespace`, e.g. `sales.2024` and
+/// `sales.eu.2024` in `sales`.
+fn in_namespace(name: &str, namespace: &str) -> bool {
+    name.strip_prefix(namespace)
+        .is_some_and(|rest| rest.starts_with(NAMESPACE_SEPARATOR))
+}
+
+/// `name` moved from namespace `from` to `to`, when it is `from` itself or
+/// lies in it.
+fn renamed(name: &str, from: &str, to: &str) -> Option<String> {
+    if name == from || in_namespace(name, from) {
+        Some(format!("{}{}", to, &name[from.len()..]))
+    } else {
+        None
+    }
+}
+
+impl Reader {
+    /// Names of the tables in `namespace`, in file order. Table names are
+    /// otherwise opaque: `records("sales")` reads only a table named exactly
+    /// `sales`, which `tables_in("sales")` does not list.
+    ///
+    /// ```
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-namespace.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// for table in ["sales", "sales.2023", "salesforce"] {
+    ///     writer.add_table(table, &[("total", "f64")])?;
+    /// }
+    /// writer.finish()?;
+    ///
+    /// let reader = Reader::new(&path)?;
+    /// assert_eq!(reader.tables_in("sales"), ["sales.2023"]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn tables_in(&self, namespace: &str) -> Vec<String> {
+        self.jumps
+            .keys()
+            .filter(|name| in_namespace(name, namespace))
+            .cloned()
+            .collect()
+    }
+}
+
+impl Document {
+    /// Moves the table named `from` and every table in namespace `from` to
+    /// `to`, along with `->table` references to them, and returns how many
+    /// tables were renamed. The jumps line follows on `write`. Nothing
+    /// changes when a new name is not a valid table name or is taken by a
+    /// table outside the namespace.
+    ///
+    /// ```
+    /// use xrave::{Document, Writer};
+    ///
+    /// let mut writer = Writer::new(Vec::new());
+    /// writer.add_table("sales.2023", &[("total", "f64")])?;
+    /// writer.add_table("sales.2024", &[("total", "f64")])?;
+    /// let mut document = Document::read(&writer.finish()?[..])?;
+    /// assert_eq!(document.rename_namespace("sales", "revenue")?, 2);
+    /// assert!(document.table("revenue.2024").is_some());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn rename_namespace(&mut self, from: &str, to: &str) -> Result<usize, XRVErr> {
+        let mut renames: Vec<(usize, String)> = Vec::new();
+        for (idx, table) in self.tables.iter().enumerate() {
+            if let Some(name) = renamed(&table.name, from, to) {
+                validate_table_name(&name)?;
+                renames.push((idx, name));
+            }
+        }
+        for (_, name) in renames.iter() {
+            let taken = self
+                .tables
+                .iter()
+                .any(|table| table.name == *name && renamed(&table.name, from, to).is_none());
+            if taken {
+                return Err(XRVErr::DuplicateTable(name.clone()));
+            }
+        }
+
+        for (idx, name) in renames.iter() {
+            self.tables[*idx].name = name.clone();
+        }
+        for table in self.tables.iter_mut() {
+            for col in table.cols.iter_mut() {
+                let (decl, default) = split_default(&col.value);
+                let (head, target) = match decl.split_once(REFERENCE_MARKER) {
+                    None => continue,
+                    Some(parts) => parts,
+                };
+                let target = match renamed(target, from, to) {
+                    None => continue,
+                    Some(target) => target,
+                };
+                let mut value = format!("{}{}{}", head, REFERENCE_MARKER, target);
+                if let Some(default) = default {
+                    value.push('=');
+                    value.push_str(default);
+                }
+                col.value = value;
+            }
+        }
+        Ok(renames.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn namespaces_list_only_dotted_children() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Reader, Writer};
+
+        let path = std::env::temp_dir().join("xrave-test-namespace.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        for table in [
+            "sales",
+            "sales.2023",
+            "hr.people",
+            "sales.eu.2024",
+            "salesforce",
+        ] {
+            writer.add_table(table, &[("total", "f64")])?;
+        }
+        writer.add_record("sales", "all", &[("total", "30")])?;
+        writer.add_record("sales.2023", "q1", &[("total", "10")])?;
+        writer.finish()?;
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        assert_eq!(reader.tables_in("sales"), ["sales.2023", "sales.eu.2024"]);
+        assert_eq!(reader.tables_in("sales.eu"), ["sales.eu.2024"]);
+        assert!(reader.tables_in("finance").is_empty());
+
+        let ids: Vec<_> = reader.records("sales")?.map(|r| r.unwrap().id).collect();
+        assert_eq!(ids, ["all"]);
+        let ids: Vec<_> = reader
+            .records("sales.2023")?
+            .map(|r| r.unwrap().id)
+            .collect();
+        assert_eq!(ids, ["q1"]);
+        Ok(())
+    }
+
+    #[test]
+    fn rename_namespace_moves_tables_and_references() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Document, Writer, XRVErr};
+
+        let mut writer = Writer::new(Vec::new());
+        writer.add_table("sales.2023", &[("total", "f64")])?;
+        writer.add_table("sales.2024", &[("total", "f64")])?;
+        writer.add_table("hr.people", &[("best_year", "str?->sales.2024")])?;
+        writer.add_record("sales.2024", "q1", &[("total", "10")])?;
+        let mut document = Document::read(&writer.finish()?[..])?;
+
+        assert_eq!(document.rename_namespace("sales", "revenue")?, 2);
+        let names: Vec<_> = document
+            .tables
+            .iter()
+            .map(|table| table.name.as_str())
+            .collect();
+        assert_eq!(names, ["revenue.2023", "revenue.2024", "hr.people"]);
+        assert_eq!(
+            document.table("hr.people").unwrap().cols[0].value,
+            "str?->revenue.2024"
+        );
+
+        let text = String::from_utf8(document.write(Vec::new(), Default::default())?)?;
+        assert!(text.starts_with("v:xrave 3\nj:jumps revenue.2023:"));
+        assert_eq!(
+            Document::read(text.as_bytes())?
+                .table("revenue.2024")
+                .unwrap()
+                .len(),
+            1
+        );
+
+        assert!(matches!(
+            document.rename_namespace("revenue.2023", "hr.people"),
+            Err(XRVErr::DuplicateTable(_))
+        ));
+        assert!(matches!(
+            document.rename_namespace("revenue", "a..b"),
+            Err(XRVErr::InvalidIdentifier(_))
+        ));
+        Ok(())
+    }
+}
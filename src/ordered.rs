@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+/// Map from names to `V` that iterates in insertion order, so tables,
+/// styles and jumps come back in the order the file declares them.
+#[derive(Debug, Clone)]
+pub struct OrderedMap<V> {
+    entries: Vec<(String, V)>,
+    index: HashMap<String, usize>,
+}
+
+impl<V> OrderedMap<V> {
+    pub fn new() -> Self {
+        OrderedMap {
+            entries: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&V> {
+        self.index.get(name).map(|idx| &self.entries[*idx].1)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut V> {
+        self.index.get(name).map(|idx| &mut self.entries[*idx].1)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.index.contains_key(name)
+    }
+
+    /// Inserts `value` under `name`. A name already present keeps its
+    /// position and has its value replaced, which is returned.
+    pub fn insert(&mut self, name: String, value: V) -> Option<V> {
+        match self.index.get(&name) {
+            Some(idx) => Some(std::mem::replace(&mut self.entries[*idx].1, value)),
+            None => {
+                self.index.insert(name.clone(), self.entries.len());
+                self.entries.push((name, value));
+                None
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &V)> {
+        self.entries.iter().map(|(name, value)| (name, value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().map(|(name, _)| name)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.iter().map(|(_, value)| value)
+    }
+}
+
+impl<V> Default for OrderedMap<V> {
+    fn default() -> Self {
+        OrderedMap::new()
+    }
+}
@@ -0,0 +1,238 @@
+use crate::{
+    error::XRVErr,
+    line::{
+        Field, Jump, LineField, LineJump, LineKind, OwnedField, OwnedRecordLine, OwnedStyleLine,
+        OwnedTableLine, RecordLine, StyleLine, TableLine,
+    },
+    tokenizer::{diagnose, RawLine},
+    version::parse_version_line,
+};
+
+/// One line parsed on its own by `parse_line`, borrowing from its bytes.
+#[derive(Debug)]
+pub enum ParsedLine<'b> {
+    /// The `v:xrave N` line declaring the format version.
+    Version(u32),
+    Jumps(Vec<Jump<'b>>),
+    Table(TableLine<'b>),
+    Style(StyleLine<'b>),
+    Record(RecordLine<'b>),
+    /// A checksum line: the algorithm and one checksum per section.
+    Checksum {
+        algorithm: &'b str,
+        sections: Vec<Field<'b>>,
+    },
+    Tombstone(RecordLine<'b>),
+    /// A table's dictionary: the table and the full value of each token.
+    Dictionary {
+        table: &'b str,
+        entries: Vec<Field<'b>>,
+    },
+}
+
+/// A region of the jumps line, as held by `OwnedParsedLine::Jumps`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedJump {
+    pub name: String,
+    pub seek: usize,
+    pub len: usize,
+}
+
+/// A `ParsedLine` owning its names and decoded values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OwnedParsedLine {
+    Version(u32),
+    Jumps(Vec<OwnedJump>),
+    Table(OwnedTableLine),
+    Style(OwnedStyleLine),
+    Record(OwnedRecordLine),
+    Checksum {
+        algorithm: String,
+        sections: Vec<OwnedField>,
+    },
+    Tombstone(OwnedRecordLine),
+    Dictionary {
+        table: String,
+        entries: Vec<OwnedField>,
+    },
+}
+
+impl<'b> ParsedLine<'b> {
+    pub fn to_owned(&self) -> OwnedParsedLine {
+        match self {
+            ParsedLine::Version(version) => OwnedParsedLine::Version(*version),
+            ParsedLine::Jumps(jumps) => OwnedParsedLine::Jumps(
+                jumps
+                    .iter()
+                    .map(|jump| OwnedJump {
+                        name: jump.name().to_owned(),
+                        seek: jump.seek(),
+                        len: jump.len(),
+                    })
+                    .collect(),
+            ),
+            ParsedLine::Table(table) => OwnedParsedLine::Table(table.to_owned()),
+            ParsedLine::Style(style) => OwnedParsedLine::Style(style.to_owned()),
+            ParsedLine::Record(record) => OwnedParsedLine::Record(record.to_owned()),
+            ParsedLine::Checksum {
+                algorithm,
+                sections,
+            } => OwnedParsedLine::Checksum {
+                algorithm: (*algorithm).to_owned(),
+                sections: sections.iter().map(Field::to_owned).collect(),
+            },
+            ParsedLine::Tombstone(record) => OwnedParsedLine::Tombstone(record.to_owned()),
+            ParsedLine::Dictionary { table, entries } => OwnedParsedLine::Dictionary {
+                table: (*table).to_owned(),
+                entries: entries.iter().map(Field::to_owned).collect(),
+            },
+        }
+    }
+}
+
+/// Parses the first line of `bytes` on its own, in the syntax of the
+/// current format version, without a `Reader` or the rest of a file. A
+/// table line is not checked against its region and a record line not
+/// against its table. Errors are `XRVErr::AtLine` at line 1 and the byte
+/// column they point at.
+///
+/// ```
+/// use xrave::{parse_line, ParsedLine};
+///
+/// assert!(matches!(parse_line(b"v:xrave 2\n")?, ParsedLine::Version(2)));
+/// if let ParsedLine::Record(record) = parse_line(b"r:7 name:\"Anna\\tSmith\"\r\n")? {
+///     assert_eq!(record.id(), "7");
+///     assert_eq!(record.cols()[0].value_unescaped(), "Anna\tSmith");
+/// }
+/// assert!(parse_line(b"q:1").is_err());
+/// # Ok::<(), xrave::XRVErr>(())
+/// ```
+pub fn parse_line(bytes: &[u8]) -> Result<ParsedLine<'_>, XRVErr> {
+    let content = bytes.split(|byte| *byte == b'\n').next().unwrap_or(bytes);
+    let content = content.strip_suffix(b"\r").unwrap_or(content);
+    if let Some(version) = parse_version_line(content) {
+        return match version {
+            Err(err) => Err(diagnose(bytes, 1, 1, err)),
+            Ok(version) => Ok(ParsedLine::Version(version)),
+        };
+    }
+    let raw_line = RawLine::parse(bytes)?;
+    let at = |err: XRVErr| {
+        let col = err.position().1;
+        diagnose(bytes, 1, col, err)
+    };
+    let line_field = match raw_line.kind {
+        LineKind::Jump => {
+            let jumps = LineJump::try_from(raw_line).map_err(at)?;
+            return Ok(ParsedLine::Jumps(jumps.jumps));
+        }
+        _ => LineField::try_from(raw_line).map_err(at)?,
+    };
+    let parsed = match line_field.kind {
+        LineKind::Jump => ParsedLine::Jumps(Vec::new()),
+        LineKind::Table => ParsedLine::Table(TableLine::try_from(line_field).map_err(at)?),
+        LineKind::Style => ParsedLine::Style(StyleLine::try_from(line_field).map_err(at)?),
+        LineKind::Record => ParsedLine::Record(RecordLine::try_from(line_field).map_err(at)?),
+        LineKind::Checksum => ParsedLine::Checksum {
+            algorithm: line_field.name,
+            sections: line_field.fields,
+        },
+        LineKind::Tombstone => ParsedLine::Tombstone(RecordLine {
+            id: line_field.name,
+            cols: line_field.fields,
+        }),
+        LineKind::Dictionary => ParsedLine::Dictionary {
+            table: line_field.name,
+            entries: line_field.fields,
+        },
+    };
+    Ok(parsed)
+}
+
+/// Parses the first line of `bytes` like `parse_line`, decoding values
+/// into an `OwnedParsedLine`.
+pub fn parse_line_owned(bytes: &[u8]) -> Result<OwnedParsedLine, XRVErr> {
+    parse_line(bytes).map(|line| line.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_line_reads_every_line_kind() -> Result<(), crate::XRVErr> {
+        use crate::{parse_line, parse_line_owned, OwnedParsedLine, ParsedLine, XRVErr};
+
+        assert!(matches!(
+            parse_line(b"v:xrave 2\n")?,
+            ParsedLine::Version(2)
+        ));
+
+        match parse_line(b"j:jumps users:120-40 notes:160-0")? {
+            ParsedLine::Jumps(jumps) => {
+                let regions: Vec<_> = jumps
+                    .iter()
+                    .map(|j| (j.name(), j.seek(), j.len()))
+                    .collect();
+                assert_eq!(regions, [("users", 120, 40), ("notes", 160, 0)]);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        match parse_line(b"t:1 name:users pos:120 len:40 \"first name\":str age:i32?\n")? {
+            ParsedLine::Table(table) => {
+                assert_eq!((table.name(), table.pos(), table.len()), ("users", 120, 40));
+                assert_eq!(table.cols()[0].name(), "first name");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        match parse_line(b"s:bold weight:700")? {
+            ParsedLine::Style(style) => assert_eq!(style.cols()[0].value(), "700"),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        match parse_line(b"r:7 name:\"Anna\\tSmith\" age:31\r\n")? {
+            ParsedLine::Record(record) => {
+                assert_eq!(record.id(), "7");
+                assert_eq!(record.cols()[0].value_unescaped(), "Anna\tSmith");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        match parse_line(b"c:crc32 header:1A2B3C4D users:00000000")? {
+            ParsedLine::Checksum {
+                algorithm,
+                sections,
+            } => {
+                assert_eq!((algorithm, sections.len()), ("crc32", 2));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        assert!(matches!(
+            parse_line(b"x:7 name:Anna")?,
+            ParsedLine::Tombstone(_)
+        ));
+
+        match parse_line_owned(b"r:8 tag:a tag:b")? {
+            OwnedParsedLine::Record(record) => {
+                assert_eq!(record.get_all("tag").collect::<Vec<_>>(), ["a", "b"]);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        assert!(matches!(
+            parse_line(b"r:1 name:\"Anna\"Smith"),
+            Err(XRVErr::AtLine {
+                line: 1,
+                col: 16,
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse_line(b"t:1 name:users pos:x len:0"),
+            Err(XRVErr::AtLine { line: 1, .. })
+        ));
+        assert!(parse_line(b"q:1").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,3658 @@
+use std::io::prelude::*;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufReader, SeekFrom},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{
+    cache::RecordCache,
+    checksum::{
+        ChecksumReport, Crc32, SectionChecksum, CHECKSUM_ALGORITHM, CHECKSUM_HEADER_SECTION,
+    },
+    dictionary::{expand_field_bytes, expand_fields, expand_record, parse_dictionary},
+    error::{open_error, XRVErr},
+    headers::HeaderIndex,
+    intern::Interner,
+    line::{
+        check_fields, decode_record, Jump, LineField, LineJump, LineKind, OwnedRecordLine,
+        OwnedStyleLine, OwnedTableLine, RecordBytes, RecordLine, Span, StyleLine, TableLine,
+    },
+    lock::{lock_shared, LockMode},
+    ordered::OrderedMap,
+    schema::Schema,
+    tail::Tail,
+    tokenizer::{
+        key_link, link_value, starts_with_kind, Dialect, LineLimits, RawLine, XraveBuffer,
+        CHECKSUM_ID, CR_CHAR, DEFAULT_XRAVE_NEW_BUFFER_CAPACITY, NL_CHAR,
+    },
+    version::read_first_line,
+};
+
+#[cfg(feature = "gzip")]
+use flate2::bufread::GzDecoder;
+#[cfg(feature = "stats")]
+use std::time::Instant;
+
+const DEFAULT_MAX_LINE_LEN: usize = 16 * 1024 * 1024;
+
+#[cfg(feature = "gzip")]
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+pub(crate) const DEFAULT_MAX_FIELDS_PER_LINE: usize = 4096;
+pub(crate) const DEFAULT_MAX_VALUE_LEN: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    Strict,
+    Lenient,
+}
+
+/// How record values that are not valid UTF-8 are read. Field names and
+/// record ids must be UTF-8 in every mode, and borrowed `RecordLine`s always
+/// need UTF-8 values.
+///
+/// ```
+/// use xrave::{Encoding, ParseOptions, Reader};
+///
+/// let path = std::env::temp_dir().join("xrave-doc-encoding.xrv");
+/// std::fs::write(&path, b"j:jumps\nr:1 text:caf\xE9\n")?;
+/// let options = ParseOptions { encoding: Encoding::Bytes, ..ParseOptions::default() };
+/// let mut reader = Reader::with_options(&path, options)?;
+/// let record = reader.next_record_bytes()?.unwrap();
+/// assert_eq!(record.get("text"), Some(&b"caf\xE9"[..]));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// Fail with `XRVErr::CantParseFieldStrValue` at the first bad byte.
+    #[default]
+    Utf8Strict,
+    /// Replace invalid sequences with U+FFFD in owned records.
+    Utf8Lossy,
+    /// Leave values unchecked, to be read with `Reader::next_record_bytes`.
+    /// Owned records decode them as `Utf8Lossy` does.
+    Bytes,
+}
+
+/// What owned and borrowed lines keep of a field name used more than once
+/// on a line, so that lookups by name agree whichever way they search.
+/// Only `RawLine::fields` hands out every field as written.
+///
+/// ```
+/// use xrave::{DuplicateFields, ParseOptions, Reader};
+///
+/// let path = std::env::temp_dir().join("xrave-doc-duplicates.xrv");
+/// std::fs::write(&path, "j:jumps\nr:1 a:x b:1 a:y\n")?;
+/// let options = ParseOptions { duplicate_fields: DuplicateFields::KeepLast, ..ParseOptions::default() };
+/// let mut reader = Reader::with_options(&path, options)?;
+/// let record = reader.next_record()?.unwrap();
+/// let fields: Vec<_> = record.cols().iter().map(|f| (f.name(), f.value())).collect();
+/// assert_eq!(fields, [("b", "1"), ("a", "y")]);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateFields {
+    /// Keep the first field of each name, in line order.
+    KeepFirst,
+    /// Keep the last field of each name, in line order.
+    KeepLast,
+    /// Keep every field as written.
+    #[default]
+    KeepAll,
+    /// Fail with `XRVErr::DuplicateField`.
+    Error,
+}
+
+/// Guards against hostile input. Each limit fails with
+/// `XRVErr::LimitExceeded` naming it, wrapped in `XRVErr::AtLine` for the
+/// offending line. Field count and value length are checked while the line
+/// is tokenized, before its fields are collected.
+///
+/// ```
+/// use xrave::{ParseOptions, Reader, XRVErr};
+///
+/// let path = std::env::temp_dir().join("xrave-doc-limits.xrv");
+/// std::fs::write(&path, "j:jumps\nr:1 a:1 b:2 c:3\n")?;
+/// let options = ParseOptions { max_fields_per_line: 3, ..ParseOptions::default() };
+/// let mut reader = Reader::with_options(&path, options)?;
+/// assert!(matches!(reader.next_record(), Err(XRVErr::AtLine { line: 2, .. })));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    pub mode: ParseMode,
+    pub max_line_len: usize,
+    pub encoding: Encoding,
+    /// Share one allocation per distinct field name across all owned lines
+    /// instead of allocating the name again for every record.
+    pub intern_names: bool,
+    /// Most fields on one line, counting its `kind:name` head.
+    pub max_fields_per_line: usize,
+    /// Longest value in bytes, not counting quotes.
+    pub max_value_len: usize,
+    /// Most tables on the jumps line and table lines; unlimited when `None`.
+    pub max_tables: Option<usize>,
+    /// Most records read from one table region; unlimited when `None`.
+    pub max_records_per_table: Option<usize>,
+    /// Field separator, quote and null byte of the lines, checked on open.
+    pub dialect: Dialect,
+    pub duplicate_fields: DuplicateFields,
+    /// Parse every table and style line when the file is opened, rather
+    /// than each header the first time `Reader::load_table` or
+    /// `Reader::load_style` asks for it.
+    pub eager_headers: bool,
+    /// Skip spaces between the colon of a field and its value, so that
+    /// `name: bob` reads as `name:bob`. An empty value then has to be
+    /// quoted, as `Writer` always does. Off by default, where such a line
+    /// fails to parse.
+    ///
+    /// ```
+    /// use xrave::{ParseOptions, Reader};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-space-after-colon.xrv");
+    /// std::fs::write(&path, "j:jumps\nr:1 name: bob\n")?;
+    /// let options = ParseOptions { allow_space_after_colon: true, ..ParseOptions::default() };
+    /// let mut reader = Reader::with_options(&path, options)?;
+    /// assert_eq!(reader.next_record()?.unwrap().cols()[0].value(), "bob");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub allow_space_after_colon: bool,
+    /// Most records `Reader::record_at` keeps after reading them, the least
+    /// recently used dropped first; 0, the default, keeps none. Point
+    /// lookups by key and `RecordCursor::prev` read through the same
+    /// records. They are dropped whenever the reader writes to the file or
+    /// `Reader::poll_new_records` finds it grown or truncated.
+    ///
+    /// ```
+    /// use xrave::{ParseOptions, Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-record-cache.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("name", "str")])?;
+    /// writer.add_record("users", "1", &[("name", "anna")])?;
+    /// writer.finish()?;
+    ///
+    /// let options = ParseOptions { record_cache_entries: 4, ..ParseOptions::default() };
+    /// let mut reader = Reader::with_options(&path, options)?;
+    /// let span = reader.records("users")?.next().unwrap()?.span;
+    /// reader.record_at(span)?;
+    /// reader.record_at(span)?;
+    /// assert_eq!(reader.stats().record_cache_hits, 1);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub record_cache_entries: usize,
+}
+
+impl ParseOptions {
+    pub(crate) fn line_limits(&self) -> LineLimits {
+        LineLimits {
+            max_fields: self.max_fields_per_line,
+            max_value_len: self.max_value_len,
+            escapes: false,
+            dialect: self.dialect,
+            duplicates: self.duplicate_fields,
+            space_after_colon: self.allow_space_after_colon,
+            nulls: false,
+        }
+    }
+}
+
+/// A `ParseOptions` limit, with its configured maximum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    FieldsPerLine(usize),
+    ValueLen(usize),
+    Tables(usize),
+    RecordsPerTable(usize),
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            mode: ParseMode::Strict,
+            max_line_len: DEFAULT_MAX_LINE_LEN,
+            encoding: Encoding::Utf8Strict,
+            intern_names: false,
+            max_fields_per_line: DEFAULT_MAX_FIELDS_PER_LINE,
+            max_value_len: DEFAULT_MAX_VALUE_LEN,
+            max_tables: None,
+            max_records_per_table: None,
+            dialect: Dialect::default(),
+            duplicate_fields: DuplicateFields::default(),
+            eager_headers: false,
+            allow_space_after_colon: false,
+            record_cache_entries: 0,
+        }
+    }
+}
+
+/// Snapshot of a scan handed to the callback set with
+/// `Reader::set_progress_callback`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub bytes_read: u64,
+    /// File size from metadata; `None` for sources that cannot report it
+    /// up front, such as gzip input.
+    pub total_bytes: Option<u64>,
+    pub lines: usize,
+    pub records: usize,
+}
+
+struct ProgressReporter {
+    callback: Box<dyn FnMut(Progress) + Send>,
+    every: u64,
+    total_bytes: Option<u64>,
+    next_at: u64,
+    last_at: Option<u64>,
+}
+
+impl std::fmt::Debug for ProgressReporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProgressReporter")
+            .field("every", &self.every)
+            .field("total_bytes", &self.total_bytes)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Counters a `Reader` keeps while it reads, returned by `Reader::stats`.
+/// The two timings stay zero unless the `stats` feature is enabled, so that
+/// a default build never reads the clock.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReaderStats {
+    /// Bytes of lines read, the version and jumps lines included.
+    pub bytes_read: u64,
+    /// Lines tokenized, the version and jumps lines included; blank lines
+    /// and lines skipped while resyncing are not.
+    pub lines_parsed: usize,
+    pub records_parsed: usize,
+    /// Lines that failed to parse, returned or kept in `diagnostics()`.
+    pub errors: usize,
+    /// Times the line buffer had to grow to hold a line.
+    pub allocations_grown: usize,
+    /// Longest line read in bytes, its terminator included.
+    pub largest_line_len: usize,
+    /// Record lines whose field names outgrew the inline scratch list used
+    /// to check them for `DuplicateFields::Error`, and had to allocate.
+    pub scratch_spills: usize,
+    /// Records `Reader::record_at` found in, or had to read and add to, the
+    /// cache sized by `ParseOptions::record_cache_entries`.
+    pub record_cache_hits: usize,
+    pub record_cache_misses: usize,
+    pub time_in_io: Duration,
+    pub time_in_parse: Duration,
+}
+
+/// Measures the time spent in a step when the `stats` feature is enabled,
+/// and is a no-op otherwise.
+struct Stopwatch {
+    #[cfg(feature = "stats")]
+    started: Instant,
+}
+
+impl Stopwatch {
+    #[cfg(feature = "stats")]
+    fn start() -> Stopwatch {
+        Stopwatch {
+            started: Instant::now(),
+        }
+    }
+
+    #[cfg(not(feature = "stats"))]
+    fn start() -> Stopwatch {
+        Stopwatch {}
+    }
+
+    #[cfg(feature = "stats")]
+    fn add_to(self, total: &mut Duration) {
+        *total += self.started.elapsed();
+    }
+
+    #[cfg(not(feature = "stats"))]
+    fn add_to(self, _total: &mut Duration) {}
+}
+
+struct LineHook(Box<dyn FnMut(LineKind, usize) + Send>);
+
+impl std::fmt::Debug for LineHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("LineHook").finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum Source {
+    File(File),
+    #[cfg(feature = "gzip")]
+    Gzip(Box<GzDecoder<BufReader<File>>>),
+    /// Input that can only be read forward, such as a pipe.
+    Stream(Stream),
+}
+
+/// Input given to `Reader::from_unseekable`.
+pub(crate) struct Stream(Box<dyn Read + Send>);
+
+impl std::fmt::Debug for Stream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Stream").finish_non_exhaustive()
+    }
+}
+
+impl Source {
+    /// Wraps `file` in a gzip decoder when it starts with the gzip magic
+    /// bytes and the `gzip` feature is enabled.
+    #[cfg(feature = "gzip")]
+    pub(crate) fn open(mut file: File) -> Result<Source, XRVErr> {
+        let mut magic = [0u8; 2];
+        let read = match file.read(&mut magic) {
+            Err(err) => return Err(XRVErr::FailToReadLine(err)),
+            Ok(read) => read,
+        };
+        if let Err(err) = file.seek(SeekFrom::Start(0)) {
+            return Err(XRVErr::FailToSeek(err));
+        }
+        if read < magic.len() || magic != GZIP_MAGIC {
+            return Ok(Source::File(file));
+        }
+        Ok(Source::Gzip(Box::new(GzDecoder::new(BufReader::new(file)))))
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    pub(crate) fn open(file: File) -> Result<Source, XRVErr> {
+        Ok(Source::File(file))
+    }
+}
+
+impl Read for Source {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Source::File(file) => file.read(buf),
+            #[cfg(feature = "gzip")]
+            Source::Gzip(decoder) => decoder.read(buf),
+            Source::Stream(stream) => stream.0.read(buf),
+        }
+    }
+}
+
+impl Seek for Source {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            Source::File(file) => file.seek(pos),
+            #[cfg(feature = "gzip")]
+            Source::Gzip(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "gzip input is not seekable",
+            )),
+            Source::Stream(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "input is not seekable",
+            )),
+        }
+    }
+}
+
+pub(crate) fn seek_error(err: std::io::Error) -> XRVErr {
+    match err.kind() {
+        std::io::ErrorKind::Unsupported => XRVErr::SourceNotSeekable,
+        _ => XRVErr::FailToSeek(err),
+    }
+}
+
+#[derive(Debug)]
+pub struct Reader {
+    /// File the reader was opened from, `None` for `Reader::from_file`.
+    pub(crate) path: Option<PathBuf>,
+    pub(crate) source: BufReader<Source>,
+    pub(crate) buffer: XraveBuffer,
+    pub(crate) options: ParseOptions,
+    pub(crate) jumps: OrderedMap<(usize, usize)>,
+    pub(crate) tables: OrderedMap<OwnedTableLine>,
+    pub(crate) styles: OrderedMap<OwnedStyleLine>,
+    /// Entries of each table's dictionary, full values by token.
+    pub(crate) dictionaries: OrderedMap<OrderedMap<String>>,
+    /// Whether the lines before the first table region have been parsed.
+    pub(crate) headers_loaded: bool,
+    /// Offset of the line after the jumps line and the number of the jumps
+    /// line, where the headers start.
+    pub(crate) headers_start: (u64, usize),
+    /// Offset and line number the reader stood at when it first jumped to
+    /// a table region before all headers were parsed: the headers from
+    /// there on were not read in order.
+    pub(crate) left_headers: Option<(u64, usize)>,
+    /// Where each header starts, once a header was loaded on its own.
+    pub(crate) header_index: Option<HeaderIndex>,
+    /// Offsets of the header lines loaded on their own, skipped when
+    /// reading on.
+    pub(crate) loaded_headers: HashSet<u64>,
+    pub(crate) names: Interner,
+    pub(crate) batch: Option<(String, u64)>,
+    progress: Option<ProgressReporter>,
+    /// Length of the file when opened, for checking regions against;
+    /// `None` for sources that cannot report it up front, such as gzip input.
+    file_len: Option<u64>,
+    pub(crate) stats: ReaderStats,
+    on_line: Option<LineHook>,
+    /// End of the table region being read and the records met in it, kept
+    /// only when `ParseOptions::max_records_per_table` is set.
+    region: Option<(u64, usize)>,
+    pub(crate) diagnostics: Vec<XRVErr>,
+    /// Error of the last malformed line in `ParseMode::Lenient` and the
+    /// offset right after it, while lines are skipped up to the next one
+    /// that starts with a line kind.
+    pub(crate) resync: Option<(XRVErr, u64)>,
+    /// Format version declared on the first line, 1 when there is none.
+    pub(crate) version: u32,
+    pub(crate) tail: Option<Tail>,
+    /// Records read by span, kept when `ParseOptions::record_cache_entries`
+    /// is set.
+    pub(crate) cache: RecordCache,
+}
+
+impl Reader {
+    /// Opens `path` with default options.
+    ///
+    /// With the `gzip` feature, a file that starts with the gzip magic bytes
+    /// is decompressed as it is read. The check is made on the opened file,
+    /// not on the path, so `with_options`, `from_file` and
+    /// `from_path_shared` detect gzip input the same way: whichever way a
+    /// file is opened, it reads the same. Gzip input cannot seek, so tables
+    /// are read forward only, as described on `seek_to`.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Reader, XRVErr> {
+        Reader::with_options(path, ParseOptions::default())
+    }
+
+    /// Reads an already open `file` with default options. The reader does
+    /// not know the file's path, so work that opens the file again, such as
+    /// `Reader::parse_tables_parallel` or `SharedReader`, fails with
+    /// `XRVErr::PathUnknown`. Open errors name the file they were about.
+    /// Gzip input is detected as by `Reader::new`.
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-from-file.xrv");
+    /// let mut writer = Writer::new(File::create(&path)?);
+    /// writer.add_table("users", &[("name", "str")])?;
+    /// writer.add_record("users", "1", &[("name", "Anna")])?;
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::from_file(File::open(&path)?)?;
+    /// assert_eq!(reader.records("users")?.count(), 1);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_file(file: File) -> Result<Reader, XRVErr> {
+        Reader::open_file(None, file, ParseOptions::default())
+    }
+
+    /// Reads `input`, such as standard input or a pipe, forward only, with
+    /// default options. Tables are read as with gzip input: `seek_to`, and
+    /// so `records`, reads on and discards bytes up to a region ahead, and
+    /// fails with `XRVErr::SourceNotSeekable` for one already passed, as
+    /// does every other access that has to go back, like `cursor`.
+    /// Headers are parsed as they are read past. Regions are not checked
+    /// against the length of the input, which is unknown up front.
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let mut writer = Writer::new(Vec::new());
+    /// writer.add_table("users", &[("name", "str")])?;
+    /// writer.add_record("users", "1", &[("name", "Anna")])?;
+    /// let bytes = writer.finish()?;
+    ///
+    /// let mut reader = Reader::from_unseekable(Cursor::new(bytes))?;
+    /// assert_eq!(reader.records("users")?.count(), 1);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_unseekable<R: Read + Send + 'static>(input: R) -> Result<Reader, XRVErr> {
+        let source = Source::Stream(Stream(Box::new(input)));
+        Reader::open_source(None, source, ParseOptions::default())
+    }
+
+    /// Opens `path` with `options`. Regions on the jumps line and on table
+    /// lines are checked against the length of the file: one that runs past
+    /// its end fails with `XRVErr::SectionOutOfBounds`, and one whose end
+    /// does not fit in a `usize` with `XRVErr::RegionOverflow`. In
+    /// `ParseMode::Lenient` a jump out of bounds is left out and kept among
+    /// the diagnostics instead. Gzip input is detected as by `Reader::new`.
+    ///
+    /// ```
+    /// use xrave::{ParseMode, ParseOptions, Reader};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-bounds.xrv");
+    /// std::fs::write(&path, "j:jumps missing:9000-10\n")?;
+    /// assert!(Reader::new(&path).is_err());
+    ///
+    /// let options = ParseOptions { mode: ParseMode::Lenient, ..ParseOptions::default() };
+    /// let reader = Reader::with_options(&path, options)?;
+    /// assert_eq!((reader.jumps().count(), reader.diagnostics().len()), (0, 1));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_options<P: AsRef<Path>>(path: P, options: ParseOptions) -> Result<Reader, XRVErr> {
+        let path = path.as_ref();
+        match File::open(path) {
+            Err(err) => Err(open_error(path, err)),
+            Ok(file) => Reader::open_file(Some(path.to_path_buf()), file, options),
+        }
+    }
+
+    /// Opens `path` and checks its table headers against `schema` before
+    /// anything else is read, failing with `XRVErr::SchemaDeviations` that
+    /// lists every missing or extra table and column and every column
+    /// declared otherwise. Columns the file declares without a kind take
+    /// the schema's declaration, so records are read with its kinds.
+    ///
+    /// ```
+    /// use xrave::{ColKind, Reader, Schema, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-with-schema.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("name", "str"), ("age", "i32?")])?;
+    /// writer.add_record("users", "1", &[("name", "Anna"), ("age", "31")])?;
+    /// writer.finish()?;
+    ///
+    /// let schema = Schema::new()
+    ///     .table("users", |t| t.col("name", ColKind::String).col("age", ColKind::I32.optional()));
+    /// let mut reader = Reader::with_schema(&path, &schema)?;
+    /// assert_eq!(reader.records("users")?.count(), 1);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_schema<P: AsRef<Path>>(path: P, schema: &Schema) -> Result<Reader, XRVErr> {
+        let mut reader = Reader::new(path)?;
+        reader.load_headers()?;
+        let diffs = schema.lock_tables(&mut reader.tables);
+        match diffs.is_empty() {
+            true => Ok(reader),
+            false => Err(XRVErr::SchemaDeviations(diffs)),
+        }
+    }
+
+    /// Opens `path` holding a shared advisory lock for the reader's
+    /// lifetime, so a `Writer::open_locked` on the same file waits or fails
+    /// until the reader is dropped.
+    pub fn from_path_shared<P: AsRef<Path>>(path: P, lock: LockMode) -> Result<Reader, XRVErr> {
+        let path = path.as_ref();
+        let file = match File::open(path) {
+            Err(err) => return Err(open_error(path, err)),
+            Ok(file) => file,
+        };
+        lock_shared(&file, path, lock)?;
+        Reader::open_file(Some(path.to_path_buf()), file, ParseOptions::default())
+    }
+
+    fn open_file(
+        path: Option<PathBuf>,
+        file: File,
+        options: ParseOptions,
+    ) -> Result<Reader, XRVErr> {
+        Reader::open_source(path, Source::open(file)?, options)
+    }
+
+    fn open_source(
+        path: Option<PathBuf>,
+        source: Source,
+        options: ParseOptions,
+    ) -> Result<Reader, XRVErr> {
+        options.dialect.validate()?;
+        let mut source = BufReader::new(source);
+        let mut buffer = XraveBuffer::new();
+        buffer.limits = options.line_limits();
+        let mut diagnostics = Vec::new();
+        let version = read_first_line(
+            &mut buffer,
+            &mut source,
+            options.max_line_len,
+            options.mode,
+            &mut diagnostics,
+        )?;
+
+        let mut jumps = OrderedMap::new();
+        let raw_line: RawLine = (&buffer).try_into()?;
+        let line_jump = match LineJump::try_from(raw_line) {
+            Err(err) => return Err(buffer.diagnose(err.position().1, err)),
+            Ok(line_jump) => line_jump,
+        };
+        if let Some(max) = options.max_tables {
+            if line_jump.jumps.len() > max {
+                let err = XRVErr::LimitExceeded(Limit::Tables(max));
+                return Err(buffer.diagnose(1, err));
+            }
+        }
+        let file_len = match source.get_ref() {
+            Source::File(file) => file.metadata().ok().map(|metadata| metadata.len()),
+            #[cfg(feature = "gzip")]
+            Source::Gzip(_) => None,
+            Source::Stream(_) => None,
+        };
+        for jump in line_jump.jumps {
+            if let Err(err) = check_bounds(jump.name, jump.seek, jump.len, file_len) {
+                match options.mode {
+                    ParseMode::Strict => return Err(err),
+                    ParseMode::Lenient => {
+                        diagnostics.push(err);
+                        continue;
+                    }
+                }
+            }
+            insert_unique(
+                &mut jumps,
+                jump.name,
+                (jump.seek, jump.len),
+                options.mode,
+                &mut diagnostics,
+                XRVErr::DuplicateJump,
+            )?;
+        }
+
+        let stats = ReaderStats {
+            bytes_read: buffer.offset,
+            lines_parsed: buffer.line,
+            largest_line_len: buffer.buffer.len(),
+            ..ReaderStats::default()
+        };
+        let headers_start = (buffer.offset, buffer.line);
+        let eager_headers = options.eager_headers;
+        let cache = RecordCache::new(options.record_cache_entries);
+        let mut reader = Reader {
+            path,
+            source,
+            buffer,
+            names: Interner::new(options.intern_names),
+            batch: None,
+            progress: None,
+            file_len,
+            stats,
+            on_line: None,
+            region: None,
+            resync: None,
+            version,
+            options,
+            jumps,
+            tables: OrderedMap::new(),
+            styles: OrderedMap::new(),
+            dictionaries: OrderedMap::new(),
+            headers_loaded: false,
+            headers_start,
+            left_headers: None,
+            header_index: None,
+            loaded_headers: HashSet::new(),
+            diagnostics,
+            tail: None,
+            cache,
+        };
+        if eager_headers {
+            reader.load_headers()?;
+        }
+        Ok(reader)
+    }
+
+    /// Format version the file declares on its first line, `v:xrave 3`, or 1
+    /// for files without a version line. Escape sequences and line
+    /// continuations need version 2 and nulls version 3, so older files
+    /// never change meaning. A version newer than this crate supports fails
+    /// to open with `XRVErr::UnsupportedVersion`, or in `ParseMode::Lenient`
+    /// opens with a diagnostic.
+    ///
+    /// ```
+    /// use xrave::Reader;
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-version.xrv");
+    /// std::fs::write(&path, "v:xrave 2\nj:jumps\nr:1 note:\"a\\tb\"\n")?;
+    /// let mut reader = Reader::new(&path)?;
+    /// assert_eq!(reader.format_version(), 2);
+    /// assert_eq!(reader.next_record()?.unwrap().cols()[0].value_unescaped(), "a\tb");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn format_version(&self) -> u32 {
+        self.version
+    }
+
+    /// Calls `callback` whenever at least `every` more bytes have been read
+    /// since the previous call, and once more when a scan reaches the end of
+    /// the file or of the table region being iterated.
+    ///
+    /// ```
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-progress.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("name", "str")])?;
+    /// for id in 0..1_000 {
+    ///     writer.add_record("users", &id.to_string(), &[("name", "Anna")])?;
+    /// }
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(&path)?;
+    /// reader.set_progress_callback(4096, |progress| {
+    ///     println!("{} of {:?} bytes", progress.bytes_read, progress.total_bytes);
+    /// });
+    /// while reader.next_record()?.is_some() {}
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_progress_callback<F>(&mut self, every: u64, callback: F)
+    where
+        F: FnMut(Progress) + Send + 'static,
+    {
+        let total_bytes = match self.source.get_ref() {
+            Source::File(file) => file.metadata().ok().map(|metadata| metadata.len()),
+            #[cfg(feature = "gzip")]
+            Source::Gzip(_) => None,
+            Source::Stream(_) => None,
+        };
+        self.progress = Some(ProgressReporter {
+            callback: Box::new(callback),
+            every: every.max(1),
+            total_bytes,
+            next_at: self.buffer.offset,
+            last_at: None,
+        });
+    }
+
+    fn report_progress(&mut self, done: bool) {
+        let progress = match &mut self.progress {
+            None => return,
+            Some(progress) => progress,
+        };
+        let bytes_read = self.buffer.offset;
+        if progress.last_at == Some(bytes_read) || (!done && bytes_read < progress.next_at) {
+            return;
+        }
+        progress.last_at = Some(bytes_read);
+        progress.next_at = bytes_read.saturating_add(progress.every);
+        (progress.callback)(Progress {
+            bytes_read,
+            total_bytes: progress.total_bytes,
+            lines: self.buffer.lines_read(),
+            records: self.stats.records_parsed,
+        });
+    }
+
+    /// Recomputes the CRC32 of every section listed on the `c:crc32` line and
+    /// compares it with the recorded value. The stream position is restored
+    /// afterwards, so this can be called between `parse_next` calls.
+    pub fn verify_checksums(&mut self) -> Result<ChecksumReport, XRVErr> {
+        let resume = self.buffer.offset;
+        let report = self.checksum_report();
+        match self.source.seek(SeekFrom::Start(resume)) {
+            Err(err) => Err(seek_error(err)),
+            Ok(_) => report,
+        }
+    }
+
+    /// Positions the reader at the start of the region recorded for `name` on
+    /// the jumps line, so the next `parse_next` reads its first record.
+    ///
+    /// Sources that cannot seek (gzip input) fall back to reading forward and
+    /// discarding bytes up to the region; a region that lies behind the
+    /// current position fails with `XRVErr::SourceNotSeekable` there.
+    ///
+    /// The region must start a line: the byte before it is a terminator,
+    /// or it starts the file. Otherwise this fails with
+    /// `XRVErr::RegionStartsMidLine`; `ParseMode::Lenient` keeps the error
+    /// among the diagnostics and skips the rest of that line instead.
+    ///
+    /// `name` is the table name as declared, without the quotes the jumps
+    /// line puts around names holding a separator or colon. Only the value
+    /// of a jump is split at its dash, so names may hold dashes too.
+    ///
+    /// ```
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-seek-quoted.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("my table", &[("name", "str")])?;
+    /// writer.add_record("my table", "1", &[("name", "Anna")])?;
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(&path)?;
+    /// reader.seek_to("my table")?;
+    /// assert_eq!(reader.next_record()?.unwrap().id(), "1");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn seek_to(&mut self, name: &str) -> Result<(), XRVErr> {
+        let (seek, len) = match self.jumps.get(name) {
+            None => return Err(XRVErr::UnknownTable(name.to_owned())),
+            Some((seek, len)) => (*seek as u64, *len),
+        };
+        // Records are read with the table's dictionary.
+        self.load_table(name)?;
+        if !self.headers_loaded && self.left_headers.is_none() {
+            self.left_headers = Some((self.buffer.offset, self.buffer.lines_read()));
+        }
+
+        // Sources that cannot seek back are only ever left at the end of a
+        // line, so a region starting there needs no check.
+        let seekable = matches!(self.source.get_ref(), Source::File(_));
+        let starts_line = if seek == 0 || (seek == self.buffer.offset && !seekable) {
+            self.move_to(seek, name, (seek, len))?;
+            true
+        } else {
+            self.move_to(seek - 1, name, (seek, len))?;
+            let mut before = [0; 1];
+            if let Err(err) = self.source.read_exact(&mut before) {
+                return Err(XRVErr::FailToReadLine(err));
+            }
+            matches!(before[0], NL_CHAR | CR_CHAR)
+        };
+        self.end_resync(self.buffer.offset);
+        self.buffer.offset = seek;
+        self.batch = None;
+        self.region = None;
+        if !starts_line {
+            let err = XRVErr::RegionStartsMidLine {
+                table: name.to_owned(),
+                offset: seek,
+            };
+            if self.options.mode == ParseMode::Strict {
+                return Err(err);
+            }
+            self.diagnostics.push(err);
+            match self.read_next_line() {
+                Ok(_) | Err(XRVErr::LineTooLong { .. }) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the source to `offset`, on the way to the region of `name` at
+    /// `seek` of `len` bytes.
+    fn move_to(
+        &mut self,
+        offset: u64,
+        name: &str,
+        (seek, len): (u64, usize),
+    ) -> Result<(), XRVErr> {
+        match self.source.seek(SeekFrom::Start(offset)) {
+            Ok(_) => {}
+            Err(err) if err.kind() != std::io::ErrorKind::Unsupported => {
+                return Err(XRVErr::FailToSeek(err))
+            }
+            Err(_) if offset < self.buffer.offset => return Err(XRVErr::SourceNotSeekable),
+            Err(_) => {
+                let skip = offset - self.buffer.offset;
+                match std::io::copy(&mut (&mut self.source).take(skip), &mut std::io::sink()) {
+                    Err(err) => return Err(XRVErr::FailToReadLine(err)),
+                    Ok(skipped) if skipped < skip => {
+                        return Err(XRVErr::SectionOutOfBounds {
+                            name: name.to_owned(),
+                            seek: seek as usize,
+                            len,
+                            file_len: self.buffer.offset + skipped,
+                        })
+                    }
+                    Ok(_) => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn checksum_report(&mut self) -> Result<ChecksumReport, XRVErr> {
+        if let Err(err) = self.source.seek(SeekFrom::Start(0)) {
+            return Err(seek_error(err));
+        }
+
+        let mut buffer = XraveBuffer::new();
+        buffer.set_version(self.version);
+        let mut offset: usize = 0;
+        let mut expected: Option<(usize, Vec<(String, u32)>)> = None;
+        loop {
+            let read = buffer.read_line(&mut self.source, self.options.max_line_len)?;
+            if read == 0 {
+                break;
+            }
+            if buffer.buffer.first() == Some(&CHECKSUM_ID) {
+                let raw_line: RawLine = (&buffer).try_into()?;
+                let line_field: LineField = raw_line.try_into()?;
+                if let (LineKind::Checksum, CHECKSUM_ALGORITHM) =
+                    (&line_field.kind, line_field.name)
+                {
+                    let mut sections = Vec::new();
+                    for field in line_field.fields {
+                        match u32::from_str_radix(field.value, 16) {
+                            Err(_) => return Err(XRVErr::CantParseChecksum),
+                            Ok(crc) => sections.push((field.name.to_owned(), crc)),
+                        }
+                    }
+                    expected = Some((offset, sections));
+                }
+            }
+            offset += read;
+        }
+
+        let (checksum_offset, sections) = match expected {
+            None => return Ok(ChecksumReport::NotPresent),
+            Some(expected) => expected,
+        };
+
+        let header_end = self
+            .jumps
+            .values()
+            .map(|(seek, _)| *seek)
+            .min()
+            .unwrap_or(checksum_offset)
+            .min(checksum_offset);
+
+        let mut report = Vec::new();
+        for (name, expected) in sections {
+            let (seek, len) = match (name.as_str(), self.jumps.get(&name)) {
+                (CHECKSUM_HEADER_SECTION, _) => (0, header_end),
+                (_, Some(jump)) => *jump,
+                (_, None) => return Err(XRVErr::UnknownTable(name)),
+            };
+            let actual = self.section_crc32(seek, len)?;
+            report.push(SectionChecksum {
+                name,
+                expected,
+                actual,
+            });
+        }
+        Ok(ChecksumReport::Sections(report))
+    }
+
+    fn section_crc32(&mut self, seek: usize, len: usize) -> Result<u32, XRVErr> {
+        if let Err(err) = self.source.seek(SeekFrom::Start(seek as u64)) {
+            return Err(seek_error(err));
+        }
+        let mut crc = Crc32::new();
+        let mut chunk = [0u8; DEFAULT_XRAVE_NEW_BUFFER_CAPACITY];
+        let mut remaining = len;
+        while remaining > 0 {
+            let want = remaining.min(chunk.len());
+            match self.source.read(&mut chunk[..want]) {
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(XRVErr::FailToReadLine(err)),
+                Ok(0) => break,
+                Ok(n) => {
+                    crc.update(&chunk[..n]);
+                    remaining -= n;
+                }
+            }
+        }
+        Ok(crc.finish())
+    }
+
+    /// Path to open the file again from, for work that needs a handle of
+    /// its own.
+    pub(crate) fn reopen_path(&self) -> Result<&Path, XRVErr> {
+        self.path.as_deref().ok_or(XRVErr::PathUnknown)
+    }
+
+    /// Parses the record regions of the named tables concurrently, one thread
+    /// per table, each with a reader of its own on the file, opened with the
+    /// same `ParseOptions`. Tables are read as by `records`, so lenient mode
+    /// and `ParseOptions::max_records_per_table` apply, but lines a worker
+    /// skips in lenient mode are not added to `diagnostics`. Records keep
+    /// their file order within each table. A failure in any worker is
+    /// returned as `XRVErr::InTable` naming the table.
+    pub fn parse_tables_parallel(
+        &self,
+        names: &[&str],
+    ) -> Result<HashMap<String, Vec<OwnedRecordLine>>, XRVErr> {
+        if !matches!(self.source.get_ref(), Source::File(_)) {
+            return Err(XRVErr::SourceNotSeekable);
+        }
+        if let Some(name) = names.iter().find(|name| !self.jumps.contains_key(name)) {
+            return Err(XRVErr::UnknownTable(name.to_string()));
+        }
+
+        let path = self.reopen_path()?;
+        let options = &self.options;
+        let results: Vec<(&str, Result<Vec<OwnedRecordLine>, XRVErr>)> =
+            std::thread::scope(|scope| {
+                let workers: Vec<_> = names
+                    .iter()
+                    .map(|name| {
+                        let worker = scope.spawn(move || {
+                            let mut reader = Reader::with_options(path, options.clone())?;
+                            let records = reader.records(name)?;
+                            records.collect::<Result<Vec<_>, _>>()
+                        });
+                        (*name, worker)
+                    })
+                    .collect();
+                workers
+                    .into_iter()
+                    .map(|(name, worker)| match worker.join() {
+                        Err(_) => (name, Err(XRVErr::WorkerPanicked)),
+                        Ok(result) => (name, result),
+                    })
+                    .collect()
+            });
+
+        let mut tables = HashMap::with_capacity(results.len());
+        for (name, result) in results {
+            match result {
+                Err(err) => {
+                    return Err(XRVErr::InTable {
+                        table: name.to_owned(),
+                        err: Box::new(err),
+                    })
+                }
+                Ok(records) => {
+                    tables.insert(name.to_owned(), records);
+                }
+            }
+        }
+        Ok(tables)
+    }
+
+    /// Regions listed on the jumps line, in the order they are listed.
+    pub fn jumps(&self) -> impl Iterator<Item = Jump<'_>> {
+        self.jumps.iter().map(|(name, (seek, len))| Jump {
+            name,
+            seek: *seek,
+            len: *len,
+        })
+    }
+
+    /// Table headers parsed so far, keyed by table name in file order.
+    pub fn tables(&self) -> &OrderedMap<OwnedTableLine> {
+        &self.tables
+    }
+
+    /// Header of the table `name` if it was parsed already; see
+    /// `Reader::load_table`.
+    pub fn table(&self, name: &str) -> Option<&OwnedTableLine> {
+        self.tables.get(name)
+    }
+
+    /// Styles parsed so far, keyed by style id in file order.
+    pub fn styles(&self) -> &OrderedMap<OwnedStyleLine> {
+        &self.styles
+    }
+
+    /// Style `id` if it was parsed already; see `Reader::load_style`.
+    pub fn style(&self, id: &str) -> Option<&OwnedStyleLine> {
+        self.styles.get(id)
+    }
+
+    /// Iterates the records of `table`, reading only the table's region.
+    /// A region whose end falls inside a line, rather than right after a
+    /// terminator, fails with `XRVErr::RegionEndsMidLine` on that line;
+    /// `ParseMode::Lenient` drops the line, keeps the error among the
+    /// diagnostics and ends the table there.
+    ///
+    /// ```
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-records.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("name", "str")])?;
+    /// writer.add_record("users", "1", &[("name", "Anna Smith")])?;
+    /// writer.add_record("users", "2", &[("name", "Bob")])?;
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+    /// let mut names = Vec::new();
+    /// for record in reader.records("users")? {
+    ///     names.push(record?.cols[0].value.clone());
+    /// }
+    /// assert_eq!(names, ["Anna Smith", "Bob"]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn records(&mut self, table: &str) -> Result<Records<'_>, XRVErr> {
+        self.seek_to(table)?;
+        let end = match self.jumps.get(table) {
+            None => return Err(XRVErr::UnknownTable(table.to_owned())),
+            Some((seek, len)) => (*seek + *len) as u64,
+        };
+        Ok(Records {
+            reader: self,
+            table: table.to_owned(),
+            end,
+            done: false,
+        })
+    }
+
+    /// Calls `f` with the `col` key, as by `extract_key`, and the span
+    /// of each record of `table` that has one. Lines are checked as by
+    /// `records`, but only the key is decoded.
+    pub(crate) fn for_each_key<F: FnMut(String, Span)>(
+        &mut self,
+        table: &str,
+        col: &str,
+        mut f: F,
+    ) -> Result<(), XRVErr> {
+        self.seek_to(table)?;
+        let end = match self.jumps.get(table) {
+            None => return Err(XRVErr::UnknownTable(table.to_owned())),
+            Some((seek, len)) => (*seek + *len) as u64,
+        };
+        while self.buffer.offset < end {
+            match self.parse_next()? {
+                None => break,
+                // A lenient resync can carry past the end of the region.
+                Some(_) if self.buffer.start >= end => continue,
+                Some(_) if !self.within_region(table, end)? => break,
+                Some(LineKind::Tombstone) => continue,
+                Some(LineKind::Record) => {}
+                Some(_) => return Err(XRVErr::NotRecordLine),
+            }
+            let buffer = &self.buffer;
+            if let Some(link) = key_link(&buffer.buffer, col.as_bytes(), buffer.limits) {
+                let quote = buffer.limits.dialect.quote;
+                let key = link_value(
+                    &buffer.buffer,
+                    buffer.line,
+                    &link,
+                    quote,
+                    self.options.encoding,
+                )?;
+                f(key, buffer.span());
+            }
+        }
+        self.report_progress(true);
+        Ok(())
+    }
+
+    /// Appends up to `max` records of `table` to `out` and returns how many
+    /// were added, 0 once the table is exhausted. Consecutive calls for the
+    /// same table continue where the previous one stopped; naming another
+    /// table, or any `seek_to`/`records` call, starts over at the region start.
+    ///
+    /// On a parse error the records read before it stay in `out` and the
+    /// error is returned, so the count for that call is the growth of
+    /// `out.len()`. The failing line is consumed, so the next call carries
+    /// on after it. `out` is never cleared, letting callers reuse its
+    /// capacity between batches.
+    pub fn read_batch(
+        &mut self,
+        table: &str,
+        out: &mut Vec<OwnedRecordLine>,
+        max: usize,
+    ) -> Result<usize, XRVErr> {
+        if max == 0 {
+            return Ok(0);
+        }
+        let end = match &self.batch {
+            Some((name, end)) if name == table => *end,
+            _ => {
+                self.seek_to(table)?;
+                let end = match self.jumps.get(table) {
+                    None => return Err(XRVErr::UnknownTable(table.to_owned())),
+                    Some((seek, len)) => (*seek + *len) as u64,
+                };
+                self.batch = Some((table.to_owned(), end));
+                end
+            }
+        };
+
+        let mut count = 0;
+        while count < max && self.buffer.offset < end {
+            match self.parse_next()? {
+                None => break,
+                // A lenient resync can carry past the end of the region.
+                Some(_) if self.buffer.start >= end => continue,
+                Some(_) if !self.within_region(table, end)? => break,
+                Some(LineKind::Tombstone) => continue,
+                Some(_) => {}
+            }
+            out.push(self.owned_record()?);
+            count += 1;
+        }
+        if self.buffer.offset >= end {
+            self.report_progress(true);
+        }
+        Ok(count)
+    }
+
+    /// Reads the records at the start of `table` whose lines fit in
+    /// `budget_bytes` together, terminators included, stopping before the
+    /// first record that would exceed it. The flag is set when records were
+    /// left out.
+    ///
+    /// ```
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-prefix.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("name", "str")])?;
+    /// for id in 0..10 {
+    ///     writer.add_record("users", &id.to_string(), &[("name", "Anna")])?;
+    /// }
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(&path)?;
+    /// let (records, truncated) = reader.load_table_prefix("users", 3 * "r:0 name:Anna\n".len())?;
+    /// assert_eq!((records.len(), truncated), (3, true));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn load_table_prefix(
+        &mut self,
+        table: &str,
+        budget_bytes: usize,
+    ) -> Result<(Vec<OwnedRecordLine>, bool), XRVErr> {
+        let mut records = Vec::new();
+        let mut used: usize = 0;
+        for record in self.records(table)? {
+            let record = record?;
+            used = used.saturating_add(record.span.len);
+            if used > budget_bytes {
+                return Ok((records, true));
+            }
+            records.push(record);
+        }
+        Ok((records, false))
+    }
+
+    /// Reads forward to the next record line, skipping jumps, table, style
+    /// and checksum lines. Table and style lines passed on the way are still
+    /// recorded in `tables()` and `styles()`.
+    ///
+    /// Lines holding only spaces and tabs are skipped wherever they are, and
+    /// so is a last line of control bytes such as the DOS end-of-file mark
+    /// `\x1a`. Any other stray line still fails in `ParseMode::Strict`.
+    ///
+    /// ```
+    /// use xrave::Reader;
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-tails.xrv");
+    /// std::fs::write(&path, "j:jumps\nr:1 a:x\n  \t \n\nr:2 a:y\n\x1a")?;
+    /// let mut reader = Reader::new(&path)?;
+    /// let mut ids = Vec::new();
+    /// while let Some(record) = reader.next_record()? {
+    ///     ids.push(record.id().to_owned());
+    /// }
+    /// assert_eq!(ids, ["1", "2"]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn next_record(&mut self) -> Result<Option<RecordLine<'_>>, XRVErr> {
+        loop {
+            match self.parse_next()? {
+                None => return Ok(None),
+                Some(LineKind::Record) => break,
+                Some(_) => continue,
+            }
+        }
+        let raw_line: RawLine = (&self.buffer).try_into()?;
+        let line_field: LineField = raw_line.try_into()?;
+        let mut record: RecordLine = line_field.try_into()?;
+        if let Some((table, dictionary)) = self.dictionary_at(self.buffer.start) {
+            if let Err(err) = expand_fields(dictionary, table, &mut record.cols) {
+                return Err(self.buffer.diagnose(1, err));
+            }
+        }
+        Ok(Some(record))
+    }
+
+    /// Like `next_record`, but hands out values as bytes without checking
+    /// them for UTF-8. Pair with `Encoding::Bytes` so the scan does not
+    /// reject them first.
+    pub fn next_record_bytes(&mut self) -> Result<Option<RecordBytes<'_>>, XRVErr> {
+        loop {
+            match self.parse_next()? {
+                None => return Ok(None),
+                Some(LineKind::Record) => break,
+                Some(_) => continue,
+            }
+        }
+        let raw_line: RawLine = (&self.buffer).try_into()?;
+        let mut record: RecordBytes = raw_line.try_into()?;
+        if let Some((table, dictionary)) = self.dictionary_at(self.buffer.start) {
+            if let Err(err) = expand_field_bytes(dictionary, table, &mut record.cols) {
+                return Err(self.buffer.diagnose(1, err));
+            }
+        }
+        Ok(Some(record))
+    }
+
+    /// Reads the single record at `span` again, as found by an earlier
+    /// scan, its dictionary tokens expanded; the reader's position is left
+    /// untouched. Only plain files can be read this way.
+    ///
+    /// ```
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-span.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("name", "str")])?;
+    /// writer.add_record("users", "1", &[("name", "Anna")])?;
+    /// writer.add_record("users", "2", &[("name", "Bob")])?;
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(&path)?;
+    /// let records = reader.records("users")?.collect::<Result<Vec<_>, _>>()?;
+    /// assert_eq!(reader.record_at(records[1].span)?, records[1]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn record_at(&mut self, span: Span) -> Result<OwnedRecordLine, XRVErr> {
+        if !self.cache.is_enabled() {
+            return self.read_record_at(span);
+        }
+        if let Some(record) = self.cache.get(span.offset) {
+            self.stats.record_cache_hits += 1;
+            return Ok(OwnedRecordLine::clone(&record));
+        }
+        self.stats.record_cache_misses += 1;
+        let record = self.read_record_at(span)?;
+        self.cache.insert(span.offset, Arc::new(record.clone()));
+        Ok(record)
+    }
+
+    fn read_record_at(&mut self, span: Span) -> Result<OwnedRecordLine, XRVErr> {
+        let line = self.read_line_at(span.offset)?;
+        let raw_line: RawLine = (&line).try_into()?;
+        let mut owned = decode_record(raw_line, self.options.encoding, &mut self.names)?;
+        owned.span = Span {
+            offset: span.offset,
+            len: line.offset as usize,
+        };
+        if let Some((table, dictionary)) = self.dictionary_at(span.offset) {
+            if let Err(err) = expand_record(dictionary, table, &mut owned) {
+                return Err(line.diagnose(1, err));
+            }
+        }
+        Ok(owned)
+    }
+
+    /// Reads the next physical line and only tokenizes it: field values are
+    /// not checked for UTF-8, and table and style lines are not recorded.
+    ///
+    /// ```
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-raw-reader.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("name", "str")])?;
+    /// writer.add_record("users", "1", &[("name", "Anna")])?;
+    /// writer.add_record("users", "2", &[("name", "Bob")])?;
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+    /// let mut lines = 0;
+    /// while let Some(line) = reader.next_raw_line()? {
+    ///     lines += 1;
+    ///     assert_eq!(line.line(), lines + 2);
+    /// }
+    /// assert_eq!(lines, 3);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn next_raw_line(&mut self) -> Result<Option<RawLine<'_>>, XRVErr> {
+        loop {
+            if self.read_next_line()? == 0 {
+                self.report_progress(true);
+                return Ok(None);
+            }
+            if !self.buffer.is_filler(&mut self.source)? {
+                break;
+            }
+        }
+        self.report_progress(false);
+        let watch = Stopwatch::start();
+        let raw_line = RawLine::try_from(&self.buffer);
+        watch.add_to(&mut self.stats.time_in_parse);
+        match &raw_line {
+            Err(_) => self.stats.errors += 1,
+            Ok(raw_line) => {
+                self.stats.lines_parsed += 1;
+                if let Some(hook) = &mut self.on_line {
+                    (hook.0)(
+                        raw_line.kind,
+                        (self.buffer.offset - self.buffer.start) as usize,
+                    );
+                }
+            }
+        }
+        Ok(Some(raw_line?))
+    }
+
+    /// Counters gathered since the reader was opened.
+    ///
+    /// ```
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-stats.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("name", "str")])?;
+    /// for id in 0..100 {
+    ///     writer.add_record("users", &id.to_string(), &[("name", "Anna")])?;
+    /// }
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(&path)?;
+    /// while reader.next_record()?.is_some() {}
+    /// let stats = reader.stats();
+    /// assert_eq!((stats.records_parsed, stats.errors), (100, 0));
+    /// assert_eq!(stats.bytes_read, std::fs::metadata(&path)?.len());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn stats(&self) -> ReaderStats {
+        self.stats
+    }
+
+    /// Calls `hook` with the kind and length in bytes, terminator included,
+    /// of every line the reader parses, for metrics `stats()` does not keep.
+    /// Blank lines and lines that fail to parse are not passed.
+    pub fn set_line_hook<F>(&mut self, hook: F)
+    where
+        F: FnMut(LineKind, usize) + Send + 'static,
+    {
+        self.on_line = Some(LineHook(Box::new(hook)));
+    }
+
+    /// Reads the next line into the buffer like `XraveBuffer::read_line`,
+    /// counting it in `stats`.
+    pub(crate) fn read_next_line(&mut self) -> Result<usize, XRVErr> {
+        let capacity = self.buffer.buffer.capacity();
+        let watch = Stopwatch::start();
+        let read = self
+            .buffer
+            .read_line(&mut self.source, self.options.max_line_len);
+        watch.add_to(&mut self.stats.time_in_io);
+        if self.buffer.buffer.capacity() > capacity {
+            self.stats.allocations_grown += 1;
+        }
+        let len = self.buffer.offset - self.buffer.start;
+        self.stats.bytes_read += len;
+        self.stats.largest_line_len = self.stats.largest_line_len.max(len as usize);
+        read
+    }
+
+    /// Number of distinct field names shared through the interner; always 0
+    /// unless `ParseOptions::intern_names` is set.
+    ///
+    /// ```
+    /// use xrave::{ParseOptions, Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-intern.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("events", &[("name", "str"), ("ts", "int")])?;
+    /// writer.add_record("events", "1", &[("name", "tick"), ("ts", "1")])?;
+    /// writer.add_record("events", "2", &[("name", "tick"), ("ts", "2")])?;
+    /// writer.finish()?;
+    ///
+    /// let options = ParseOptions { intern_names: true, ..ParseOptions::default() };
+    /// let mut reader = Reader::with_options(&path, options)?;
+    /// let records = reader.records("events")?.collect::<Result<Vec<_>, _>>()?;
+    /// assert_eq!((records.len(), reader.interned_names()), (2, 2));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn interned_names(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Problems skipped over in `ParseMode::Lenient`, in the order they were
+    /// met. After a malformed line the reader skips every following line
+    /// that does not start with a line kind such as `r:` or `t:`, so the
+    /// rest of a value broken over several lines is not parsed as lines of
+    /// its own. The error of a line followed by skipped bytes comes wrapped
+    /// in `XRVErr::Resynced` with their count.
+    ///
+    /// ```
+    /// use xrave::{ParseMode, ParseOptions, Reader};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-resync.xrv");
+    /// std::fs::write(&path, "j:jumps\nr:1 note:ok\nr:2 note:\"first\nsecond\"\nr:3 note:ok\n")?;
+    /// let options = ParseOptions { mode: ParseMode::Lenient, ..ParseOptions::default() };
+    /// let mut reader = Reader::with_options(&path, options)?;
+    /// while reader.next_record()?.is_some() {}
+    /// assert_eq!(reader.diagnostics().len(), 1);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn diagnostics(&self) -> &[XRVErr] {
+        &self.diagnostics
+    }
+
+    /// Parses the table and style lines that precede the first table region,
+    /// filling the reader's table and style maps. Headers the reader jumped
+    /// past to read a table are parsed without moving it.
+    pub fn load_headers(&mut self) -> Result<(), XRVErr> {
+        if self.headers_loaded {
+            return Ok(());
+        }
+        let first_region = self
+            .jumps
+            .values()
+            .map(|(seek, _)| *seek as u64)
+            .min()
+            .unwrap_or(u64::MAX);
+        let parse = |reader: &mut Reader| -> Result<(), XRVErr> {
+            while reader.buffer.offset < first_region {
+                if reader.parse_next()?.is_none() {
+                    break;
+                }
+            }
+            Ok(())
+        };
+        match self.left_headers {
+            None => parse(self)?,
+            Some(at) => self.detour(at, parse)?,
+        }
+        self.headers_loaded = true;
+        Ok(())
+    }
+
+    /// Parses the headers unless that was done already, for work that
+    /// needs the tables' dictionaries.
+    pub(crate) fn ensure_headers(&mut self) -> Result<(), XRVErr> {
+        match self.headers_loaded {
+            true => Ok(()),
+            false => self.load_headers(),
+        }
+    }
+
+    /// Dictionary of `table`, full values by token, when its header has
+    /// one. Records read through the reader come with their `^token`
+    /// values replaced already; in a table without a dictionary such a
+    /// value is read as it is.
+    ///
+    /// ```
+    /// use xrave::{Reader, Writer, WriterOptions};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-dictionary.xrv");
+    /// let options = WriterOptions { dictionary_threshold: Some(8), ..WriterOptions::default() };
+    /// let mut writer = Writer::with_options(std::fs::File::create(&path)?, options);
+    /// writer.add_table("users", &[("status", "str")])?;
+    /// for id in 0..10 {
+    ///     writer.add_record("users", &id.to_string(), &[("status", "active")])?;
+    /// }
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(&path)?;
+    /// let record = reader.records("users")?.next().unwrap()?;
+    /// assert_eq!(record.get_all("status").next(), Some("active"));
+    /// assert_eq!(reader.dictionary("users").unwrap().get("1").map(String::as_str), Some("active"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn dictionary(&self, table: &str) -> Option<&OrderedMap<String>> {
+        self.dictionaries.get(table)
+    }
+
+    /// Table whose region holds byte `offset`, with its dictionary, when
+    /// it has one.
+    pub(crate) fn dictionary_at(&self, offset: u64) -> Option<(&str, &OrderedMap<String>)> {
+        if self.dictionaries.is_empty() {
+            return None;
+        }
+        let (table, _) = self
+            .jumps
+            .iter()
+            .find(|(_, (seek, len))| (*seek as u64..(*seek + *len) as u64).contains(&offset))?;
+        self.dictionaries
+            .get(table)
+            .map(|dictionary| (table.as_str(), dictionary))
+    }
+
+    /// Reads the next line, recording table and style lines in the reader's
+    /// maps. In `ParseMode::Lenient` lines that fail to parse are skipped and
+    /// their error, with a snippet of the line, kept in `diagnostics()`.
+    pub(crate) fn parse_next(&mut self) -> Result<Option<LineKind>, XRVErr> {
+        loop {
+            match self.read_next_line() {
+                Err(err @ XRVErr::LineTooLong { .. })
+                    if self.options.mode == ParseMode::Lenient =>
+                {
+                    self.stats.errors += 1;
+                    self.diagnostics.push(err);
+                    continue;
+                }
+                Err(err) => return Err(err),
+                Ok(0) => {
+                    self.end_resync(self.buffer.offset);
+                    self.report_progress(true);
+                    return Ok(None);
+                }
+                Ok(_) => {}
+            }
+            if self.buffer.is_filler(&mut self.source)? {
+                continue;
+            }
+            if self.resync.is_some() {
+                if !starts_with_kind(&self.buffer.buffer) {
+                    continue;
+                }
+                self.end_resync(self.buffer.start);
+            }
+            if !self.loaded_headers.is_empty() && self.loaded_headers.contains(&self.buffer.start) {
+                continue;
+            }
+
+            let watch = Stopwatch::start();
+            let registered = self.register_line();
+            watch.add_to(&mut self.stats.time_in_parse);
+            match registered {
+                Err(err) if self.options.mode == ParseMode::Lenient => {
+                    self.stats.errors += 1;
+                    self.resync = Some((err, self.buffer.offset));
+                }
+                Err(err) => {
+                    self.stats.errors += 1;
+                    return Err(err);
+                }
+                Ok(kind) => {
+                    self.stats.lines_parsed += 1;
+                    self.call_line_hook(kind);
+                    if kind == LineKind::Record {
+                        self.stats.records_parsed += 1;
+                        self.count_region_record()?;
+                    }
+                    self.report_progress(false);
+                    return Ok(Some(kind));
+                }
+            }
+        }
+    }
+
+    /// Passes the line in the buffer to the hook set with
+    /// `Reader::set_line_hook`.
+    pub(crate) fn call_line_hook(&mut self, kind: LineKind) {
+        if let Some(hook) = &mut self.on_line {
+            (hook.0)(kind, (self.buffer.offset - self.buffer.start) as usize);
+        }
+    }
+
+    /// Records the pending resync error as a diagnostic, with the bytes
+    /// skipped between the malformed line and `until`.
+    fn end_resync(&mut self, until: u64) {
+        if let Some((err, from)) = self.resync.take() {
+            let skipped = until.saturating_sub(from);
+            self.diagnostics.push(match skipped {
+                0 => err,
+                skipped => XRVErr::Resynced {
+                    skipped,
+                    err: Box::new(err),
+                },
+            });
+        }
+    }
+
+    /// Whether the line in the buffer ends within the region of `table`
+    /// ending at `end`. One that runs past it fails with
+    /// `XRVErr::RegionEndsMidLine`, or in `ParseMode::Lenient` is to be
+    /// dropped, with the error kept among the diagnostics.
+    pub(crate) fn within_region(&mut self, table: &str, end: u64) -> Result<bool, XRVErr> {
+        if self.buffer.offset <= end {
+            return Ok(true);
+        }
+        let err = XRVErr::RegionEndsMidLine {
+            table: table.to_owned(),
+            offset: end,
+        };
+        self.stats.errors += 1;
+        if self.options.mode == ParseMode::Strict {
+            return Err(err);
+        }
+        self.diagnostics.push(err);
+        Ok(false)
+    }
+
+    /// Counts the record in the buffer against its table region, failing
+    /// once `ParseOptions::max_records_per_table` is exceeded.
+    fn count_region_record(&mut self) -> Result<(), XRVErr> {
+        let max = match self.options.max_records_per_table {
+            None => return Ok(()),
+            Some(max) => max,
+        };
+        let start = self.buffer.start;
+        let count = match &mut self.region {
+            Some((end, count)) if start < *end => count,
+            region => {
+                let end = self
+                    .jumps
+                    .values()
+                    .map(|(seek, len)| (*seek as u64, (*seek + *len) as u64))
+                    .find(|(seek, end)| (*seek..*end).contains(&start));
+                match end {
+                    None => return Ok(()),
+                    Some((_, end)) => &mut region.insert((end, 0)).1,
+                }
+            }
+        };
+        *count += 1;
+        if *count > max {
+            let err = XRVErr::LimitExceeded(Limit::RecordsPerTable(max));
+            return Err(self.buffer.diagnose(1, err));
+        }
+        Ok(())
+    }
+
+    /// Converts the line in the buffer to an owned record, interning its
+    /// field names when `ParseOptions::intern_names` is set.
+    pub(crate) fn owned_record(&mut self) -> Result<OwnedRecordLine, XRVErr> {
+        let raw_line: RawLine = (&self.buffer).try_into()?;
+        let mut owned = decode_record(raw_line, self.options.encoding, &mut self.names)?;
+        owned.span = self.buffer.span();
+        if let Some((table, dictionary)) = self.dictionary_at(self.buffer.start) {
+            if let Err(err) = expand_record(dictionary, table, &mut owned) {
+                return Err(self.buffer.diagnose(1, err));
+            }
+        }
+        Ok(owned)
+    }
+
+    pub(crate) fn register_line(&mut self) -> Result<LineKind, XRVErr> {
+        let raw_line: RawLine = (&self.buffer).try_into()?;
+        if matches!(raw_line.kind, LineKind::Record | LineKind::Tombstone) {
+            let values = self.options.encoding == Encoding::Utf8Strict;
+            return match check_fields(&raw_line, values, &mut self.stats.scratch_spills) {
+                Err(err) => Err(self.buffer.diagnose(err.position().1, err)),
+                Ok(()) => Ok(raw_line.kind),
+            };
+        }
+        let line_field = match LineField::try_from(raw_line) {
+            Err(err) => return Err(self.buffer.diagnose(err.position().1, err)),
+            Ok(line_field) => line_field,
+        };
+        let kind = line_field.kind;
+        match kind {
+            LineKind::Table => {
+                let table = match TableLine::try_from(line_field) {
+                    Err(err) => return Err(self.buffer.diagnose(err.position().1, err)),
+                    Ok(table) => table,
+                };
+                if let Err(err) = check_bounds(table.name, table.pos, table.len, self.file_len) {
+                    return Err(self.buffer.diagnose(1, err));
+                }
+                if let Some(max) = self.options.max_tables {
+                    if self.tables.len() >= max && !self.tables.contains_key(table.name) {
+                        let err = XRVErr::LimitExceeded(Limit::Tables(max));
+                        return Err(self.buffer.diagnose(1, err));
+                    }
+                }
+                insert_unique(
+                    &mut self.tables,
+                    table.name,
+                    table.to_owned_in(&mut self.names),
+                    self.options.mode,
+                    &mut self.diagnostics,
+                    XRVErr::DuplicateTable,
+                )?;
+            }
+            LineKind::Dictionary => {
+                let (table, entries) = parse_dictionary(line_field);
+                insert_unique(
+                    &mut self.dictionaries,
+                    &table,
+                    entries,
+                    self.options.mode,
+                    &mut self.diagnostics,
+                    XRVErr::DuplicateDictionary,
+                )?;
+            }
+            LineKind::Style => {
+                let style: StyleLine = line_field.try_into()?;
+                insert_unique(
+                    &mut self.styles,
+                    style.id,
+                    style.to_owned_in(&mut self.names),
+                    self.options.mode,
+                    &mut self.diagnostics,
+                    XRVErr::DuplicateStyle,
+                )?;
+            }
+            _ => {}
+        }
+        Ok(kind)
+    }
+}
+
+/// Iterator over one table's records, returned by `Reader::records`.
+#[derive(Debug)]
+pub struct Records<'r> {
+    reader: &'r mut Reader,
+    table: String,
+    end: u64,
+    done: bool,
+}
+
+impl<'r> Iterator for Records<'r> {
+    type Item = Result<OwnedRecordLine, XRVErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.done {
+                return None;
+            }
+            if self.reader.buffer.offset >= self.end {
+                self.done = true;
+                self.reader.report_progress(true);
+                return None;
+            }
+            let record = match self.reader.parse_next() {
+                Err(err) => Err(err),
+                Ok(None) => return None,
+                // A lenient resync can carry past the end of the region.
+                Ok(Some(_)) if self.reader.buffer.start >= self.end => continue,
+                Ok(Some(kind)) => match self.reader.within_region(&self.table, self.end) {
+                    Err(err) => Err(err),
+                    Ok(false) => {
+                        self.done = true;
+                        self.reader.report_progress(true);
+                        return None;
+                    }
+                    Ok(true) if kind == LineKind::Tombstone => continue,
+                    Ok(true) => self.reader.owned_record(),
+                },
+            };
+            self.done = record.is_err();
+            return Some(record);
+        }
+    }
+}
+/// Fails with `XRVErr::SectionOutOfBounds` when the region of `len` bytes
+/// at `seek` runs past `file_len`; unknown lengths are checked on use.
+fn check_bounds(name: &str, seek: usize, len: usize, file_len: Option<u64>) -> Result<(), XRVErr> {
+    match file_len {
+        Some(file_len) if (seek as u64).saturating_add(len as u64) > file_len => {
+            Err(XRVErr::SectionOutOfBounds {
+                name: name.to_owned(),
+                seek,
+                len,
+                file_len,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Inserts `value` under `name` unless the name is already taken. A collision
+/// fails with `duplicate` in strict mode; lenient mode keeps the first entry
+/// and records the collision as a diagnostic.
+fn insert_unique<V>(
+    map: &mut OrderedMap<V>,
+    name: &str,
+    value: V,
+    mode: ParseMode,
+    diagnostics: &mut Vec<XRVErr>,
+    duplicate: fn(String) -> XRVErr,
+) -> Result<(), XRVErr> {
+    if !map.contains_key(name) {
+        map.insert(name.to_owned(), value);
+        return Ok(());
+    }
+    match mode {
+        ParseMode::Strict => Err(duplicate(name.to_owned())),
+        ParseMode::Lenient => {
+            diagnostics.push(duplicate(name.to_owned()));
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{
+        document::Document, line::OwnedField, shared::SharedReader, tokenizer::RawLineIter,
+        writer::Writer,
+    };
+
+    fn temp(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("xrave-test-reader-{}.xrv", name));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    const CITIES: [&str; 6] = ["Łódź", "Zürich café", "🦀", "a 🦀 b", "日本語 \"q\"", "é:ü"];
+
+    fn cities_fixture(name: &str) -> PathBuf {
+        let path = temp(name, b"");
+        let mut writer = Writer::new(File::create(&path).unwrap());
+        writer.add_table("cities", &[("name", "str")]).unwrap();
+        for (id, city) in CITIES.iter().enumerate() {
+            writer
+                .add_record("cities", &id.to_string(), &[("name", city)])
+                .unwrap();
+        }
+        writer.finish().unwrap();
+        path
+    }
+
+    const ENDINGS: [&str; 3] = ["\n", "\r\n", "\r"];
+
+    #[derive(Debug, PartialEq)]
+    enum Parsed {
+        Table(OwnedTableLine),
+        Record(OwnedRecordLine),
+    }
+
+    fn parse_lines(bytes: &[u8]) -> Vec<Parsed> {
+        RawLineIter::new(bytes)
+            .map(|line| {
+                let line_field = LineField::try_from(line.unwrap()).unwrap();
+                match line_field.kind {
+                    LineKind::Table => {
+                        Parsed::Table(TableLine::try_from(line_field).unwrap().to_owned())
+                    }
+                    _ => Parsed::Record(RecordLine::try_from(line_field).unwrap().to_owned()),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn line_endings_tokenize_alike() {
+        let lines = [
+            "t:1 name:cities pos:0 len:0 name:str note:str",
+            "r:1 name:\"Łódź\" note:\"a b\"",
+            "r:2 name:plain note:x",
+            "r:3 name:\"quoted\\r\" note:\"\"",
+        ];
+        let expected = parse_lines(lines.join("\n").as_bytes());
+        assert_eq!(expected.len(), 4);
+        match &expected[2] {
+            Parsed::Record(record) => assert_eq!(record.cols[1].value, "x"),
+            other => panic!("expected a record, got {:?}", other),
+        }
+        for ending in ENDINGS {
+            let unterminated = lines.join(ending);
+            let terminated = unterminated.clone() + ending;
+            assert_eq!(
+                parse_lines(unterminated.as_bytes()),
+                expected,
+                "{:?}",
+                ending
+            );
+            assert_eq!(parse_lines(terminated.as_bytes()), expected, "{:?}", ending);
+        }
+    }
+
+    /// A format 2 file with one table, named first in `header` and followed
+    /// by its columns, whose region holds `records`.
+    fn one_table(header: &str, records: &[&str], ending: &str) -> String {
+        let (table, cols) = header.split_once(' ').unwrap();
+        let records: String = records
+            .iter()
+            .map(|line| format!("{}{}", line, ending))
+            .collect();
+        let head = |pos: usize, len: usize| {
+            format!(
+                "v:xrave 2{e}j:jumps {table}:{pos:05}-{len:05}{e}\
+                 t:1 name:{table} pos:{pos:05} len:{len:05} {cols}{e}",
+                e = ending,
+                table = table,
+                cols = cols,
+                pos = pos,
+                len = len,
+            )
+        };
+        head(head(0, 0).len(), records.len()) + &records
+    }
+
+    fn cities_with_ending(ending: &str) -> String {
+        let records = ["r:1 name:\"Łódź\" note:\"a b\"", "r:2 name:plain note:x"];
+        one_table("cities name:str note:str", &records, ending)
+    }
+
+    #[test]
+    fn line_endings_read_alike() {
+        let read = |idx: usize, ending: &str| {
+            let path = temp(
+                &format!("endings-{}", idx),
+                cities_with_ending(ending).as_bytes(),
+            );
+            let mut reader = Reader::new(&path).unwrap();
+            reader.load_headers().unwrap();
+            let mut table = reader.table("cities").unwrap().clone();
+            (table.pos, table.len) = (0, 0);
+            let records: Vec<_> = reader
+                .records("cities")
+                .unwrap()
+                .map(|record| {
+                    let record = record.unwrap();
+                    (record.id, record.cols)
+                })
+                .collect();
+            (table, records)
+        };
+        let expected = read(0, "\n");
+        assert_eq!(expected.1.len(), 2);
+        assert_eq!(expected.1[1].1[1].value, "x");
+        for (idx, ending) in ENDINGS.iter().enumerate() {
+            assert_eq!(read(idx, ending), expected, "{:?}", ending);
+        }
+    }
+
+    fn long_line_fixture(name: &str, value_len: usize) -> PathBuf {
+        let text = format!(
+            "v:xrave 2\nj:jumps\nr:1 text:{}\nr:2 text:short\n",
+            "a".repeat(value_len)
+        );
+        temp(name, text.as_bytes())
+    }
+
+    #[test]
+    fn megabyte_line_under_the_limit_reads_in_both_modes() {
+        let path = long_line_fixture("long-line-under", 1_000_000);
+        for mode in [ParseMode::Strict, ParseMode::Lenient] {
+            let options = ParseOptions {
+                mode,
+                ..ParseOptions::default()
+            };
+            let mut reader = Reader::with_options(&path, options).unwrap();
+            let record = reader.next_record().unwrap().unwrap();
+            assert_eq!(record.cols()[0].value().len(), 1_000_000);
+            assert_eq!(reader.next_record().unwrap().unwrap().id(), "2");
+            assert!(reader.next_record().unwrap().is_none());
+            assert!(reader.diagnostics().is_empty());
+        }
+    }
+
+    #[test]
+    fn line_over_the_limit_fails_in_strict_mode_without_buffering_it() {
+        let path = long_line_fixture("long-line-strict", 1_000_000);
+        let max = 64 * 1024;
+        let options = ParseOptions {
+            max_line_len: max,
+            ..ParseOptions::default()
+        };
+        let mut reader = Reader::with_options(&path, options).unwrap();
+        match reader.next_record() {
+            Err(XRVErr::LineTooLong {
+                line,
+                len,
+                max: got,
+            }) => {
+                assert_eq!((line, len, got), (3, "r:1 text:".len() + 1_000_000, max));
+            }
+            other => panic!("expected LineTooLong, got {:?}", other.map(|_| ())),
+        }
+        assert!(reader.buffer.buffer.capacity() < 2 * max);
+    }
+
+    #[test]
+    fn line_over_the_limit_is_skipped_in_lenient_mode() {
+        let path = long_line_fixture("long-line-lenient", 1_000_000);
+        let options = ParseOptions {
+            mode: ParseMode::Lenient,
+            max_line_len: 64 * 1024,
+            ..ParseOptions::default()
+        };
+        let mut reader = Reader::with_options(&path, options).unwrap();
+        let record = reader.next_record().unwrap().unwrap();
+        assert_eq!((record.id(), record.cols()[0].value()), ("2", "short"));
+        assert!(reader.next_record().unwrap().is_none());
+        assert!(matches!(
+            reader.diagnostics(),
+            [XRVErr::LineTooLong { line: 3, .. }]
+        ));
+    }
+
+    const DUPLICATE_POLICIES: [DuplicateFields; 4] = [
+        DuplicateFields::KeepAll,
+        DuplicateFields::KeepFirst,
+        DuplicateFields::KeepLast,
+        DuplicateFields::Error,
+    ];
+
+    fn open_with(
+        path: &Path,
+        mode: ParseMode,
+        duplicate_fields: DuplicateFields,
+    ) -> Result<Reader, XRVErr> {
+        let options = ParseOptions {
+            mode,
+            duplicate_fields,
+            ..ParseOptions::default()
+        };
+        Reader::with_options(path, options)
+    }
+
+    fn cause(err: &XRVErr) -> &XRVErr {
+        match err {
+            XRVErr::AtLine { err, .. } => err,
+            err => err,
+        }
+    }
+
+    #[test]
+    fn duplicate_table_and_style_names_under_every_policy() {
+        let path = temp(
+            "duplicate-headers",
+            b"v:xrave 2\nj:jumps\n\
+              t:1 name:users pos:0 len:0 email:str\nt:2 name:users pos:0 len:0 age:i32\n\
+              s:bold weight:700\ns:bold weight:400\n",
+        );
+        for policy in DUPLICATE_POLICIES {
+            let mut reader = open_with(&path, ParseMode::Strict, policy).unwrap();
+            let err = reader.load_headers().unwrap_err();
+            assert!(
+                matches!(cause(&err), XRVErr::DuplicateTable(name) if name == "users"),
+                "{:?}",
+                err
+            );
+
+            let mut reader = open_with(&path, ParseMode::Lenient, policy).unwrap();
+            reader.load_headers().unwrap();
+            assert_eq!(reader.tables().len(), 1);
+            assert_eq!(&*reader.table("users").unwrap().cols[0].name, "email");
+            assert_eq!(reader.style("bold").unwrap().get("weight"), Some("700"));
+            let diagnostics: Vec<&XRVErr> = reader.diagnostics().iter().map(cause).collect();
+            assert!(
+                matches!(
+                    diagnostics[..],
+                    [XRVErr::DuplicateTable(table), XRVErr::DuplicateStyle(style)]
+                        if table == "users" && style == "bold"
+                ),
+                "{:?}",
+                diagnostics
+            );
+        }
+    }
+
+    #[test]
+    fn duplicate_jump_names_under_every_policy() {
+        let path = temp(
+            "duplicate-jumps",
+            b"v:xrave 2\nj:jumps users:40-0 users:40-0\nr:1 a:x\n",
+        );
+        for policy in DUPLICATE_POLICIES {
+            let err = open_with(&path, ParseMode::Strict, policy).unwrap_err();
+            assert!(
+                matches!(cause(&err), XRVErr::DuplicateJump(name) if name == "users"),
+                "{:?}",
+                err
+            );
+
+            let reader = open_with(&path, ParseMode::Lenient, policy).unwrap();
+            assert_eq!(reader.jumps().count(), 1);
+            let diagnostics: Vec<&XRVErr> = reader.diagnostics().iter().map(cause).collect();
+            assert!(
+                matches!(diagnostics[..], [XRVErr::DuplicateJump(name)] if name == "users"),
+                "{:?}",
+                diagnostics
+            );
+        }
+    }
+
+    #[test]
+    fn duplicate_fields_follow_the_policy_in_both_modes() {
+        let path = temp(
+            "duplicate-fields",
+            b"v:xrave 2\nj:jumps\nr:1 a:x b:y a:z\nr:2 a:w\n",
+        );
+        let read = |mode: ParseMode, policy: DuplicateFields| {
+            let mut reader = open_with(&path, mode, policy).unwrap();
+            let mut records = Vec::new();
+            while let Some(record) = reader.next_record().transpose() {
+                records.push(record.map(|record| {
+                    let cols: Vec<String> = record
+                        .cols()
+                        .iter()
+                        .map(|field| format!("{}:{}", field.name(), field.value()))
+                        .collect();
+                    format!("{} {}", record.id(), cols.join(" "))
+                }));
+            }
+            (records, reader)
+        };
+        let kept = |mode, policy| -> Vec<String> {
+            read(mode, policy)
+                .0
+                .into_iter()
+                .map(Result::unwrap)
+                .collect()
+        };
+
+        for mode in [ParseMode::Strict, ParseMode::Lenient] {
+            let all = kept(mode, DuplicateFields::KeepAll);
+            assert_eq!(all, ["1 a:x b:y a:z", "2 a:w"]);
+            assert_eq!(
+                kept(mode, DuplicateFields::KeepFirst),
+                ["1 a:x b:y", "2 a:w"]
+            );
+            assert_eq!(
+                kept(mode, DuplicateFields::KeepLast),
+                ["1 b:y a:z", "2 a:w"]
+            );
+        }
+
+        let (records, _) = read(ParseMode::Strict, DuplicateFields::Error);
+        match &records[..] {
+            [Err(err), Ok(next)] => {
+                assert!(
+                    matches!(cause(err), XRVErr::DuplicateField { line: 3, name } if name == "a"),
+                    "{:?}",
+                    err
+                );
+                assert_eq!(next, "2 a:w");
+            }
+            other => panic!("expected an error then record 2, got {:?}", other),
+        }
+
+        let (records, reader) = read(ParseMode::Lenient, DuplicateFields::Error);
+        assert_eq!(
+            records.into_iter().map(Result::unwrap).collect::<Vec<_>>(),
+            ["2 a:w"]
+        );
+        let diagnostics: Vec<&XRVErr> = reader.diagnostics().iter().map(cause).collect();
+        assert!(
+            matches!(diagnostics[..], [XRVErr::DuplicateField { line: 3, name }] if name == "a"),
+            "{:?}",
+            diagnostics
+        );
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzipped_copy_reads_like_the_plain_file() {
+        use flate2::{write::GzEncoder, Compression};
+
+        let plain = temp("gzip-plain", b"");
+        let mut writer = Writer::new(File::create(&plain).unwrap());
+        writer.add_table("users", &[("name", "str")]).unwrap();
+        writer.add_table("events", &[("kind", "str")]).unwrap();
+        for id in 0..2_000 {
+            let id = id.to_string();
+            writer
+                .add_record("users", &id, &[("name", "Łódź 🦀")])
+                .unwrap();
+            writer
+                .add_record("events", &id, &[("kind", "click")])
+                .unwrap();
+        }
+        writer.finish().unwrap();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&std::fs::read(&plain).unwrap()).unwrap();
+        let gzipped = temp("gzip-copy.gz", &encoder.finish().unwrap());
+
+        let read = |path: &Path| {
+            let mut reader = Reader::new(path).unwrap();
+            let mut records = Vec::new();
+            for table in ["users", "events"] {
+                for record in reader.records(table).unwrap() {
+                    let record = record.unwrap();
+                    records.push((table, record.id, record.cols));
+                }
+            }
+            records
+        };
+        let expected = read(&plain);
+        assert_eq!(expected.len(), 4_000);
+        assert_eq!(read(&gzipped), expected);
+        assert!(matches!(
+            Reader::new(&gzipped).unwrap().source.get_ref(),
+            Source::Gzip(_)
+        ));
+        let reader = Reader::from_file(File::open(&gzipped).unwrap()).unwrap();
+        assert!(matches!(reader.source.get_ref(), Source::Gzip(_)));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn corrupt_gzip_input_fails_to_read() {
+        let path = temp("gzip-corrupt", &[0x1F, 0x8B, 0x08, 0x00, 0xFF, 0xFF]);
+        assert!(Reader::new(&path).is_err());
+    }
+
+    #[test]
+    fn parallel_tables_match_sequential_reads() {
+        const TABLES: [&str; 3] = ["users", "events", "orders"];
+        let path = temp("parallel", b"");
+        let mut writer = Writer::new(File::create(&path).unwrap());
+        for table in TABLES {
+            writer
+                .add_table(table, &[("value", "str"), ("n", "i64")])
+                .unwrap();
+        }
+        for id in 0..50_000 {
+            for (idx, table) in TABLES.iter().enumerate() {
+                let value = format!("{} {}", table, id);
+                let n = (id * (idx + 1)).to_string();
+                writer
+                    .add_record(table, &id.to_string(), &[("value", &value), ("n", &n)])
+                    .unwrap();
+            }
+        }
+        writer.finish().unwrap();
+
+        let parallel = Reader::new(&path)
+            .unwrap()
+            .parse_tables_parallel(&TABLES)
+            .unwrap();
+        assert_eq!(parallel.len(), TABLES.len());
+        let mut reader = Reader::new(&path).unwrap();
+        for table in TABLES {
+            let sequential: Vec<OwnedRecordLine> = reader
+                .records(table)
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(sequential.len(), 50_000);
+            assert_eq!(parallel[table], sequential, "{}", table);
+        }
+    }
+
+    #[test]
+    fn parallel_failure_names_its_table() {
+        let header = "j:jumps a:72-8 b:80-11\nt:1 name:a pos:72 len:8\nt:2 name:b pos:80 len:11\n";
+        let path = temp(
+            "parallel-failure",
+            format!("{}r:1 x:1\nr:2 x:\"bad\n", header).as_bytes(),
+        );
+        let err = Reader::new(&path)
+            .unwrap()
+            .parse_tables_parallel(&["a", "b"])
+            .unwrap_err();
+        assert!(
+            matches!(&err, XRVErr::InTable { table, .. } if table == "b"),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn parallel_tables_read_as_sequential_ones_in_lenient_mode_with_a_limit() {
+        let path = temp("parallel-lenient", b"");
+        let mut writer = Writer::new(File::create(&path).unwrap());
+        writer.add_table("a", &[("v", "str")]).unwrap();
+        writer.add_table("b", &[("v", "str")]).unwrap();
+        for id in ["1", "2", "3", "4"] {
+            writer.add_record("a", id, &[("v", "x y")]).unwrap();
+            writer.add_record("b", id, &[("v", "z")]).unwrap();
+        }
+        writer.finish().unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, text.replace("r:2 v:\"x y\"", "r:2 v:\"x y ")).unwrap();
+
+        let options = ParseOptions {
+            mode: ParseMode::Lenient,
+            max_records_per_table: Some(3),
+            ..ParseOptions::default()
+        };
+        let reader = Reader::with_options(&path, options.clone()).unwrap();
+        let parallel = reader.parse_tables_parallel(&["a"]).unwrap();
+        let mut reader = Reader::with_options(&path, options.clone()).unwrap();
+        let sequential: Vec<OwnedRecordLine> = reader
+            .records("a")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(ids(&sequential), ["1", "3", "4"]);
+        assert_eq!(parallel["a"], sequential);
+
+        let mut reader = Reader::with_options(&path, options).unwrap();
+        let sequential = reader
+            .records("b")
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_err();
+        assert!(matches!(
+            sequential,
+            XRVErr::AtLine { ref err, .. }
+                if matches!(**err, XRVErr::LimitExceeded(Limit::RecordsPerTable(3)))
+        ));
+        match reader.parse_tables_parallel(&["a", "b"]).unwrap_err() {
+            XRVErr::InTable { table, err } => {
+                assert_eq!(table, "b");
+                assert_eq!(err.to_string(), sequential.to_string());
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn owned_records_outlive_the_lines_they_came_from() {
+        let path = temp("owned", b"");
+        let mut writer = Writer::new(File::create(&path).unwrap());
+        writer.add_table("events", &[("name", "str")]).unwrap();
+        for id in 0..10_000 {
+            let name = format!("event {}", id);
+            writer
+                .add_record("events", &id.to_string(), &[("name", &name)])
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut reader = Reader::new(&path).unwrap();
+        let mut kept: Vec<OwnedRecordLine> = Vec::new();
+        while kept.len() < 10 {
+            kept.push(reader.next_record().unwrap().unwrap().to_owned());
+        }
+        let mut later = 0;
+        while let Some(record) = reader.next_record().unwrap() {
+            assert_ne!(record.cols()[0].value(), kept[0].cols[0].value);
+            later += 1;
+        }
+        assert_eq!(later, 9_990);
+        reader.load_headers().unwrap();
+        let table = reader.table("events").unwrap().clone();
+        drop(reader);
+
+        for (id, record) in kept.iter().enumerate() {
+            assert_eq!(record.id, id.to_string());
+            assert_eq!(record.cols[0].value, format!("event {}", id));
+            let borrowed = RecordLine::from(record);
+            assert_eq!(borrowed.to_owned().cols, record.cols);
+        }
+        assert_eq!(TableLine::from(&table).to_owned(), table);
+        assert_eq!(table.cols[0].value, "str");
+    }
+
+    fn batch_fixture(name: &str) -> PathBuf {
+        let records = ["r:1 a:x", "r:2 a:x", "r:3 a:\"bad", "r:4 a:x", "r:5 a:x"];
+        temp(name, one_table("events a:str", &records, "\n").as_bytes())
+    }
+
+    fn ids(records: &[OwnedRecordLine]) -> Vec<&str> {
+        records.iter().map(|record| record.id.as_str()).collect()
+    }
+
+    #[test]
+    fn empty_batch_reads_nothing_and_leaves_the_position() {
+        let mut reader = Reader::new(batch_fixture("batch-empty")).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(reader.read_batch("events", &mut out, 0).unwrap(), 0);
+        assert!(out.is_empty());
+        assert_eq!(reader.read_batch("events", &mut out, 2).unwrap(), 2);
+        assert_eq!(reader.read_batch("events", &mut out, 0).unwrap(), 0);
+        assert_eq!(ids(&out), ["1", "2"]);
+    }
+
+    #[test]
+    fn error_mid_batch_keeps_the_records_before_it() {
+        let mut reader = Reader::new(batch_fixture("batch-error")).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(reader.read_batch("events", &mut out, 1).unwrap(), 1);
+        let err = reader.read_batch("events", &mut out, 10).unwrap_err();
+        assert!(
+            matches!(&err, XRVErr::AtLine { snippet, .. } if snippet.starts_with("r:3")),
+            "{:?}",
+            err
+        );
+        // Record 2 came before the bad line and stays; the batch ends there.
+        assert_eq!(ids(&out), ["1", "2"]);
+
+        // The bad line is consumed, so the next batch carries on after it.
+        assert_eq!(reader.read_batch("events", &mut out, 10).unwrap(), 2);
+        assert_eq!(reader.read_batch("events", &mut out, 10).unwrap(), 0);
+        assert_eq!(ids(&out), ["1", "2", "4", "5"]);
+    }
+
+    #[test]
+    fn lenient_batch_skips_the_bad_line() {
+        let options = ParseOptions {
+            mode: ParseMode::Lenient,
+            ..ParseOptions::default()
+        };
+        let mut reader = Reader::with_options(batch_fixture("batch-lenient"), options).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(reader.read_batch("events", &mut out, 10).unwrap(), 4);
+        assert_eq!(ids(&out), ["1", "2", "4", "5"]);
+        assert_eq!(reader.diagnostics().len(), 1);
+    }
+
+    #[test]
+    fn lenient_batch_stops_at_the_region_end_after_a_broken_last_record() {
+        let path = temp("batch-broken-last", b"");
+        let mut writer = Writer::new(File::create(&path).unwrap());
+        writer.add_table("events", &[("a", "str")]).unwrap();
+        writer.add_table("users", &[("a", "str")]).unwrap();
+        writer.add_record("events", "1", &[("a", "x")]).unwrap();
+        writer.add_record("events", "2", &[("a", "x y")]).unwrap();
+        writer.add_record("users", "u1", &[("a", "x")]).unwrap();
+        writer.finish().unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, text.replace("r:2 a:\"x y\"", "r:2 a:\"x y ")).unwrap();
+
+        let options = ParseOptions {
+            mode: ParseMode::Lenient,
+            ..ParseOptions::default()
+        };
+        let mut reader = Reader::with_options(&path, options).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(reader.read_batch("events", &mut out, 10).unwrap(), 1);
+        assert_eq!(reader.read_batch("events", &mut out, 10).unwrap(), 0);
+        assert_eq!(ids(&out), ["1"]);
+        assert_eq!(reader.diagnostics().len(), 1);
+    }
+
+    #[test]
+    fn every_entry_point_reads_the_same_records() {
+        let path = temp("entry-points", b"");
+        let mut writer = Writer::new(File::create(&path).unwrap());
+        writer.add_table("users", &[("name", "str")]).unwrap();
+        writer.add_table("events", &[("kind", "str")]).unwrap();
+        for id in 0..300 {
+            let id = id.to_string();
+            writer
+                .add_record("users", &id, &[("name", &format!("user {}", id))])
+                .unwrap();
+            writer
+                .add_record("events", &id, &[("kind", "click \"x\"")])
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut reader = Reader::new(&path).unwrap();
+        let users: Vec<OwnedRecordLine> = reader
+            .records("users")
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(users.len(), 300);
+
+        let mut reader = Reader::new(&path).unwrap();
+        let mut streamed = Vec::new();
+        while let Some(record) = reader.next_record().unwrap() {
+            streamed.push(record.to_owned());
+        }
+        let values = |records: &[OwnedRecordLine]| -> Vec<(String, Vec<OwnedField>)> {
+            records
+                .iter()
+                .map(|record| (record.id.clone(), record.cols.clone()))
+                .collect()
+        };
+        assert_eq!(values(&streamed[..300]), values(&users));
+
+        reader.seek_to("users").unwrap();
+        let first = reader.next_record().unwrap().unwrap().to_owned();
+        assert_eq!(values(&[first]), values(&users[..1]));
+        assert_eq!(reader.record_at(users[123].span).unwrap(), users[123]);
+
+        let mut batched = Vec::new();
+        while reader.read_batch("users", &mut batched, 64).unwrap() > 0 {}
+        assert_eq!(batched, users);
+
+        let parallel = reader.parse_tables_parallel(&["users"]).unwrap();
+        assert_eq!(parallel["users"], users);
+
+        let shared = SharedReader::new(&path).unwrap();
+        assert_eq!(
+            shared
+                .record_by_primary_key("users", "42")
+                .unwrap()
+                .unwrap(),
+            users[42]
+        );
+
+        let document = Document::open(&path).unwrap();
+        let table = document.table("users").unwrap();
+        let loaded: Vec<OwnedRecordLine> = table
+            .records()
+            .map(|record| record.unwrap().into_owned())
+            .collect();
+        assert_eq!(values(&loaded), values(&users));
+    }
+
+    /// Lines of one to 24 fields drawn from a fixed seed: quoted values
+    /// with escapes, empty values and plain ones, under repeated names.
+    fn random_records(count: usize) -> String {
+        let mut seed = 7u64;
+        let mut next = |below: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) % below
+        };
+        let mut body = String::new();
+        for id in 0..count {
+            body.push_str(&format!("r:{}", id));
+            for _ in 0..1 + next(24) {
+                let value = match next(4) {
+                    0 => format!("\"q{}\\\"x\\ty\"", next(100)),
+                    1 => String::new(),
+                    _ => format!("v{}", next(1000)),
+                };
+                body.push_str(&format!(" f{}:{}", next(30), value));
+            }
+            body.push('\n');
+        }
+        body
+    }
+
+    #[test]
+    fn owned_and_borrowed_records_decode_alike() {
+        let body = random_records(2_000);
+        let head = |len: usize| format!("v:xrave 2\nj:jumps t:{:05}-{:07}\n", 34, len);
+        assert_eq!(head(0).len(), 34);
+        let path = temp("decode-alike", (head(body.len()) + &body).as_bytes());
+
+        for encoding in [Encoding::Utf8Strict, Encoding::Utf8Lossy] {
+            for duplicate_fields in [
+                DuplicateFields::KeepAll,
+                DuplicateFields::KeepFirst,
+                DuplicateFields::KeepLast,
+            ] {
+                let options = ParseOptions {
+                    encoding,
+                    duplicate_fields,
+                    ..ParseOptions::default()
+                };
+                let mut reader = Reader::with_options(&path, options.clone()).unwrap();
+                let mut borrowed = Vec::new();
+                while let Some(record) = reader.next_record().unwrap() {
+                    let record = record.to_owned();
+                    borrowed.push((record.id, record.cols));
+                }
+                let mut reader = Reader::with_options(&path, options).unwrap();
+                let owned: Vec<_> = reader
+                    .records("t")
+                    .unwrap()
+                    .map(|record| {
+                        let record = record.unwrap();
+                        (record.id, record.cols)
+                    })
+                    .collect();
+                assert_eq!(owned.len(), 2_000);
+                assert_eq!(owned, borrowed, "{:?} {:?}", encoding, duplicate_fields);
+                assert_eq!(reader.stats().scratch_spills, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn only_long_lines_checked_for_duplicates_spill() {
+        let options = ParseOptions {
+            duplicate_fields: DuplicateFields::Error,
+            ..ParseOptions::default()
+        };
+        let path = temp("spill-duplicate", b"j:jumps\nr:1 a:x b:y a:z\n");
+        let mut reader = Reader::with_options(&path, options.clone()).unwrap();
+        assert!(reader.next_record().is_err());
+        assert_eq!(reader.stats().scratch_spills, 0);
+
+        let fields: String = (0..20).map(|idx| format!(" f{}:x", idx)).collect();
+        let path = temp(
+            "spill-long",
+            format!("j:jumps\nr:1{}\nr:2 a:x\n", fields).as_bytes(),
+        );
+        let mut reader = Reader::with_options(&path, options).unwrap();
+        while reader.next_record().unwrap().is_some() {}
+        assert_eq!(reader.stats().scratch_spills, 1);
+    }
+
+    #[test]
+    #[ignore = "timing run; use `cargo test --release -- --ignored --nocapture`"]
+    fn record_decoding_timing() {
+        let path = temp("decode-timing", b"");
+        let mut writer = Writer::new(File::create(&path).unwrap());
+        writer
+            .add_table("events", &[("name", "str"), ("ts", "i64"), ("tag", "str")])
+            .unwrap();
+        for id in 0..1_000_000 {
+            let ts = (id * 37).to_string();
+            writer
+                .add_record(
+                    "events",
+                    &id.to_string(),
+                    &[("name", "click"), ("ts", &ts), ("tag", "a")],
+                )
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut reader = Reader::new(&path).unwrap();
+        let started = std::time::Instant::now();
+        let count = reader.records("events").unwrap().count();
+        assert_eq!(count, 1_000_000);
+        println!(
+            "{} records in {:?}, {:?}",
+            count,
+            started.elapsed(),
+            reader.stats()
+        );
+    }
+
+    #[test]
+    fn multibyte_values_round_trip_through_records() {
+        let path = cities_fixture("utf8-records");
+        let mut reader = Reader::new(&path).unwrap();
+        let values: Vec<String> = reader
+            .records("cities")
+            .unwrap()
+            .map(|record| record.unwrap().cols[0].value.clone())
+            .collect();
+        assert_eq!(values, CITIES);
+    }
+
+    #[test]
+    fn multibyte_values_round_trip_through_next_record() {
+        let path = cities_fixture("utf8-next");
+        let mut reader = Reader::new(&path).unwrap();
+        let mut values = Vec::new();
+        while let Some(record) = reader.next_record().unwrap() {
+            values.push(record.cols()[0].value_unescaped().into_owned());
+        }
+        assert_eq!(values, CITIES);
+    }
+
+    #[test]
+    fn unquoted_multibyte_values_and_names_read_as_written() {
+        let path = temp(
+            "utf8-unquoted",
+            "v:xrave 2\nj:jumps\nr:1 miasto:Łódź emoji:🦀🦀 quoted:\"🦀 x\"\n".as_bytes(),
+        );
+        let mut reader = Reader::new(&path).unwrap();
+        let record = reader.next_record().unwrap().unwrap();
+        let cols: Vec<_> = record
+            .cols()
+            .iter()
+            .map(|field| (field.name(), field.value_unescaped()))
+            .collect();
+        assert_eq!(
+            cols,
+            [
+                ("miasto", "Łódź".into()),
+                ("emoji", "🦀🦀".into()),
+                ("quoted", "🦀 x".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn byte_order_mark_is_skipped() {
+        let path = temp(
+            "utf8-bom",
+            "\u{FEFF}v:xrave 2\nj:jumps\nr:1 name:Łódź\n".as_bytes(),
+        );
+        let mut reader = Reader::new(&path).unwrap();
+        let record = reader.next_record().unwrap().unwrap();
+        assert_eq!(record.id(), "1");
+        assert_eq!(record.cols()[0].value(), "Łódź");
+    }
+
+    #[test]
+    fn invalid_byte_after_multibyte_chars_reports_its_column() {
+        let path = temp(
+            "utf8-invalid",
+            b"v:xrave 2\nj:jumps\nr:1 name:\xC5\x81\xC3\xB3\xFFd\n",
+        );
+        let mut reader = Reader::new(&path).unwrap();
+        match reader.next_record().unwrap_err() {
+            XRVErr::AtLine { line, col, err, .. } => {
+                assert_eq!((line, col), (3, 14));
+                assert!(
+                    matches!(*err, XRVErr::CantParseFieldStrValue { col: 14, .. }),
+                    "{:?}",
+                    err
+                );
+            }
+            other => panic!("expected AtLine, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn encodings_read_bad_bytes_as_documented() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Encoding, ParseOptions, Reader, XRVErr};
+
+        let path = std::env::temp_dir().join("xrave-test-encoding.xrv");
+        let mut bytes = b"j:jumps notes:58-29\nt:1 name:notes pos:58 len:29 text:str\n".to_vec();
+        bytes.extend_from_slice(b"r:1 text:caf\xE9\nr:2 text:plain\n");
+        std::fs::write(&path, &bytes)?;
+        let open = |encoding| {
+            let options = ParseOptions {
+                encoding,
+                ..ParseOptions::default()
+            };
+            Reader::with_options(path.to_string_lossy().into_owned(), options)
+        };
+
+        let mut strict = open(Encoding::Utf8Strict)?;
+        match strict.records("notes")?.next().unwrap() {
+            Err(XRVErr::AtLine { err, .. }) => {
+                assert!(matches!(
+                    *err,
+                    XRVErr::CantParseFieldStrValue { col: 13, .. }
+                ))
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let mut lossy = open(Encoding::Utf8Lossy)?;
+        let records = lossy.records("notes")?.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(records[0].cols[0].value, "caf\u{FFFD}");
+        assert_eq!(records[1].cols[0].value, "plain");
+
+        let mut raw = open(Encoding::Bytes)?;
+        let record = raw.next_record_bytes()?.unwrap();
+        assert_eq!(record.get("text"), Some(&b"caf\xE9"[..]));
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_field_policies_keep_what_they_say() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{DuplicateFields, ParseOptions, Reader, XRVErr};
+
+        let path = std::env::temp_dir().join("xrave-test-duplicates.xrv");
+        std::fs::write(&path, "j:jumps\nr:1 a:x b:1 a:y a:z\n")?;
+        let values = |duplicate_fields| -> Result<Vec<String>, XRVErr> {
+            let options = ParseOptions {
+                duplicate_fields,
+                ..ParseOptions::default()
+            };
+            let mut reader = Reader::with_options(path.to_string_lossy().into_owned(), options)?;
+            let record = reader.next_record()?.unwrap();
+            Ok(record
+                .cols()
+                .iter()
+                .map(|f| format!("{}:{}", f.name(), f.value()))
+                .collect())
+        };
+
+        assert_eq!(
+            values(DuplicateFields::KeepAll)?,
+            ["a:x", "b:1", "a:y", "a:z"]
+        );
+        assert_eq!(values(DuplicateFields::KeepFirst)?, ["a:x", "b:1"]);
+        assert_eq!(values(DuplicateFields::KeepLast)?, ["b:1", "a:z"]);
+        match values(DuplicateFields::Error) {
+            Err(XRVErr::AtLine { err, .. }) => assert!(matches!(
+                *err,
+                XRVErr::DuplicateField { line: 2, ref name } if name == "a"
+            )),
+            other => panic!("unexpected {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn limits_fail_on_the_offending_line() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Limit, ParseOptions, Reader, XRVErr};
+
+        let path = std::env::temp_dir().join("xrave-test-limits.xrv");
+        let open = |text: String, options: ParseOptions| -> Result<Vec<String>, XRVErr> {
+            std::fs::write(&path, text).unwrap();
+            let mut reader = Reader::with_options(path.to_string_lossy().into_owned(), options)?;
+            reader.load_headers()?;
+            let mut ids = Vec::new();
+            while let Some(record) = reader.next_record()? {
+                ids.push(record.id().to_owned());
+            }
+            Ok(ids)
+        };
+        let limit = |result: Result<Vec<String>, XRVErr>| match result {
+            Err(XRVErr::AtLine { line, err, .. }) => match *err {
+                XRVErr::LimitExceeded(limit) => Some((line, limit)),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        let header =
+            "j:jumps a:74-37 b:111-0\nt:1 name:a pos:74 len:37\nt:2 name:b pos:111 len:0\n";
+        let records = format!("{}r:1 x:1\nr:2 x:\"a long value\"\nr:3 x:1\n", header);
+        assert_eq!(
+            open(records.clone(), ParseOptions::default())?,
+            ["1", "2", "3"]
+        );
+
+        let fields = format!("{}r:1{}\n", header, " x:1".repeat(10));
+        let options = ParseOptions {
+            max_fields_per_line: 8,
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            limit(open(fields, options)),
+            Some((4, Limit::FieldsPerLine(8)))
+        );
+
+        let options = ParseOptions {
+            max_value_len: 8,
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            limit(open(records.clone(), options)),
+            Some((5, Limit::ValueLen(8)))
+        );
+
+        let options = ParseOptions {
+            max_tables: Some(1),
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            limit(open(records.clone(), options)),
+            Some((1, Limit::Tables(1)))
+        );
+
+        let options = ParseOptions {
+            max_records_per_table: Some(2),
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            limit(open(records, options)),
+            Some((6, Limit::RecordsPerTable(2)))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn space_after_colon_is_skipped_only_when_allowed() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{ParseOptions, Reader, XRVErr};
+
+        let path = std::env::temp_dir().join("xrave-test-space-after-colon.xrv");
+        std::fs::write(&path, "j:jumps\nr:1 name: bob  age:  \"42\" note:\"\"\n")?;
+        let options = ParseOptions {
+            allow_space_after_colon: true,
+            ..ParseOptions::default()
+        };
+        let mut reader = Reader::with_options(path.to_string_lossy().into_owned(), options)?;
+        let record = reader.next_record()?.unwrap();
+        let fields: Vec<_> = record
+            .cols()
+            .iter()
+            .map(|f| (f.name(), f.value()))
+            .collect();
+        assert_eq!(fields, [("name", "bob"), ("age", "42"), ("note", "")]);
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        match reader.next_record() {
+            Err(XRVErr::AtLine { line, col, .. }) => assert_eq!((line, col), (2, 14)),
+            other => panic!("unexpected {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn record_cache_counts_hits_and_misses() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{ParseOptions, Reader, Writer};
+
+        let path = std::env::temp_dir().join("xrave-test-record-cache.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("users", &[("name", "str")])?;
+        for id in 0..10 {
+            writer.add_record("users", &id.to_string(), &[("name", "anna")])?;
+        }
+        writer.finish()?;
+
+        let options = ParseOptions {
+            record_cache_entries: 4,
+            ..ParseOptions::default()
+        };
+        let mut reader = Reader::with_options(&path, options)?;
+        let spans: Vec<_> = reader
+            .records("users")?
+            .map(|record| record.unwrap().span)
+            .collect();
+        for span in &spans[..4] {
+            reader.record_at(*span)?;
+        }
+        for span in spans[..4].iter().rev() {
+            reader.record_at(*span)?;
+        }
+        let stats = reader.stats();
+        assert_eq!((stats.record_cache_hits, stats.record_cache_misses), (4, 4));
+
+        reader.record_at(spans[9])?;
+        reader.record_at(spans[3])?;
+        assert_eq!(reader.stats().record_cache_misses, 6);
+
+        reader.update_record_field(spans[0].offset, "name", "bob")?;
+        let record = reader.record_at(spans[0])?;
+        assert_eq!(record.get_all("name").collect::<Vec<_>>(), ["bob"]);
+        assert_eq!(reader.stats().record_cache_misses, 7);
+        Ok(())
+    }
+
+    #[test]
+    fn from_file_reads_and_open_errors_name_the_file() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Reader, Writer};
+        use std::fs::File;
+
+        let path = std::env::temp_dir().join("xrave-test-from-file.xrv");
+        let mut writer = Writer::new(File::create(&path)?);
+        writer.add_table("users", &[("name", "str")])?;
+        writer.add_record("users", "1", &[("name", "Anna")])?;
+        writer.finish()?;
+
+        let mut reader = Reader::from_file(File::open(&path)?)?;
+        assert_eq!(reader.records("users")?.count(), 1);
+
+        let missing = std::env::temp_dir().join("xrave-test-missing.xrv");
+        let err = Reader::new(&missing).unwrap_err();
+        assert!(err.to_string().contains("xrave-test-missing.xrv"));
+
+        #[cfg(unix)]
+        {
+            use std::ffi::OsStr;
+            use std::os::unix::ffi::OsStrExt;
+
+            let odd = std::env::temp_dir().join(OsStr::from_bytes(b"xrave-test-\xFF.xrv"));
+            std::fs::copy(&path, &odd)?;
+            let mut reader = Reader::new(&odd)?;
+            assert_eq!(reader.records("users")?.count(), 1);
+            std::fs::remove_file(&odd)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn unseekable_input_reads_forward_only() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Reader, Writer, XRVErr};
+        use std::io::{Cursor, Read};
+
+        /// Hides `Seek`, like a pipe.
+        struct Pipe(Cursor<Vec<u8>>);
+        impl Read for Pipe {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.0.read(buf)
+            }
+        }
+
+        let mut writer = Writer::new(Vec::new());
+        writer.add_table("users", &[("name", "str")])?;
+        writer.add_table("events", &[("kind", "str")])?;
+        for id in 0..20 {
+            writer.add_record("users", &id.to_string(), &[("name", "Anna")])?;
+            writer.add_record("events", &id.to_string(), &[("kind", "login")])?;
+        }
+        let bytes = writer.finish()?;
+        let pipe = || Pipe(Cursor::new(bytes.clone()));
+
+        let path = std::env::temp_dir().join("xrave-test-unseekable.xrv");
+        std::fs::write(&path, &bytes)?;
+        let mut file = Reader::new(&path)?;
+        let mut all = Vec::new();
+        while let Some(record) = file.next_record()? {
+            all.push(record.to_owned());
+        }
+        let mut reader = Reader::from_unseekable(pipe())?;
+        let mut streamed = Vec::new();
+        while let Some(record) = reader.next_record()? {
+            streamed.push(record.to_owned());
+        }
+        assert_eq!((streamed.len(), &streamed), (40, &all));
+        assert_eq!(reader.tables().len(), 2);
+
+        let mut reader = Reader::from_unseekable(pipe())?;
+        let users = reader.records("users")?.collect::<Result<Vec<_>, _>>()?;
+        let ids: Vec<_> = users.iter().map(|record| record.id.as_str()).collect();
+        assert_eq!(ids, (0..20).map(|id| id.to_string()).collect::<Vec<_>>());
+        assert_eq!(reader.table("users").unwrap().cols.len(), 1);
+        assert_eq!(reader.records("events")?.count(), 20);
+        assert!(matches!(
+            reader.records("users"),
+            Err(XRVErr::SourceNotSeekable)
+        ));
+        assert!(matches!(
+            reader.cursor("events"),
+            Err(XRVErr::SourceNotSeekable)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn regions_past_the_end_fail_or_become_diagnostics() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{ParseMode, ParseOptions, Reader, Writer, XRVErr};
+
+        let path = std::env::temp_dir().join("xrave-test-bounds.xrv");
+        let mut out = Vec::new();
+        let mut writer = Writer::new(&mut out);
+        writer.add_table("users", &[("name", "str")])?;
+        writer.add_record("users", "1", &[("name", "Anna")])?;
+        writer.finish()?;
+        let text = String::from_utf8(out)?;
+        let open = |text: &str, options: ParseOptions| {
+            std::fs::write(&path, text).unwrap();
+            Reader::with_options(path.to_string_lossy().into_owned(), options)
+        };
+
+        let past_end = text.replacen("j:jumps users:", "j:jumps missing:9000-10 users:", 1);
+        match open(&past_end, ParseOptions::default()) {
+            Err(XRVErr::SectionOutOfBounds {
+                name,
+                seek: 9000,
+                len: 10,
+                ..
+            }) => assert_eq!(name, "missing"),
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        }
+        let lenient = ParseOptions {
+            mode: ParseMode::Lenient,
+            ..ParseOptions::default()
+        };
+        let reader = open(&past_end, lenient)?;
+        assert_eq!(
+            reader.jumps().map(|jump| jump.name()).collect::<Vec<_>>(),
+            ["users"]
+        );
+        assert!(matches!(
+            reader.diagnostics(),
+            [XRVErr::SectionOutOfBounds { .. }]
+        ));
+
+        let overflow = format!("j:jumps missing:{}-10 users:", usize::MAX);
+        let overflow = text.replacen("j:jumps users:", &overflow, 1);
+        match open(&overflow, ParseOptions::default()) {
+            Err(XRVErr::AtLine { err, .. }) => {
+                assert!(matches!(*err, XRVErr::RegionOverflow { len: 10, .. }))
+            }
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn with_schema_checks_and_fills_in_kinds() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Col, ColKind, Reader, Schema, SchemaDiff, Writer, XRVErr};
+
+        let schema = Schema::new()
+            .table("users", |t| {
+                t.col("name", ColKind::String)
+                    .col("age", ColKind::I32.optional())
+            })
+            .table("tags", |t| t.col("label", ColKind::String));
+        let path = std::env::temp_dir().join("xrave-test-with-schema.xrv");
+        let write = |age: &str| -> Result<(), XRVErr> {
+            let mut writer = Writer::new(std::fs::File::create(&path).unwrap());
+            writer.add_table("users", &[("name", "str"), ("age", age)])?;
+            writer.add_table("tags", &[("label", "str")])?;
+            writer.add_record("users", "1", &[("name", "Anna"), ("age", "31")])?;
+            writer.finish().map(drop)
+        };
+        let open = || Reader::with_schema(path.to_string_lossy().into_owned(), &schema);
+
+        write("i32?")?;
+        assert_eq!(open()?.records("users")?.count(), 1);
+
+        write("")?;
+        let reader = open()?;
+        assert_eq!(
+            reader.table("users").unwrap().col("age"),
+            Some(ColKind::I32.optional())
+        );
+
+        write("i64?")?;
+        match open() {
+            Err(XRVErr::SchemaDeviations(diffs)) => assert_eq!(
+                diffs,
+                [SchemaDiff::KindChanged {
+                    table: "users".into(),
+                    column: "age".into(),
+                    expected: ColKind::I32.optional(),
+                    found: ColKind::I64.optional(),
+                }]
+            ),
+            other => panic!("unexpected {:?}", other.map(drop)),
+        }
+
+        let other = Schema::new().table("users", |t| {
+            t.col("name", ColKind::String)
+                .col("email", Col::from(ColKind::String))
+        });
+        match Reader::with_schema(path.to_string_lossy().into_owned(), &other) {
+            Err(err @ XRVErr::SchemaDeviations(_)) => assert_eq!(
+                err.to_string(),
+                "file deviates from the schema: missing column 'email' in table 'users'; \
+                 extra column 'age' in table 'users'; extra table 'tags'"
+            ),
+            other => panic!("unexpected {:?}", other.map(drop)),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn format_version_decides_escapes() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{ParseMode, ParseOptions, Reader, Writer, XRVErr};
+
+        let path = std::env::temp_dir().join("xrave-test-version.xrv");
+        let open = |text: &str, options: ParseOptions| {
+            std::fs::write(&path, text).unwrap();
+            Reader::with_options(path.to_string_lossy().into_owned(), options)
+        };
+        let body = "j:jumps\nr:1 note:\"a\\tb\"\n";
+
+        let mut reader = open(body, ParseOptions::default())?;
+        assert_eq!(reader.format_version(), 1);
+        let record = reader.next_record()?.unwrap();
+        assert_eq!(record.cols()[0].value_unescaped(), "a\\tb");
+
+        let mut reader = open(&format!("v:xrave 1\n{}", body), ParseOptions::default())?;
+        assert_eq!(reader.format_version(), 1);
+        assert_eq!(
+            reader.next_record()?.unwrap().cols()[0].value_unescaped(),
+            "a\\tb"
+        );
+
+        let mut reader = open(&format!("v:xrave 2\n{}", body), ParseOptions::default())?;
+        assert_eq!(reader.format_version(), 2);
+        assert_eq!(
+            reader.next_record()?.unwrap().cols()[0].value_unescaped(),
+            "a\tb"
+        );
+
+        let future = format!("v:xrave 99\n{}", body);
+        assert!(matches!(
+            open(&future, ParseOptions::default()),
+            Err(XRVErr::UnsupportedVersion {
+                found: 99,
+                max_supported: 3
+            })
+        ));
+        let lenient = ParseOptions {
+            mode: ParseMode::Lenient,
+            ..ParseOptions::default()
+        };
+        let mut reader = open(&future, lenient)?;
+        assert_eq!(reader.format_version(), 99);
+        assert!(matches!(
+            reader.diagnostics(),
+            [XRVErr::UnsupportedVersion { found: 99, .. }]
+        ));
+        assert_eq!(reader.next_record()?.unwrap().id(), "1");
+
+        let mut out = Vec::new();
+        Writer::new(&mut out).finish()?;
+        assert_eq!(out, b"v:xrave 3\nj:jumps\n");
+        Ok(())
+    }
+
+    #[test]
+    fn progress_reaches_the_end_of_the_file() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Reader, Writer};
+        use std::sync::{Arc, Mutex};
+
+        let path = std::env::temp_dir().join("xrave-test-progress.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("users", &[("name", "str")])?;
+        for id in 0..1_000 {
+            writer.add_record("users", &id.to_string(), &[("name", "Anna")])?;
+        }
+        writer.finish()?;
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        let sink = Arc::clone(&seen);
+        reader.set_progress_callback(1024, move |progress| sink.lock().unwrap().push(progress));
+        while reader.next_record()?.is_some() {}
+
+        let seen = seen.lock().unwrap();
+        assert!(seen.windows(2).all(|w| w[0].bytes_read < w[1].bytes_read));
+        let last = seen.last().unwrap();
+        assert_eq!(Some(last.bytes_read), last.total_bytes);
+        assert_eq!(last.records, 1_000);
+        Ok(())
+    }
+
+    #[test]
+    fn seek_to_takes_names_as_declared() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Reader, Writer};
+
+        let path = std::env::temp_dir().join("xrave-test-seek-quoted.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("my table", &[("name", "str")])?;
+        writer.add_table("2024-q1", &[("name", "str")])?;
+        writer.add_record("my table", "1", &[("name", "Anna")])?;
+        writer.add_record("2024-q1", "2", &[("name", "Bob")])?;
+        writer.finish()?;
+        let text = std::fs::read_to_string(&path)?;
+        assert!(text.contains("j:jumps \"my table\":"));
+        assert!(text.contains(" 2024-q1:"));
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        let jumps: Vec<_> = reader.jumps().map(|jump| jump.name().to_owned()).collect();
+        assert_eq!(jumps, ["my table", "2024-q1"]);
+        reader.seek_to("2024-q1")?;
+        assert_eq!(reader.next_record()?.unwrap().id(), "2");
+        reader.seek_to("my table")?;
+        assert_eq!(reader.next_record()?.unwrap().id(), "1");
+        assert!(reader.seek_to("\"my table\"").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn region_must_start_and_end_a_line() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{ParseMode, ParseOptions, Reader, XRVErr};
+
+        let path = std::env::temp_dir().join("xrave-test-region-framing.xrv");
+        let body = "r:1 name:Anna\nr:2 name:Bob\nr:3 name:Cy\n";
+        let len = "r:1 name:Anna\nr:2 name:Bob\n".len();
+        let open = |seek: usize, len: usize, mode: ParseMode| {
+            std::fs::write(
+                &path,
+                format!("j:jumps users:{:03}-{:03}\n{}", seek, len, body),
+            )
+            .unwrap();
+            Reader::with_options(
+                &path,
+                ParseOptions {
+                    mode,
+                    ..ParseOptions::default()
+                },
+            )
+        };
+        let ids = |reader: &mut Reader| -> Result<Vec<String>, XRVErr> {
+            reader
+                .records("users")?
+                .map(|record| Ok(record?.id))
+                .collect()
+        };
+        let seek = "j:jumps users:000-000\n".len();
+
+        assert_eq!(ids(&mut open(seek, len, ParseMode::Strict)?)?, ["1", "2"]);
+        for len in [len - 1, len + 1] {
+            match ids(&mut open(seek, len, ParseMode::Strict)?) {
+                Err(XRVErr::RegionEndsMidLine { table, offset }) => {
+                    assert_eq!((table.as_str(), offset), ("users", (seek + len) as u64))
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+            let reader = open(seek, len, ParseMode::Strict)?;
+            assert!(reader.parse_tables_parallel(&["users"]).is_err());
+        }
+
+        let mut reader = open(seek, len - 1, ParseMode::Lenient)?;
+        assert_eq!(ids(&mut reader)?, ["1"]);
+        assert!(matches!(
+            reader.diagnostics(),
+            [XRVErr::RegionEndsMidLine { .. }]
+        ));
+
+        assert!(matches!(
+            ids(&mut open(seek + 1, len - 1, ParseMode::Strict)?),
+            Err(XRVErr::RegionStartsMidLine { .. })
+        ));
+        let mut reader = open(seek + 1, len - 1, ParseMode::Lenient)?;
+        assert_eq!(ids(&mut reader)?, ["2"]);
+        assert!(matches!(
+            reader.diagnostics(),
+            [XRVErr::RegionStartsMidLine { .. }]
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn load_table_prefix_stays_within_budget() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Reader, Writer};
+
+        let path = std::env::temp_dir().join("xrave-test-prefix.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("users", &[("name", "str")])?;
+        for id in 0..10 {
+            writer.add_record("users", &id.to_string(), &[("name", "Anna")])?;
+        }
+        writer.finish()?;
+        let line = "r:0 name:Anna\n".len();
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        let (records, truncated) = reader.load_table_prefix("users", 3 * line + 5)?;
+        assert_eq!((records.len(), truncated), (3, true));
+        assert_eq!(records[2].id, "2");
+
+        let (records, truncated) = reader.load_table_prefix("users", line - 1)?;
+        assert!(records.is_empty() && truncated);
+
+        let (records, truncated) = reader.load_table_prefix("users", 1 << 20)?;
+        assert_eq!((records.len(), truncated), (10, false));
+        let (records, truncated) = reader.load_table_prefix("users", 10 * line)?;
+        assert_eq!((records.len(), truncated), (10, false));
+        Ok(())
+    }
+
+    #[test]
+    fn filler_tails_are_skipped() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Reader, XRVErr};
+
+        let path = std::env::temp_dir().join("xrave-test-tails.xrv");
+        let ids = |text: &str| -> Result<Vec<String>, XRVErr> {
+            std::fs::write(&path, text).unwrap();
+            let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+            let mut ids = Vec::new();
+            while let Some(record) = reader.next_record()? {
+                ids.push(record.id().to_owned());
+            }
+            Ok(ids)
+        };
+
+        let head = "j:jumps\nr:1 a:x\n  \t \n\nr:2 a:y";
+        for tail in [
+            "\n",
+            "\r",
+            "\r\n",
+            "\n   \n\n",
+            "\n\x1a",
+            "\n\x1a\n",
+            "\r\n \x00\t\x1a",
+        ] {
+            assert_eq!(ids(&format!("{}{}", head, tail))?, ["1", "2"], "{:?}", tail);
+        }
+        assert!(ids(&format!("{}\n\x1a\nr:3 a:z\n", head)).is_err());
+        assert!(ids(&format!("{}\n\x1agarbage\n", head)).is_err());
+        assert!(ids(&format!("{}\n# trailer\n", head)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn record_at_rereads_any_span() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Reader, Writer};
+
+        let path = std::env::temp_dir().join("xrave-test-span.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("users", &[("name", "str")])?;
+        writer.add_table("admins", &[("name", "str")])?;
+        for id in 0..50 {
+            let name = format!("user {}", id);
+            writer.add_record("users", &id.to_string(), &[("name", &name)])?;
+            writer.add_record("admins", &id.to_string(), &[("name", "root")])?;
+        }
+        writer.finish()?;
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        let mut records = reader.records("users")?.collect::<Result<Vec<_>, _>>()?;
+        records.extend(reader.records("admins")?.collect::<Result<Vec<_>, _>>()?);
+
+        let mut order: Vec<usize> = (0..records.len()).collect();
+        order.sort_by_key(|idx| (idx * 37) % records.len());
+        for idx in order {
+            assert_eq!(reader.record_at(records[idx].span)?, records[idx]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn stats_count_lines_records_and_errors() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{LineKind, ParseMode, ParseOptions, Reader, Writer};
+        use std::{
+            sync::{Arc, Mutex},
+            time::Duration,
+        };
+
+        let path = std::env::temp_dir().join("xrave-test-stats.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("users", &[("name", "str")])?;
+        for id in 0..100 {
+            writer.add_record("users", &id.to_string(), &[("name", "Anna")])?;
+        }
+        writer.finish()?;
+        let len = std::fs::metadata(&path)?.len();
+
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        let sink = Arc::clone(&lines);
+        reader.set_line_hook(move |kind, len| sink.lock().unwrap().push((kind, len)));
+        while reader.next_record()?.is_some() {}
+
+        let stats = reader.stats();
+        assert_eq!(stats.bytes_read, len);
+        assert_eq!(stats.lines_parsed, 103);
+        assert_eq!(stats.records_parsed, 100);
+        assert_eq!(stats.errors, 0);
+        assert!(stats.largest_line_len >= "r:99 name:Anna\n".len());
+        if cfg!(feature = "stats") {
+            assert!(stats.time_in_io > Duration::ZERO && stats.time_in_parse > Duration::ZERO);
+        } else {
+            assert_eq!(
+                (stats.time_in_io, stats.time_in_parse),
+                (Duration::ZERO, Duration::ZERO)
+            );
+        }
+
+        let lines = lines.lock().unwrap();
+        assert_eq!(lines.len(), 101);
+        assert_eq!(lines[0].0, LineKind::Table);
+        assert_eq!(lines[100], (LineKind::Record, "r:99 name:Anna\n".len()));
+
+        std::fs::write(&path, "j:jumps\nr:1 a:x\nr:2 a:\"x\nr:3 a:y\n")?;
+        let options = ParseOptions {
+            mode: ParseMode::Lenient,
+            ..ParseOptions::default()
+        };
+        let mut reader = Reader::with_options(path.to_string_lossy().into_owned(), options)?;
+        while reader.next_record()?.is_some() {}
+        assert_eq!(
+            (reader.stats().records_parsed, reader.stats().errors),
+            (2, 1)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn interned_names_counts_distinct_names() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{ParseOptions, Reader, Writer};
+
+        let path = std::env::temp_dir().join("xrave-test-intern.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("events", &[("name", "str"), ("ts", "int")])?;
+        for id in 0..10_000 {
+            let id = id.to_string();
+            writer.add_record("events", &id, &[("name", "tick"), ("ts", &id)])?;
+        }
+        writer.finish()?;
+
+        let options = ParseOptions {
+            intern_names: true,
+            ..ParseOptions::default()
+        };
+        let mut reader = Reader::with_options(path.to_string_lossy().into_owned(), options)?;
+        let records = reader.records("events")?.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(records.len(), 10_000);
+        assert_eq!(reader.interned_names(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn lenient_reader_resyncs_after_a_broken_value() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{ParseMode, ParseOptions, Reader, XRVErr};
+
+        let path = std::env::temp_dir().join("xrave-test-resync.xrv");
+        let text = "j:jumps\n\
+            r:1 note:ok\n\
+            r:2 note:\"first\n\
+            second line\n\
+            third\"\n\
+            r:3 note:ok\n\
+            r:4 note:ok\n";
+        std::fs::write(&path, text)?;
+        let options = ParseOptions {
+            mode: ParseMode::Lenient,
+            ..ParseOptions::default()
+        };
+        let mut reader = Reader::with_options(path.to_string_lossy().into_owned(), options)?;
+        let mut ids = Vec::new();
+        while let Some(record) = reader.next_record()? {
+            ids.push(record.id().to_owned());
+        }
+        assert_eq!(ids, ["1", "3", "4"]);
+
+        assert_eq!(reader.diagnostics().len(), 1);
+        match &reader.diagnostics()[0] {
+            XRVErr::Resynced { skipped, err } => {
+                assert_eq!(*skipped, "second line\nthird\"\n".len() as u64);
+                assert!(matches!(**err, XRVErr::AtLine { line: 3, .. }));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn dictionary_tokens_expand_on_every_path() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Document, Reader, Writer, WriterOptions, XRVErr};
+
+        let path = std::env::temp_dir().join("xrave-test-dictionary.xrv");
+        let options = WriterOptions {
+            dictionary_threshold: Some(8),
+            ..WriterOptions::default()
+        };
+        let mut writer = Writer::with_options(std::fs::File::create(&path)?, options);
+        writer.add_table(
+            "users",
+            &[("name", "str"), ("status", "str"), ("note", "str?")],
+        )?;
+        writer.add_table("plain", &[("note", "str")])?;
+        let statuses = ["active", "disabled", "on leave", "active"];
+        for id in 0..100 {
+            let name = format!("user {}", id);
+            let status = statuses[id % statuses.len()];
+            let mut fields = vec![("name", name.as_str()), ("status", status)];
+            if id == 7 {
+                fields.push(("note", "^1 is not a token"));
+            }
+            writer.add_record("users", &id.to_string(), &fields)?;
+        }
+        writer.add_record("plain", "1", &[("note", "^1")])?;
+        writer.finish()?;
+
+        let text = std::fs::read_to_string(&path)?;
+        assert!(
+            text.contains("\nd:users 1:active 2:disabled 3:\"on leave\" 4:\"^1 is not a token\"\n")
+        );
+        assert!(text.contains("\nr:2 name:\"user 2\" status:^3\n"));
+        assert!(text.contains("\nr:7 name:\"user 7\" status:^1 note:^4\n"));
+        assert!(text.contains("\nr:1 note:^1\n"));
+
+        let mut reader = Reader::new(&path)?;
+        let records = reader.records("users")?.collect::<Result<Vec<_>, _>>()?;
+        let status: Vec<_> = records
+            .iter()
+            .map(|r| r.get_all("status").next().unwrap())
+            .take(4)
+            .collect();
+        assert_eq!(status, statuses);
+        assert_eq!(records[7].get_all("note").next(), Some("^1 is not a token"));
+        assert_eq!(
+            reader
+                .dictionary("users")
+                .unwrap()
+                .get("2")
+                .map(String::as_str),
+            Some("disabled")
+        );
+        assert!(reader.dictionary("plain").is_none());
+        assert_eq!(
+            reader
+                .records("plain")?
+                .next()
+                .unwrap()?
+                .get_all("note")
+                .next(),
+            Some("^1")
+        );
+
+        let mut reader = Reader::new(&path)?;
+        let mut sequential = Vec::new();
+        while let Some(record) = reader.next_record()? {
+            sequential.push(record.to_owned());
+        }
+        let fields = |records: &[crate::OwnedRecordLine]| -> Vec<_> {
+            records
+                .iter()
+                .map(|record| (record.id.clone(), record.cols.clone()))
+                .collect()
+        };
+        assert_eq!(fields(&sequential[..100]), fields(&records));
+        let parallel = Reader::new(&path)?.parse_tables_parallel(&["users"])?;
+        assert_eq!(parallel["users"], records);
+
+        let document = Document::read(text.as_bytes())?;
+        let read = document
+            .table("users")
+            .unwrap()
+            .records()
+            .map(|r| r.map(|r| r.into_owned()));
+        assert_eq!(
+            fields(&read.collect::<Result<Vec<_>, _>>()?),
+            fields(&records)
+        );
+        let mut plain = Vec::new();
+        document.write(&mut plain, WriterOptions::default())?;
+        assert!(!String::from_utf8(plain)?.contains("\nd:"));
+
+        let broken = text.replace("status:^3", "status:^9");
+        assert!(matches!(
+            Document::read(broken.as_bytes()),
+            Err(XRVErr::AtLine { ref err, .. })
+                if matches!(**err, XRVErr::UnknownDictionaryToken { ref token, .. } if token == "9")
+        ));
+        Ok(())
+    }
+}
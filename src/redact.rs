@@ -0,0 +1,254 @@
+use std::io::prelude::*;
+use std::{cell::Cell, rc::Rc, time::Duration};
+
+use crate::{
+    base64,
+    error::XRVErr,
+    schema::{Col, ColKind},
+    sha256::Sha256,
+    transform::{transform, Transform},
+    units::{format_duration, format_size},
+};
+
+/// What `redact` puts in place of a value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Redaction {
+    /// An empty value; `0`, or the lowest integer in bounds, for numeric
+    /// and `size` columns, `0s` for `duration` columns and `false` for
+    /// booleans.
+    Blank,
+    /// A value derived from the SHA-256 of the salt and the value, so that
+    /// equal values stay equal across tables and runs with the same salt:
+    /// 16 hex digits, a base64 string for `bytes` columns, a size or
+    /// duration for `size` and `duration` columns, and for numeric columns
+    /// an integer in `0..2^31`, or within the column's bounds.
+    Hash,
+    Fixed(String),
+}
+
+/// Columns `redact` replaces the values of, per table.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RedactSpec {
+    /// Hashed together with each value under `Redaction::Hash`.
+    pub salt: Vec<u8>,
+    /// Table, column and how its values are replaced.
+    pub columns: Vec<(String, String, Redaction)>,
+}
+
+impl RedactSpec {
+    pub fn new(salt: &[u8]) -> RedactSpec {
+        RedactSpec {
+            salt: salt.to_vec(),
+            columns: Vec::new(),
+        }
+    }
+
+    pub fn column(mut self, table: &str, column: &str, redaction: Redaction) -> Self {
+        self.columns
+            .push((table.to_owned(), column.to_owned(), redaction));
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedactedColumn {
+    pub table: String,
+    pub column: String,
+    /// Values replaced, counting each value of a list column.
+    pub values: usize,
+}
+
+/// Values `redact` replaced, per column of the spec.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RedactReport {
+    pub columns: Vec<RedactedColumn>,
+}
+
+impl RedactReport {
+    pub fn values(&self, table: &str, column: &str) -> Option<usize> {
+        self.columns
+            .iter()
+            .find(|redacted| redacted.table == table && redacted.column == column)
+            .map(|redacted| redacted.values)
+    }
+}
+
+/// Streams `input` to `output` like `transform`, replacing every value of
+/// the columns in `spec`. Replacements fit the declared kind of their
+/// column, so the output still passes kind checks.
+///
+/// ```
+/// use xrave::{redact, RedactSpec, Redaction, Writer};
+///
+/// let mut input = Vec::new();
+/// let mut writer = Writer::new(&mut input);
+/// writer.add_table("users", &[("phone", "str")])?;
+/// writer.add_record("users", "1", &[("phone", "555")])?;
+/// writer.finish()?;
+///
+/// let spec = RedactSpec::new(b"pepper").column("users", "phone", Redaction::Fixed("REDACTED".into()));
+/// let mut output = Vec::new();
+/// let report = redact(&input[..], &mut output, &spec)?;
+/// assert_eq!(report.values("users", "phone"), Some(1));
+/// assert!(String::from_utf8(output)?.contains("\nr:1 phone:REDACTED\n"));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn redact<R: Read, W: Write>(
+    input: R,
+    output: W,
+    spec: &RedactSpec,
+) -> Result<RedactReport, XRVErr> {
+    let mut changes = Transform::new();
+    let mut counters = Vec::new();
+    for (table, column, redaction) in spec.columns.iter() {
+        let count = Rc::new(Cell::new(0));
+        counters.push((table, column, Rc::clone(&count)));
+        let (salt, redaction) = (spec.salt.clone(), redaction.clone());
+        changes = changes.map_declared_value(table, column, move |decl, value| {
+            count.set(count.get() + 1);
+            Some(replacement(&redaction, &salt, decl, value))
+        });
+    }
+    transform(input, output, &changes)?;
+
+    let columns = counters
+        .into_iter()
+        .map(|(table, column, count)| RedactedColumn {
+            table: table.clone(),
+            column: column.clone(),
+            values: count.get(),
+        })
+        .collect();
+    Ok(RedactReport { columns })
+}
+
+/// The value `redaction` puts in place of `value` in a column declared as
+/// `decl`.
+fn replacement(redaction: &Redaction, salt: &[u8], decl: &str, value: &str) -> String {
+    let col = Col::parse(decl).ok();
+    let kind = col.as_ref().map_or(ColKind::String, |col| col.kind);
+    match redaction {
+        Redaction::Fixed(fixed) => fixed.clone(),
+        Redaction::Blank => match kind {
+            ColKind::Bool => "false".to_owned(),
+            ColKind::Size => "0".to_owned(),
+            ColKind::Duration => "0s".to_owned(),
+            kind if kind.is_numeric() => number(col.as_ref(), 0),
+            _ => String::new(),
+        },
+        Redaction::Hash => {
+            let mut hasher = Sha256::new();
+            hasher.update(salt);
+            hasher.update(value.as_bytes());
+            let digest = hasher.finish();
+            let n = u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]);
+            match kind {
+                ColKind::Bool => (digest[0] & 1 == 1).to_string(),
+                ColKind::Bytes => base64::encode(&digest[..12]),
+                ColKind::Size => format_size(u64::from(n)),
+                ColKind::Duration => format_duration(Duration::from_secs(u64::from(n >> 16))),
+                kind if kind.is_numeric() => number(col.as_ref(), u64::from(n >> 1)),
+                _ => digest[..8]
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect(),
+            }
+        }
+    }
+}
+
+/// `n` as a value of a numeric column, wrapped into the integers within
+/// the column's bounds when it has some.
+fn number(col: Option<&Col>, n: u64) -> String {
+    match col.and_then(|col| col.bounds) {
+        None => n.to_string(),
+        Some((min, max)) => {
+            let (low, high) = (min.ceil() as i64, max.floor() as i64);
+            if high < low {
+                return min.to_string();
+            }
+            let span = (high - low) as u64 + 1;
+            (low + (n % span) as i64).to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn redaction_is_salted_consistent_and_fits_kinds() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{redact, Document, RedactSpec, Redaction, Writer};
+
+        let mut input = Vec::new();
+        let mut writer = Writer::new(&mut input);
+        writer.add_table(
+            "users",
+            &[
+                ("email", "str"),
+                ("phone", "str?"),
+                ("zip", "u32[1000..9999]"),
+            ],
+        )?;
+        writer.add_table("orders", &[("email", "str"), ("total", "f64")])?;
+        writer.add_record(
+            "users",
+            "1",
+            &[
+                ("email", "anna@example.com"),
+                ("phone", "555"),
+                ("zip", "1010"),
+            ],
+        )?;
+        writer.add_record(
+            "users",
+            "2",
+            &[("email", "bob@example.com"), ("zip", "8020")],
+        )?;
+        writer.add_record(
+            "orders",
+            "o1",
+            &[("email", "anna@example.com"), ("total", "9.5")],
+        )?;
+        writer.finish()?;
+
+        let spec = |salt: &[u8]| {
+            RedactSpec::new(salt)
+                .column("users", "email", Redaction::Hash)
+                .column("users", "phone", Redaction::Fixed("REDACTED".into()))
+                .column("users", "zip", Redaction::Hash)
+                .column("orders", "email", Redaction::Hash)
+        };
+        let run = |salt: &[u8]| -> Result<_, crate::XRVErr> {
+            let mut output = Vec::new();
+            let report = redact(&input[..], &mut output, &spec(salt))?;
+            Ok((report, output))
+        };
+
+        let (report, first) = run(b"pepper")?;
+        assert_eq!(report.values("users", "email"), Some(2));
+        assert_eq!(report.values("users", "phone"), Some(1));
+        assert_eq!(report.values("orders", "email"), Some(1));
+        assert_eq!(run(b"pepper")?.1, first);
+        assert_ne!(run(b"salt")?.1, first);
+
+        let document = Document::read(&first[..])?;
+        let records = |name| {
+            document
+                .table(name)
+                .unwrap()
+                .records()
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let (users, orders) = (records("users")?, records("orders")?);
+        assert_ne!(users[0].get_all("email").next(), Some("anna@example.com"));
+        assert_eq!(
+            users[0].get_all("email").next(),
+            orders[0].get_all("email").next()
+        );
+        assert_eq!(users[0].get_all("phone").next(), Some("REDACTED"));
+        let zip: u32 = users[1].get_all("zip").next().unwrap().parse()?;
+        assert!((1000..=9999).contains(&zip));
+        assert_eq!(orders[0].get_all("total").next(), Some("9.5"));
+        Ok(())
+    }
+}
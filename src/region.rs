@@ -0,0 +1,134 @@
+use std::io::prelude::*;
+
+use crate::{error::XRVErr, reader::Reader};
+
+/// The raw bytes of one table region, returned by `Reader::table_reader`.
+/// Reads end at the region end even when the file goes on, and fail with
+/// `std::io::ErrorKind::UnexpectedEof` when the file ends first.
+#[derive(Debug)]
+pub struct TableRegionReader<'r> {
+    reader: &'r mut Reader,
+    remaining: u64,
+}
+
+impl Reader {
+    /// Streams the bytes of the region of `name`, `pos..pos + len` as listed
+    /// on the jumps line, without parsing them. The reader is left at the
+    /// first byte not read, so `records` or `seek_to` start over cleanly.
+    ///
+    /// ```
+    /// use std::io::BufRead;
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-table-reader.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("name", "str")])?;
+    /// writer.add_record("users", "1", &[("name", "Anna")])?;
+    /// writer.add_record("users", "2", &[("name", "Bob")])?;
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(&path)?;
+    /// let lines = reader.table_reader("users")?.lines().collect::<Result<Vec<_>, _>>()?;
+    /// assert_eq!(lines, ["r:1 name:Anna", "r:2 name:Bob"]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn table_reader(&mut self, name: &str) -> Result<TableRegionReader<'_>, XRVErr> {
+        let len = match self.jumps.get(name) {
+            None => return Err(XRVErr::UnknownTable(name.to_owned())),
+            Some((_, len)) => *len as u64,
+        };
+        self.seek_to(name)?;
+        Ok(TableRegionReader {
+            reader: self,
+            remaining: len,
+        })
+    }
+}
+
+impl<'r> TableRegionReader<'r> {
+    /// Bytes of the region not read yet.
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+}
+
+impl<'r> Read for TableRegionReader<'r> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let available = self.fill_buf()?;
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.consume(read);
+        Ok(read)
+    }
+}
+
+impl<'r> BufRead for TableRegionReader<'r> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        if self.remaining == 0 {
+            return Ok(&[]);
+        }
+        let available = self.reader.source.fill_buf()?;
+        if available.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "file ends inside the table region",
+            ));
+        }
+        let len = (available.len() as u64).min(self.remaining) as usize;
+        Ok(&available[..len])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let amt = (amt as u64).min(self.remaining) as usize;
+        self.reader.source.consume(amt);
+        self.reader.buffer.offset += amt as u64;
+        self.remaining -= amt as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn table_reader_streams_exactly_the_region() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Reader, Writer};
+        use std::io::{BufRead, Read};
+
+        let path = std::env::temp_dir().join("xrave-test-table-reader.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("users", &[("name", "str")])?;
+        writer.add_table("events", &[("note", "str")])?;
+        for id in 0..2_000 {
+            writer.add_record("users", &id.to_string(), &[("name", "Anna")])?;
+            writer.add_record("events", &id.to_string(), &[("note", "tick")])?;
+        }
+        writer.finish()?;
+
+        let bytes = std::fs::read(&path)?;
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        let regions: Vec<_> = reader
+            .jumps()
+            .map(|j| (j.name().to_owned(), j.seek(), j.len()))
+            .collect();
+        for (table, pos, len) in regions {
+            let table = table.as_str();
+            let mut copied = Vec::new();
+            std::io::copy(&mut reader.table_reader(table)?, &mut copied)?;
+            assert_eq!(copied, &bytes[pos..pos + len]);
+
+            let mut region = reader.table_reader(table)?;
+            let mut byte = [0u8; 1];
+            let mut single = Vec::new();
+            while region.read(&mut byte)? == 1 {
+                single.push(byte[0]);
+            }
+            assert_eq!(single, copied);
+            assert_eq!(region.read(&mut byte)?, 0);
+
+            let lines = reader.table_reader(table)?.lines().count();
+            assert_eq!(lines, 2_000);
+        }
+        assert_eq!(reader.records("users")?.count(), 2_000);
+        assert!(reader.table_reader("orders").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{error::XRVErr, line::OwnedRecordLine, reader::Reader};
+
+/// Which records of a table `Reader::sample` keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleSpec {
+    /// The first record and every `n`th one after it.
+    EveryNth(usize),
+    /// `count` records picked uniformly at random; the same `seed` picks the
+    /// same records from the same table.
+    Random { count: usize, seed: u64 },
+}
+
+impl Reader {
+    /// Reads a sample of the records of `table`, in file order. Each record
+    /// keeps its `span`, so `record_at` can fetch it again later. Random
+    /// samples use reservoir sampling and hold at most `count` records; a
+    /// table with fewer records is returned whole. `EveryNth(0)` fails with
+    /// `XRVErr::ZeroSampleInterval`.
+    ///
+    /// ```
+    /// use xrave::{Reader, SampleSpec, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-sample.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("events", &[("n", "i32")])?;
+    /// for n in 0..100 {
+    ///     writer.add_record("events", &format!("e{}", n), &[("n", &n.to_string())])?;
+    /// }
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(&path)?;
+    /// let sample = reader.sample("events", SampleSpec::EveryNth(40))?;
+    /// assert_eq!(sample.iter().map(|record| record.id.as_str()).collect::<Vec<_>>(), ["e0", "e40", "e80"]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn sample(
+        &mut self,
+        table: &str,
+        spec: SampleSpec,
+    ) -> Result<Vec<OwnedRecordLine>, XRVErr> {
+        match spec {
+            SampleSpec::EveryNth(0) => Err(XRVErr::ZeroSampleInterval),
+            SampleSpec::EveryNth(n) => {
+                let mut sample = Vec::new();
+                for (idx, record) in self.records(table)?.enumerate() {
+                    let record = record?;
+                    if idx % n == 0 {
+                        sample.push(record);
+                    }
+                }
+                Ok(sample)
+            }
+            SampleSpec::Random { count, seed } => {
+                let mut rng = SplitMix64(seed);
+                let mut sample = Vec::with_capacity(count);
+                for (idx, record) in self.records(table)?.enumerate() {
+                    let record = record?;
+                    if sample.len() < count {
+                        sample.push(record);
+                        continue;
+                    }
+                    let slot = rng.below(idx as u64 + 1) as usize;
+                    if slot < count {
+                        sample[slot] = record;
+                    }
+                }
+                sample.sort_by_key(|record| record.span.offset);
+                Ok(sample)
+            }
+        }
+    }
+}
+
+/// Small seedable generator, enough to make samples reproducible.
+pub(crate) struct SplitMix64(pub(crate) u64);
+
+impl SplitMix64 {
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `0..bound`.
+    pub(crate) fn below(&mut self, bound: u64) -> u64 {
+        ((self.next() as u128 * bound as u128) >> 64) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn samples_are_regular_or_seeded() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Reader, SampleSpec, Writer, XRVErr};
+
+        let path = std::env::temp_dir().join("xrave-test-sample.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("events", &[("n", "i32")])?;
+        for n in 0..100 {
+            writer.add_record("events", &format!("e{}", n), &[("n", &n.to_string())])?;
+        }
+        writer.finish()?;
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        let ids = |records: Vec<crate::OwnedRecordLine>| -> Vec<String> {
+            records.into_iter().map(|record| record.id).collect()
+        };
+        assert_eq!(
+            ids(reader.sample("events", SampleSpec::EveryNth(40))?),
+            ["e0", "e40", "e80"]
+        );
+        assert_eq!(
+            reader.sample("events", SampleSpec::EveryNth(1000))?.len(),
+            1
+        );
+        assert!(matches!(
+            reader.sample("events", SampleSpec::EveryNth(0)),
+            Err(XRVErr::ZeroSampleInterval)
+        ));
+
+        let random = SampleSpec::Random { count: 5, seed: 7 };
+        let sample = reader.sample("events", random)?;
+        assert_eq!(ids(sample.clone()), ["e6", "e12", "e49", "e57", "e89"]);
+        assert_eq!(ids(reader.sample("events", random)?), ids(sample.clone()));
+        assert_eq!(reader.record_at(sample[1].span)?.id, "e12");
+
+        let all = SampleSpec::Random {
+            count: 500,
+            seed: 7,
+        };
+        assert_eq!(reader.sample("events", all)?.len(), 100);
+        Ok(())
+    }
+}
@@ -0,0 +1,757 @@
+use std::{fmt, sync::Arc};
+
+use crate::{
+    base64,
+    document::{Document, DocumentTable},
+    error::XRVErr,
+    line::{OwnedField, OwnedTableLine},
+    namespace::NAMESPACE_SEPARATOR,
+    ordered::OrderedMap,
+    typed::NumericLocale,
+    units::{parse_duration, parse_size, unit_error},
+};
+
+/// Declared type of a table column, written as the column's value in the
+/// table header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColKind {
+    Bool,
+    I32,
+    I64,
+    U32,
+    U64,
+    F32,
+    F64,
+    String,
+    /// Binary data, stored as standard base64 with `=` padding.
+    Bytes,
+    /// A number of bytes, such as `64MiB`, `1.5kB` or `512`; see
+    /// `TypedRecord::get_size`.
+    Size,
+    /// A length of time, such as `30s`, `250ms` or `1.5h`; see
+    /// `TypedRecord::get_duration`.
+    Duration,
+}
+
+impl ColKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ColKind::Bool => "bool",
+            ColKind::I32 => "i32",
+            ColKind::I64 => "i64",
+            ColKind::U32 => "u32",
+            ColKind::U64 => "u64",
+            ColKind::F32 => "f32",
+            ColKind::F64 => "f64",
+            ColKind::String => "str",
+            ColKind::Bytes => "bytes",
+            ColKind::Size => "size",
+            ColKind::Duration => "duration",
+        }
+    }
+
+    /// Parses a header kind, accepting `int` for `i64`, `float` for `f64`
+    /// and `string` for `str`.
+    pub fn parse(kind: &str) -> Option<ColKind> {
+        match kind {
+            "bool" => Some(ColKind::Bool),
+            "i32" => Some(ColKind::I32),
+            "i64" | "int" => Some(ColKind::I64),
+            "u32" => Some(ColKind::U32),
+            "u64" => Some(ColKind::U64),
+            "f32" => Some(ColKind::F32),
+            "f64" | "float" => Some(ColKind::F64),
+            "str" | "string" => Some(ColKind::String),
+            "bytes" => Some(ColKind::Bytes),
+            "size" => Some(ColKind::Size),
+            "duration" => Some(ColKind::Duration),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ColKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A column declaration: its kind and, for numeric kinds, an inclusive
+/// range of accepted values. The range is written after the kind as
+/// `[min..max]`, e.g. `i32[0..150]` or `f64[-40..60.5]`; `..` separates the
+/// bounds so negative numbers need no escaping.
+///
+/// ```
+/// use xrave::{Col, ColKind};
+///
+/// let col = Col::parse("i32[-40..60]")?;
+/// assert_eq!(col, ColKind::I32.bounded(-40.0, 60.0));
+/// assert_eq!(col.to_string(), "i32[-40..60]");
+/// assert_eq!(Col::parse("i32?->users")?, ColKind::I32.optional().references("users"));
+/// # Ok::<(), xrave::XRVErr>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Col {
+    pub kind: ColKind,
+    pub bounds: Option<(f64, f64)>,
+    /// Written as a trailing `?`, e.g. `email:str?`: records may leave the
+    /// column out.
+    pub optional: bool,
+    /// Written as a trailing `!`, e.g. `id:i32!`: the table's primary key,
+    /// unique across its records.
+    pub key: bool,
+    /// Written as `->table` after the kind and markers, e.g.
+    /// `user:i32->users`: values must be primary keys of that table.
+    pub references: Option<String>,
+    /// Written last as `=value`, e.g. `status:str=active`, or quoted as a
+    /// whole when the default has a space: `status:"str=in review"`. A
+    /// record that leaves the column out takes this value.
+    pub default: Option<String>,
+    /// Written as the kind in brackets, e.g. `tag:[str]` or
+    /// `score:[i32[0..10]]`: records repeat the field once per value, and
+    /// may leave it out for none.
+    pub list: bool,
+}
+
+impl ColKind {
+    /// Declares a column of this kind that only accepts values in
+    /// `min..=max`.
+    pub fn bounded(self, min: f64, max: f64) -> Col {
+        Col {
+            bounds: Some((min, max)),
+            ..Col::from(self)
+        }
+    }
+
+    /// Declares a column of this kind that records may leave out.
+    pub fn optional(self) -> Col {
+        Col::from(self).optional()
+    }
+
+    /// Declares the primary key column of a table.
+    pub fn key(self) -> Col {
+        Col {
+            key: true,
+            ..Col::from(self)
+        }
+    }
+
+    /// Declares a column of this kind that takes `default` when a record
+    /// leaves it out.
+    pub fn with_default(self, default: &str) -> Col {
+        Col::from(self).with_default(default)
+    }
+
+    /// Declares a column holding primary keys of `table`.
+    pub fn references(self, table: &str) -> Col {
+        Col::from(self).references(table)
+    }
+
+    /// Declares a column holding any number of values of this kind.
+    pub fn list(self) -> Col {
+        Col::from(self).list()
+    }
+
+    pub(crate) fn is_numeric(self) -> bool {
+        !matches!(
+            self,
+            ColKind::Bool | ColKind::String | ColKind::Bytes | ColKind::Size | ColKind::Duration
+        )
+    }
+}
+
+impl Col {
+    /// Parses a header kind with an optional `[min..max]` suffix, optionally
+    /// wrapped in brackets for a list, an optional `?` or `!`, an optional
+    /// `->table` and an optional `=default`. Bounds on a non-numeric kind,
+    /// bounds that are not finite numbers and `min` above `max` are rejected with `XRVErr::InvalidColumnKind`.
+    pub fn parse(value: &str) -> Result<Col, XRVErr> {
+        let invalid = || XRVErr::InvalidColumnKind(value.to_owned());
+        let (decl, default) = split_default(value);
+        let (decl, references) = match decl.split_once("->") {
+            None => (decl, None),
+            Some((_, "")) => return Err(invalid()),
+            Some((decl, table)) => (decl, Some(table.to_owned())),
+        };
+        let (decl, optional) = match decl.strip_suffix('?') {
+            None => (decl, false),
+            Some(decl) => (decl, true),
+        };
+        let (decl, key) = match decl.strip_suffix('!') {
+            None => (decl, false),
+            Some(_) if optional => return Err(invalid()),
+            Some(decl) => (decl, true),
+        };
+        let (decl, list) = match decl.strip_prefix('[').and_then(|d| d.strip_suffix(']')) {
+            None => (decl, false),
+            Some(decl) => (decl, true),
+        };
+        let (kind, bounds) = match decl.split_once('[') {
+            None => (decl, None),
+            Some((kind, rest)) => (kind, Some(rest.strip_suffix(']').ok_or_else(invalid)?)),
+        };
+        let kind = ColKind::parse(kind).ok_or_else(invalid)?;
+        let bounds = match bounds {
+            None => None,
+            Some(_) if !kind.is_numeric() => return Err(invalid()),
+            Some(bounds) => {
+                let (min, max) = bounds.split_once("..").ok_or_else(invalid)?;
+                match (min.parse::<f64>(), max.parse::<f64>()) {
+                    (Ok(min), Ok(max)) if min.is_finite() && max.is_finite() && min <= max => {
+                        Some((min, max))
+                    }
+                    _ => return Err(invalid()),
+                }
+            }
+        };
+        Ok(Col {
+            kind,
+            bounds,
+            optional,
+            key,
+            references,
+            default: default.map(str::to_owned),
+            list,
+        })
+    }
+
+    pub fn list(mut self) -> Col {
+        self.list = true;
+        self
+    }
+
+    pub fn optional(mut self) -> Col {
+        self.optional = true;
+        self
+    }
+
+    pub fn with_default(mut self, default: &str) -> Col {
+        self.default = Some(default.to_owned());
+        self
+    }
+
+    pub fn references(mut self, table: &str) -> Col {
+        self.references = Some(table.to_owned());
+        self
+    }
+
+    /// Whether `value` satisfies the column's bounds; always true without
+    /// bounds.
+    pub fn accepts(&self, value: &str) -> bool {
+        self.accepts_in(value, NumericLocale::default())
+    }
+
+    /// Like `accepts`, reading `value` as a number written in `locale`.
+    pub fn accepts_in(&self, value: &str, locale: NumericLocale) -> bool {
+        let number = locale
+            .normalize(value)
+            .and_then(|value| value.parse::<f64>().ok());
+        match (self.bounds, number) {
+            (None, _) => true,
+            (Some((min, max)), Some(value)) => min <= value && value <= max,
+            (Some(_), None) => false,
+        }
+    }
+}
+
+impl From<ColKind> for Col {
+    fn from(kind: ColKind) -> Self {
+        Col {
+            kind,
+            bounds: None,
+            optional: false,
+            key: false,
+            references: None,
+            default: None,
+            list: false,
+        }
+    }
+}
+
+impl fmt::Display for Col {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.list {
+            write!(f, "[")?;
+        }
+        write!(f, "{}", self.kind)?;
+        if let Some((min, max)) = self.bounds {
+            write!(f, "[{}..{}]", min, max)?;
+        }
+        if self.list {
+            write!(f, "]")?;
+        }
+        if self.key {
+            write!(f, "!")?;
+        }
+        if self.optional {
+            write!(f, "?")?;
+        }
+        if let Some(table) = &self.references {
+            write!(f, "->{}", table)?;
+        }
+        if let Some(default) = &self.default {
+            write!(f, "={}", default)?;
+        }
+        Ok(())
+    }
+}
+
+/// Name of the column declared as the primary key with `!`, if any.
+pub(crate) fn key_column(cols: &[OwnedField]) -> Option<&str> {
+    cols.iter()
+        .find(|col| Col::parse(&col.value).is_ok_and(|col| col.key))
+        .map(|col| &*col.name)
+}
+
+/// Splits a header declaration into the part before `=` and the default
+/// after it, if any.
+pub(crate) fn split_default(decl: &str) -> (&str, Option<&str>) {
+    match decl.split_once('=') {
+        None => (decl, None),
+        Some((decl, default)) => (decl, Some(default)),
+    }
+}
+
+/// Checks the fields of a new record against the declarations in `cols`:
+/// every required column must be present, and non-empty unless
+/// `allow_empty` is set, only list columns may repeat, bounded columns must
+/// hold values in range and `bytes` columns valid base64. Columns whose declaration does not parse as a
+/// `Col` are not checked.
+pub(crate) fn check_fields(
+    table: &str,
+    cols: &[OwnedField],
+    fields: &[(&str, &str)],
+    allow_empty: bool,
+) -> Result<(), XRVErr> {
+    for header in cols {
+        let col = match Col::parse(&header.value) {
+            Err(_) => continue,
+            Ok(col) => col,
+        };
+        let column = || header.name.to_string();
+        let mut values = fields
+            .iter()
+            .filter(|(name, _)| **name == *header.name)
+            .map(|(_, value)| *value);
+        let value = match values.next() {
+            None if col.optional || col.list || col.default.is_some() => continue,
+            None => {
+                return Err(XRVErr::MissingColumn {
+                    table: table.to_owned(),
+                    column: column(),
+                })
+            }
+            Some(value) => value,
+        };
+        if !col.list && values.next().is_some() {
+            return Err(XRVErr::RepeatedColumn {
+                table: table.to_owned(),
+                column: column(),
+            });
+        }
+        for value in std::iter::once(value).chain(values) {
+            if value.is_empty() {
+                if col.optional || allow_empty {
+                    continue;
+                }
+                return Err(XRVErr::EmptyColumn {
+                    table: table.to_owned(),
+                    column: column(),
+                });
+            }
+            if col.kind == ColKind::Bytes {
+                if let Err(pos) = base64::decode(value) {
+                    return Err(XRVErr::InvalidBase64 {
+                        column: column(),
+                        pos,
+                    });
+                }
+            }
+            let unit = match col.kind {
+                ColKind::Size => parse_size(value).err(),
+                ColKind::Duration => parse_duration(value).err(),
+                _ => None,
+            };
+            if let Some(err) = unit {
+                return Err(unit_error(err, table, &column(), value, col.kind));
+            }
+            if let (Some((min, max)), false) = (col.bounds, col.accepts(value)) {
+                return Err(XRVErr::ValueOutOfRange {
+                    table: table.to_owned(),
+                    column: column(),
+                    value: value.to_owned(),
+                    min,
+                    max,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TableSchema {
+    name: String,
+    cols: Vec<(String, Col)>,
+}
+
+impl TableSchema {
+    pub fn col<C: Into<Col>>(mut self, name: &str, col: C) -> TableSchema {
+        self.cols.push((name.to_owned(), col.into()));
+        self
+    }
+
+    /// Declares the columns `build` adds under the dotted names
+    /// `name.col`, the way structured values are kept flat in a line.
+    /// Groups nest, and a column named like a group fails the build with
+    /// `XRVErr::ColumnGroupCollision`.
+    ///
+    /// ```
+    /// use xrave::{ColKind, Schema};
+    ///
+    /// let document = Schema::new()
+    ///     .table("users", |t| t.group("addr", |g| g.col("city", ColKind::String)))
+    ///     .build()?;
+    /// assert_eq!(&*document.table("users").unwrap().cols[0].name, "addr.city");
+    /// # Ok::<(), xrave::XRVErr>(())
+    /// ```
+    pub fn group<F>(mut self, name: &str, build: F) -> TableSchema
+    where
+        F: FnOnce(TableSchema) -> TableSchema,
+    {
+        let group = build(TableSchema {
+            name: self.name.clone(),
+            cols: Vec::new(),
+        });
+        self.cols.extend(
+            group
+                .cols
+                .into_iter()
+                .map(|(col, decl)| (format!("{}{}{}", name, GROUP_SEPARATOR, col), decl)),
+        );
+        self
+    }
+}
+
+/// Separator between a group and its columns in dotted column names.
+pub(crate) const GROUP_SEPARATOR: char = '.';
+
+/// Whether `name` is a column of the group `group`, `addr.city` of `addr`.
+pub(crate) fn in_group(name: &str, group: &str) -> bool {
+    name.strip_prefix(group)
+        .is_some_and(|rest| rest.starts_with(GROUP_SEPARATOR))
+}
+
+/// Declares tables and their columns for a new, empty `Document`.
+///
+/// ```
+/// use xrave::{ColKind, Schema};
+///
+/// let mut document = Schema::new()
+///     .table("users", |t| t.col("age", ColKind::I32).col("name", ColKind::String))
+///     .build()?;
+/// document.add_record("users", "1", &[("age", "31"), ("name", "Anna Smith")])?;
+/// document.save(std::env::temp_dir().join("xrave-doc-schema.xrv"))?;
+/// # Ok::<(), xrave::XRVErr>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    tables: Vec<TableSchema>,
+}
+
+impl Schema {
+    pub fn new() -> Schema {
+        Schema::default()
+    }
+
+    pub fn table<F>(mut self, name: &str, build: F) -> Schema
+    where
+        F: FnOnce(TableSchema) -> TableSchema,
+    {
+        self.tables.push(build(TableSchema {
+            name: name.to_owned(),
+            cols: Vec::new(),
+        }));
+        self
+    }
+
+    /// Checks that table names and each table's column names are unique
+    /// identifiers, no column named like a group of dotted ones, table
+    /// names without empty `.` segments, and returns a document with the declared, empty tables.
+    pub fn build(self) -> Result<Document, XRVErr> {
+        let mut document = Document::default();
+        for table in self.tables {
+            validate_table_name(&table.name)?;
+            if document.table(&table.name).is_some() {
+                return Err(XRVErr::DuplicateTable(table.name));
+            }
+            let mut cols: Vec<OwnedField> = Vec::with_capacity(table.cols.len());
+            for (name, col) in table.cols {
+                validate_identifier(&name)?;
+                if cols.iter().any(|col| *col.name == *name) {
+                    return Err(XRVErr::DuplicateColumn {
+                        table: table.name,
+                        column: name,
+                    });
+                }
+                if let Some(col) = cols
+                    .iter()
+                    .find(|col| in_group(&name, &col.name) || in_group(&col.name, &name))
+                {
+                    let column = match in_group(&name, &col.name) {
+                        true => col.name.to_string(),
+                        false => name,
+                    };
+                    return Err(XRVErr::ColumnGroupCollision {
+                        table: table.name,
+                        column,
+                    });
+                }
+                cols.push(OwnedField {
+                    name: Arc::from(name),
+                    value: col.to_string(),
+                    null: false,
+                });
+            }
+            document
+                .tables
+                .push(DocumentTable::new(&table.name, cols, Vec::new()));
+        }
+        Ok(document)
+    }
+}
+
+/// One way the table headers of a file deviate from a `Schema`, as
+/// reported by `Reader::with_schema`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaDiff {
+    MissingTable(String),
+    ExtraTable(String),
+    MissingColumn {
+        table: String,
+        column: String,
+    },
+    ExtraColumn {
+        table: String,
+        column: String,
+    },
+    /// A column declared with another kind, or with other markers, bounds
+    /// or default.
+    KindChanged {
+        table: String,
+        column: String,
+        expected: Col,
+        found: Col,
+    },
+}
+
+impl fmt::Display for SchemaDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaDiff::MissingTable(table) => write!(f, "missing table '{}'", table),
+            SchemaDiff::ExtraTable(table) => write!(f, "extra table '{}'", table),
+            SchemaDiff::MissingColumn { table, column } => {
+                write!(f, "missing column '{}' in table '{}'", column, table)
+            }
+            SchemaDiff::ExtraColumn { table, column } => {
+                write!(f, "extra column '{}' in table '{}'", column, table)
+            }
+            SchemaDiff::KindChanged {
+                table,
+                column,
+                expected,
+                found,
+            } => write!(
+                f,
+                "column '{}' in table '{}' is declared '{}', expected '{}'",
+                column, table, found, expected
+            ),
+        }
+    }
+}
+
+impl Schema {
+    /// Compares `tables` against the declared tables, returning every
+    /// deviation. A column whose declaration does not parse, such as one
+    /// without a kind, matches any declaration and takes the schema's.
+    pub(crate) fn lock_tables(&self, tables: &mut OrderedMap<OwnedTableLine>) -> Vec<SchemaDiff> {
+        let mut diffs = Vec::new();
+        for expected in self.tables.iter() {
+            let table = match tables.get_mut(&expected.name) {
+                None => {
+                    diffs.push(SchemaDiff::MissingTable(expected.name.clone()));
+                    continue;
+                }
+                Some(table) => table,
+            };
+            for (name, col) in expected.cols.iter() {
+                let field = match table.cols.iter_mut().find(|field| *field.name == **name) {
+                    None => {
+                        diffs.push(SchemaDiff::MissingColumn {
+                            table: expected.name.clone(),
+                            column: name.clone(),
+                        });
+                        continue;
+                    }
+                    Some(field) => field,
+                };
+                match Col::parse(&field.value) {
+                    Err(_) => field.value = col.to_string(),
+                    Ok(found) if found == *col => {}
+                    Ok(found) => diffs.push(SchemaDiff::KindChanged {
+                        table: expected.name.clone(),
+                        column: name.clone(),
+                        expected: col.clone(),
+                        found,
+                    }),
+                }
+            }
+            for field in table.cols.iter() {
+                if !expected.cols.iter().any(|(name, _)| *field.name == **name) {
+                    diffs.push(SchemaDiff::ExtraColumn {
+                        table: expected.name.clone(),
+                        column: field.name.to_string(),
+                    });
+                }
+            }
+        }
+        for name in tables.keys() {
+            if !self.tables.iter().any(|table| table.name == *name) {
+                diffs.push(SchemaDiff::ExtraTable(name.clone()));
+            }
+        }
+        diffs
+    }
+}
+
+/// Table and column names must be non-empty and free of `"` and control
+/// characters such as line breaks. Names with spaces or colons are written
+/// quoted.
+pub(crate) fn validate_identifier(name: &str) -> Result<(), XRVErr> {
+    let invalid = name.is_empty() || name.chars().any(|c| c == '"' || c.is_control());
+    if invalid {
+        return Err(XRVErr::InvalidIdentifier(name.to_owned()));
+    }
+    Ok(())
+}
+
+/// Table names are identifiers whose `.`-separated namespace segments are
+/// all non-empty, rejecting `a..b`, `.a` and `a.`.
+pub(crate) fn validate_table_name(name: &str) -> Result<(), XRVErr> {
+    validate_identifier(name)?;
+    if name.split(NAMESPACE_SEPARATOR).any(str::is_empty) {
+        return Err(XRVErr::InvalidIdentifier(name.to_owned()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn col_declarations_parse_print_and_bound_values() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Col, ColKind, Schema, XRVErr};
+
+        let col = Col::parse("i32[-40..60]")?;
+        assert_eq!(col, ColKind::I32.bounded(-40.0, 60.0));
+        assert_eq!(col.to_string(), "i32[-40..60]");
+        assert!(Col::parse("i32[-40-60]").is_err());
+        assert!(Col::parse("i32[60..-40]").is_err());
+        assert!(Col::parse("str[0..1]").is_err());
+        assert_eq!(
+            Col::parse("str=in review")?,
+            ColKind::String.with_default("in review")
+        );
+        assert_eq!(Col::parse("i32!")?, ColKind::I32.key());
+        assert!(Col::parse("i32!?").is_err());
+        assert_eq!(
+            Col::parse("i32?->users")?,
+            ColKind::I32.optional().references("users")
+        );
+        assert_eq!(Col::parse("[bytes]?")?, ColKind::Bytes.list().optional());
+        assert_eq!(ColKind::Bytes.list().optional().to_string(), "[bytes]?");
+
+        let mut document = Schema::new()
+            .table("users", |t| t.col("age", ColKind::I32.bounded(0.0, 150.0)))
+            .build()?;
+        document.add_record("users", "1", &[("age", "0")])?;
+        document.add_record("users", "2", &[("age", "150")])?;
+        assert!(matches!(
+            document.add_record("users", "3", &[("age", "-1")]),
+            Err(XRVErr::ValueOutOfRange { .. })
+        ));
+        assert!(matches!(
+            document.add_record("users", "4", &[("age", "151")]),
+            Err(XRVErr::ValueOutOfRange { .. })
+        ));
+        assert_eq!(document.table("users").unwrap().len(), 2);
+
+        let mut out = Vec::new();
+        document.write(&mut out, Default::default())?;
+        assert!(String::from_utf8(out)?.contains(" age:i32[0..150]"));
+        Ok(())
+    }
+
+    #[test]
+    fn groups_nest_into_dotted_names() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{ColKind, Schema, XRVErr};
+
+        let document = Schema::new()
+            .table("users", |t| {
+                t.col("name", ColKind::String).group("addr", |g| {
+                    g.col("city", ColKind::String)
+                        .group("geo", |g| g.col("lat", ColKind::F64))
+                })
+            })
+            .build()?;
+        let cols: Vec<_> = document
+            .table("users")
+            .unwrap()
+            .cols
+            .iter()
+            .map(|c| &*c.name)
+            .collect();
+        assert_eq!(cols, ["name", "addr.city", "addr.geo.lat"]);
+
+        let collision = Schema::new()
+            .table("users", |t| {
+                t.col("addr", ColKind::String)
+                    .group("addr", |g| g.col("city", ColKind::String))
+            })
+            .build();
+        assert!(matches!(
+            collision,
+            Err(XRVErr::ColumnGroupCollision { ref column, .. }) if column == "addr"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn built_documents_save_and_read_back() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{ColKind, Reader, Schema};
+
+        let mut document = Schema::new()
+            .table("users", |t| {
+                t.col("age", ColKind::I32).col("name", ColKind::String)
+            })
+            .table("tags", |t| t.col("label", ColKind::String))
+            .build()?;
+        document.add_record("users", "1", &[("age", "31"), ("name", "Anna Smith")])?;
+        document.add_record("tags", "t1", &[("label", "admin")])?;
+
+        let path = std::env::temp_dir().join("xrave-test-schema.xrv");
+        document.save(&path)?;
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        reader.load_headers()?;
+        let kinds: Vec<_> = reader
+            .table("users")
+            .unwrap()
+            .cols
+            .iter()
+            .map(|col| ColKind::parse(&col.value))
+            .collect();
+        assert_eq!(kinds, [Some(ColKind::I32), Some(ColKind::String)]);
+        assert_eq!(reader.records("users")?.count(), 1);
+        Ok(())
+    }
+}
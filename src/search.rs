@@ -0,0 +1,296 @@
+use std::collections::VecDeque;
+
+use crate::{
+    dictionary::expand_record,
+    error::XRVErr,
+    intern::Interner,
+    line::{decode_record, LineKind, Span},
+    reader::Reader,
+    tokenizer::RawLine,
+};
+
+/// Narrows `Reader::search`. Every table and column is searched when left
+/// as `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub tables: Option<Vec<String>>,
+    pub columns: Option<Vec<String>>,
+    /// Match ASCII letters regardless of case.
+    pub case_insensitive: bool,
+}
+
+/// A field value holding the needle, yielded by `Search`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub table: String,
+    /// Id of the record holding the value.
+    pub record_key: String,
+    pub column: String,
+    /// Location of the record line.
+    pub span: Span,
+    pub value: String,
+}
+
+/// Iterator over the matches of `Reader::search`, in file order within each
+/// table. Stops after the first error.
+#[derive(Debug)]
+pub struct Search<'r> {
+    reader: &'r mut Reader,
+    needle: Vec<u8>,
+    options: SearchOptions,
+    /// Whether the needle reads the same in the raw line, which it does not
+    /// when it holds a character that is written escaped.
+    prefilter: bool,
+    /// Tables still to search, with the end of their region.
+    tables: VecDeque<(String, u64)>,
+    current: Option<(String, u64)>,
+    pending: VecDeque<Match>,
+    tokenized: u64,
+    done: bool,
+}
+
+impl Reader {
+    /// Finds the field values holding `needle` across the tables of the
+    /// file. Each raw line is checked for the needle before anything else,
+    /// so only lines holding it are tokenized and decoded; a needle with a
+    /// newline, tab, quote or backslash, which values hold escaped, turns
+    /// that check off.
+    ///
+    /// ```
+    /// use xrave::{Reader, SearchOptions, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-search.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("name", "str"), ("city", "str")])?;
+    /// writer.add_record("users", "anna", &[("name", "Anna"), ("city", "Oslo")])?;
+    /// writer.add_record("users", "bob", &[("name", "Bob"), ("city", "Bergen")])?;
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(&path)?;
+    /// let hits = reader.search("Bergen", SearchOptions::default())?.collect::<Result<Vec<_>, _>>()?;
+    /// assert_eq!((hits.len(), hits[0].record_key.as_str(), hits[0].column.as_str()), (1, "bob", "city"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn search(&mut self, needle: &str, options: SearchOptions) -> Result<Search<'_>, XRVErr> {
+        let mut tables = VecDeque::new();
+        match &options.tables {
+            None => {
+                for (name, (seek, len)) in self.jumps.iter() {
+                    tables.push_back((name.clone(), (*seek + *len) as u64));
+                }
+            }
+            Some(names) => {
+                for name in names {
+                    match self.jumps.get(name) {
+                        None => return Err(XRVErr::UnknownTable(name.clone())),
+                        Some((seek, len)) => {
+                            tables.push_back((name.clone(), (*seek + *len) as u64))
+                        }
+                    }
+                }
+            }
+        }
+        let mut needle = needle.as_bytes().to_vec();
+        if options.case_insensitive {
+            needle.make_ascii_lowercase();
+        }
+        let quote = self.options.dialect.quote;
+        // Values held as dictionary tokens are not in the raw lines.
+        self.ensure_headers()?;
+        let prefilter = self.dictionaries.is_empty()
+            && !needle
+                .iter()
+                .any(|byte| *byte == quote || matches!(byte, b'\n' | b'\r' | b'\t' | b'\\'));
+        Ok(Search {
+            reader: self,
+            needle,
+            options,
+            prefilter,
+            tables,
+            current: None,
+            pending: VecDeque::new(),
+            tokenized: 0,
+            done: false,
+        })
+    }
+}
+
+impl<'r> Search<'r> {
+    /// Lines tokenized so far: those whose raw bytes held the needle.
+    pub fn lines_tokenized(&self) -> u64 {
+        self.tokenized
+    }
+
+    fn contains(&self, haystack: &[u8]) -> bool {
+        if self.needle.is_empty() {
+            return true;
+        }
+        haystack
+            .windows(self.needle.len())
+            .any(|window| match self.options.case_insensitive {
+                true => window.eq_ignore_ascii_case(&self.needle),
+                false => window == self.needle.as_slice(),
+            })
+    }
+
+    /// Reads the next line of the current table, queueing its matches.
+    /// Returns `false` once the table is exhausted.
+    fn scan_line(&mut self, table: &str, end: u64) -> Result<bool, XRVErr> {
+        let reader = &mut *self.reader;
+        if reader.buffer.offset >= end
+            || reader
+                .buffer
+                .read_line(&mut reader.source, reader.options.max_line_len)?
+                == 0
+        {
+            return Ok(false);
+        }
+        if reader.buffer.is_filler(&mut reader.source)? {
+            return Ok(true);
+        }
+        if self.prefilter && !self.contains(&self.reader.buffer.buffer) {
+            return Ok(true);
+        }
+
+        self.tokenized += 1;
+        let reader = &mut *self.reader;
+        let raw_line: RawLine = (&reader.buffer).try_into()?;
+        if raw_line.kind != LineKind::Record {
+            return Ok(true);
+        }
+        let mut record =
+            decode_record(raw_line, reader.options.encoding, &mut Interner::new(false))?;
+        if let Some(dictionary) = reader.dictionaries.get(table) {
+            if let Err(err) = expand_record(dictionary, table, &mut record) {
+                return Err(reader.buffer.diagnose(1, err));
+            }
+        }
+        let span = reader.buffer.span();
+        for field in record.cols.iter() {
+            if let Some(columns) = &self.options.columns {
+                if !columns.iter().any(|column| **column == *field.name) {
+                    continue;
+                }
+            }
+            if self.contains(field.value.as_bytes()) {
+                self.pending.push_back(Match {
+                    table: table.to_owned(),
+                    record_key: record.id.clone(),
+                    column: field.name.to_string(),
+                    span,
+                    value: field.value.clone(),
+                });
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl<'r> Iterator for Search<'r> {
+    type Item = Result<Match, XRVErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(hit) = self.pending.pop_front() {
+                return Some(Ok(hit));
+            }
+            if self.done {
+                return None;
+            }
+            let (table, end) = match self.current.take() {
+                Some(current) => current,
+                None => match self.tables.pop_front() {
+                    None => {
+                        self.done = true;
+                        return None;
+                    }
+                    Some((table, end)) => {
+                        if let Err(err) = self.reader.seek_to(&table) {
+                            self.done = true;
+                            return Some(Err(err));
+                        }
+                        (table, end)
+                    }
+                },
+            };
+            match self.scan_line(&table, end) {
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+                Ok(false) => {}
+                Ok(true) => self.current = Some((table, end)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn search_tokenizes_only_matching_lines() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Reader, SearchOptions, Writer};
+
+        let path = std::env::temp_dir().join("xrave-test-search.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("users", &[("name", "str"), ("city", "str")])?;
+        writer.add_table("notes", &[("text", "str")])?;
+        for id in 0..100 {
+            writer.add_record(
+                "users",
+                &id.to_string(),
+                &[("name", "Anna"), ("city", "Oslo")],
+            )?;
+        }
+        writer.add_record("users", "bob", &[("name", "Bob"), ("city", "Bergen")])?;
+        writer.add_record("notes", "n1", &[("text", "met BOB in bergen")])?;
+        writer.finish()?;
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        let mut search = reader.search("bergen", SearchOptions::default())?;
+        let hits: Vec<_> = search
+            .by_ref()
+            .map(|hit| hit.map(|hit| (hit.table, hit.record_key, hit.column, hit.value)))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(
+            hits,
+            [(
+                "notes".into(),
+                "n1".into(),
+                "text".into(),
+                "met BOB in bergen".into()
+            )]
+        );
+        assert_eq!(search.lines_tokenized(), 1);
+
+        let options = SearchOptions {
+            case_insensitive: true,
+            ..SearchOptions::default()
+        };
+        let mut search = reader.search("bob", options)?;
+        let hits: Vec<_> = search.by_ref().map(|hit| hit.unwrap().record_key).collect();
+        assert_eq!(hits, ["bob", "n1"]);
+        assert_eq!(search.lines_tokenized(), 2);
+
+        let options = SearchOptions {
+            tables: Some(vec!["users".into()]),
+            columns: Some(vec!["city".into()]),
+            case_insensitive: true,
+        };
+        let hits: Vec<_> = reader
+            .search("B", options)?
+            .map(|hit| hit.unwrap().value)
+            .collect();
+        assert_eq!(hits, ["Bergen"]);
+        assert!(reader
+            .search(
+                "x",
+                SearchOptions {
+                    tables: Some(vec!["orders".into()]),
+                    ..SearchOptions::default()
+                }
+            )
+            .is_err());
+        Ok(())
+    }
+}
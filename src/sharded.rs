@@ -0,0 +1,394 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{open_error, XRVErr},
+    tokenizer::RECORD_ID,
+    writer::{write_line, Writer, WriterOptions},
+};
+
+/// File name part of the shard for records without a value in the
+/// `ShardBy::ColumnValue` column.
+const NULL_SHARD: &str = "_null";
+/// Longest value kept in a shard file name.
+const MAX_NAME_VALUE_LEN: usize = 64;
+
+/// How a `ShardedWriter` spreads records over files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShardBy {
+    /// Starts a new file once the record lines of the current one would
+    /// pass this many bytes. A record larger than the limit still gets a
+    /// file of its own.
+    MaxBytes(u64),
+    /// One file per value of the named column. Records whose table lacks
+    /// the column, or with the value empty or left out, go to the `_null`
+    /// file.
+    ColumnValue(String),
+}
+
+/// A file written by a `ShardedWriter`, as listed by
+/// `ShardedWriter::finish`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShardFile {
+    pub path: PathBuf,
+    pub records: usize,
+    /// The column value the file holds the records of, `None` for the
+    /// `_null` file and for `ShardBy::MaxBytes`.
+    pub value: Option<String>,
+}
+
+#[derive(Debug)]
+struct Shard {
+    file: ShardFile,
+    writer: Option<Writer<File>>,
+    bytes: u64,
+}
+
+/// Writes one dataset as several `.xrv` files in `dir`, named
+/// `{prefix}-0001.xrv`, `{prefix}-0002.xrv`, ... when sharding by size and
+/// `{prefix}-{value}.xrv` when sharding by column value. Every file declares
+/// all tables and styles, so the shards read back as one with
+/// `MultiReader`; each gets its own jumps line on `finish`.
+///
+/// ```
+/// use xrave::{MultiReader, ShardBy, ShardedWriter};
+///
+/// let dir = std::env::temp_dir().join("xrave-doc-sharded");
+/// let _ = std::fs::remove_dir_all(&dir);
+/// std::fs::create_dir_all(&dir)?;
+///
+/// let mut writer = ShardedWriter::new(&dir, "data", ShardBy::MaxBytes(64));
+/// writer.add_table("users", &[("name", "str")])?;
+/// for id in 0..10 {
+///     writer.add_record("users", &id.to_string(), &[("name", "user")])?;
+/// }
+/// assert_eq!(writer.finish()?.len(), 3);
+///
+/// let mut shards = MultiReader::open_glob(&dir, "data-*.xrv")?;
+/// assert_eq!(shards.records("users")?.count(), 10);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct ShardedWriter {
+    dir: PathBuf,
+    prefix: String,
+    by: ShardBy,
+    options: WriterOptions,
+    /// Checks declarations once, before they are copied to every shard.
+    template: Writer<std::io::Sink>,
+    tables: Vec<(String, Vec<(String, String)>)>,
+    styles: Vec<(String, Vec<(String, String)>)>,
+    shards: Vec<Shard>,
+    /// Shard of each column value, the `_null` shard under `None`.
+    by_value: HashMap<Option<String>, usize>,
+    line: Vec<u8>,
+}
+
+impl ShardedWriter {
+    pub fn new<P: AsRef<Path>>(dir: P, prefix: &str, by: ShardBy) -> ShardedWriter {
+        ShardedWriter::with_options(dir, prefix, by, WriterOptions::default())
+    }
+
+    pub fn with_options<P: AsRef<Path>>(
+        dir: P,
+        prefix: &str,
+        by: ShardBy,
+        options: WriterOptions,
+    ) -> ShardedWriter {
+        ShardedWriter {
+            dir: dir.as_ref().to_path_buf(),
+            prefix: prefix.to_owned(),
+            by,
+            template: Writer::with_options(std::io::sink(), options.clone()),
+            options,
+            tables: Vec::new(),
+            styles: Vec::new(),
+            shards: Vec::new(),
+            by_value: HashMap::new(),
+            line: Vec::new(),
+        }
+    }
+
+    /// Declares a table in every shard, as `Writer::add_table` does.
+    pub fn add_table(&mut self, name: &str, cols: &[(&str, &str)]) -> Result<(), XRVErr> {
+        self.template.add_table(name, cols)?;
+        for writer in self
+            .shards
+            .iter_mut()
+            .filter_map(|shard| shard.writer.as_mut())
+        {
+            writer.add_table(name, cols)?;
+        }
+        self.tables.push((name.to_owned(), owned_pairs(cols)));
+        Ok(())
+    }
+
+    /// Declares a style in every shard, as `Writer::add_style` does.
+    pub fn add_style(&mut self, id: &str, fields: &[(&str, &str)]) -> Result<(), XRVErr> {
+        self.template.add_style(id, fields)?;
+        for writer in self
+            .shards
+            .iter_mut()
+            .filter_map(|shard| shard.writer.as_mut())
+        {
+            writer.add_style(id, fields)?;
+        }
+        self.styles.push((id.to_owned(), owned_pairs(fields)));
+        Ok(())
+    }
+
+    /// Adds a record to the shard `ShardBy` picks for it, creating that
+    /// shard's file when it is the first record there.
+    pub fn add_record(
+        &mut self,
+        table: &str,
+        id: &str,
+        fields: &[(&str, &str)],
+    ) -> Result<(), XRVErr> {
+        if !self.tables.iter().any(|(name, _)| name == table) {
+            return Err(XRVErr::UnknownTable(table.to_owned()));
+        }
+        let idx = match self.by {
+            ShardBy::MaxBytes(max) => self.size_shard(id, fields, max)?,
+            ShardBy::ColumnValue(ref column) => {
+                let value = fields
+                    .iter()
+                    .find(|(name, _)| name == column)
+                    .map(|(_, value)| *value)
+                    .filter(|value| !value.is_empty())
+                    .map(str::to_owned);
+                self.value_shard(value)?
+            }
+        };
+        // Size shards are closed as the next one starts and never picked
+        // again, so the writer is always there.
+        let shard = &mut self.shards[idx];
+        match shard.writer.as_mut() {
+            None => return Err(XRVErr::UnknownTable(table.to_owned())),
+            Some(writer) => writer.add_record(table, id, fields)?,
+        }
+        shard.file.records += 1;
+        Ok(())
+    }
+
+    /// The shard a record line of `id` and `fields` goes to under
+    /// `ShardBy::MaxBytes(max)`, closing the last one and starting the next
+    /// when the line does not fit.
+    fn size_shard(&mut self, id: &str, fields: &[(&str, &str)], max: u64) -> Result<usize, XRVErr> {
+        self.line.clear();
+        write_line(&mut self.line, RECORD_ID, id, fields, self.options.dialect);
+        let len = self.line.len() as u64;
+        let fits = match self.shards.last() {
+            None => false,
+            Some(shard) => shard.file.records == 0 || shard.bytes + len <= max,
+        };
+        if !fits {
+            if let Some(shard) = self.shards.last_mut() {
+                close(shard)?;
+            }
+            let name = format!("{:04}", self.shards.len() + 1);
+            self.open_shard(&name, None)?;
+        }
+        let idx = self.shards.len() - 1;
+        self.shards[idx].bytes += len;
+        Ok(idx)
+    }
+
+    /// The shard for records with `value` in the `ShardBy::ColumnValue`
+    /// column, started on its first record.
+    fn value_shard(&mut self, value: Option<String>) -> Result<usize, XRVErr> {
+        if let Some(idx) = self.by_value.get(&value) {
+            return Ok(*idx);
+        }
+        let name = self.value_file_name(value.as_deref());
+        self.open_shard(&name, value.clone())?;
+        let idx = self.shards.len() - 1;
+        self.by_value.insert(value, idx);
+        Ok(idx)
+    }
+
+    /// Writes out every shard still open and lists the files created, in
+    /// the order they were started.
+    pub fn finish(mut self) -> Result<Vec<ShardFile>, XRVErr> {
+        for shard in self.shards.iter_mut() {
+            close(shard)?;
+        }
+        Ok(self.shards.into_iter().map(|shard| shard.file).collect())
+    }
+
+    /// File name part for the shard of `value`: its ASCII letters, digits,
+    /// `-` and `_` kept and every other character turned into `_`, cut to
+    /// `MAX_NAME_VALUE_LEN`, with a counter added when another value
+    /// already came out the same.
+    fn value_file_name(&self, value: Option<&str>) -> String {
+        let base: String = match value {
+            None => return NULL_SHARD.to_owned(),
+            Some(value) => value
+                .chars()
+                .take(MAX_NAME_VALUE_LEN)
+                .map(
+                    |c| match c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                        true => c,
+                        false => '_',
+                    },
+                )
+                .collect(),
+        };
+        let taken = |name: &str| {
+            let path = self.shard_path(name);
+            self.shards.iter().any(|shard| shard.file.path == path)
+        };
+        let mut name = base.clone();
+        let mut count = 1;
+        while name == NULL_SHARD || taken(&name) {
+            count += 1;
+            name = format!("{}-{}", base, count);
+        }
+        name
+    }
+
+    fn shard_path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}-{}.xrv", self.prefix, name))
+    }
+
+    /// Creates the file for a new shard and declares every table and style
+    /// in it.
+    fn open_shard(&mut self, name: &str, value: Option<String>) -> Result<(), XRVErr> {
+        let path = self.shard_path(name);
+        let file = match File::create(&path) {
+            Err(err) => return Err(open_error(&path, err)),
+            Ok(file) => file,
+        };
+        let mut writer = Writer::with_options(file, self.options.clone());
+        for (name, cols) in self.tables.iter() {
+            writer.add_table(name, &borrowed_pairs(cols))?;
+        }
+        for (id, fields) in self.styles.iter() {
+            writer.add_style(id, &borrowed_pairs(fields))?;
+        }
+        self.shards.push(Shard {
+            file: ShardFile {
+                path,
+                records: 0,
+                value,
+            },
+            writer: Some(writer),
+            bytes: 0,
+        });
+        Ok(())
+    }
+}
+
+/// Lays out the shard's file and closes it, if it is still open.
+fn close(shard: &mut Shard) -> Result<(), XRVErr> {
+    match shard.writer.take() {
+        None => Ok(()),
+        Some(writer) => writer.finish().map(drop),
+    }
+}
+
+fn owned_pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+fn borrowed_pairs(pairs: &[(String, String)]) -> Vec<(&str, &str)> {
+    pairs
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn shards_split_by_size_and_by_value() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{MultiReader, Reader, ShardBy, ShardedWriter, XRVErr};
+
+        let dir = std::env::temp_dir().join("xrave-test-sharded");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+
+        let line = "r:000 name:user region:eu\n".len() as u64;
+        let mut writer = ShardedWriter::new(&dir, "data", ShardBy::MaxBytes(4 * line));
+        writer.add_table("users", &[("name", "str"), ("region", "str?")])?;
+        let regions = ["eu", "us", "eu", "", "ap/south", "us"];
+        for id in 0..10 {
+            let region = regions[id % regions.len()];
+            let fields = [("name", "user"), ("region", region)];
+            writer.add_record(
+                "users",
+                &format!("{:03}", id),
+                &fields[..if region.is_empty() { 1 } else { 2 }],
+            )?;
+        }
+        let files = writer.finish()?;
+        assert_eq!(
+            files.iter().map(|file| file.records).collect::<Vec<_>>(),
+            [4, 3, 3]
+        );
+        assert!(files[0].path.ends_with("data-0001.xrv"));
+
+        let mut shards = MultiReader::open_glob(&dir, "data-*.xrv")?;
+        let ids = shards
+            .records("users")?
+            .map(|record| Ok(record?.id))
+            .collect::<Result<Vec<_>, XRVErr>>()?;
+        assert_eq!(
+            ids,
+            (0..10).map(|id| format!("{:03}", id)).collect::<Vec<_>>()
+        );
+
+        let mut writer = ShardedWriter::new(&dir, "region", ShardBy::ColumnValue("region".into()));
+        writer.add_table("users", &[("name", "str"), ("region", "str?")])?;
+        writer.add_table("tags", &[("label", "str")])?;
+        for id in 0..10 {
+            let region = regions[id % regions.len()];
+            writer.add_record(
+                "users",
+                &id.to_string(),
+                &[("name", "user"), ("region", region)],
+            )?;
+        }
+        writer.add_record("tags", "t1", &[("label", "admin")])?;
+        let files = writer.finish()?;
+        let names: Vec<_> = files
+            .iter()
+            .map(|file| file.path.file_name().unwrap().to_owned())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "region-eu.xrv",
+                "region-us.xrv",
+                "region-_null.xrv",
+                "region-ap_south.xrv"
+            ]
+        );
+        assert_eq!(files[3].value.as_deref(), Some("ap/south"));
+
+        for file in files {
+            let mut reader = Reader::new(&file.path)?;
+            let records = reader.records("users")?.collect::<Result<Vec<_>, _>>()?;
+            let expected = file.records - usize::from(file.value.is_none());
+            assert_eq!(records.len(), expected);
+            for record in records {
+                let region = record
+                    .cols
+                    .iter()
+                    .find(|field| &*field.name == "region")
+                    .map(|field| field.value.as_str());
+                assert_eq!(
+                    region.filter(|region| !region.is_empty()),
+                    file.value.as_deref()
+                );
+            }
+        }
+        Ok(())
+    }
+}
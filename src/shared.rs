@@ -0,0 +1,338 @@
+use std::io::prelude::*;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufReader,
+    path::Path,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use crate::{
+    cache::RecordCache,
+    dictionary::expand_record,
+    error::{open_error, XRVErr},
+    intern::Interner,
+    line::{
+        decode_record, LineKind, OwnedRecordLine, OwnedStyleLine, OwnedTableLine, RECORD_KEY_FIELD,
+    },
+    ordered::OrderedMap,
+    reader::{ParseOptions, Reader, Source},
+    schema::key_column,
+    tokenizer::{key_link, link_value, RawLine, XraveBuffer},
+};
+
+/// A read-only view of a `.xrv` file that can be shared between threads.
+///
+/// Headers and jumps are parsed once on open. Every lookup reads through the
+/// one file handle with positioned reads, so no call needs `&mut self`. Key
+/// indexes are built on the first lookup of each table/column pair and kept
+/// behind a lock for later calls, and so are the records found when
+/// `ParseOptions::record_cache_entries` is set.
+#[derive(Debug)]
+pub struct SharedReader {
+    file: File,
+    options: ParseOptions,
+    version: u32,
+    jumps: OrderedMap<(usize, usize)>,
+    tables: OrderedMap<OwnedTableLine>,
+    styles: OrderedMap<OwnedStyleLine>,
+    dictionaries: OrderedMap<OrderedMap<String>>,
+    indexes: RwLock<HashMap<(String, String), HashMap<String, u64>>>,
+    names: Mutex<Interner>,
+    cache: Mutex<RecordCache>,
+}
+
+// Sharing across threads is the point of the type; keep it checked.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<SharedReader>();
+};
+
+impl SharedReader {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<SharedReader, XRVErr> {
+        SharedReader::with_options(path, ParseOptions::default())
+    }
+
+    pub fn with_options<P: AsRef<Path>>(
+        path: P,
+        options: ParseOptions,
+    ) -> Result<SharedReader, XRVErr> {
+        let mut reader = Reader::with_options(path, options)?;
+        reader.load_headers()?;
+        if !matches!(reader.source.get_ref(), Source::File(_)) {
+            return Err(XRVErr::SourceNotSeekable);
+        }
+        let path = reader.reopen_path()?;
+        let file = match File::open(path) {
+            Err(err) => return Err(open_error(path, err)),
+            Ok(file) => file,
+        };
+
+        Ok(SharedReader {
+            file,
+            options: reader.options,
+            version: reader.version,
+            jumps: reader.jumps,
+            tables: reader.tables,
+            styles: reader.styles,
+            dictionaries: reader.dictionaries,
+            indexes: RwLock::new(HashMap::new()),
+            names: Mutex::new(reader.names),
+            cache: Mutex::new(reader.cache),
+        })
+    }
+
+    pub fn tables(&self) -> &OrderedMap<OwnedTableLine> {
+        &self.tables
+    }
+
+    pub fn table(&self, name: &str) -> Option<&OwnedTableLine> {
+        self.tables.get(name)
+    }
+
+    pub fn styles(&self) -> &OrderedMap<OwnedStyleLine> {
+        &self.styles
+    }
+
+    pub fn style(&self, id: &str) -> Option<&OwnedStyleLine> {
+        self.styles.get(id)
+    }
+
+    /// Finds the record of `table` whose primary key equals `key`: the
+    /// column declared with `!` in the header, or the record id when the
+    /// table declares none.
+    pub fn record_by_primary_key(
+        &self,
+        table: &str,
+        key: &str,
+    ) -> Result<Option<OwnedRecordLine>, XRVErr> {
+        let col = self
+            .tables
+            .get(table)
+            .and_then(|header| key_column(&header.cols))
+            .unwrap_or(RECORD_KEY_FIELD);
+        self.record_by_key(table, col, key)
+    }
+
+    /// Finds the first record of `table` whose `col` field equals `key`.
+    /// `col` falls back to the record id when no field has that name.
+    pub fn record_by_key(
+        &self,
+        table: &str,
+        col: &str,
+        key: &str,
+    ) -> Result<Option<OwnedRecordLine>, XRVErr> {
+        let index_key = (table.to_owned(), col.to_owned());
+        let cached = match self.indexes.read() {
+            Err(_) => return Err(XRVErr::WorkerPanicked),
+            Ok(indexes) => indexes.get(&index_key).map(|index| index.get(key).copied()),
+        };
+        let offset = match cached {
+            Some(offset) => offset,
+            None => {
+                let index = self.build_index(table, col)?;
+                let offset = index.get(key).copied();
+                match self.indexes.write() {
+                    Err(_) => return Err(XRVErr::WorkerPanicked),
+                    Ok(mut indexes) => {
+                        indexes.entry(index_key).or_insert(index);
+                    }
+                }
+                offset
+            }
+        };
+
+        let offset = match offset {
+            None => return Ok(None),
+            Some(offset) => offset,
+        };
+        match self.cache.lock() {
+            Err(_) => return Err(XRVErr::WorkerPanicked),
+            Ok(mut cache) => {
+                if let Some(record) = cache.get(offset) {
+                    return Ok(Some(OwnedRecordLine::clone(&record)));
+                }
+            }
+        }
+        let record = self.read_record(table, offset)?;
+        match self.cache.lock() {
+            Err(_) => Err(XRVErr::WorkerPanicked),
+            Ok(mut cache) => {
+                if cache.is_enabled() {
+                    cache.insert(offset, Arc::new(record.clone()));
+                }
+                Ok(Some(record))
+            }
+        }
+    }
+
+    fn read_record(&self, table: &str, offset: u64) -> Result<OwnedRecordLine, XRVErr> {
+        let mut source = BufReader::new(PositionedReader {
+            file: &self.file,
+            pos: offset,
+        });
+        let mut buffer = XraveBuffer::new();
+        buffer.offset = offset;
+        buffer.limits = self.options.line_limits();
+        buffer.set_version(self.version);
+        buffer.read_line(&mut source, self.options.max_line_len)?;
+        let raw_line: RawLine = (&buffer).try_into()?;
+        match self.names.lock() {
+            Err(_) => Err(XRVErr::WorkerPanicked),
+            Ok(mut names) => {
+                let mut owned = decode_record(raw_line, self.options.encoding, &mut names)?;
+                owned.span = buffer.span();
+                if let Some(dictionary) = self.dictionaries.get(table) {
+                    if let Err(err) = expand_record(dictionary, table, &mut owned) {
+                        return Err(buffer.diagnose(1, err));
+                    }
+                }
+                Ok(owned)
+            }
+        }
+    }
+
+    fn build_index(&self, table: &str, col: &str) -> Result<HashMap<String, u64>, XRVErr> {
+        let (seek, len) = match self.jumps.get(table) {
+            None => return Err(XRVErr::UnknownTable(table.to_owned())),
+            Some(region) => *region,
+        };
+        let mut source = BufReader::new(PositionedReader {
+            file: &self.file,
+            pos: seek as u64,
+        })
+        .take(len as u64);
+        let mut buffer = XraveBuffer::new();
+        buffer.offset = seek as u64;
+        buffer.limits = self.options.line_limits();
+        buffer.set_version(self.version);
+
+        let mut index = HashMap::new();
+        loop {
+            let start = buffer.offset;
+            if buffer.read_line(&mut source, self.options.max_line_len)? == 0 {
+                break;
+            }
+            if buffer.is_filler(&mut source)? {
+                continue;
+            }
+            let raw_line: RawLine = (&buffer).try_into()?;
+            if raw_line.kind == LineKind::Tombstone {
+                continue;
+            }
+            if raw_line.kind != LineKind::Record {
+                return Err(XRVErr::NotRecordLine);
+            }
+            let (line, limits) = (&buffer.buffer, buffer.limits);
+            if let Some(link) = key_link(line, col.as_bytes(), limits) {
+                let quote = limits.dialect.quote;
+                let key = link_value(line, buffer.line, &link, quote, self.options.encoding)?;
+                index.entry(key).or_insert(start);
+            }
+        }
+        Ok(index)
+    }
+}
+
+/// `Read` over a shared `File` using positioned reads, so several threads can
+/// read through one handle without moving each other's cursor.
+struct PositionedReader<'f> {
+    file: &'f File,
+    pos: u64,
+}
+
+impl<'f> Read for PositionedReader<'f> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        #[cfg(unix)]
+        let read = std::os::unix::fs::FileExt::read_at(self.file, buf, self.pos)?;
+        #[cfg(windows)]
+        let read = std::os::windows::fs::FileExt::seek_read(self.file, buf, self.pos)?;
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, thread};
+
+    use super::*;
+    use crate::writer::Writer;
+
+    const USERS: usize = 2_000;
+    const THREADS: usize = 8;
+
+    fn fixture(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("xrave-test-shared-{}.xrv", name));
+        let mut writer = Writer::new(File::create(&path).unwrap());
+        writer
+            .add_table("users", &[("name", "str"), ("email", "str")])
+            .unwrap();
+        writer.add_table("events", &[("user", "str")]).unwrap();
+        for id in 0..USERS {
+            let (name, email) = (format!("user {}", id), format!("u{}@example.com", id));
+            let id = id.to_string();
+            writer
+                .add_record("users", &id, &[("name", &name), ("email", &email)])
+                .unwrap();
+            writer.add_record("events", &id, &[("user", &id)]).unwrap();
+        }
+        writer.finish().unwrap();
+        path
+    }
+
+    fn read_from_threads(reader: SharedReader) {
+        let reader = Arc::new(reader);
+        let workers: Vec<_> = (0..THREADS)
+            .map(|worker| {
+                let reader = Arc::clone(&reader);
+                thread::spawn(move || {
+                    // Each worker walks its own stride of ids, alternating
+                    // tables and lookups so reads from all threads interleave.
+                    for id in (worker..USERS).step_by(THREADS) {
+                        let key = id.to_string();
+                        let user = reader
+                            .record_by_primary_key("users", &key)
+                            .unwrap()
+                            .unwrap();
+                        assert_eq!(user.cols[0].value, format!("user {}", id));
+
+                        let event = reader
+                            .record_by_key("events", "user", &key)
+                            .unwrap()
+                            .unwrap();
+                        assert_eq!(event.id, key);
+
+                        let email = format!("u{}@example.com", id);
+                        let user = reader
+                            .record_by_key("users", "email", &email)
+                            .unwrap()
+                            .unwrap();
+                        assert_eq!(user.id, key);
+                    }
+                    assert!(reader
+                        .record_by_primary_key("users", "missing")
+                        .unwrap()
+                        .is_none());
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn threads_read_interleaved() {
+        read_from_threads(SharedReader::new(fixture("threads")).unwrap());
+    }
+
+    #[test]
+    fn threads_read_interleaved_through_the_cache() {
+        let options = ParseOptions {
+            record_cache_entries: 64,
+            ..ParseOptions::default()
+        };
+        read_from_threads(SharedReader::with_options(fixture("threads-cache"), options).unwrap());
+    }
+}
@@ -0,0 +1,455 @@
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fs::File,
+    io::BufReader,
+    mem::size_of,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    document::{Document, DocumentTable},
+    error::{open_error, XRVErr},
+    line::{OwnedField, OwnedRecordLine},
+    reader::Reader,
+};
+
+/// How much memory `Document::open_with_options` may spend on records.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadOptions {
+    /// Most bytes the records may take, estimated from the length of every
+    /// id, field name and value plus a fixed overhead per record and field;
+    /// unlimited when `None`. Loading fails with
+    /// `XRVErr::MemoryBudgetExceeded` once over it.
+    pub max_memory_bytes: Option<usize>,
+    /// Once over budget, keep only the id and span of each record and read
+    /// its fields from the file again when asked for, rather than failing.
+    /// Loading still fails if the ids alone outgrow the budget.
+    pub spill: bool,
+}
+
+/// Reader of the file a spilled document was loaded from, shared by its
+/// tables, and the offsets of the record lines whose fields were dropped.
+#[derive(Debug, Clone)]
+pub(crate) struct Backing {
+    reader: Arc<Mutex<Reader>>,
+    spilled: HashSet<u64>,
+}
+
+impl PartialEq for Backing {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.reader, &other.reader) && self.spilled == other.spilled
+    }
+}
+
+impl Eq for Backing {}
+
+/// Estimate of the bytes the records of a document being loaded take.
+#[derive(Debug)]
+pub(crate) struct Budget {
+    max: usize,
+    spill: bool,
+    used: usize,
+    pub(crate) spilled: bool,
+    /// Offsets of the record lines cut down to their id and span.
+    offsets: HashSet<u64>,
+}
+
+impl Budget {
+    /// Counts `record` against the budget. Once over it when spilling,
+    /// every record loaded so far and every one after is cut down to its id
+    /// and span.
+    pub(crate) fn admit(
+        &mut self,
+        tables: &mut [DocumentTable],
+        record: &mut OwnedRecordLine,
+    ) -> Result<(), XRVErr> {
+        if self.spilled {
+            record.cols = Vec::new();
+            self.offsets.insert(record.span.offset);
+            self.used += record_cost(record);
+        } else {
+            self.used += record_cost(record);
+            if self.used > self.max && self.spill {
+                self.spilled = true;
+                self.used = 0;
+                let loaded = tables.iter_mut().flat_map(|table| table.records.iter_mut());
+                for record in loaded.chain(std::iter::once(record)) {
+                    record.cols = Vec::new();
+                    self.offsets.insert(record.span.offset);
+                    self.used += record_cost(record);
+                }
+            }
+        }
+        match self.used > self.max {
+            true => Err(XRVErr::MemoryBudgetExceeded {
+                used: self.used,
+                budget: self.max,
+            }),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Estimated bytes `record` takes in memory.
+fn record_cost(record: &OwnedRecordLine) -> usize {
+    let fields: usize = record
+        .cols
+        .iter()
+        .map(|field| size_of::<OwnedField>() + field.name.len() + field.value.len())
+        .sum();
+    size_of::<OwnedRecordLine>() + record.id.len() + fields
+}
+
+impl Document {
+    /// Opens `path` like `Document::open`, keeping the memory its records
+    /// take within `options`. The records of a spilled document read the
+    /// same through `DocumentTable::records`, `record` and
+    /// `typed_records`, through `Document::record`, `find_record` and
+    /// `records`, and through `write`, `save` and `to_json`, as those of
+    /// one held in memory. `records_mut`, `migrate`, `merge` and
+    /// `sort_records_by` read the records they change back first, as does
+    /// `add_record` on a table with a `!` key column. Records loaded under
+    /// a budget keep the span of their line.
+    ///
+    /// The id and span of every record stay in memory, so a file whose ids
+    /// alone outgrow the budget fails with `XRVErr::MemoryBudgetExceeded`
+    /// even with `spill` set.
+    ///
+    /// ```
+    /// use xrave::{Document, LoadOptions, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-budget.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("name", "str")])?;
+    /// for id in 0..200 {
+    ///     writer.add_record("users", &id.to_string(), &[("name", &format!("user {}", id))])?;
+    /// }
+    /// writer.finish()?;
+    ///
+    /// let options = LoadOptions { max_memory_bytes: Some(16_000), spill: true };
+    /// let document = Document::open_with_options(&path, options)?;
+    /// assert!(document.is_spilled());
+    /// let user = document.find_record("users", "17")?.unwrap();
+    /// assert_eq!(user.get_all("name").next(), Some("user 17"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn open_with_options<P: AsRef<Path>>(
+        path: P,
+        options: LoadOptions,
+    ) -> Result<Document, XRVErr> {
+        let path = path.as_ref();
+        let max = match options.max_memory_bytes {
+            None => return Document::open(path),
+            Some(max) => max,
+        };
+        let file = match File::open(path) {
+            Err(err) => return Err(open_error(path, err)),
+            Ok(file) => file,
+        };
+        let mut budget = Budget {
+            max,
+            spill: options.spill,
+            used: 0,
+            spilled: false,
+            offsets: HashSet::new(),
+        };
+        let mut document = Document::load(BufReader::new(file), Some(&mut budget))?;
+        if budget.spilled {
+            let mut reader = Reader::new(path)?;
+            reader.load_headers()?;
+            let reader = Arc::new(Mutex::new(reader));
+            for table in document.tables.iter_mut() {
+                let spilled: HashSet<u64> = table
+                    .records
+                    .iter()
+                    .map(|record| record.span.offset)
+                    .filter(|offset| budget.offsets.contains(offset))
+                    .collect();
+                if !spilled.is_empty() {
+                    table.backing = Some(Backing {
+                        reader: Arc::clone(&reader),
+                        spilled,
+                    });
+                }
+            }
+        }
+        Ok(document)
+    }
+
+    /// Whether some records were loaded as ids only, to be read from the
+    /// file again when asked for.
+    pub fn is_spilled(&self) -> bool {
+        self.tables.iter().any(DocumentTable::is_spilled)
+    }
+
+    /// Record at `index` of `table`, read from the file again when spilled.
+    pub fn record(
+        &self,
+        table: &str,
+        index: usize,
+    ) -> Result<Option<Cow<'_, OwnedRecordLine>>, XRVErr> {
+        self.spilled_table(table)?.record(index)
+    }
+
+    /// First record of `table` with id `id`, read from the file again when
+    /// spilled.
+    pub fn find_record(
+        &self,
+        table: &str,
+        id: &str,
+    ) -> Result<Option<Cow<'_, OwnedRecordLine>>, XRVErr> {
+        let table = self.spilled_table(table)?;
+        match table.ids().position(|ours| ours == id) {
+            None => Ok(None),
+            Some(index) => table.record(index),
+        }
+    }
+
+    /// Every record of `table` in order, each read from the file again
+    /// when spilled.
+    pub fn records(
+        &self,
+        table: &str,
+    ) -> Result<impl Iterator<Item = Result<Cow<'_, OwnedRecordLine>, XRVErr>>, XRVErr> {
+        Ok(self.spilled_table(table)?.records())
+    }
+
+    /// Reads every spilled record back into memory, regardless of the
+    /// budget it was loaded with.
+    pub fn unspill(&mut self) -> Result<(), XRVErr> {
+        for table in self.tables.iter_mut() {
+            table.unspill()?;
+        }
+        Ok(())
+    }
+
+    fn spilled_table(&self, name: &str) -> Result<&DocumentTable, XRVErr> {
+        self.table(name)
+            .ok_or_else(|| XRVErr::UnknownTable(name.to_owned()))
+    }
+}
+
+impl DocumentTable {
+    /// Whether some records of the table were loaded as ids only.
+    pub fn is_spilled(&self) -> bool {
+        self.backing.is_some()
+    }
+
+    /// Reads the spilled records of the table back into memory.
+    pub fn unspill(&mut self) -> Result<(), XRVErr> {
+        let backing = match self.backing.take() {
+            None => return Ok(()),
+            Some(backing) => backing,
+        };
+        let records = self
+            .records
+            .iter()
+            .map(|record| fill(Some(&backing), record).map(Cow::into_owned))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.records = records;
+        Ok(())
+    }
+}
+
+/// `record` with its fields, read from `backing` when it was spilled.
+pub(crate) fn fill<'t>(
+    backing: Option<&Backing>,
+    record: &'t OwnedRecordLine,
+) -> Result<Cow<'t, OwnedRecordLine>, XRVErr> {
+    let backing = match backing {
+        Some(backing) if backing.spilled.contains(&record.span.offset) => backing,
+        _ => return Ok(Cow::Borrowed(record)),
+    };
+    match backing.reader.lock() {
+        Err(_) => Err(XRVErr::WorkerPanicked),
+        Ok(mut reader) => reader.record_at(record.span).map(Cow::Owned),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{
+        export::ExportOptions, merge::MergePolicy, migrate::Migration, schema::ColKind,
+        writer::Writer,
+    };
+
+    /// Writes `users` and `events` of 200 records each, with two styles of
+    /// which only `bold` is used.
+    fn fixture(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("xrave-test-spill-{}.xrv", name));
+        let mut writer = Writer::new(File::create(&path).unwrap());
+        let users = [("name", "str"), ("age", "i32"), ("style", "str")];
+        writer.add_table("users", &users).unwrap();
+        writer.add_table("events", &[("kind", "str")]).unwrap();
+        writer.add_style("bold", &[("weight", "700")]).unwrap();
+        writer.add_style("dim", &[("weight", "300")]).unwrap();
+        for id in 0..200 {
+            let (name, age) = (format!("user {}", id), (id % 90).to_string());
+            let fields = [("name", name.as_str()), ("age", &age), ("style", "bold")];
+            writer
+                .add_record("users", &id.to_string(), &fields)
+                .unwrap();
+            writer
+                .add_record("events", &id.to_string(), &[("kind", "login")])
+                .unwrap();
+        }
+        writer.finish().unwrap();
+        path
+    }
+
+    fn spilled(path: &Path) -> Document {
+        let options = LoadOptions {
+            max_memory_bytes: Some(40_000),
+            spill: true,
+        };
+        let document = Document::open_with_options(path, options).unwrap();
+        assert!(document.is_spilled());
+        document
+    }
+
+    fn fields(record: &OwnedRecordLine) -> (String, Vec<OwnedField>) {
+        (record.id.clone(), record.cols.clone())
+    }
+
+    fn table_fields(table: &DocumentTable) -> Vec<(String, Vec<OwnedField>)> {
+        table
+            .records()
+            .map(|record| fields(&record.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn spilled_document_reads_like_a_loaded_one() {
+        let path = fixture("reads");
+        let full = Document::open(&path).unwrap();
+        let spilled = spilled(&path);
+        assert!(spilled.tables.iter().all(DocumentTable::is_spilled));
+
+        for table in full.tables.iter() {
+            let ours = spilled.table(&table.name).unwrap();
+            assert_eq!(ours.len(), table.len());
+            assert!(ours.ids().eq(table.ids()));
+            assert_eq!(table_fields(ours), table_fields(table));
+            for idx in [0, 17, 199, 200] {
+                let expected = table.record(idx).unwrap().map(|record| fields(&record));
+                let record = ours.record(idx).unwrap().map(|record| fields(&record));
+                assert_eq!(record, expected);
+            }
+            let found = spilled.find_record(&table.name, "42").unwrap().unwrap();
+            assert_eq!(fields(&found), fields(&table.record(42).unwrap().unwrap()));
+            let read: Vec<_> = spilled.records(&table.name).unwrap().collect();
+            assert_eq!(read.len(), table.len());
+            assert_eq!(
+                ours.to_json(ExportOptions::default()).unwrap(),
+                table.to_json(ExportOptions::default()).unwrap()
+            );
+        }
+
+        let users = spilled.table("users").unwrap();
+        let typed = users.typed_records().next().unwrap().unwrap();
+        assert_eq!(typed.get_opt_str("name"), Some("user 0"));
+        let ages: Vec<i32> = users
+            .typed_records()
+            .map(|record| record?.get_i32("age"))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(ages, (0..200).map(|id| id % 90).collect::<Vec<_>>());
+
+        let write = |document: &Document| document.write(Vec::new(), Default::default()).unwrap();
+        assert_eq!(write(&spilled), write(&full));
+        let options = ExportOptions::default();
+        assert_eq!(
+            spilled.to_json(options).unwrap(),
+            full.to_json(options).unwrap()
+        );
+    }
+
+    #[test]
+    fn unspill_reads_every_record_back() {
+        let path = fixture("unspill");
+        let full = Document::open(&path).unwrap();
+        let mut document = spilled(&path);
+        document.unspill().unwrap();
+        assert!(!document.is_spilled());
+        for (ours, theirs) in document.tables.iter().zip(full.tables.iter()) {
+            assert_eq!(table_fields(ours), table_fields(theirs));
+        }
+
+        let mut document = spilled(&path);
+        let records = document.table_mut("events").unwrap().records_mut().unwrap();
+        records[3].cols[0].value = "logout".into();
+        assert!(!document.table("events").unwrap().is_spilled());
+        assert!(document.table("users").unwrap().is_spilled());
+        let record = document.record("events", 3).unwrap().unwrap();
+        assert_eq!(record.get_all("kind").next(), Some("logout"));
+    }
+
+    #[test]
+    fn changes_to_a_spilled_document_keep_every_field() {
+        let path = fixture("change");
+        let full = Document::open(&path).unwrap();
+        let text = |document: &Document| {
+            String::from_utf8(document.write(Vec::new(), Default::default()).unwrap()).unwrap()
+        };
+
+        let mut document = spilled(&path);
+        let migration = Migration::new().add_column("events", "source", ColKind::String, "web");
+        document.migrate(&migration).unwrap();
+        assert!(text(&document).contains("r:0 kind:login source:web\n"));
+        assert!(text(&document).contains("r:0 name:\"user 0\" age:0 style:bold\n"));
+
+        let mut document = spilled(&path);
+        document.sort_records_by("name").unwrap();
+        let users = document.table("users").unwrap();
+        assert_eq!(users.ids().take(3).collect::<Vec<_>>(), ["0", "1", "10"]);
+        let first = users.record(0).unwrap().unwrap();
+        assert_eq!(first.get_all("name").next(), Some("user 0"));
+
+        let mut document = spilled(&path);
+        assert_eq!(document.remove_unused_styles().unwrap(), ["dim"]);
+        assert_eq!(document.rename_namespace("users", "people").unwrap(), 1);
+        assert!(text(&document).contains("r:50 name:\"user 50\" age:50 style:bold\n"));
+
+        let mut ours = spilled(&path);
+        ours.merge(spilled(&path), MergePolicy::Error).unwrap();
+        assert!(!ours.is_spilled());
+        assert_eq!(text(&ours), text(&full));
+    }
+
+    #[test]
+    fn budget_too_small_fails() {
+        let path = fixture("budget");
+        let tiny = LoadOptions {
+            max_memory_bytes: Some(4_096),
+            spill: false,
+        };
+        match Document::open_with_options(&path, tiny) {
+            Err(XRVErr::MemoryBudgetExceeded { used, budget }) => {
+                assert!(used > budget && budget == 4_096)
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // Ids and spans are kept even when spilling.
+        let ids_only = LoadOptions {
+            max_memory_bytes: Some(4_096),
+            spill: true,
+        };
+        assert!(matches!(
+            Document::open_with_options(&path, ids_only),
+            Err(XRVErr::MemoryBudgetExceeded { .. })
+        ));
+
+        let roomy = LoadOptions {
+            max_memory_bytes: Some(1 << 20),
+            spill: true,
+        };
+        let document = Document::open_with_options(&path, roomy).unwrap();
+        assert!(!document.is_spilled());
+    }
+}
@@ -0,0 +1,488 @@
+use std::{path::Path, sync::Arc};
+
+use rusqlite::{
+    params_from_iter,
+    types::{Value, ValueRef},
+    Connection,
+};
+
+use crate::{
+    base64,
+    document::{Document, DocumentTable},
+    error::XRVErr,
+    line::OwnedField,
+    reader::Reader,
+    schema::{validate_identifier, validate_table_name, Col, ColKind},
+    units::parse_size,
+};
+
+/// Column holding the record id in exported tables; imported tables use it
+/// as the id when they have it, and the rowid otherwise.
+const ID_COLUMN: &str = "xrv_id";
+
+/// Name of a table to export and its columns.
+type ExportTable = (String, Vec<(Arc<str>, Col)>);
+
+/// Declared SQLite type of a column of `kind`. Booleans are stored as the
+/// integers 0 and 1 under a type of their own, so that importing maps them
+/// back to `bool`.
+fn sql_type(kind: ColKind) -> &'static str {
+    match kind {
+        ColKind::I32 | ColKind::I64 | ColKind::U32 | ColKind::U64 => "INTEGER",
+        ColKind::F32 | ColKind::F64 => "REAL",
+        ColKind::String => "TEXT",
+        ColKind::Bool => "BOOLEAN",
+        ColKind::Bytes => "BLOB",
+        ColKind::Size => "INTEGER",
+        ColKind::Duration => "TEXT",
+    }
+}
+
+/// Kind of a column declared as `sql_type`, following the SQLite rules for
+/// type affinity. Types without one, such as `NUMERIC` or `DATE`, hold
+/// whatever values were stored and come back as `str`.
+fn col_kind(sql_type: &str) -> ColKind {
+    let sql_type = sql_type.to_ascii_uppercase();
+    let has = |part: &str| sql_type.contains(part);
+    if has("BOOL") {
+        ColKind::Bool
+    } else if has("INT") {
+        ColKind::I64
+    } else if has("CHAR") || has("CLOB") || has("TEXT") {
+        ColKind::String
+    } else if has("BLOB") {
+        ColKind::Bytes
+    } else if has("REAL") || has("FLOA") || has("DOUB") {
+        ColKind::F64
+    } else {
+        ColKind::String
+    }
+}
+
+/// Quotes `name` as a SQL identifier, so that any table or column name is
+/// used as written, `.` and spaces included.
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// `name` with the characters not allowed in an xrave name replaced by `_`.
+fn sanitize_name(name: &str, table: bool) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            ':' | '"' => '_',
+            c if c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect();
+    let valid = match table {
+        true => validate_table_name(&name).is_ok(),
+        false => validate_identifier(&name).is_ok(),
+    };
+    match valid {
+        true => name,
+        false if name.is_empty() => "_".to_owned(),
+        false => name.replace('.', "_"),
+    }
+}
+
+/// SQLite value of `value` in a column of `kind`. A value that does not
+/// parse as its kind is stored as the text it was written as.
+fn sql_value(kind: ColKind, value: &str) -> Value {
+    let parsed = match kind {
+        ColKind::I32 | ColKind::I64 | ColKind::U32 | ColKind::U64 => {
+            value.parse().ok().map(Value::Integer)
+        }
+        ColKind::F32 | ColKind::F64 => value.parse().ok().map(Value::Real),
+        ColKind::Bool => match value {
+            "true" => Some(Value::Integer(1)),
+            "false" => Some(Value::Integer(0)),
+            _ => None,
+        },
+        ColKind::Bytes => base64::decode(value).ok().map(Value::Blob),
+        ColKind::Size => parse_size(value)
+            .ok()
+            .and_then(|bytes| i64::try_from(bytes).ok())
+            .map(Value::Integer),
+        ColKind::String | ColKind::Duration => None,
+    };
+    parsed.unwrap_or_else(|| Value::Text(value.to_owned()))
+}
+
+/// xrave form of `value` in a column of `kind`, `None` for SQL `NULL`.
+fn xrv_value(kind: ColKind, value: ValueRef<'_>) -> Option<Result<String, ()>> {
+    let value = match (kind, value) {
+        (_, ValueRef::Null) => return None,
+        (ColKind::Bool, ValueRef::Integer(flag)) => (flag != 0).to_string(),
+        (ColKind::Bytes, ValueRef::Blob(bytes) | ValueRef::Text(bytes)) => base64::encode(bytes),
+        (_, ValueRef::Integer(int)) => int.to_string(),
+        (_, ValueRef::Real(real)) => real.to_string(),
+        (_, ValueRef::Text(bytes) | ValueRef::Blob(bytes)) => match std::str::from_utf8(bytes) {
+            Err(_) => return Some(Err(())),
+            Ok(text) => text.to_owned(),
+        },
+    };
+    Some(Ok(value))
+}
+
+impl Reader {
+    /// Writes every table to a new SQLite database at `db_path`, as by
+    /// `export_sqlite_to`.
+    pub fn export_sqlite<P: AsRef<Path>>(&mut self, db_path: P) -> Result<(), XRVErr> {
+        let mut connection = Connection::open(db_path).map_err(XRVErr::Sqlite)?;
+        self.export_sqlite_to(&mut connection)
+    }
+
+    /// Creates one SQL table per table, named as the table and holding the
+    /// record id in an `xrv_id` column followed by one column per declared
+    /// column: `INTEGER` for the integer kinds, `REAL` for `f32` and `f64`,
+    /// `TEXT` for `str`, `BOOLEAN` holding 0 or 1 for `bool` and `BLOB` for
+    /// decoded `bytes`. Required columns are `NOT NULL`, a column left out
+    /// of a record takes its default or `NULL`, and a null is `NULL`.
+    /// Ranges, keys and references are not carried over, and list columns
+    /// fail with `XRVErr::SqliteType`. The records are inserted through one
+    /// prepared statement per table, all in a single transaction that is
+    /// rolled back on failure.
+    ///
+    /// ```
+    /// use rusqlite::Connection;
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-sqlite.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("name", "str"), ("admin", "bool")])?;
+    /// writer.add_record("users", "u1", &[("name", "Anna"), ("admin", "true")])?;
+    /// writer.add_record("users", "u2", &[("name", "Bob"), ("admin", "false")])?;
+    /// writer.finish()?;
+    ///
+    /// let mut connection = Connection::open_in_memory()?;
+    /// Reader::new(&path)?.export_sqlite_to(&mut connection)?;
+    /// let admins: i64 =
+    ///     connection.query_row("SELECT count(*) FROM users WHERE admin", [], |row| row.get(0))?;
+    /// assert_eq!(admins, 1);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn export_sqlite_to(&mut self, connection: &mut Connection) -> Result<(), XRVErr> {
+        self.load_headers()?;
+        let tables: Vec<ExportTable> = self
+            .tables
+            .values()
+            .map(|table| {
+                let cols = table
+                    .cols
+                    .iter()
+                    .map(|field| match Col::parse(&field.value) {
+                        Ok(col) if !col.list => Ok((field.name.clone(), col)),
+                        _ => Err(XRVErr::SqliteType {
+                            table: table.name.clone(),
+                            column: field.name.to_string(),
+                        }),
+                    });
+                Ok((table.name.clone(), cols.collect::<Result<_, XRVErr>>()?))
+            })
+            .collect::<Result<_, XRVErr>>()?;
+
+        let transaction = connection.transaction().map_err(XRVErr::Sqlite)?;
+        for (name, cols) in tables.iter() {
+            let mut defs = vec![format!("{} TEXT NOT NULL", quote_identifier(ID_COLUMN))];
+            for (col_name, col) in cols.iter() {
+                let mut def = format!("{} {}", quote_identifier(col_name), sql_type(col.kind));
+                if !col.optional && col.default.is_none() {
+                    def.push_str(" NOT NULL");
+                }
+                defs.push(def);
+            }
+            let create = format!(
+                "CREATE TABLE {} ({})",
+                quote_identifier(name),
+                defs.join(", ")
+            );
+            transaction.execute(&create, []).map_err(XRVErr::Sqlite)?;
+            if !self.jumps.contains_key(name) {
+                continue;
+            }
+
+            let placeholders = vec!["?"; cols.len() + 1].join(", ");
+            let insert = format!(
+                "INSERT INTO {} VALUES ({})",
+                quote_identifier(name),
+                placeholders
+            );
+            let mut statement = transaction.prepare(&insert).map_err(XRVErr::Sqlite)?;
+            let mut values: Vec<Value> = Vec::with_capacity(cols.len() + 1);
+            for record in self.records(name)? {
+                let record = record?;
+                values.clear();
+                values.push(Value::Text(record.id));
+                for (col_name, col) in cols.iter() {
+                    let value = match record.cols.iter().find(|field| field.name == *col_name) {
+                        Some(field) if field.null => None,
+                        Some(field) => Some(field.value.as_str()),
+                        None => col.default.as_deref(),
+                    };
+                    values.push(value.map_or(Value::Null, |value| sql_value(col.kind, value)));
+                }
+                statement
+                    .execute(params_from_iter(values.iter()))
+                    .map_err(XRVErr::Sqlite)?;
+            }
+        }
+        transaction.commit().map_err(XRVErr::Sqlite)
+    }
+}
+
+/// Reads `tables` from the SQLite database at `db_path` into a document, as
+/// by `import_sqlite_from`.
+pub fn import_sqlite<P: AsRef<Path>>(db_path: P, tables: &[&str]) -> Result<Document, XRVErr> {
+    let connection = Connection::open(db_path).map_err(XRVErr::Sqlite)?;
+    import_sqlite_from(&connection, tables)
+}
+
+/// Reads `tables` into a document, one table each in the order given. The
+/// kind of every column follows its declared SQL type, so that `INTEGER`
+/// becomes `i64`, `REAL` `f64`, `TEXT` `str`, `BOOLEAN` `bool` and `BLOB`
+/// base64 `bytes`; columns that allow `NULL` are optional and a `NULL` is
+/// left out of its record. Records take their id from an `xrv_id` column,
+/// as written by `Reader::export_sqlite_to`, or from the rowid. Names that
+/// are not valid in xrave have the offending characters replaced by `_`.
+/// Empty text is kept in required columns, as `allow_empty_required` is
+/// set on the document. A table missing from the database fails with
+/// `XRVErr::UnknownTable`,
+/// and text that is not UTF-8 with `XRVErr::SqliteType`.
+///
+/// ```
+/// use rusqlite::Connection;
+/// use xrave::import_sqlite_from;
+///
+/// let connection = Connection::open_in_memory()?;
+/// connection.execute_batch(
+///     "CREATE TABLE \"new hires\" (\"first name\" VARCHAR(20) NOT NULL, started DATE);
+///      INSERT INTO \"new hires\" VALUES ('Anna', '2024-03-01'), ('Bob', NULL);",
+/// )?;
+/// let document = import_sqlite_from(&connection, &["new hires"])?;
+/// let table = document.table("new_hires").unwrap();
+/// assert_eq!(table.cols[0].name.as_ref(), "first_name");
+/// assert_eq!(table.ids().collect::<Vec<_>>(), ["1", "2"]);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn import_sqlite_from(connection: &Connection, tables: &[&str]) -> Result<Document, XRVErr> {
+    let mut document = Document {
+        allow_empty_required: true,
+        ..Document::default()
+    };
+    for name in tables {
+        let mut info = connection
+            .prepare(&format!("PRAGMA table_info({})", quote_identifier(name)))
+            .map_err(XRVErr::Sqlite)?;
+        let sql_cols = info
+            .query_map([], |row| {
+                let name: String = row.get(1)?;
+                let sql_type: String = row.get(2)?;
+                let not_null: bool = row.get(3)?;
+                Ok((name, sql_type, not_null))
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(XRVErr::Sqlite)?;
+        if sql_cols.is_empty() {
+            return Err(XRVErr::UnknownTable((*name).to_owned()));
+        }
+
+        let table_name = sanitize_name(name, true);
+        let has_id = sql_cols.iter().any(|(col, _, _)| col == ID_COLUMN);
+        let mut kinds = Vec::with_capacity(sql_cols.len());
+        let mut cols = Vec::with_capacity(sql_cols.len());
+        for (col, sql_type, not_null) in sql_cols.iter() {
+            if col == ID_COLUMN {
+                continue;
+            }
+            let kind = col_kind(sql_type);
+            let decl = match not_null {
+                true => Col::from(kind),
+                false => kind.optional(),
+            };
+            kinds.push(kind);
+            cols.push(OwnedField {
+                name: Arc::from(sanitize_name(col, false)),
+                value: decl.to_string(),
+                null: false,
+            });
+        }
+        document
+            .tables
+            .push(DocumentTable::new(&table_name, cols, Vec::new()));
+
+        let selected: Vec<String> = sql_cols
+            .iter()
+            .filter(|(col, _, _)| col != ID_COLUMN)
+            .map(|(col, _, _)| quote_identifier(col))
+            .collect();
+        let id = match has_id {
+            true => quote_identifier(ID_COLUMN),
+            false => "rowid".to_owned(),
+        };
+        let query = format!(
+            "SELECT {} FROM {}",
+            std::iter::once(id)
+                .chain(selected)
+                .collect::<Vec<_>>()
+                .join(", "),
+            quote_identifier(name)
+        );
+        let mut statement = connection.prepare(&query).map_err(XRVErr::Sqlite)?;
+        let mut rows = statement.query([]).map_err(XRVErr::Sqlite)?;
+        let col_names: Vec<Arc<str>> = document.tables.last().map_or(Vec::new(), |table| {
+            table.cols.iter().map(|col| col.name.clone()).collect()
+        });
+        while let Some(row) = rows.next().map_err(XRVErr::Sqlite)? {
+            let id_value = row.get_ref(0).map_err(XRVErr::Sqlite)?;
+            let id = match xrv_value(ColKind::String, id_value) {
+                Some(Ok(id)) => id,
+                _ => {
+                    return Err(XRVErr::SqliteType {
+                        table: table_name,
+                        column: ID_COLUMN.to_owned(),
+                    })
+                }
+            };
+            let mut values: Vec<(&str, String)> = Vec::with_capacity(kinds.len());
+            for (idx, (kind, col)) in kinds.iter().zip(col_names.iter()).enumerate() {
+                let value = row.get_ref(idx + 1).map_err(XRVErr::Sqlite)?;
+                match xrv_value(*kind, value) {
+                    None => {}
+                    Some(Ok(value)) => values.push((col, value)),
+                    Some(Err(())) => {
+                        return Err(XRVErr::SqliteType {
+                            table: table_name,
+                            column: col.to_string(),
+                        })
+                    }
+                }
+            }
+            let fields: Vec<(&str, &str)> = values
+                .iter()
+                .map(|(name, value)| (*name, value.as_str()))
+                .collect();
+            document.add_record(&table_name, &id, &fields)?;
+        }
+    }
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn export_and_import_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{import_sqlite_from, Document, Reader, Writer};
+        use rusqlite::Connection;
+
+        let path = std::env::temp_dir().join("xrave-test-sqlite.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table(
+            "users",
+            &[
+                ("name", "str"),
+                ("age", "i64?"),
+                ("admin", "bool"),
+                ("avatar", "bytes?"),
+            ],
+        )?;
+        writer.add_table("sales.2024", &[("total", "f64"), ("note", "str?")])?;
+        writer.add_record(
+            "users",
+            "u1",
+            &[
+                ("name", "Anna \"Nan\" Smith"),
+                ("age", "31"),
+                ("admin", "true"),
+                ("avatar", "AAEC/w=="),
+            ],
+        )?;
+        writer.add_record("users", "u2", &[("name", "Bob"), ("admin", "false")])?;
+        writer.add_record(
+            "sales.2024",
+            "q1",
+            &[("total", "10.5"), ("note", "two\nlines")],
+        )?;
+        writer.add_record("sales.2024", "q2", &[("total", "-3")])?;
+        writer.finish()?;
+
+        let mut connection = Connection::open_in_memory()?;
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        reader.export_sqlite_to(&mut connection)?;
+        let query = |sql: &str| connection.query_row(sql, [], |row| row.get::<_, i64>(0));
+        assert_eq!(query("SELECT count(*) FROM users WHERE admin")?, 1);
+        assert_eq!(
+            query("SELECT count(*) FROM \"sales.2024\" WHERE note IS NULL")?,
+            1
+        );
+        let avatar: Vec<u8> =
+            connection.query_row("SELECT avatar FROM users", [], |row| row.get(0))?;
+        assert_eq!(avatar, [0, 1, 2, 255]);
+
+        let imported = import_sqlite_from(&connection, &["users", "sales.2024"])?;
+        let users = imported.table("users").unwrap();
+        let decls: Vec<_> = users
+            .cols
+            .iter()
+            .map(|col| (&*col.name, col.value.as_str()))
+            .collect();
+        assert_eq!(
+            decls,
+            [
+                ("name", "str"),
+                ("age", "i64?"),
+                ("admin", "bool"),
+                ("avatar", "bytes?")
+            ]
+        );
+
+        let original = Document::open(&path)?;
+        assert_eq!(imported.tables.len(), original.tables.len());
+        for (imported, original) in imported.tables.iter().zip(&original.tables) {
+            assert_eq!(imported.name, original.name);
+            let fields = |table: &crate::DocumentTable| -> Vec<(String, Vec<(String, String)>)> {
+                table
+                    .records()
+                    .map(|record| record.unwrap())
+                    .map(|record| {
+                        let cols = record
+                            .cols
+                            .iter()
+                            .map(|f| (f.name.to_string(), f.value.clone()));
+                        (record.id.clone(), cols.collect())
+                    })
+                    .collect()
+            };
+            assert_eq!(fields(imported), fields(original));
+        }
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        assert!(reader.export_sqlite_to(&mut connection).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn import_cleans_names_and_fails_on_missing_tables() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{import_sqlite_from, XRVErr};
+        use rusqlite::Connection;
+
+        let connection = Connection::open_in_memory()?;
+        connection.execute_batch(
+            "CREATE TABLE \"new hires\" (\"first name\" VARCHAR(20) NOT NULL, started DATE);
+             INSERT INTO \"new hires\" VALUES ('Anna', '2024-03-01'), ('Bob', NULL);",
+        )?;
+        let document = import_sqlite_from(&connection, &["new hires"])?;
+        let table = document.table("new_hires").unwrap();
+        assert_eq!(table.cols[0].name.as_ref(), "first_name");
+        assert_eq!(table.cols[1].value, "str?");
+        let ids: Vec<_> = table.ids().collect();
+        assert_eq!(ids, ["1", "2"]);
+        assert_eq!(table.record(1)?.unwrap().cols.len(), 1);
+
+        assert!(matches!(
+            import_sqlite_from(&connection, &["missing"]),
+            Err(XRVErr::UnknownTable(_))
+        ));
+        Ok(())
+    }
+}
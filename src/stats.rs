@@ -0,0 +1,252 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::{error::XRVErr, line::Value, reader::Reader, schema::Col};
+
+/// Distinct values tracked per column before counting stops and the count
+/// is flagged approximate.
+const DISTINCT_CAP: usize = 10_000;
+
+/// Summary of one declared column over every record of a table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColStats {
+    pub name: String,
+    /// Records holding a non-empty value.
+    pub values: u64,
+    /// Records holding an empty value, e.g. `email:` or `email:""`.
+    pub empty: u64,
+    /// Records holding a null, `email:~`.
+    pub nulls: u64,
+    /// Records without the field.
+    pub missing: u64,
+    /// Distinct non-empty values; a lower bound when `distinct_approximate`.
+    pub distinct: usize,
+    pub distinct_approximate: bool,
+    /// Longest value in bytes.
+    pub max_len: usize,
+    /// Set for columns declared with a numeric kind holding at least one
+    /// value that parses as a number.
+    pub numeric: Option<NumericStats>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumericStats {
+    pub min: f64,
+    pub max: f64,
+    pub sum: f64,
+    pub mean: f64,
+}
+
+impl Reader {
+    /// Computes a `ColStats` for each column declared in the header of
+    /// `table`, in header order, in one pass over its records. At most
+    /// 10 000 distinct values are held per column.
+    ///
+    /// ```
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-column-stats.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("users", &[("age", "i32")])?;
+    /// writer.add_record("users", "1", &[("age", "33")])?;
+    /// writer.add_record("users", "2", &[("age", "20")])?;
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(&path)?;
+    /// let age = reader.column_stats("users")?[0].numeric.unwrap();
+    /// assert_eq!((age.min, age.max, age.mean), (20.0, 33.0, 26.5));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn column_stats(&mut self, table: &str) -> Result<Vec<ColStats>, XRVErr> {
+        self.load_table(table)?;
+        let mut columns: Vec<ColAccumulator> = match self.table(table) {
+            None => return Err(XRVErr::UnknownTable(table.to_owned())),
+            Some(header) => header
+                .cols
+                .iter()
+                .map(|col| ColAccumulator {
+                    stats: ColStats {
+                        name: col.name.to_string(),
+                        values: 0,
+                        empty: 0,
+                        nulls: 0,
+                        missing: 0,
+                        distinct: 0,
+                        distinct_approximate: false,
+                        max_len: 0,
+                        numeric: None,
+                    },
+                    numeric: Col::parse(&col.value).is_ok_and(|col| col.kind.is_numeric()),
+                    numbers: 0,
+                    seen: HashSet::new(),
+                })
+                .collect(),
+        };
+
+        for record in self.records(table)? {
+            let record = record?;
+            for column in columns.iter_mut() {
+                let field = record
+                    .cols
+                    .iter()
+                    .find(|field| *field.name == *column.stats.name);
+                column.add(field.map(|field| field.as_value()));
+            }
+        }
+        Ok(columns.into_iter().map(ColAccumulator::finish).collect())
+    }
+}
+
+/// Running state behind one `ColStats`.
+struct ColAccumulator {
+    stats: ColStats,
+    /// Whether the column is declared with a numeric kind.
+    numeric: bool,
+    /// Values that parsed as numbers.
+    numbers: u64,
+    seen: HashSet<String>,
+}
+
+impl ColAccumulator {
+    fn add(&mut self, value: Option<Value>) {
+        let stats = &mut self.stats;
+        let value = match value {
+            None => {
+                stats.missing += 1;
+                return;
+            }
+            Some(Value::Null) => {
+                stats.nulls += 1;
+                return;
+            }
+            Some(Value::Str("")) => {
+                stats.empty += 1;
+                return;
+            }
+            Some(Value::Str(value)) => value,
+        };
+        stats.values += 1;
+        stats.max_len = stats.max_len.max(value.len());
+        if !stats.distinct_approximate && !self.seen.contains(value) {
+            if self.seen.len() == DISTINCT_CAP {
+                stats.distinct_approximate = true;
+                self.seen = HashSet::new();
+            } else {
+                self.seen.insert(value.to_owned());
+                stats.distinct = self.seen.len();
+            }
+        }
+        if !self.numeric {
+            return;
+        }
+        if let Ok(number) = value.parse::<f64>() {
+            self.numbers += 1;
+            stats.numeric = Some(match stats.numeric {
+                None => NumericStats {
+                    min: number,
+                    max: number,
+                    sum: number,
+                    mean: number,
+                },
+                Some(numeric) => NumericStats {
+                    min: numeric.min.min(number),
+                    max: numeric.max.max(number),
+                    sum: numeric.sum + number,
+                    mean: numeric.mean,
+                },
+            });
+        }
+    }
+
+    fn finish(mut self) -> ColStats {
+        if let Some(numeric) = self.stats.numeric.as_mut() {
+            numeric.mean = numeric.sum / self.numbers as f64;
+        }
+        self.stats
+    }
+}
+
+impl fmt::Display for ColStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} values, {} empty, {} null, {} missing, {}{} distinct, max {} bytes",
+            self.name,
+            self.values,
+            self.empty,
+            self.nulls,
+            self.missing,
+            self.distinct,
+            if self.distinct_approximate { "+" } else { "" },
+            self.max_len
+        )?;
+        if let Some(numeric) = self.numeric {
+            write!(
+                f,
+                ", min {} max {} mean {} sum {}",
+                numeric.min, numeric.max, numeric.mean, numeric.sum
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn column_stats_count_values_and_numbers() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Reader, Value, Writer};
+
+        let path = std::env::temp_dir().join("xrave-test-column-stats.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table(
+            "users",
+            &[("name", "str"), ("age", "i32"), ("note", "str?")],
+        )?;
+        writer.add_record(
+            "users",
+            "1",
+            &[("name", "Anna"), ("age", "33"), ("note", "")],
+        )?;
+        writer.add_record("users", "2", &[("name", "Bob"), ("age", "20")])?;
+        writer.add_record("users", "3", &[("name", "Anna"), ("age", ""), ("note", "")])?;
+        writer.add_record_values(
+            "users",
+            "4",
+            &[("name", "Cleo".into()), ("age", Value::Null)],
+        )?;
+        writer.finish()?;
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        let stats = reader.column_stats("users")?;
+        let names: Vec<_> = stats.iter().map(|col| col.name.as_str()).collect();
+        assert_eq!(names, ["name", "age", "note"]);
+
+        assert_eq!(
+            (stats[0].values, stats[0].distinct, stats[0].max_len),
+            (4, 3, 4)
+        );
+        assert!(stats[0].numeric.is_none());
+
+        let age = stats[1].numeric.unwrap();
+        assert_eq!(
+            (age.min, age.max, age.sum, age.mean),
+            (20.0, 33.0, 53.0, 26.5)
+        );
+        assert_eq!(
+            (stats[1].empty, stats[1].nulls, stats[1].missing),
+            (1, 1, 0)
+        );
+
+        assert_eq!(
+            (stats[2].values, stats[2].empty, stats[2].missing),
+            (0, 2, 2)
+        );
+        assert_eq!((stats[2].distinct, stats[2].max_len), (0, 0));
+        assert_eq!(
+            stats[1].to_string(),
+            "age: 2 values, 1 empty, 1 null, 0 missing, 2 distinct, max 2 bytes, min 20 max 33 mean 26.5 sum 53"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,297 @@
+use std::io::prelude::*;
+use std::{fs::File, io::SeekFrom};
+
+use crate::{
+    dictionary::expand_record,
+    error::{open_error, XRVErr},
+    line::{decode_record, LineKind, OwnedRecordLine},
+    reader::{ParseMode, Reader},
+    tokenizer::{RawLine, XraveBuffer, CR_CHAR, NL_CHAR},
+};
+
+/// Where `Reader::poll_new_records` stopped: the file offset up to which
+/// bytes have been read and the bytes of a final line still waiting for its
+/// terminator.
+#[derive(Debug, Default)]
+pub(crate) struct Tail {
+    offset: u64,
+    partial: Vec<u8>,
+}
+
+impl Reader {
+    /// Makes the next `poll_new_records` start reading at `offset`, which
+    /// must be the start of a line.
+    pub fn resume_from(&mut self, offset: u64) {
+        self.tail = Some(Tail {
+            offset,
+            partial: Vec::new(),
+        });
+    }
+
+    /// Returns the records appended to the file since the previous poll.
+    /// The first poll starts at the region of `table`, or where
+    /// `resume_from` pointed, and reads to the end of the file, so `table`
+    /// should be the last table of a file that grows by appending. A final
+    /// line without its terminator is held back until a later poll sees it
+    /// complete. A file that shrank below the bytes already read fails with
+    /// `XRVErr::FileTruncated`.
+    ///
+    /// A line that fails to parse ends the poll there: the records before
+    /// it are returned, and the next poll starts at that line and fails
+    /// with its error, until `resume_from` moves past it. In
+    /// `ParseMode::Lenient` the line is skipped instead and its error kept
+    /// among the diagnostics.
+    ///
+    /// ```
+    /// use std::io::Write;
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-tail.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_table("events", &[("name", "str")])?;
+    /// writer.add_record("events", "1", &[("name", "start")])?;
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(&path)?;
+    /// assert_eq!(reader.poll_new_records("events")?.len(), 1);
+    /// let mut file = std::fs::OpenOptions::new().append(true).open(&path)?;
+    /// file.write_all(b"r:2 name:tick\n")?;
+    /// assert_eq!(reader.poll_new_records("events")?[0].id, "2");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn poll_new_records(&mut self, table: &str) -> Result<Vec<OwnedRecordLine>, XRVErr> {
+        self.ensure_headers()?;
+        let mut tail = match self.tail.take() {
+            Some(tail) => tail,
+            None => match self.jumps.get(table) {
+                None => return Err(XRVErr::UnknownTable(table.to_owned())),
+                Some((seek, _)) => Tail {
+                    offset: *seek as u64,
+                    partial: Vec::new(),
+                },
+            },
+        };
+        let records = self.read_appended(table, &mut tail);
+        self.tail = Some(tail);
+        records
+    }
+
+    fn read_appended(
+        &mut self,
+        table: &str,
+        tail: &mut Tail,
+    ) -> Result<Vec<OwnedRecordLine>, XRVErr> {
+        let path = self.reopen_path()?;
+        let mut file = match File::open(path) {
+            Err(err) => return Err(open_error(path, err)),
+            Ok(file) => file,
+        };
+        let len = match file.metadata() {
+            Err(err) => return Err(XRVErr::FailToReadLine(err)),
+            Ok(metadata) => metadata.len(),
+        };
+        if len != tail.offset {
+            self.cache.clear();
+        }
+        if len < tail.offset {
+            return Err(XRVErr::FileTruncated {
+                len,
+                offset: tail.offset,
+            });
+        }
+        if let Err(err) = file.seek(SeekFrom::Start(tail.offset)) {
+            return Err(XRVErr::FailToSeek(err));
+        }
+        let start = tail.offset - tail.partial.len() as u64;
+        let mut bytes = std::mem::take(&mut tail.partial);
+        match file.take(len - tail.offset).read_to_end(&mut bytes) {
+            Err(err) => return Err(XRVErr::FailToReadLine(err)),
+            Ok(read) => tail.offset += read as u64,
+        }
+
+        // A trailing CR may still be followed by the LF of a CRLF, so its
+        // line is held back too.
+        let search = match bytes.last() {
+            Some(&CR_CHAR) => &bytes[..bytes.len() - 1],
+            _ => &bytes[..],
+        };
+        let complete = search
+            .iter()
+            .rposition(|byte| *byte == NL_CHAR || *byte == CR_CHAR)
+            .map_or(0, |pos| pos + 1);
+        tail.partial = bytes.split_off(complete);
+
+        let mut source = &bytes[..];
+        let mut buffer = XraveBuffer::new();
+        buffer.offset = start;
+        buffer.limits = self.options.line_limits();
+        buffer.set_version(self.version);
+        let mut records = Vec::new();
+        loop {
+            let line_start = buffer.offset;
+            let record = match buffer.read_line(&mut source, self.options.max_line_len) {
+                Ok(0) => break,
+                Ok(_) => self.appended_record(table, &buffer, &mut source),
+                Err(err) => Err(err),
+            };
+            match record {
+                Ok(None) => {}
+                Ok(Some(record)) => records.push(record),
+                Err(err) if self.options.mode == ParseMode::Lenient => {
+                    self.stats.errors += 1;
+                    self.diagnostics.push(err);
+                }
+                Err(err) => {
+                    self.stats.errors += 1;
+                    tail.offset = line_start;
+                    tail.partial.clear();
+                    if records.is_empty() {
+                        return Err(err);
+                    }
+                    break;
+                }
+            }
+        }
+        Ok(records)
+    }
+
+    /// Decodes the appended line in `buffer` when it is a record.
+    fn appended_record(
+        &mut self,
+        table: &str,
+        buffer: &XraveBuffer,
+        source: &mut &[u8],
+    ) -> Result<Option<OwnedRecordLine>, XRVErr> {
+        if buffer.is_filler(source)? {
+            return Ok(None);
+        }
+        let raw_line: RawLine = buffer.try_into()?;
+        if raw_line.kind != LineKind::Record {
+            return Ok(None);
+        }
+        let mut owned = decode_record(raw_line, self.options.encoding, &mut self.names)?;
+        owned.span = buffer.span();
+        if let Some(dictionary) = self.dictionaries.get(table) {
+            if let Err(err) = expand_record(dictionary, table, &mut owned) {
+                return Err(buffer.diagnose(1, err));
+            }
+        }
+        Ok(Some(owned))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::OpenOptions, path::PathBuf};
+
+    use super::*;
+    use crate::{reader::ParseOptions, writer::Writer};
+
+    fn fixture(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("xrave-test-tail-{}.xrv", name));
+        let mut writer = Writer::new(File::create(&path).unwrap());
+        writer.add_table("events", &[("name", "str")]).unwrap();
+        writer
+            .add_record("events", "1", &[("name", "start")])
+            .unwrap();
+        writer.finish().unwrap();
+        path
+    }
+
+    fn append(path: &PathBuf, bytes: &[u8]) {
+        let mut file = OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(bytes).unwrap();
+    }
+
+    fn ids(records: Vec<OwnedRecordLine>) -> Vec<String> {
+        records.into_iter().map(|record| record.id).collect()
+    }
+
+    #[test]
+    fn bad_line_keeps_earlier_records_and_stops_there() {
+        let path = fixture("bad-line");
+        let mut reader = Reader::new(&path).unwrap();
+        assert_eq!(ids(reader.poll_new_records("events").unwrap()), ["1"]);
+
+        let bad_at = std::fs::metadata(&path).unwrap().len() + "r:2 name:ok\n".len() as u64;
+        append(
+            &path,
+            b"r:2 name:ok\nr:3 name:\"unterminated\nr:4 name:ok\n",
+        );
+        assert_eq!(ids(reader.poll_new_records("events").unwrap()), ["2"]);
+        for _ in 0..2 {
+            let err = reader.poll_new_records("events").unwrap_err();
+            assert!(matches!(err, XRVErr::AtLine { .. }), "{:?}", err);
+        }
+
+        append(&path, b"r:5 name:later\n");
+        let next = bad_at + "r:3 name:\"unterminated\n".len() as u64;
+        reader.resume_from(next);
+        assert_eq!(ids(reader.poll_new_records("events").unwrap()), ["4", "5"]);
+        assert!(reader.poll_new_records("events").unwrap().is_empty());
+    }
+
+    #[test]
+    fn bad_first_line_fails_without_moving_on() {
+        let path = fixture("bad-first");
+        let mut reader = Reader::new(&path).unwrap();
+        reader.poll_new_records("events").unwrap();
+
+        append(&path, b"r:2 name:\"unterminated\nr:3 name:ok\n");
+        assert!(reader.poll_new_records("events").is_err());
+        append(&path, b"r:4 name:ok\n");
+        assert!(reader.poll_new_records("events").is_err());
+    }
+
+    #[test]
+    fn lenient_skips_bad_line() {
+        let path = fixture("lenient");
+        let options = ParseOptions {
+            mode: ParseMode::Lenient,
+            ..ParseOptions::default()
+        };
+        let mut reader = Reader::with_options(&path, options).unwrap();
+        reader.poll_new_records("events").unwrap();
+
+        append(
+            &path,
+            b"r:2 name:ok\nr:3 name:\"unterminated\nr:4 name:ok\n",
+        );
+        assert_eq!(ids(reader.poll_new_records("events").unwrap()), ["2", "4"]);
+        assert_eq!(reader.diagnostics().len(), 1);
+        assert!(reader.poll_new_records("events").unwrap().is_empty());
+    }
+
+    #[test]
+    fn polling_picks_up_appended_records() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Reader, Writer, XRVErr};
+        use std::io::Write;
+
+        let path = std::env::temp_dir().join("xrave-test-tail.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("events", &[("name", "str")])?;
+        writer.add_record("events", "1", &[("name", "start")])?;
+        writer.finish()?;
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        let ids = |records: Vec<crate::OwnedRecordLine>| -> Vec<String> {
+            records.into_iter().map(|record| record.id).collect()
+        };
+        assert_eq!(ids(reader.poll_new_records("events")?), ["1"]);
+
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path)?;
+        file.write_all(b"r:2 name:tick\nr:3 name:tick\nr:4 na")?;
+        assert_eq!(ids(reader.poll_new_records("events")?), ["2", "3"]);
+        assert!(reader.poll_new_records("events")?.is_empty());
+
+        file.write_all(b"me:stop\n")?;
+        assert_eq!(ids(reader.poll_new_records("events")?), ["4"]);
+
+        file.set_len(10)?;
+        assert!(matches!(
+            reader.poll_new_records("events"),
+            Err(XRVErr::FileTruncated { .. })
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,514 @@
+use std::fmt;
+use std::io::{Cursor, Write};
+
+use crate::{
+    document::Document,
+    error::XRVErr,
+    line::OwnedField,
+    reader::Reader,
+    sample::SplitMix64,
+    schema::ColKind,
+    writer::{Writer, WriterOptions},
+};
+
+/// Bytes random strings are drawn from.
+const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+/// Kind byte written over the start of a corrupted record line, which no
+/// line kind starts with.
+const CORRUPT_KIND: u8 = b'?';
+
+/// How the values of a generated column are drawn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GenValues {
+    /// `start` on the first row, then one more on each row after it.
+    Sequential { start: i64 },
+    /// Integers drawn uniformly from `min..=max`.
+    RandomInt { min: i64, max: i64 },
+    /// Letters and digits, with a length drawn uniformly from
+    /// `min_len..=max_len`.
+    RandomString { min_len: usize, max_len: usize },
+    /// One of the choices, each as likely as the others.
+    Choice(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenCol {
+    pub name: String,
+    /// Kind declared for the column in the table line.
+    pub kind: ColKind,
+    pub values: GenValues,
+}
+
+/// A table `generate` writes, with records numbered from 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenTable {
+    pub name: String,
+    pub rows: usize,
+    pub cols: Vec<GenCol>,
+}
+
+impl GenTable {
+    pub fn new(name: &str, rows: usize) -> GenTable {
+        GenTable {
+            name: name.to_owned(),
+            rows,
+            cols: Vec::new(),
+        }
+    }
+
+    pub fn col(mut self, name: &str, kind: ColKind, values: GenValues) -> Self {
+        self.cols.push(GenCol {
+            name: name.to_owned(),
+            kind,
+            values,
+        });
+        self
+    }
+}
+
+/// Tables `generate` writes and how. The same spec always gives the same
+/// bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenSpec {
+    pub tables: Vec<GenTable>,
+    /// Seeds the generator every random value and corrupted line is drawn
+    /// with.
+    pub seed: u64,
+    /// Share of record lines, from 0 to 1, written with a kind no line
+    /// starts with, for exercising `ParseMode::Lenient`. Corrupted lines
+    /// keep their length, so the jumps and table regions stay right.
+    pub error_rate: f64,
+}
+
+impl GenSpec {
+    pub fn new(seed: u64) -> GenSpec {
+        GenSpec {
+            tables: Vec::new(),
+            seed,
+            error_rate: 0.0,
+        }
+    }
+
+    pub fn table(mut self, table: GenTable) -> Self {
+        self.tables.push(table);
+        self
+    }
+
+    pub fn error_rate(mut self, error_rate: f64) -> Self {
+        self.error_rate = error_rate;
+        self
+    }
+}
+
+/// Writes a file of made-up records as `spec` describes to `out`, for
+/// tests and benchmarks that need large inputs. The whole file is laid out
+/// in memory before any of it is written.
+///
+/// ```
+/// use xrave::testkit::{generate, GenSpec, GenTable, GenValues};
+/// use xrave::{ColKind, Document};
+///
+/// let spec = GenSpec::new(42).table(
+///     GenTable::new("users", 100).col("age", ColKind::I64, GenValues::RandomInt { min: 18, max: 90 }),
+/// );
+/// let mut bytes = Vec::new();
+/// generate(spec, &mut bytes)?;
+/// assert_eq!(Document::read(&bytes[..])?.table("users").unwrap().len(), 100);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn generate(spec: GenSpec, mut out: impl Write) -> Result<(), XRVErr> {
+    check_spec(&spec)?;
+    let mut rng = SplitMix64(spec.seed);
+    let mut writer = Writer::new(Vec::new());
+    for table in spec.tables.iter() {
+        let cols: Vec<(&str, &str)> = table
+            .cols
+            .iter()
+            .map(|col| (col.name.as_str(), col.kind.as_str()))
+            .collect();
+        writer.add_table(&table.name, &cols)?;
+    }
+    for table in spec.tables.iter() {
+        for row in 0..table.rows {
+            let values: Vec<String> = table
+                .cols
+                .iter()
+                .map(|col| draw(&col.values, row, &mut rng))
+                .collect();
+            let fields: Vec<(&str, &str)> = table
+                .cols
+                .iter()
+                .zip(values.iter())
+                .map(|(col, value)| (col.name.as_str(), value.as_str()))
+                .collect();
+            writer.add_record(&table.name, &row.to_string(), &fields)?;
+        }
+    }
+
+    let mut bytes = writer.finish()?;
+    if spec.error_rate > 0.0 {
+        for line in bytes.split_mut(|byte| *byte == b'\n') {
+            if line.starts_with(b"r:") && unit(&mut rng) < spec.error_rate {
+                line[0] = CORRUPT_KIND;
+            }
+        }
+    }
+    if let Err(err) = out.write_all(&bytes) {
+        return Err(XRVErr::FailToWrite(err));
+    }
+    Ok(())
+}
+
+fn check_spec(spec: &GenSpec) -> Result<(), XRVErr> {
+    if !(0.0..=1.0).contains(&spec.error_rate) {
+        return Err(XRVErr::InvalidGenSpec(format!(
+            "error rate {} is not between 0 and 1",
+            spec.error_rate
+        )));
+    }
+    for table in spec.tables.iter() {
+        for col in table.cols.iter() {
+            let problem = match &col.values {
+                GenValues::RandomInt { min, max } if min > max => "its range is empty",
+                GenValues::RandomString { min_len, max_len } if min_len > max_len => {
+                    "its length range is empty"
+                }
+                GenValues::Choice(choices) if choices.is_empty() => "it has no choices",
+                _ => continue,
+            };
+            return Err(XRVErr::InvalidGenSpec(format!(
+                "column '{}' of table '{}': {}",
+                col.name, table.name, problem
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn draw(values: &GenValues, row: usize, rng: &mut SplitMix64) -> String {
+    match values {
+        GenValues::Sequential { start } => start.wrapping_add(row as i64).to_string(),
+        GenValues::RandomInt { min, max } => {
+            let span = max.abs_diff(*min).wrapping_add(1);
+            let offset = match span {
+                0 => rng.next(),
+                span => rng.below(span),
+            };
+            min.wrapping_add_unsigned(offset).to_string()
+        }
+        GenValues::RandomString { min_len, max_len } => {
+            let len = min_len + rng.below((max_len - min_len) as u64 + 1) as usize;
+            (0..len)
+                .map(|_| ALPHABET[rng.below(ALPHABET.len() as u64) as usize] as char)
+                .collect()
+        }
+        GenValues::Choice(choices) => choices[rng.below(choices.len() as u64) as usize].clone(),
+    }
+}
+
+/// Uniform value in `0..1`.
+fn unit(rng: &mut SplitMix64) -> f64 {
+    (rng.next() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Why `roundtrip` failed.
+#[derive(Debug)]
+pub enum RoundtripFailure {
+    /// The document could not be written, or what was written could not be
+    /// read.
+    Failed(XRVErr),
+    /// What was read back first differs from the document at `path`, such
+    /// as `tables[users].records[3].fields[name]`. Values are shown quoted,
+    /// or as `null` or `missing`.
+    Diverged {
+        path: String,
+        written: String,
+        read: String,
+    },
+}
+
+impl fmt::Display for RoundtripFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoundtripFailure::Failed(err) => write!(f, "round trip failed: {}", err),
+            RoundtripFailure::Diverged {
+                path,
+                written,
+                read,
+            } => write!(f, "{}: wrote {}, read back {}", path, written, read),
+        }
+    }
+}
+
+impl std::error::Error for RoundtripFailure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RoundtripFailure::Failed(err) => Some(err),
+            RoundtripFailure::Diverged { .. } => None,
+        }
+    }
+}
+
+impl From<XRVErr> for RoundtripFailure {
+    fn from(err: XRVErr) -> Self {
+        RoundtripFailure::Failed(err)
+    }
+}
+
+/// Writes `doc` through a `Writer`, reads the bytes back through a
+/// `Reader` and checks that the tables, styles and records read equal the
+/// document's, field by field and in order.
+///
+/// ```
+/// use xrave::testkit::roundtrip;
+/// use xrave::{ColKind, Schema};
+///
+/// let mut doc = Schema::new().table("users", |t| t.col("name", ColKind::String)).build()?;
+/// doc.add_record("users", "1", &[("name", "two\nlines")])?;
+/// roundtrip(&doc)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn roundtrip(doc: &Document) -> Result<(), RoundtripFailure> {
+    let options = WriterOptions {
+        checksums: doc.checksums,
+        ..WriterOptions::default()
+    };
+    let bytes = doc.write(Vec::new(), options)?;
+    let mut reader = Reader::from_unseekable(Cursor::new(bytes))?;
+    reader.load_headers()?;
+
+    for table in doc.tables.iter() {
+        let path = format!("tables[{}]", table.name);
+        let read = match reader.table(&table.name) {
+            None => return diverged(path, "a table".into(), MISSING.into()),
+            Some(read) => read,
+        };
+        compare_fields(&format!("{}.cols", path), &table.cols, &read.cols)?;
+        if table.next_id != read.next_id {
+            let show = |next_id: Option<u64>| next_id.map_or(MISSING.into(), |id| id.to_string());
+            let path = format!("{}.next_id", path);
+            return diverged(path, show(table.next_id), show(read.next_id));
+        }
+    }
+    if let Some(name) = reader
+        .tables()
+        .keys()
+        .find(|name| doc.table(name).is_none())
+    {
+        return diverged(
+            format!("tables[{}]", name),
+            MISSING.into(),
+            "a table".into(),
+        );
+    }
+    for style in doc.styles.iter() {
+        let path = format!("styles[{}]", style.id);
+        match reader.style(&style.id) {
+            None => return diverged(path, "a style".into(), MISSING.into()),
+            Some(read) => compare_fields(&format!("{}.fields", path), &style.cols, &read.cols)?,
+        }
+    }
+    if let Some(id) = reader.styles().keys().find(|id| doc.style(id).is_none()) {
+        return diverged(format!("styles[{}]", id), MISSING.into(), "a style".into());
+    }
+
+    for table in doc.tables.iter() {
+        let read = reader
+            .records(&table.name)?
+            .collect::<Result<Vec<_>, _>>()?;
+        for idx in 0..table.len().max(read.len()) {
+            let path = format!("tables[{}].records[{}]", table.name, idx);
+            let written = match table.record(idx)? {
+                None => return diverged(path, MISSING.into(), format!("{:?}", read[idx].id)),
+                Some(record) => record,
+            };
+            let read = match read.get(idx) {
+                None => return diverged(path, format!("{:?}", written.id), MISSING.into()),
+                Some(record) => record,
+            };
+            if written.id != read.id {
+                let (written, read) = (format!("{:?}", written.id), format!("{:?}", read.id));
+                return diverged(format!("{}.id", path), written, read);
+            }
+            compare_fields(&format!("{}.fields", path), &written.cols, &read.cols)?;
+        }
+    }
+    Ok(())
+}
+
+/// How `RoundtripFailure::Diverged` shows a side that has nothing there.
+const MISSING: &str = "missing";
+
+fn diverged(path: String, written: String, read: String) -> Result<(), RoundtripFailure> {
+    Err(RoundtripFailure::Diverged {
+        path,
+        written,
+        read,
+    })
+}
+
+/// Compares two lists of fields in order, naming the first that differs
+/// by the field written there.
+fn compare_fields(
+    path: &str,
+    written: &[OwnedField],
+    read: &[OwnedField],
+) -> Result<(), RoundtripFailure> {
+    for idx in 0..written.len().max(read.len()) {
+        let (ours, theirs) = (written.get(idx), read.get(idx));
+        if let (Some(ours), Some(theirs)) = (ours, theirs) {
+            if ours == theirs {
+                continue;
+            }
+        }
+        let name = match ours.or(theirs) {
+            None => continue,
+            Some(field) => &field.name,
+        };
+        let path = format!("{}[{}]", path, name);
+        let read = match (ours, theirs) {
+            (Some(ours), Some(theirs)) if ours.name != theirs.name => {
+                format!("{} as '{}'", show(Some(theirs)), theirs.name)
+            }
+            _ => show(theirs),
+        };
+        return diverged(path, show(ours), read);
+    }
+    Ok(())
+}
+
+fn show(field: Option<&OwnedField>) -> String {
+    match field {
+        None => MISSING.into(),
+        Some(field) if field.null => "null".into(),
+        Some(field) => format!("{:?}", field.value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn generated_files_are_reproducible_and_corrupted_as_asked(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        use crate::testkit::{generate, GenSpec, GenTable, GenValues};
+        use crate::{ColKind, ParseMode, ParseOptions, Reader};
+
+        let spec = GenSpec::new(42)
+            .table(
+                GenTable::new("users", 10_000)
+                    .col("serial", ColKind::I64, GenValues::Sequential { start: 100 })
+                    .col(
+                        "age",
+                        ColKind::I64,
+                        GenValues::RandomInt { min: 18, max: 90 },
+                    )
+                    .col(
+                        "name",
+                        ColKind::String,
+                        GenValues::RandomString {
+                            min_len: 3,
+                            max_len: 12,
+                        },
+                    )
+                    .col(
+                        "role",
+                        ColKind::String,
+                        GenValues::Choice(vec!["admin".into(), "a user".into()]),
+                    ),
+            )
+            .error_rate(0.01);
+
+        let mut first = Vec::new();
+        generate(spec.clone(), &mut first)?;
+        let mut second = Vec::new();
+        generate(spec, &mut second)?;
+        assert!(first == second);
+
+        let path = std::env::temp_dir().join("xrave-test-testkit.xrv");
+        std::fs::write(&path, &first)?;
+        let options = ParseOptions {
+            mode: ParseMode::Lenient,
+            ..ParseOptions::default()
+        };
+        let mut reader = Reader::with_options(&path, options)?;
+        let records = reader.records("users")?.collect::<Result<Vec<_>, _>>()?;
+        let corrupted = first
+            .split(|byte| *byte == b'\n')
+            .filter(|line| line.starts_with(b"?:"))
+            .count();
+        assert!(corrupted > 50 && corrupted < 150);
+        assert_eq!(records.len() + corrupted, 10_000);
+        assert!(!reader.diagnostics().is_empty());
+        assert!(records
+            .iter()
+            .all(|record| record.get_all("role").all(|role| !role.is_empty())));
+        Ok(())
+    }
+
+    #[test]
+    fn awkward_values_survive_a_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::testkit::{generate, roundtrip, GenSpec, GenTable, GenValues};
+        use crate::{ColKind, Document, OwnedField, OwnedStyleLine, Schema};
+
+        let mut doc = Schema::new()
+            .table("users", |t| {
+                t.col("name", ColKind::String)
+                    .col("note", ColKind::String.optional())
+            })
+            .table("empty", |t| t.col("k", ColKind::String))
+            .build()?;
+        let values = [
+            "plain",
+            "a b",
+            "",
+            "~",
+            "\"quoted\"",
+            "x:y",
+            "two\nlines",
+            "tab\there",
+            "ü",
+        ];
+        doc.allow_empty_required = true;
+        for (idx, value) in values.iter().enumerate() {
+            doc.add_record(
+                "users",
+                &idx.to_string(),
+                &[("name", value), ("note", value)],
+            )?;
+        }
+        let note = &mut doc.table_mut("users").unwrap().records_mut()?[2].cols[1];
+        note.null = true;
+        doc.styles.push(OwnedStyleLine {
+            id: "bold".into(),
+            cols: vec![OwnedField {
+                name: "weight".into(),
+                value: "700".into(),
+                null: false,
+            }],
+        });
+        roundtrip(&doc)?;
+
+        let spec = GenSpec::new(3).table(
+            GenTable::new("users", 500)
+                .col("id", ColKind::I64, GenValues::Sequential { start: -250 })
+                .col(
+                    "name",
+                    ColKind::String,
+                    GenValues::RandomString {
+                        min_len: 0,
+                        max_len: 20,
+                    },
+                )
+                .col(
+                    "role",
+                    ColKind::String,
+                    GenValues::Choice(vec!["a b".into(), "~".into(), "\"".into()]),
+                ),
+        );
+        let mut bytes = Vec::new();
+        generate(spec, &mut bytes)?;
+        roundtrip(&Document::read(&bytes[..])?)?;
+        Ok(())
+    }
+}
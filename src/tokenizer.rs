@@ -0,0 +1,1493 @@
+use std::{borrow::Cow, io::BufRead, ops::Range};
+
+use crate::{
+    error::XRVErr,
+    line::{LineKind, Span, RECORD_KEY_FIELD},
+    reader::{
+        DuplicateFields, Encoding, Limit, DEFAULT_MAX_FIELDS_PER_LINE, DEFAULT_MAX_VALUE_LEN,
+    },
+    version::{parse_version_line, EXTENDED_SYNTAX_VERSION, NULL_VALUE_VERSION},
+};
+
+#[derive(Debug)]
+enum ExpectField {
+    Name,
+    QuotedName,
+    NameClosed,
+    Colon,
+    Value,
+    Skip,
+    Qoute,
+    Escape,
+    Closed,
+}
+
+/// One tokenized line: its kind, its name and the spans of its fields,
+/// borrowed from the line bytes without any UTF-8 validation.
+#[derive(Debug, Clone)]
+pub struct RawLine<'b> {
+    pub kind: LineKind,
+    pub name: &'b [u8],
+    pub fields: FieldSpans<'b>,
+    pub(crate) line: usize,
+}
+
+impl<'b> RawLine<'b> {
+    /// Tokenizes the first line of `bytes` in the syntax of the current
+    /// format version, escape sequences included, as line 1.
+    pub fn parse(bytes: &'b [u8]) -> Result<RawLine<'b>, XRVErr> {
+        let limits = LineLimits {
+            escapes: true,
+            nulls: true,
+            ..LineLimits::default()
+        };
+        tokenize(bytes, 1, limits)
+    }
+
+    /// 1-based number of the line in its source.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+/// Lazily yields the `(name, value)` byte spans of a tokenized line, quotes
+/// stripped and escape sequences left as written. A name may be quoted like
+/// a value, so that it can hold separators and colons, but never holds
+/// escape sequences: it ends at the next quote. The line grammar is
+/// checked up front, so iterating never fails and never allocates.
+///
+/// Every span starts right after the separator or quote before it:
+///
+/// ```
+/// use xrave::RawLineIter;
+///
+/// let line = b"r:1 name:bob  age:33 note:\"a b\"\n";
+/// let raw = RawLineIter::new(line).next().unwrap()?;
+/// let at = |span: &[u8]| {
+///     let start = span.as_ptr() as usize - line.as_ptr() as usize;
+///     start..start + span.len()
+/// };
+/// assert_eq!(at(raw.name), 2..3);
+/// let spans: Vec<_> = raw.fields.map(|(name, value)| (at(name), at(value))).collect();
+/// assert_eq!(spans, [(4..8, 9..12), (14..17, 18..20), (21..25, 27..30)]);
+/// # Ok::<(), xrave::XRVErr>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct FieldSpans<'b> {
+    pub(crate) buffer: &'b [u8],
+    pos: usize,
+    /// Whether quoted values may hold escape sequences.
+    pub(crate) escapes: bool,
+    pub(crate) dialect: Dialect,
+    /// What lines collected from these fields keep of repeated names.
+    pub(crate) duplicates: DuplicateFields,
+    /// Whether spaces between a colon and its value are skipped.
+    pub(crate) space_after_colon: bool,
+    /// Whether a bare value of just the null byte of the dialect is a null.
+    pub(crate) nulls: bool,
+}
+
+impl<'b> FieldSpans<'b> {
+    /// Offsets of the next field in `buffer`.
+    fn next_link(&mut self) -> Option<Link> {
+        let buffer = self.buffer;
+        let Dialect {
+            separator,
+            quote,
+            null,
+        } = self.dialect;
+        while buffer.get(self.pos) == Some(&separator) {
+            self.pos += 1;
+        }
+        let (name_start, name_end) = match buffer.get(self.pos) {
+            Some(byte) if *byte == quote => {
+                let name_start = self.pos + 1;
+                let len = buffer.get(name_start..)?.iter().position(|b| *b == quote)?;
+                (name_start, name_start + len)
+            }
+            _ => {
+                let len = buffer
+                    .get(self.pos..)?
+                    .iter()
+                    .position(|b| *b == COLON_CHAR)?;
+                (self.pos, self.pos + len)
+            }
+        };
+        // The colon after the name, past the closing quote of a quoted one.
+        let colon = match name_start > self.pos {
+            true => name_end + 1,
+            false => name_end,
+        };
+        let mut colon = colon;
+        while self.space_after_colon && buffer.get(colon + 1) == Some(&SPACE_CHAR) {
+            colon += 1;
+        }
+        let link = match buffer.get(colon + 1) {
+            Some(byte) if *byte == quote => {
+                let value_start = colon + 2;
+                let mut value_end = value_start;
+                loop {
+                    match *buffer.get(value_end)? {
+                        byte if byte == quote => break,
+                        ESCAPE_CHAR if self.escapes => value_end += 2,
+                        _ => value_end += 1,
+                    }
+                }
+                self.pos = value_end + 1;
+                Link {
+                    name_start,
+                    name_end,
+                    value_start,
+                    value_end,
+                    quoted: true,
+                    escaped: self.escapes,
+                    null: false,
+                }
+            }
+            _ => {
+                let value_start = colon + 1;
+                let value_end = buffer[value_start..]
+                    .iter()
+                    .position(|byte| *byte == separator)
+                    .map_or(buffer.len(), |pos| value_start + pos);
+                self.pos = value_end;
+                Link {
+                    name_start,
+                    name_end,
+                    value_start,
+                    value_end,
+                    quoted: false,
+                    escaped: false,
+                    null: self.nulls && buffer[value_start..value_end] == [null],
+                }
+            }
+        };
+        Some(link)
+    }
+
+    /// Byte ranges of the name and value of each remaining field within
+    /// the line, quotes excluded.
+    pub fn ranges(self) -> impl Iterator<Item = (Range<usize>, Range<usize>)> + 'b {
+        self.links().map(|link| {
+            (
+                link.name_start..link.name_end,
+                link.value_start..link.value_end,
+            )
+        })
+    }
+
+    /// The remaining fields as offsets into `buffer`.
+    pub(crate) fn links(mut self) -> impl Iterator<Item = Link> + 'b {
+        std::iter::from_fn(move || self.next_link())
+    }
+}
+
+impl<'b> Iterator for FieldSpans<'b> {
+    type Item = (&'b [u8], &'b [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_link().map(|link| {
+            (
+                &self.buffer[link.name_start..link.name_end],
+                &self.buffer[link.value_start..link.value_end],
+            )
+        })
+    }
+}
+
+/// Value of the first field called `key_name` in a record line, as written
+/// between its quotes, or the record id for `id` when no field has that
+/// name. Fields are walked only up to the key, quoted values skipped
+/// whole, and nothing is collected or checked: `line` is taken to be a
+/// valid record line in the syntax of the current format version.
+///
+/// ```
+/// use xrave::extract_key;
+///
+/// let line = b"r:7 note:\"email:x\" email:a@x\n";
+/// assert_eq!(extract_key(line, b"email"), Some(&b"a@x"[..]));
+/// assert_eq!(extract_key(line, b"id"), Some(&b"7"[..]));
+/// assert_eq!(extract_key(line, b"name"), None);
+/// ```
+pub fn extract_key<'b>(line: &'b [u8], key_name: &[u8]) -> Option<&'b [u8]> {
+    let limits = LineLimits {
+        escapes: true,
+        nulls: true,
+        ..LineLimits::default()
+    };
+    let end = line
+        .iter()
+        .position(|byte| *byte == NL_CHAR || *byte == CR_CHAR)
+        .unwrap_or(line.len());
+    let line = &line[..end];
+    key_link(line, key_name, limits).map(|link| &line[link.value_start..link.value_end])
+}
+
+/// The field of the record line `line` that `extract_key` takes the key
+/// from: the first one called `key_name`, the last one under
+/// `DuplicateFields::KeepLast`, or for `id` the line kind and id when there
+/// is none.
+pub(crate) fn key_link(line: &[u8], key_name: &[u8], limits: LineLimits) -> Option<Link> {
+    let mut fields = FieldSpans {
+        buffer: line,
+        pos: 0,
+        escapes: limits.escapes,
+        dialect: limits.dialect,
+        duplicates: limits.duplicates,
+        space_after_colon: limits.space_after_colon,
+        nulls: limits.nulls,
+    };
+    let head = fields.next_link()?;
+    let mut named = fields
+        .links()
+        .filter(|link| &line[link.name_start..link.name_end] == key_name);
+    let link = match limits.duplicates {
+        DuplicateFields::KeepLast => named.last(),
+        _ => named.next(),
+    };
+    match link {
+        None if key_name == RECORD_KEY_FIELD.as_bytes() => Some(head),
+        link => link,
+    }
+}
+
+/// Text of the value of `link` in line `line_no`, escape sequences
+/// resolved. Invalid UTF-8 fails under `Encoding::Utf8Strict` and is
+/// replaced otherwise.
+pub(crate) fn link_value(
+    line: &[u8],
+    line_no: usize,
+    link: &Link,
+    quote: u8,
+    encoding: Encoding,
+) -> Result<String, XRVErr> {
+    if link.null {
+        return Ok(String::new());
+    }
+    let bytes = &line[link.value_start..link.value_end];
+    let value = match encoding {
+        Encoding::Utf8Strict => match std::str::from_utf8(bytes) {
+            Err(err) => {
+                return Err(XRVErr::CantParseFieldStrValue {
+                    line: line_no,
+                    col: link.value_start + err.valid_up_to() + 1,
+                })
+            }
+            Ok(value) => Cow::Borrowed(value),
+        },
+        _ => String::from_utf8_lossy(bytes),
+    };
+    Ok(match link.escaped {
+        false => value.into_owned(),
+        true => unescape(&value, quote).into_owned(),
+    })
+}
+
+#[derive(Debug)]
+pub(crate) struct Link {
+    pub(crate) name_start: usize,
+    pub(crate) name_end: usize,
+    pub(crate) value_start: usize,
+    pub(crate) value_end: usize,
+    pub(crate) quoted: bool,
+    /// Whether the value was quoted in a line that allows escape sequences.
+    pub(crate) escaped: bool,
+    /// Whether the value is the bare null byte in a line that allows nulls.
+    pub(crate) null: bool,
+}
+
+pub(crate) const JUMP_ID: u8 = b'j';
+pub(crate) const TABLE_ID: u8 = b't';
+pub(crate) const STYLE_ID: u8 = b's';
+pub(crate) const RECORD_ID: u8 = b'r';
+pub(crate) const CHECKSUM_ID: u8 = b'c';
+/// Starts a table's dictionary of repeated values.
+pub(crate) const DICTIONARY_ID: u8 = b'd';
+/// Replaces the kind byte of a deleted record.
+pub(crate) const TOMBSTONE_ID: u8 = b'x';
+/// Starts the optional first line declaring the format version.
+pub(crate) const VERSION_ID: u8 = b'v';
+
+pub(crate) const COLON_CHAR: u8 = b':';
+pub(crate) const QUOTE_CHAR: u8 = b'"';
+/// Written bare as a whole value, stands for a null from format version 3
+/// on.
+pub(crate) const NULL_CHAR: u8 = b'~';
+/// Introduces an escape sequence inside a quoted value: `\n`, `\r`, `\t`,
+/// `\\` or `\"`.
+pub(crate) const ESCAPE_CHAR: u8 = b'\\';
+pub(crate) const SPACE_CHAR: u8 = b' ';
+pub(crate) const TAB_CHAR: u8 = b'\t';
+pub(crate) const CR_CHAR: u8 = b'\r';
+pub(crate) const NL_CHAR: u8 = b'\n';
+
+pub(crate) const UTF8_BOM: [u8; 3] = [0xEF, 0xBB, 0xBF];
+
+/// The bytes separating the fields of a line, quoting values and standing
+/// for a null, space, `"` and `~` by default. The version line is always
+/// written with a space.
+///
+/// ```
+/// use xrave::{Dialect, Writer, WriterOptions};
+///
+/// let dialect = Dialect { separator: b';', quote: b'|', null: b'-' };
+/// let options = WriterOptions { dialect, ..WriterOptions::default() };
+/// let mut writer = Writer::with_options(Vec::new(), options);
+/// writer.add_table("users", &[("name", "str")])?;
+/// writer.add_record("users", "1", &[("name", "Anna Smith")])?;
+/// let text = String::from_utf8(writer.finish()?)?;
+/// assert!(text.ends_with("\nr:1;name:Anna Smith\n"));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dialect {
+    pub separator: u8,
+    pub quote: u8,
+    /// Written bare as a whole value, stands for a null rather than the
+    /// one-character string, which the writer quotes instead.
+    pub null: u8,
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Dialect {
+            separator: SPACE_CHAR,
+            quote: QUOTE_CHAR,
+            null: NULL_CHAR,
+        }
+    }
+}
+
+impl Dialect {
+    /// Checks that the separator, quote and null are three distinct ASCII
+    /// bytes, none alphanumeric nor one the line grammar already gives a
+    /// meaning: the colon, the backslash and the line terminators.
+    pub fn validate(&self) -> Result<(), XRVErr> {
+        let usable = |byte: u8| {
+            byte.is_ascii()
+                && !byte.is_ascii_alphanumeric()
+                && !matches!(byte, COLON_CHAR | ESCAPE_CHAR | CR_CHAR | NL_CHAR)
+        };
+        let distinct =
+            self.separator != self.quote && self.null != self.separator && self.null != self.quote;
+        match usable(self.separator) && usable(self.quote) && usable(self.null) && distinct {
+            true => Ok(()),
+            false => Err(XRVErr::InvalidDialect {
+                separator: self.separator,
+                quote: self.quote,
+                null: self.null,
+            }),
+        }
+    }
+}
+
+/// Whether `line` opens with a known `kind:` marker.
+pub(crate) fn starts_with_kind(line: &[u8]) -> bool {
+    matches!(
+        line,
+        [
+            JUMP_ID | TABLE_ID | STYLE_ID | RECORD_ID | CHECKSUM_ID | TOMBSTONE_ID | DICTIONARY_ID,
+            COLON_CHAR,
+            ..
+        ]
+    )
+}
+
+/// Whether `line` holds nothing to parse: it is empty or only spaces and
+/// tabs, or it is the `last` line of its source and only control bytes and
+/// spaces, like the DOS end-of-file mark `\x1a` some exporters append.
+pub(crate) fn is_filler(line: &[u8], last: bool) -> bool {
+    if last {
+        line.iter().all(|byte| *byte <= SPACE_CHAR)
+    } else {
+        line.iter()
+            .all(|byte| matches!(*byte, SPACE_CHAR | TAB_CHAR))
+    }
+}
+
+/// Most bytes of the failing line kept in an `XRVErr::AtLine` snippet.
+const SNIPPET_MAX_LEN: usize = 120;
+
+impl<'b> TryFrom<&'b XraveBuffer> for RawLine<'b> {
+    type Error = XRVErr;
+    fn try_from(xrave_buffer: &'b XraveBuffer) -> Result<Self, XRVErr> {
+        tokenize(&xrave_buffer.buffer, xrave_buffer.line, xrave_buffer.limits)
+            .map_err(|err| xrave_buffer.relocate(err))
+    }
+}
+
+/// Per-line limits checked while tokenizing, before any field is collected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LineLimits {
+    pub(crate) max_fields: usize,
+    pub(crate) max_value_len: usize,
+    /// Whether a backslash inside quotes starts an escape sequence, as from
+    /// format version 2 on.
+    pub(crate) escapes: bool,
+    pub(crate) dialect: Dialect,
+    pub(crate) duplicates: DuplicateFields,
+    /// Whether spaces between a colon and its value are skipped rather
+    /// than read as the end of an empty value.
+    pub(crate) space_after_colon: bool,
+    /// Whether a bare value of just the null byte of the dialect is a null,
+    /// as from format version 3 on.
+    pub(crate) nulls: bool,
+}
+
+impl Default for LineLimits {
+    fn default() -> Self {
+        LineLimits {
+            max_fields: DEFAULT_MAX_FIELDS_PER_LINE,
+            max_value_len: DEFAULT_MAX_VALUE_LEN,
+            escapes: false,
+            dialect: Dialect::default(),
+            duplicates: DuplicateFields::default(),
+            space_after_colon: false,
+            nulls: false,
+        }
+    }
+}
+
+/// Decodes the escape sequences of a value quoted with `quote`. A backslash
+/// before any other byte is kept as written, as is a trailing one.
+pub(crate) fn unescape(value: &str, quote: u8) -> Cow<'_, str> {
+    if !value.bytes().any(|byte| byte == ESCAPE_CHAR) {
+        return Cow::Borrowed(value);
+    }
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != ESCAPE_CHAR as char {
+            out.push(c);
+            continue;
+        }
+        match chars.clone().next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some(c) if c == ESCAPE_CHAR as char || c == quote as char => out.push(c),
+            _ => {
+                out.push(c);
+                continue;
+            }
+        }
+        chars.next();
+    }
+    Cow::Owned(out)
+}
+
+/// Where the line grammar stands between two bytes of a line, so that a
+/// line can be checked in one piece by `tokenize` or in chunks by
+/// `LineTokenizer` alike.
+#[derive(Debug)]
+struct Scan {
+    state: ExpectField,
+    fields: usize,
+    value_start: usize,
+    name_start: usize,
+}
+
+impl Scan {
+    fn new() -> Self {
+        Scan {
+            state: ExpectField::Name,
+            fields: 0,
+            value_start: 0,
+            name_start: 0,
+        }
+    }
+
+    /// Checks `byte`, at offset `idx` of the line, against the grammar.
+    /// Line terminators are never fed here but end the line through
+    /// `finish`. Errors come with their 1-based column.
+    fn step(&mut self, idx: usize, byte: u8, limits: &LineLimits) -> Result<(), (usize, XRVErr)> {
+        let Dialect {
+            separator, quote, ..
+        } = limits.dialect;
+        let col = idx + 1;
+        if matches!(
+            self.state,
+            ExpectField::Skip | ExpectField::Qoute | ExpectField::Escape
+        ) && idx - self.value_start >= limits.max_value_len
+        {
+            let limit = Limit::ValueLen(limits.max_value_len);
+            return Err((col, XRVErr::LimitExceeded(limit)));
+        }
+        match self.state {
+            ExpectField::Name => match byte {
+                COLON_CHAR => return Err((col, XRVErr::ExpectSpaceOrAlpha)),
+                byte if byte == separator => {}
+                byte => {
+                    self.fields += 1;
+                    if self.fields > limits.max_fields {
+                        let limit = Limit::FieldsPerLine(limits.max_fields);
+                        return Err((col, XRVErr::LimitExceeded(limit)));
+                    }
+                    self.name_start = col;
+                    self.state = match byte == quote {
+                        true => ExpectField::QuotedName,
+                        false => ExpectField::Colon,
+                    }
+                }
+            },
+            ExpectField::QuotedName => match byte {
+                byte if byte == quote && idx == self.name_start => {
+                    return Err((col, XRVErr::ExpectSpaceOrAlpha))
+                }
+                byte if byte == quote => self.state = ExpectField::NameClosed,
+                _ => {}
+            },
+            ExpectField::NameClosed => match byte {
+                COLON_CHAR => {
+                    self.value_start = col;
+                    self.state = ExpectField::Value
+                }
+                _ => return Err((col, XRVErr::NameMustFolowedByColon)),
+            },
+            ExpectField::Colon => match byte {
+                COLON_CHAR => {
+                    self.value_start = col;
+                    self.state = ExpectField::Value
+                }
+                byte if byte == quote => return Err((col, XRVErr::NameMustNotContainQoutes)),
+                byte if byte == separator => return Err((col, XRVErr::NameMustFolowedByColon)),
+                _ => {}
+            },
+            ExpectField::Value => match byte {
+                COLON_CHAR => return Err((col, XRVErr::ExpectAlpha)),
+                SPACE_CHAR if limits.space_after_colon => self.value_start = col,
+                byte if byte == separator => self.state = ExpectField::Name,
+                byte if byte == quote => {
+                    self.value_start = col;
+                    self.state = ExpectField::Qoute
+                }
+                _ => self.state = ExpectField::Skip,
+            },
+            ExpectField::Skip => match byte {
+                byte if byte == COLON_CHAR || byte == quote => {
+                    return Err((col, XRVErr::ExpectingSpaceOrNewline))
+                }
+                byte if byte == separator => self.state = ExpectField::Name,
+                _ => {}
+            },
+            ExpectField::Qoute => match byte {
+                byte if byte == quote => self.state = ExpectField::Closed,
+                ESCAPE_CHAR if limits.escapes => self.state = ExpectField::Escape,
+                _ => {}
+            },
+            ExpectField::Escape => self.state = ExpectField::Qoute,
+            ExpectField::Closed => match byte {
+                byte if byte == separator => self.state = ExpectField::Name,
+                _ => return Err((col, XRVErr::ExpectingSpaceOrNewline)),
+            },
+        };
+        Ok(())
+    }
+
+    /// Checks that a line may end after its first `end` bytes.
+    fn finish(&self, end: usize) -> Result<(), (usize, XRVErr)> {
+        match self.state {
+            ExpectField::Name | ExpectField::Closed | ExpectField::Skip | ExpectField::Value => {
+                Ok(())
+            }
+            ExpectField::Colon | ExpectField::NameClosed => {
+                Err((end + 1, XRVErr::NameMustFolowedByColon))
+            }
+            ExpectField::QuotedName | ExpectField::Qoute | ExpectField::Escape => {
+                Err((end + 1, XRVErr::ExpectingQouteNotNewline))
+            }
+        }
+    }
+}
+
+/// Checks `value` against the line grammar and `limits` and splits off its
+/// kind and name; the fields are left for `FieldSpans` to walk lazily. The
+/// line ends at the first terminator in `value`, if any.
+pub(crate) fn tokenize(
+    value: &[u8],
+    line: usize,
+    limits: LineLimits,
+) -> Result<RawLine<'_>, XRVErr> {
+    let end = value
+        .iter()
+        .position(|byte| *byte == NL_CHAR || *byte == CR_CHAR)
+        .unwrap_or(value.len());
+    let mut scan = Scan::new();
+    value[..end]
+        .iter()
+        .enumerate()
+        .try_for_each(|(idx, byte)| scan.step(idx, *byte, &limits))
+        .and_then(|_| scan.finish(end))
+        .map_err(|(col, err)| diagnose(value, line, col, err))?;
+    line_head(value, end, line, limits)
+}
+
+/// Splits the kind and name off the first `end` bytes of `value`, which
+/// the grammar has been checked for.
+fn line_head(
+    value: &[u8],
+    end: usize,
+    line: usize,
+    limits: LineLimits,
+) -> Result<RawLine<'_>, XRVErr> {
+    let mut fields = FieldSpans {
+        buffer: &value[..end],
+        pos: 0,
+        escapes: limits.escapes,
+        dialect: limits.dialect,
+        duplicates: limits.duplicates,
+        space_after_colon: limits.space_after_colon,
+        nulls: limits.nulls,
+    };
+    let head = match fields.next_link() {
+        None => return Err(diagnose(value, line, 1, XRVErr::FailToGetLineKind)),
+        Some(head) => head,
+    };
+    let kind: LineKind = match value[head.name_start..head.name_end] {
+        [JUMP_ID] => LineKind::Jump,
+        [TABLE_ID] => LineKind::Table,
+        [STYLE_ID] => LineKind::Style,
+        [RECORD_ID] => LineKind::Record,
+        [CHECKSUM_ID] => LineKind::Checksum,
+        [TOMBSTONE_ID] => LineKind::Tombstone,
+        [DICTIONARY_ID] => LineKind::Dictionary,
+        _ => {
+            return Err(diagnose(
+                value,
+                line,
+                head.name_start + 1,
+                XRVErr::UnkwnownLineKind,
+            ))
+        }
+    };
+
+    Ok(RawLine {
+        kind,
+        name: &value[head.value_start..head.value_end],
+        fields,
+        line,
+    })
+}
+
+/// What `LineTokenizer::push` made of a chunk.
+#[derive(Debug)]
+pub enum TokenizerEvent<'t> {
+    /// The whole chunk went into a line that has not ended yet.
+    NeedMore,
+    /// A line ended within the first `consumed` bytes of the chunk, its
+    /// terminator included; the rest of the chunk is left to push again.
+    Line {
+        consumed: usize,
+        line: Result<RawLine<'t>, XRVErr>,
+    },
+}
+
+/// Tokenizes lines fed in chunks split anywhere, even inside a quoted
+/// value or a name, with the same state machine as the other readers, so
+/// that every split of the same bytes yields the same lines. Bytes are
+/// checked as they come in and kept until their line ends. Every line is
+/// handed out, blank ones as `XRVErr::FailToGetLineKind`, and version
+/// lines are left to the caller; lines are in the syntax of the current
+/// format version.
+///
+/// ```
+/// use xrave::{LineTokenizer, TokenizerEvent};
+///
+/// let mut tokenizer = LineTokenizer::new();
+/// let mut ids = Vec::new();
+/// for mut chunk in [&b"r:1 note:\"a "[..], b"b\"\nr:2 x:y\n"] {
+///     while let TokenizerEvent::Line { consumed, line } = tokenizer.push(chunk) {
+///         ids.push(String::from_utf8_lossy(line?.name).into_owned());
+///         chunk = &chunk[consumed..];
+///     }
+/// }
+/// assert_eq!(ids, ["1", "2"]);
+/// # Ok::<(), xrave::XRVErr>(())
+/// ```
+#[derive(Debug)]
+pub struct LineTokenizer {
+    scan: Scan,
+    limits: LineLimits,
+    /// Bytes of the current line so far, terminator left out.
+    bytes: Vec<u8>,
+    /// Number of the last line handed out.
+    line: usize,
+    /// First grammar error of the current line, with its column.
+    error: Option<(usize, XRVErr)>,
+    /// Whether the last line ended in a CR at the end of a chunk, so that an
+    /// LF starting the next one belongs to it.
+    after_cr: bool,
+    /// Whether the current line was handed out and is cleared on next push.
+    ended: bool,
+}
+
+impl Default for LineTokenizer {
+    fn default() -> Self {
+        LineTokenizer::new()
+    }
+}
+
+impl LineTokenizer {
+    pub fn new() -> Self {
+        LineTokenizer {
+            scan: Scan::new(),
+            limits: LineLimits {
+                escapes: true,
+                nulls: true,
+                ..LineLimits::default()
+            },
+            bytes: Vec::new(),
+            line: 0,
+            error: None,
+            after_cr: false,
+            ended: false,
+        }
+    }
+
+    /// Feeds the next chunk, stopping at the first line that ends in it.
+    pub fn push(&mut self, chunk: &[u8]) -> TokenizerEvent<'_> {
+        if chunk.is_empty() {
+            return TokenizerEvent::NeedMore;
+        }
+        self.start_line();
+        let mut consumed = 0;
+        if std::mem::take(&mut self.after_cr) && chunk[0] == NL_CHAR {
+            consumed = 1;
+        }
+        let rest = &chunk[consumed..];
+        let end = rest
+            .iter()
+            .position(|byte| *byte == NL_CHAR || *byte == CR_CHAR);
+        let content = &rest[..end.unwrap_or(rest.len())];
+        for byte in content {
+            if self.error.is_none() {
+                let idx = self.bytes.len();
+                self.error = self.scan.step(idx, *byte, &self.limits).err();
+            }
+            self.bytes.push(*byte);
+        }
+        consumed += content.len();
+        let end = match end {
+            None => return TokenizerEvent::NeedMore,
+            Some(end) => end,
+        };
+        consumed += 1;
+        if rest[end] == CR_CHAR {
+            match rest.get(end + 1) {
+                Some(&NL_CHAR) => consumed += 1,
+                Some(_) => {}
+                None => self.after_cr = true,
+            }
+        }
+        TokenizerEvent::Line {
+            consumed,
+            line: self.end_line(),
+        }
+    }
+
+    /// Hands out the last line when the input does not end in a
+    /// terminator.
+    pub fn finish(&mut self) -> Option<Result<RawLine<'_>, XRVErr>> {
+        self.start_line();
+        self.after_cr = false;
+        match self.bytes.is_empty() {
+            true => None,
+            false => Some(self.end_line()),
+        }
+    }
+
+    /// Clears the line handed out last.
+    fn start_line(&mut self) {
+        if std::mem::take(&mut self.ended) {
+            self.bytes.clear();
+            self.scan = Scan::new();
+            self.error = None;
+        }
+    }
+
+    fn end_line(&mut self) -> Result<RawLine<'_>, XRVErr> {
+        self.ended = true;
+        self.line += 1;
+        let checked = match self.error.take() {
+            Some(err) => Err(err),
+            None => self.scan.finish(self.bytes.len()),
+        };
+        match checked {
+            Err((col, err)) => Err(diagnose(&self.bytes, self.line, col, err)),
+            Ok(()) => line_head(&self.bytes, self.bytes.len(), self.line, self.limits),
+        }
+    }
+}
+
+pub(crate) const DEFAULT_XRAVE_NEW_BUFFER_CAPACITY: usize = 4 * 1024;
+
+#[derive(Debug)]
+pub(crate) struct XraveBuffer {
+    pub(crate) buffer: Vec<u8>,
+    /// Number of the first physical line of the line in the buffer.
+    pub(crate) line: usize,
+    /// Offset of the first byte of the line in the buffer.
+    pub(crate) start: u64,
+    pub(crate) offset: u64,
+    pub(crate) limits: LineLimits,
+    /// Whether a trailing backslash continues a line, as from format version
+    /// 2 on.
+    pub(crate) continuations: bool,
+    /// For each continuation spliced into the buffer, the buffer offset the
+    /// next physical line starts at and the number of bytes dropped before
+    /// it: the backslash and the line terminator.
+    pub(crate) continued: Vec<(usize, usize)>,
+}
+
+impl XraveBuffer {
+    pub(crate) fn new() -> Self {
+        XraveBuffer {
+            buffer: Vec::with_capacity(DEFAULT_XRAVE_NEW_BUFFER_CAPACITY),
+            line: 0,
+            start: 0,
+            offset: 0,
+            limits: LineLimits::default(),
+            continuations: false,
+            continued: Vec::new(),
+        }
+    }
+
+    /// Turns on the line syntax of format `version`: escape sequences and
+    /// continuations from version 2 on, nulls from version 3 on.
+    pub(crate) fn set_version(&mut self, version: u32) {
+        let extended = version >= EXTENDED_SYNTAX_VERSION;
+        self.limits.escapes = extended;
+        self.continuations = extended;
+        self.limits.nulls = version >= NULL_VALUE_VERSION;
+    }
+
+    /// Reads the next logical line into the buffer, accepting LF, CRLF and
+    /// CR-only terminators. With `continuations` on, a physical line ending
+    /// in a backslash right before its terminator continues on the next one:
+    /// the two are spliced without the backslash and terminator. Terminators are consumed but
+    /// never stored, so the returned byte count can be larger than the
+    /// buffer length.
+    ///
+    /// A line longer than `max_len` is consumed up to its terminator without
+    /// being stored and reported as `XRVErr::LineTooLong`, leaving the source
+    /// positioned at the start of the next line.
+    pub(crate) fn read_line<R: BufRead>(
+        &mut self,
+        source: &mut R,
+        max_len: usize,
+    ) -> Result<usize, XRVErr> {
+        self.line += self.continued.len();
+        self.continued.clear();
+        self.buffer.clear();
+        self.start = self.offset;
+        let mut read: usize = 0;
+        let mut len: usize = 0;
+        let mut too_long = false;
+        loop {
+            let line_start = len;
+            let (physical, continues) = self.read_physical_line(source, max_len, &mut len)?;
+            if physical == 0 {
+                break;
+            }
+            if read == 0 {
+                self.line += 1;
+            }
+            read += physical;
+            too_long |= len > max_len;
+            if !continues {
+                break;
+            }
+            len -= 1;
+            if !too_long {
+                self.buffer.pop();
+            }
+            self.continued.push((len, physical - (len - line_start)));
+        }
+
+        self.offset += read as u64;
+        if too_long {
+            self.buffer.clear();
+            return Err(XRVErr::LineTooLong {
+                line: self.line,
+                len,
+                max: max_len,
+            });
+        }
+        Ok(read)
+    }
+
+    /// Appends one physical line to the buffer, adding its length to `len`.
+    /// Returns the bytes consumed and whether the line ends in a
+    /// continuation.
+    fn read_physical_line<R: BufRead>(
+        &mut self,
+        source: &mut R,
+        max_len: usize,
+        len: &mut usize,
+    ) -> Result<(usize, bool), XRVErr> {
+        let mut read: usize = 0;
+        let mut last: Option<u8> = None;
+        loop {
+            let available = match source.fill_buf() {
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(XRVErr::FailToReadLine(err)),
+                Ok(available) => available,
+            };
+            if available.is_empty() {
+                return Ok((read, false));
+            }
+
+            let (content, terminator) = match available
+                .iter()
+                .position(|byte| *byte == NL_CHAR || *byte == CR_CHAR)
+            {
+                None => (available.len(), None),
+                Some(pos) => (pos, Some(available[pos])),
+            };
+            if content > 0 {
+                last = Some(available[content - 1]);
+            }
+            *len += content;
+            if *len <= max_len {
+                self.buffer.extend_from_slice(&available[..content]);
+            }
+
+            match terminator {
+                None => {
+                    source.consume(content);
+                    read += content;
+                }
+                Some(terminator) => {
+                    source.consume(content + 1);
+                    read += content + 1;
+                    if terminator == CR_CHAR {
+                        match source.fill_buf() {
+                            Err(err) => return Err(XRVErr::FailToReadLine(err)),
+                            Ok([NL_CHAR, ..]) => {
+                                source.consume(1);
+                                read += 1;
+                            }
+                            Ok(_) => {}
+                        }
+                    }
+                    return Ok((read, self.continuations && last == Some(ESCAPE_CHAR)));
+                }
+            }
+        }
+    }
+
+    /// Location of the line in the buffer.
+    pub(crate) fn span(&self) -> Span {
+        Span {
+            offset: self.start,
+            len: (self.offset - self.start) as usize,
+        }
+    }
+
+    /// Physical lines read so far, counting every line of a continued one.
+    pub(crate) fn lines_read(&self) -> usize {
+        self.line + self.continued.len()
+    }
+
+    /// Offset from the start of the line in the source of the byte at `pos`
+    /// in the buffer, counting the bytes dropped by the continuations
+    /// before it.
+    pub(crate) fn line_offset(&self, pos: usize) -> usize {
+        let dropped: usize = self
+            .continued
+            .iter()
+            .take_while(|(at, _)| *at <= pos)
+            .map(|(_, dropped)| dropped)
+            .sum();
+        pos + dropped
+    }
+
+    /// Wraps `err` in `XRVErr::AtLine` for the line in the buffer, at the
+    /// physical line and column the 1-based buffer column `col` came from.
+    pub(crate) fn diagnose(&self, col: usize, err: XRVErr) -> XRVErr {
+        let breaks = self
+            .continued
+            .iter()
+            .take_while(|(at, _)| *at < col)
+            .count();
+        let start = match breaks {
+            0 => 0,
+            n => self.continued[n - 1].0,
+        };
+        let end = self
+            .continued
+            .get(breaks)
+            .map_or(self.buffer.len(), |c| c.0);
+        diagnose(
+            &self.buffer[start..end],
+            self.line + breaks,
+            col - start,
+            err,
+        )
+    }
+
+    /// Moves an `XRVErr::AtLine` raised against the whole buffer, as by
+    /// `tokenize`, to the physical line it came from.
+    pub(crate) fn relocate(&self, err: XRVErr) -> XRVErr {
+        match err {
+            XRVErr::AtLine { col, err, .. } if !self.continued.is_empty() => {
+                self.diagnose(col, *err)
+            }
+            err => err,
+        }
+    }
+
+    /// Whether the line in the buffer is filler, as by `is_filler`, peeking
+    /// at `source` only to tell whether it is the last line.
+    pub(crate) fn is_filler<R: BufRead>(&self, source: &mut R) -> Result<bool, XRVErr> {
+        if self.buffer.iter().any(|byte| *byte > SPACE_CHAR) {
+            return Ok(false);
+        }
+        loop {
+            return match source.fill_buf() {
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => Err(XRVErr::FailToReadLine(err)),
+                Ok(rest) => Ok(is_filler(&self.buffer, rest.is_empty())),
+            };
+        }
+    }
+
+    pub(crate) fn strip_bom(&mut self) {
+        if self.buffer.starts_with(&UTF8_BOM) {
+            self.buffer.drain(..UTF8_BOM.len());
+            for (at, _) in self.continued.iter_mut() {
+                *at -= UTF8_BOM.len();
+            }
+        }
+    }
+}
+
+/// Wraps `err` in `XRVErr::AtLine` with the line number, the 1-based byte
+/// column `col` and up to `SNIPPET_MAX_LEN` bytes of the line text. Errors
+/// that already carry a snippet are returned unchanged.
+pub(crate) fn diagnose(buffer: &[u8], line: usize, col: usize, err: XRVErr) -> XRVErr {
+    if let XRVErr::AtLine { .. } = err {
+        return err;
+    }
+    let mut snippet = &buffer[..buffer.len().min(SNIPPET_MAX_LEN)];
+    if let Err(utf8) = std::str::from_utf8(snippet) {
+        if utf8.error_len().is_none() {
+            snippet = &snippet[..utf8.valid_up_to()];
+        }
+    }
+    XRVErr::AtLine {
+        line,
+        col,
+        snippet: String::from_utf8_lossy(snippet).into_owned(),
+        err: Box::new(err),
+    }
+}
+
+/// Splits an in-memory file into tokenized lines, accepting LF, CRLF and
+/// CR-only terminators. Field names and values are handed out as bytes, so
+/// lines whose values are not valid UTF-8 still come through; only the
+/// line grammar is checked. A version line is read for the syntax it
+/// declares but not handed out. Lines are borrowed as they are, so continued
+/// lines are not spliced but come through one physical line at a time.
+///
+/// ```
+/// use xrave::{LineKind, RawLineIter};
+///
+/// let mut lines = RawLineIter::new(b"j:jumps\nr:1 data:\xFF\xFE\n");
+/// assert_eq!(lines.next().unwrap()?.kind, LineKind::Jump);
+/// let record = lines.next().unwrap()?;
+/// assert_eq!(record.fields.clone().next(), Some((&b"data"[..], &b"\xFF\xFE"[..])));
+/// # Ok::<(), xrave::XRVErr>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct RawLineIter<'b> {
+    input: &'b [u8],
+    line: usize,
+    limits: LineLimits,
+}
+
+impl<'b> RawLineIter<'b> {
+    pub fn new(input: &'b [u8]) -> Self {
+        RawLineIter {
+            input: input.strip_prefix(&UTF8_BOM[..]).unwrap_or(input),
+            line: 0,
+            limits: LineLimits::default(),
+        }
+    }
+}
+
+impl<'b> Iterator for RawLineIter<'b> {
+    type Item = Result<RawLine<'b>, XRVErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.input.is_empty() {
+                return None;
+            }
+            let (content, rest) = match self
+                .input
+                .iter()
+                .position(|byte| *byte == NL_CHAR || *byte == CR_CHAR)
+            {
+                None => (self.input, &self.input[self.input.len()..]),
+                Some(pos) => match self.input[pos..] {
+                    [CR_CHAR, NL_CHAR, ..] => (&self.input[..pos], &self.input[pos + 2..]),
+                    _ => (&self.input[..pos], &self.input[pos + 1..]),
+                },
+            };
+            self.input = rest;
+            self.line += 1;
+            if self.line == 1 {
+                match parse_version_line(content) {
+                    None => {}
+                    Some(Err(err)) => return Some(Err(diagnose(content, 1, 1, err))),
+                    Some(Ok(version)) => {
+                        self.limits.escapes = version >= EXTENDED_SYNTAX_VERSION;
+                        self.limits.nulls = version >= NULL_VALUE_VERSION;
+                        continue;
+                    }
+                }
+            }
+            if is_filler(content, rest.is_empty()) {
+                continue;
+            }
+            return Some(tokenize(content, self.line, self.limits));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::Document;
+
+    /// Xorshift generator, so failures reproduce without a seed to record.
+    fn xorshift(mut state: u64) -> impl FnMut() -> u64 {
+        move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        }
+    }
+
+    #[test]
+    fn random_bytes_never_panic_and_fields_stay_in_bounds() {
+        let alphabet = b"jtsrcx: \"\\\r\n-09aez\xFF\xC3";
+        let mut next = xorshift(0x2545_F491_4F6C_DD1D);
+        for _ in 0..200_000 {
+            let len = (next() % 32) as usize;
+            let bytes: Vec<u8> = (0..len)
+                .map(|_| alphabet[next() as usize % alphabet.len()])
+                .collect();
+            let range = bytes.as_ptr_range();
+            for line in RawLineIter::new(&bytes).flatten() {
+                for (name, value) in line.fields.clone() {
+                    assert!(range.contains(&name.as_ptr()) && !name.is_empty());
+                    assert!(value.is_empty() || range.contains(&value.as_ptr()));
+                    assert!(value.as_ptr_range().end <= range.end);
+                }
+                for (name, value) in line.fields.ranges() {
+                    assert!(name.start < name.end && name.end <= value.start);
+                    assert!(value.start <= value.end);
+                }
+            }
+            let _ = Document::read(&bytes[..]);
+        }
+    }
+
+    fn read_error(input: &str) -> (usize, usize, XRVErr) {
+        match Document::read(input.as_bytes()) {
+            Err(XRVErr::AtLine { line, col, err, .. }) => (line, col, *err),
+            other => panic!("expected AtLine for {:?}, got {:?}", input, other),
+        }
+    }
+
+    #[test]
+    fn jump_region_past_usize_fails() {
+        let (line, _, err) = read_error("j:jumps a:18446744073709551615-5\n");
+        assert_eq!(line, 1);
+        assert!(
+            matches!(
+                err,
+                XRVErr::RegionOverflow {
+                    pos: usize::MAX,
+                    len: 5
+                }
+            ),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn table_region_past_usize_fails() {
+        let (line, _, err) =
+            read_error("j:jumps\nt:1 name:a pos:5 len:18446744073709551615\nr:1 x:1\n");
+        assert_eq!(line, 2);
+        assert!(
+            matches!(
+                err,
+                XRVErr::RegionOverflow {
+                    pos: 5,
+                    len: usize::MAX
+                }
+            ),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn jump_without_numbers_fails() {
+        let (_, _, err) = read_error("j:jumps a:-\n");
+        assert!(matches!(err, XRVErr::CantParseFieldUsizeValue), "{:?}", err);
+    }
+
+    #[test]
+    fn empty_quoted_jump_name_fails() {
+        let (line, col, err) = read_error("j:jumps \"\":1-1\n");
+        assert_eq!((line, col), (1, 10));
+        assert!(matches!(err, XRVErr::ExpectSpaceOrAlpha), "{:?}", err);
+    }
+
+    #[test]
+    fn empty_quoted_jumps_line_name_fails() {
+        let (_, _, err) = read_error("j:\"\"\n");
+        assert!(matches!(err, XRVErr::ItsNotAJumpsLine), "{:?}", err);
+    }
+
+    #[test]
+    fn empty_quoted_record_id_fails() {
+        let (line, col, err) = read_error("j:jumps a:7-99\nx:1\nr:\"\" :\n");
+        assert_eq!((line, col), (3, 6));
+        assert!(matches!(err, XRVErr::ExpectSpaceOrAlpha), "{:?}", err);
+    }
+
+    #[test]
+    fn byte_order_mark_alone_fails() {
+        let (line, _, err) = read_error("\u{FEFF}");
+        assert_eq!(line, 1);
+        assert!(matches!(err, XRVErr::FailToGetLineKind), "{:?}", err);
+    }
+
+    #[test]
+    fn extract_key_agrees_with_the_parser() -> Result<(), crate::XRVErr> {
+        use crate::{extract_key, parse_line, ParsedLine};
+
+        let line = b"r:7 note:\"id:x \\\" email:y\" email:a@x \"the key\":b\n";
+        assert_eq!(extract_key(line, b"email"), Some(&b"a@x"[..]));
+        assert_eq!(extract_key(line, b"the key"), Some(&b"b"[..]));
+        assert_eq!(extract_key(line, b"id"), Some(&b"7"[..]));
+        assert_eq!(extract_key(line, b"r"), None);
+
+        let names = ["id", "a", "b", "key"];
+        let values = [
+            "",
+            "1",
+            "x-y",
+            "\"a b\"",
+            "\"k:v\"",
+            "\"\\\"q\\\"\"",
+            "\"id:2 b:3\"",
+            "\"\"",
+        ];
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        let mut next = |n: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize % n
+        };
+        for _ in 0..10_000 {
+            let mut line = format!("r:{}", next(100));
+            for _ in 0..next(6) {
+                line += &format!(
+                    " {}:{}",
+                    names[next(names.len())],
+                    values[next(values.len())]
+                );
+            }
+            let record = match parse_line(line.as_bytes())? {
+                ParsedLine::Record(record) => record,
+                other => panic!("{:?}", other),
+            };
+            for name in names {
+                let expected = record
+                    .get_all(name)
+                    .next()
+                    .or((name == "id").then_some(record.id()));
+                let key = extract_key(line.as_bytes(), name.as_bytes());
+                assert_eq!(key, expected.map(str::as_bytes), "{} in {}", name, line);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn dialects_round_trip_and_are_validated() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Dialect, ParseOptions, Reader, Writer, WriterOptions, XRVErr};
+
+        let dialect = Dialect {
+            separator: b';',
+            quote: b'|',
+            null: b'-',
+        };
+        let write = |dialect: Dialect| -> Result<String, XRVErr> {
+            let options = WriterOptions {
+                dialect,
+                ..WriterOptions::default()
+            };
+            let mut writer = Writer::with_options(Vec::new(), options);
+            writer.add_table("users", &[("name", "str"), ("note", "str?")])?;
+            writer.add_record(
+                "users",
+                "1",
+                &[("name", "Anna Smith"), ("note", "a;b |c| \\")],
+            )?;
+            writer.add_record("users", "2", &[("name", "Bob \"B\""), ("note", "")])?;
+            Ok(String::from_utf8(writer.finish()?).unwrap())
+        };
+
+        let text = write(dialect)?;
+        assert!(text.contains("r:1;name:Anna Smith;note:|a;b \\|c\\| \\\\|\n"));
+        assert!(text.contains("r:2;name:Bob \"B\";note:||\n"));
+        assert_eq!(
+            write(Dialect::default())?,
+            "v:xrave 3\n\
+             j:jumps users:78-69\n\
+             t:1 name:users pos:78 len:69 name:str note:str?\n\
+             r:1 name:\"Anna Smith\" note:\"a;b |c| \\\\\"\n\
+             r:2 name:\"Bob \\\"B\\\"\" note:\"\"\n",
+        );
+
+        let path = std::env::temp_dir().join("xrave-test-dialect.xrv");
+        std::fs::write(&path, &text)?;
+        let options = ParseOptions {
+            dialect,
+            ..ParseOptions::default()
+        };
+        let mut reader =
+            Reader::with_options(path.to_string_lossy().into_owned(), options.clone())?;
+        let records = reader.records("users")?.collect::<Result<Vec<_>, _>>()?;
+        let values: Vec<_> = records[0]
+            .cols
+            .iter()
+            .chain(&records[1].cols)
+            .map(|f| &f.value)
+            .collect();
+        assert_eq!(values, ["Anna Smith", "a;b |c| \\", "Bob \"B\"", ""]);
+
+        let clash = Dialect {
+            separator: b'|',
+            quote: b'|',
+            ..Dialect::default()
+        };
+        let clash = ParseOptions {
+            dialect: clash,
+            ..options
+        };
+        let opened = Reader::with_options(path.to_string_lossy().into_owned(), clash);
+        assert!(matches!(opened, Err(XRVErr::InvalidDialect { .. })));
+        assert!(Dialect {
+            separator: b'x',
+            ..Dialect::default()
+        }
+        .validate()
+        .is_err());
+        assert!(Dialect {
+            separator: b':',
+            ..Dialect::default()
+        }
+        .validate()
+        .is_err());
+        assert!(Dialect {
+            null: b'"',
+            ..Dialect::default()
+        }
+        .validate()
+        .is_err());
+        assert!(Dialect {
+            separator: b'\t',
+            quote: b'\'',
+            null: b'-'
+        }
+        .validate()
+        .is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn every_split_of_the_input_yields_the_same_lines() {
+        use crate::{LineTokenizer, TokenizerEvent};
+
+        let input =
+            b"t:1 name:\"my table\" pos:0 len:9\r\nr:1 note:\"a\\\"b\" n:2\rr:2 bad\"\nr:3 x:y";
+        let lines = |chunks: &[&[u8]]| {
+            let mut tokenizer = LineTokenizer::new();
+            let mut lines = Vec::new();
+            let describe = |line: Result<crate::RawLine, crate::XRVErr>| match line {
+                Ok(line) => format!(
+                    "{} {:?} {:?}",
+                    line.line(),
+                    line.name,
+                    line.fields.ranges().collect::<Vec<_>>()
+                ),
+                Err(err) => err.to_string(),
+            };
+            for mut chunk in chunks.iter().copied() {
+                while let TokenizerEvent::Line { consumed, line } = tokenizer.push(chunk) {
+                    lines.push(describe(line));
+                    chunk = &chunk[consumed..];
+                }
+            }
+            lines.extend(tokenizer.finish().map(describe));
+            lines
+        };
+
+        let whole = lines(&[input]);
+        assert_eq!(whole.len(), 4);
+        assert!(whole[2].contains("line 3"));
+        for at in 0..=input.len() {
+            assert_eq!(
+                lines(&[&input[..at], &input[at..]]),
+                whole,
+                "split at {}",
+                at
+            );
+            for to in at..=input.len() {
+                assert_eq!(lines(&[&input[..at], &input[at..to], &input[to..]]), whole);
+            }
+        }
+        let bytes: Vec<&[u8]> = input.chunks(1).collect();
+        assert_eq!(lines(&bytes), whole);
+    }
+
+    #[test]
+    fn raw_lines_hand_out_values_as_bytes() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{LineKind, RawLineIter, Reader};
+
+        let mut bytes = b"j:jumps\nt:1 name:blobs pos:0 len:0 data:bin\n".to_vec();
+        for id in 0..100_000 {
+            bytes.extend_from_slice(format!("r:{} data:", id).as_bytes());
+            bytes.extend_from_slice(&[0xFF, 0xFE, b'\n']);
+        }
+
+        let mut records = 0;
+        for line in RawLineIter::new(&bytes) {
+            let line = line?;
+            if line.kind == LineKind::Record {
+                let (name, value) = line.fields.clone().next().unwrap();
+                assert_eq!((name, value), (&b"data"[..], &[0xFF, 0xFE][..]));
+                records += 1;
+            }
+        }
+        assert_eq!(records, 100_000);
+
+        let path = std::env::temp_dir().join("xrave-test-raw.xrv");
+        std::fs::write(&path, &bytes)?;
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        assert!(reader.next_record().is_err());
+        Ok(())
+    }
+}
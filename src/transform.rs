@@ -0,0 +1,450 @@
+use std::io::prelude::*;
+use std::{borrow::Cow, io::BufReader};
+
+use crate::{
+    dictionary::{expand_fields, parse_dictionary},
+    error::XRVErr,
+    line::{LineField, LineJump, LineKind, RecordLine, StyleLine, TableLine, Value},
+    ordered::OrderedMap,
+    reader::{ParseMode, ParseOptions},
+    schema::validate_identifier,
+    tokenizer::{RawLine, XraveBuffer, ESCAPE_CHAR, NL_CHAR, NULL_CHAR},
+    version::{read_first_line, EXTENDED_SYNTAX_VERSION, NULL_VALUE_VERSION},
+    writer::Writer,
+};
+
+/// Maps a value, given the declaration of its column in the header.
+type ValueMap = Box<dyn Fn(&str, &str) -> Option<String>>;
+
+/// Names and declarations of a table's columns.
+type Columns = Vec<(String, String)>;
+
+enum TransformOp {
+    /// Renames a column in the header and in every record.
+    RenameColumn {
+        table: String,
+        from: String,
+        to: String,
+    },
+    /// Replaces each value of a column, dropping the field on `None`.
+    MapValue {
+        table: String,
+        column: String,
+        map: ValueMap,
+    },
+    /// Leaves a table, its header and its records out of the output.
+    DropTable(String),
+}
+
+impl TransformOp {
+    fn table(&self) -> &str {
+        match self {
+            TransformOp::RenameColumn { table, .. }
+            | TransformOp::MapValue { table, .. }
+            | TransformOp::DropTable(table) => table,
+        }
+    }
+}
+
+/// Changes applied in order to every line of their table by `transform`.
+#[derive(Default)]
+pub struct Transform {
+    ops: Vec<TransformOp>,
+}
+
+impl std::fmt::Debug for Transform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Transform")
+            .field("ops", &self.ops.len())
+            .finish()
+    }
+}
+
+impl Transform {
+    pub fn new() -> Transform {
+        Transform::default()
+    }
+
+    pub fn rename_column(mut self, table: &str, from: &str, to: &str) -> Self {
+        self.ops.push(TransformOp::RenameColumn {
+            table: table.to_owned(),
+            from: from.to_owned(),
+            to: to.to_owned(),
+        });
+        self
+    }
+
+    /// Passes each decoded value of `column` to `map`; the field takes the
+    /// value returned, or is dropped from the record on `None`.
+    pub fn map_value<F>(mut self, table: &str, column: &str, map: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + 'static,
+    {
+        self.ops.push(TransformOp::MapValue {
+            table: table.to_owned(),
+            column: column.to_owned(),
+            map: Box::new(move |_, value| map(value)),
+        });
+        self
+    }
+
+    /// Like `map_value`, also passing `map` the declaration of `column`
+    /// in the header, such as `i32?`, before the value.
+    pub(crate) fn map_declared_value<F>(mut self, table: &str, column: &str, map: F) -> Self
+    where
+        F: Fn(&str, &str) -> Option<String> + 'static,
+    {
+        self.ops.push(TransformOp::MapValue {
+            table: table.to_owned(),
+            column: column.to_owned(),
+            map: Box::new(map),
+        });
+        self
+    }
+
+    pub fn drop_table(mut self, name: &str) -> Self {
+        self.ops.push(TransformOp::DropTable(name.to_owned()));
+        self
+    }
+
+    fn drops(&self, table: &str) -> bool {
+        self.ops
+            .iter()
+            .any(|op| matches!(op, TransformOp::DropTable(name) if name == table))
+    }
+
+    fn ops_on<'t>(&'t self, table: &'t str) -> impl Iterator<Item = &'t TransformOp> {
+        self.ops.iter().filter(move |op| op.table() == table)
+    }
+
+    /// Checks the renames and maps of `table` against its columns, in
+    /// order, and returns the columns renamed along with the declaration of
+    /// the column of each map.
+    fn header_cols(&self, table: &TableLine) -> Result<(Columns, Vec<String>), XRVErr> {
+        let mut cols: Vec<(String, String)> = table
+            .cols
+            .iter()
+            .map(|col| (col.name.to_owned(), col.value_unescaped().into_owned()))
+            .collect();
+        let has_col =
+            |cols: &[(String, String)], name: &str| cols.iter().any(|(col, _)| col == name);
+        let unknown = |column: &str| XRVErr::UnknownColumn {
+            table: table.name.to_owned(),
+            column: column.to_owned(),
+        };
+        let mut declared = Vec::new();
+        for op in self.ops_on(table.name) {
+            match op {
+                TransformOp::RenameColumn { from, to, .. } => {
+                    validate_identifier(to)?;
+                    if !has_col(&cols, from) {
+                        return Err(unknown(from));
+                    }
+                    if has_col(&cols, to) {
+                        return Err(XRVErr::DuplicateColumn {
+                            table: table.name.to_owned(),
+                            column: to.clone(),
+                        });
+                    }
+                    for (col, _) in cols.iter_mut().filter(|(col, _)| col == from) {
+                        col.clone_from(to);
+                    }
+                }
+                TransformOp::MapValue { column, .. } => {
+                    match cols.iter().find(|(col, _)| col == column) {
+                        None => return Err(unknown(column)),
+                        Some((_, decl)) => declared.push(decl.clone()),
+                    }
+                }
+                TransformOp::DropTable(_) => {}
+            }
+        }
+        Ok((cols, declared))
+    }
+
+    /// Fields of `record` after the renames and maps of `table`, or `None`
+    /// when they leave it as it was. `declared` holds the declaration of
+    /// the column of each map, as returned by `header_cols`.
+    fn record_fields<'r>(
+        &self,
+        table: &str,
+        declared: &[String],
+        record: &'r RecordLine,
+    ) -> Option<Vec<(Cow<'r, str>, Option<String>)>> {
+        let mut fields: Vec<(Cow<str>, Option<String>)> = record
+            .cols
+            .iter()
+            .map(|field| {
+                let value = (!field.null).then(|| field.value_unescaped().into_owned());
+                (Cow::Borrowed(field.name), value)
+            })
+            .collect();
+        let mut changed = false;
+        let mut declared = declared.iter();
+        for op in self.ops_on(table) {
+            match op {
+                TransformOp::RenameColumn { from, to, .. } => {
+                    for (name, _) in fields.iter_mut().filter(|(name, _)| name == from) {
+                        *name = Cow::Owned(to.clone());
+                        changed = true;
+                    }
+                }
+                TransformOp::MapValue { column, map, .. } => {
+                    let decl = declared.next().map_or("", String::as_str);
+                    let mut mapped = Vec::with_capacity(fields.len());
+                    for (name, value) in fields {
+                        let value = match value {
+                            Some(value) if name == column.as_str() => value,
+                            value => {
+                                mapped.push((name, value));
+                                continue;
+                            }
+                        };
+                        match map(decl, &value) {
+                            None => changed = true,
+                            Some(new) => {
+                                changed |= new != value;
+                                mapped.push((name, Some(new)));
+                            }
+                        }
+                    }
+                    fields = mapped;
+                }
+                TransformOp::DropTable(_) => {}
+            }
+        }
+        changed.then_some(fields)
+    }
+}
+
+/// A table `transform` writes out: its name and the declarations of the
+/// columns its maps apply to.
+struct KeptTable {
+    name: String,
+    declared: Vec<String>,
+}
+
+/// Streams `input` to `output` line by line, applying `transform` to the
+/// tables it names. Record lines it leaves unchanged are copied byte for
+/// byte, apart from their terminator becoming LF; the header lines, the
+/// jumps line, every `pos`/`len` and any checksums are written again for
+/// the new layout, and tombstoned lines are left out. Naming a table or
+/// column the input does not have fails, as does renaming a column to one
+/// that exists.
+///
+/// ```
+/// use xrave::{transform, Transform, Writer};
+///
+/// let mut input = Vec::new();
+/// let mut writer = Writer::new(&mut input);
+/// writer.add_table("users", &[("name", "str")])?;
+/// writer.add_record("users", "1", &[("name", "Anna")])?;
+/// writer.finish()?;
+///
+/// let changes = Transform::new().rename_column("users", "name", "full_name");
+/// let mut output = Vec::new();
+/// transform(&input[..], &mut output, &changes)?;
+/// assert!(String::from_utf8(output)?.contains("\nr:1 full_name:Anna\n"));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn transform<R: Read, W: Write>(
+    input: R,
+    output: W,
+    transform: &Transform,
+) -> Result<(), XRVErr> {
+    let max_line_len = ParseOptions::default().max_line_len;
+    let mut source = BufReader::new(input);
+    let mut buffer = XraveBuffer::new();
+    let version = read_first_line(
+        &mut buffer,
+        &mut source,
+        max_line_len,
+        ParseMode::Strict,
+        &mut Vec::new(),
+    )?;
+    let raw_line: RawLine = (&buffer).try_into()?;
+    if let Err(err) = LineJump::try_from(raw_line) {
+        return Err(buffer.diagnose(err.position().1, err));
+    }
+
+    let mut writer = Writer::new(output);
+    // Region of each table, `None` for dropped tables.
+    let mut regions: Vec<(usize, usize, Option<KeptTable>)> = Vec::new();
+    let mut seen: Vec<String> = Vec::new();
+    let mut dictionaries: OrderedMap<OrderedMap<String>> = OrderedMap::new();
+    loop {
+        let start = buffer.offset as usize;
+        if buffer.read_line(&mut source, max_line_len)? == 0 {
+            break;
+        }
+        if buffer.is_filler(&mut source)? {
+            continue;
+        }
+        let raw_line: RawLine = (&buffer).try_into()?;
+        let line_field = match LineField::try_from(raw_line) {
+            Err(err) => return Err(buffer.diagnose(err.position().1, err)),
+            Ok(line_field) => line_field,
+        };
+        match line_field.kind {
+            LineKind::Jump => return Err(buffer.diagnose(1, XRVErr::ItsNotAJumpsLine)),
+            LineKind::Checksum => writer.options.checksums = true,
+            LineKind::Tombstone => {}
+            LineKind::Table => {
+                let table = match TableLine::try_from(line_field) {
+                    Err(err) => return Err(buffer.diagnose(err.position().1, err)),
+                    Ok(table) => table,
+                };
+                if seen.iter().any(|name| name == table.name) {
+                    return Err(XRVErr::DuplicateTable(table.name.to_owned()));
+                }
+                seen.push(table.name.to_owned());
+                let end = table.pos + table.len;
+                if transform.drops(table.name) {
+                    regions.push((table.pos, end, None));
+                    continue;
+                }
+                let (cols, declared) = transform.header_cols(&table)?;
+                let cols: Vec<(&str, &str)> = cols
+                    .iter()
+                    .map(|(name, kind)| (name.as_str(), kind.as_str()))
+                    .collect();
+                writer.add_table(table.name, &cols)?;
+                if let Some(next_id) = table.next_id {
+                    writer.set_next_id(table.name, next_id)?;
+                }
+                regions.push((
+                    table.pos,
+                    end,
+                    Some(KeptTable {
+                        name: table.name.to_owned(),
+                        declared,
+                    }),
+                ));
+            }
+            LineKind::Style => writer.add_style_line(&StyleLine::try_from(line_field)?)?,
+            LineKind::Dictionary => {
+                let (table, entries) = parse_dictionary(line_field);
+                if dictionaries.insert(table.clone(), entries).is_some() {
+                    return Err(XRVErr::DuplicateDictionary(table));
+                }
+            }
+            LineKind::Record => {
+                let mut record = RecordLine::try_from(line_field)?;
+                let region = regions
+                    .iter()
+                    .find(|(pos, end, _)| (*pos..*end).contains(&start));
+                let (table, declared) = match region {
+                    None => return Err(XRVErr::RecordOutsideTable { line: buffer.line }),
+                    Some((_, _, None)) => continue,
+                    Some((_, _, Some(kept))) => (&kept.name, &kept.declared),
+                };
+                // The output has no dictionaries, so tokens are written out
+                // as their values.
+                let dictionary = dictionaries.get(table);
+                if let Some(dictionary) = dictionary {
+                    if let Err(err) = expand_fields(dictionary, table, &mut record.cols) {
+                        return Err(buffer.diagnose(1, err));
+                    }
+                }
+                match transform.record_fields(table, declared, &record) {
+                    Some(fields) => {
+                        let fields: Vec<(&str, Value)> = fields
+                            .iter()
+                            .map(|(name, value)| match value {
+                                None => (&**name, Value::Null),
+                                Some(value) => (&**name, Value::Str(value)),
+                            })
+                            .collect();
+                        writer.add_record_values(table, record.id, &fields)?;
+                    }
+                    // Copied as they are, records would keep tokens the
+                    // output has no dictionary for, records of a version 1
+                    // file would gain escape sequences and those of an older
+                    // file nulls in the current version of the output.
+                    None if dictionary.is_some()
+                        || version < EXTENDED_SYNTAX_VERSION
+                            && buffer.buffer.contains(&ESCAPE_CHAR)
+                        || version < NULL_VALUE_VERSION && buffer.buffer.contains(&NULL_CHAR) =>
+                    {
+                        writer.add_record_line(table, &record)?;
+                    }
+                    None => {
+                        writer.add_raw_records(table, &buffer.buffer)?;
+                        writer.add_raw_records(table, &[NL_CHAR])?;
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(op) = transform
+        .ops
+        .iter()
+        .find(|op| !seen.iter().any(|name| name == op.table()))
+    {
+        return Err(XRVErr::UnknownTable(op.table().to_owned()));
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn transform_renames_maps_and_drops() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{transform, Document, Transform, Writer, WriterOptions};
+
+        let options = WriterOptions {
+            align_columns: true,
+            ..WriterOptions::default()
+        };
+        let mut input = Vec::new();
+        let mut writer = Writer::with_options(&mut input, options);
+        writer.add_table("users", &[("name", "str"), ("email", "str?")])?;
+        writer.add_table("sessions", &[("user", "i32"), ("token", "str")])?;
+        writer.add_table("audit", &[("note", "str")])?;
+        writer.add_record(
+            "users",
+            "1",
+            &[("name", "Anna"), ("email", "Anna@Example.COM")],
+        )?;
+        writer.add_record("users", "2", &[("name", "Bob"), ("email", "unknown")])?;
+        writer.add_record("sessions", "s1", &[("user", "1"), ("token", "a b")])?;
+        writer.add_record("sessions", "s10", &[("user", "22"), ("token", "c")])?;
+        writer.add_record("audit", "a1", &[("note", "login")])?;
+        writer.finish()?;
+
+        let domain = |email: &str| {
+            email
+                .split_once('@')
+                .map(|(_, domain)| domain.to_lowercase())
+        };
+        let changes = Transform::new()
+            .rename_column("users", "name", "full_name")
+            .map_value("users", "email", domain)
+            .drop_table("audit");
+        let mut output = Vec::new();
+        transform(&input[..], &mut output, &changes)?;
+
+        let document = Document::read(&output[..])?;
+        assert!(document.table("audit").is_none());
+        let users = document.table("users").unwrap();
+        assert_eq!(&*users.cols[0].name, "full_name");
+        let records = users.records().collect::<Result<Vec<_>, _>>()?;
+        let fields: Vec<_> = records[0]
+            .cols
+            .iter()
+            .map(|f| (&*f.name, f.value.as_str()))
+            .collect();
+        assert_eq!(fields, [("full_name", "Anna"), ("email", "example.com")]);
+        assert_eq!(records[1].cols.len(), 1);
+
+        let sessions = "r:s1  user:1  token:\"a b\"\nr:s10 user:22 token:c\n";
+        let (input, output) = (String::from_utf8(input)?, String::from_utf8(output)?);
+        assert!(input.contains(sessions) && output.contains(sessions));
+
+        let unknown = Transform::new().drop_table("nope");
+        assert!(transform(input.as_bytes(), &mut Vec::new(), &unknown).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,939 @@
+use std::{borrow::Cow, str::FromStr, time::Duration};
+
+use crate::{
+    base64,
+    document::DocumentTable,
+    error::XRVErr,
+    line::{OwnedField, OwnedRecordLine, Value},
+    schema::{split_default, Col, ColKind, GROUP_SEPARATOR},
+    units::{parse_duration, parse_size, unit_error},
+};
+
+/// A record seen through its table's column declarations. It borrows the
+/// record, or holds it when it was read back for a spilled table.
+#[derive(Debug, Clone)]
+pub struct TypedRecord<'a> {
+    table: &'a str,
+    cols: &'a [OwnedField],
+    record: Cow<'a, OwnedRecordLine>,
+    coercion: CoercionOptions<'a>,
+}
+
+/// Lenient readings the number and bool getters of `TypedRecord` may apply
+/// before failing. Every coercion is off by default.
+///
+/// ```
+/// use xrave::{CoercionOptions, Document};
+///
+/// let document = Document::read(
+///     &b"j:jumps\n\
+///     t:1 name:users pos:45 len:15 age:i32\n\
+///     r:1 age:\" 42 \"\n"[..],
+/// )?;
+/// let record = document.table("users").unwrap().typed_records().next().unwrap()?;
+/// assert!(record.get_i32("age").is_err());
+/// let trim = CoercionOptions { trim_whitespace: true, ..CoercionOptions::default() };
+/// assert_eq!(record.with_coercion(trim).get_i32("age")?, 42);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CoercionOptions<'a> {
+    /// Accept a number wrapped in one pair of `'` or `"` marks inside the
+    /// value, as left by tools that quote on export. Quotes around the
+    /// value in the file itself are never part of the value.
+    pub quoted_numbers: bool,
+    /// Extra spellings for `bool` values besides `true` and `false`,
+    /// compared case-insensitively.
+    pub bool_words: &'a [(&'a str, bool)],
+    /// Ignore leading and trailing whitespace, also inside quote marks.
+    pub trim_whitespace: bool,
+    /// Read an empty value as if the field were left out.
+    pub empty_as_null: bool,
+    /// Separators of numbers written with locale formatting, such as
+    /// `1.234,56`; by default only plain `1234.56` is read.
+    pub numeric_locale: NumericLocale,
+}
+
+/// Decimal and thousands separators of numbers written in a locale's
+/// format, read by the number getters of `TypedRecord` with
+/// `CoercionOptions::numeric_locale` and by `Col::accepts_in`.
+///
+/// The locale alone decides what a separator means, never the value: with
+/// `decimal: ','`, `1,234` is one point two three four, and with
+/// `thousands: Some(',')` and `decimal: '.'` it is one thousand two hundred
+/// thirty-four. A thousands separator counts only before the decimal one,
+/// after one to three digits, and must be followed by groups of exactly
+/// three digits; `1,23` or `1234,567` under `thousands: Some(',')` are
+/// rejected rather than guessed at. A `.` or `,` the locale does not
+/// declare is rejected too, so that `1.234` under a comma-decimal locale
+/// without thousands separator is not read as `1.234`. A thousands
+/// separator equal to `decimal` is never used, and a space thousands
+/// separator also matches the no-break spaces U+00A0 and U+202F.
+///
+/// ```
+/// use xrave::NumericLocale;
+///
+/// let comma = NumericLocale { thousands: Some('.'), decimal: ',' };
+/// assert_eq!(comma.normalize("1.234,56").as_deref(), Some("1234.56"));
+/// assert_eq!(comma.normalize("1,234.5"), None);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumericLocale {
+    pub thousands: Option<char>,
+    pub decimal: char,
+}
+
+impl Default for NumericLocale {
+    fn default() -> Self {
+        NumericLocale {
+            thousands: None,
+            decimal: '.',
+        }
+    }
+}
+
+impl NumericLocale {
+    /// `value` rewritten without thousands separators and with `.` as the
+    /// decimal separator, ready for `str::parse`, or `None` when its
+    /// separators do not fit the locale. Under the default locale `value`
+    /// is returned as is.
+    pub fn normalize<'v>(&self, value: &'v str) -> Option<Cow<'v, str>> {
+        if *self == NumericLocale::default() {
+            return Some(Cow::Borrowed(value));
+        }
+        let mut out = String::with_capacity(value.len());
+        let body = match value.strip_prefix(['+', '-']) {
+            None => value,
+            Some(rest) => {
+                out.push_str(&value[..1]);
+                rest
+            }
+        };
+        // Digits since the last thousands separator, or since the start.
+        let mut group = 0;
+        let mut grouped = false;
+        let mut integer = true;
+        for c in body.chars() {
+            if c.is_ascii_digit() {
+                group += integer as usize;
+                out.push(c);
+                continue;
+            }
+            if integer && grouped && group != 3 && !self.is_thousands(c) {
+                return None;
+            }
+            if c == self.decimal {
+                if !integer {
+                    return None;
+                }
+                integer = false;
+                out.push('.');
+            } else if self.is_thousands(c) {
+                if !integer || group == 0 || (grouped && group != 3) || group > 3 {
+                    return None;
+                }
+                grouped = true;
+                group = 0;
+            } else if c == '.' || c == ',' {
+                return None;
+            } else {
+                integer = false;
+                out.push(c);
+            }
+        }
+        if integer && grouped && group != 3 {
+            return None;
+        }
+        Some(Cow::Owned(out))
+    }
+
+    fn is_thousands(&self, c: char) -> bool {
+        match self.thousands {
+            Some(sep) if sep == self.decimal => false,
+            Some(' ') => matches!(c, ' ' | '\u{a0}' | '\u{202f}'),
+            Some(sep) => c == sep,
+            None => false,
+        }
+    }
+}
+
+impl<'a> TypedRecord<'a> {
+    /// Pairs `record` with the column declarations of the table `table`,
+    /// as found in `OwnedTableLine::cols` or `DocumentTable::cols`.
+    pub fn new(table: &'a str, cols: &'a [OwnedField], record: &'a OwnedRecordLine) -> Self {
+        TypedRecord::from_cow(table, cols, Cow::Borrowed(record))
+    }
+
+    fn from_cow(table: &'a str, cols: &'a [OwnedField], record: Cow<'a, OwnedRecordLine>) -> Self {
+        TypedRecord {
+            table,
+            cols,
+            record,
+            coercion: CoercionOptions::default(),
+        }
+    }
+
+    /// A copy of the record read with `coercion`.
+    pub fn with_coercion(&self, coercion: CoercionOptions<'a>) -> Self {
+        TypedRecord {
+            coercion,
+            ..self.clone()
+        }
+    }
+
+    pub fn record(&self) -> &OwnedRecordLine {
+        &self.record
+    }
+
+    /// Declaration of `col`, when the table declares it as a known kind.
+    pub fn col(&self, col: &str) -> Option<Col> {
+        self.cols
+            .iter()
+            .find(|header| &*header.name == col)
+            .and_then(|header| Col::parse(&header.value).ok())
+    }
+
+    /// Columns of the dotted group `group` that have a value, as pairs of
+    /// the name after `group.` and the value, defaults resolved as by
+    /// `resolve`, in declaration order.
+    ///
+    /// ```
+    /// use xrave::{ColKind, Schema};
+    ///
+    /// let mut document = Schema::new()
+    ///     .table("users", |t| {
+    ///         t.col("name", ColKind::String).group("addr", |g| {
+    ///             g.col("city", ColKind::String)
+    ///                 .col("zip", ColKind::String.optional())
+    ///                 .col("country", ColKind::String.with_default("NL"))
+    ///         })
+    ///     })
+    ///     .build()?;
+    /// document.add_record("users", "1", &[("name", "Anna"), ("addr.city", "Delft")])?;
+    /// let table = document.table("users").unwrap();
+    /// let record = table.typed_records().next().unwrap()?;
+    /// let addr: Vec<_> = record.subfields("addr").collect();
+    /// assert_eq!(addr, [("city", "Delft"), ("country", "NL")]);
+    /// assert_eq!(record.subfields("name").count(), 0);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn subfields(&self, group: &str) -> impl Iterator<Item = (&str, &str)> + '_ {
+        let prefix = format!("{}{}", group, GROUP_SEPARATOR);
+        self.cols.iter().filter_map(move |header| {
+            let suffix = header.name.strip_prefix(prefix.as_str())?;
+            Some((suffix, self.resolve(&header.name)?))
+        })
+    }
+
+    /// Whether the record itself has a field `col`, rather than relying on
+    /// the column default.
+    pub fn has_explicit(&self, col: &str) -> bool {
+        self.record.cols.iter().any(|field| &*field.name == col)
+    }
+
+    /// Value of `col` in the record, else the column's declared default.
+    ///
+    /// ```
+    /// use xrave::{ColKind, Schema};
+    ///
+    /// let mut document = Schema::new()
+    ///     .table("tasks", |t| t.col("status", ColKind::String.with_default("in review")))
+    ///     .build()?;
+    /// document.add_record("tasks", "1", &[])?;
+    /// let record = document.table("tasks").unwrap().typed_records().next().unwrap()?;
+    /// assert_eq!(record.resolve("status"), Some("in review"));
+    /// assert!(!record.has_explicit("status"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn resolve(&self, col: &str) -> Option<&str> {
+        match self.value(col)? {
+            Value::Null => None,
+            Value::Str(value) => Some(value),
+        }
+    }
+
+    /// Value of `col` in the record, `Value::Null` for a null, else the
+    /// column's declared default. A null does not fall back to the
+    /// default.
+    pub fn value(&self, col: &str) -> Option<Value<'_>> {
+        match self.record.cols.iter().find(|field| &*field.name == col) {
+            Some(field) => Some(field.as_value()),
+            None => self
+                .cols
+                .iter()
+                .find(|header| &*header.name == col)
+                .and_then(|header| split_default(&header.value).1)
+                .map(Value::Str),
+        }
+    }
+
+    /// Value of `col`, or `None` when the record leaves it out and the
+    /// column has no default, or holds a null. An explicitly empty value is
+    /// `Some("")`, whether written as `email:""` or `email:`.
+    ///
+    /// ```
+    /// use xrave::{ColKind, Reader, Schema, TypedRecord};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-empty.xrv");
+    /// std::fs::write(&path, "j:jumps\nr:1 email: name:Anna\nr:2 name:Bob\n")?;
+    /// let schema = Schema::new()
+    ///     .table("users", |t| t.col("name", ColKind::String).col("email", ColKind::String.optional()))
+    ///     .build()?;
+    /// let cols = &schema.table("users").unwrap().cols;
+    ///
+    /// let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+    /// let first = reader.next_record()?.unwrap().to_owned();
+    /// let second = reader.next_record()?.unwrap().to_owned();
+    /// assert_eq!(TypedRecord::new("users", cols, &first).get_opt_str("email"), Some(""));
+    /// assert_eq!(TypedRecord::new("users", cols, &first).get_str("name")?, "Anna");
+    /// assert_eq!(TypedRecord::new("users", cols, &second).get_opt_str("email"), None);
+    /// assert!(TypedRecord::new("users", cols, &second).get_str("email").is_err());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn get_opt_str(&self, col: &str) -> Option<&str> {
+        self.resolve(col)
+    }
+
+    /// Value of `col`, failing with `XRVErr::MissingColumn` when the record
+    /// leaves it out and the column has no default, or holds a null.
+    pub fn get_str(&self, col: &str) -> Result<&str, XRVErr> {
+        self.required(col, self.get_opt_str(col))
+    }
+
+    /// Every value of the list column `col`, in record order, nulls left
+    /// out; empty when the record leaves it out.
+    ///
+    /// ```
+    /// use xrave::{ColKind, Schema};
+    ///
+    /// let mut document = Schema::new()
+    ///     .table("posts", |t| t.col("tag", ColKind::String.list()))
+    ///     .build()?;
+    /// document.add_record("posts", "1", &[("tag", "x"), ("tag", "y")])?;
+    /// let record = document.table("posts").unwrap().typed_records().next().unwrap()?;
+    /// assert_eq!(record.get_list("tag"), ["x", "y"]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn get_list(&self, col: &str) -> Vec<&str> {
+        self.record
+            .cols
+            .iter()
+            .filter(|field| &*field.name == col && !field.null)
+            .map(|field| field.value.as_str())
+            .collect()
+    }
+
+    pub fn get_i32(&self, col: &str) -> Result<i32, XRVErr> {
+        self.required(col, self.get_opt_i32(col)?)
+    }
+
+    pub fn get_opt_i32(&self, col: &str) -> Result<Option<i32>, XRVErr> {
+        self.get_number(col, "i32")
+    }
+
+    pub fn get_i64(&self, col: &str) -> Result<i64, XRVErr> {
+        self.required(col, self.get_opt_i64(col)?)
+    }
+
+    pub fn get_opt_i64(&self, col: &str) -> Result<Option<i64>, XRVErr> {
+        self.get_number(col, "i64")
+    }
+
+    pub fn get_f64(&self, col: &str) -> Result<f64, XRVErr> {
+        self.required(col, self.get_opt_f64(col)?)
+    }
+
+    pub fn get_opt_f64(&self, col: &str) -> Result<Option<f64>, XRVErr> {
+        self.get_number(col, "f64")
+    }
+
+    pub fn get_bool(&self, col: &str) -> Result<bool, XRVErr> {
+        self.required(col, self.get_opt_bool(col)?)
+    }
+
+    pub fn get_opt_bool(&self, col: &str) -> Result<Option<bool>, XRVErr> {
+        let value = match self.coerced(col) {
+            None => return Ok(None),
+            Some(value) => value,
+        };
+        let word = self
+            .coercion
+            .bool_words
+            .iter()
+            .find(|(word, _)| word.eq_ignore_ascii_case(value));
+        match (value, word) {
+            ("true", _) => Ok(Some(true)),
+            ("false", _) => Ok(Some(false)),
+            (_, Some((_, flag))) => Ok(Some(*flag)),
+            _ => Err(self.invalid(col, value, "bool")),
+        }
+    }
+
+    /// Decoded value of the `bytes` column `col`. A value that is not
+    /// standard base64 fails with `XRVErr::InvalidBase64` at the offending
+    /// byte.
+    ///
+    /// ```
+    /// use xrave::Document;
+    ///
+    /// let document = Document::read(
+    ///     &b"j:jumps\n\
+    ///     t:1 name:files pos:48 len:14 blob:bytes\n\
+    ///     r:1 blob:aGk=\n"[..],
+    /// )?;
+    /// let record = document.table("files").unwrap().typed_records().next().unwrap()?;
+    /// assert_eq!(record.get_bytes("blob")?, b"hi");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn get_bytes(&self, col: &str) -> Result<Vec<u8>, XRVErr> {
+        self.required(col, self.get_opt_bytes(col)?)
+    }
+
+    pub fn get_opt_bytes(&self, col: &str) -> Result<Option<Vec<u8>>, XRVErr> {
+        match self.coerced(col).map(base64::decode) {
+            None => Ok(None),
+            Some(Ok(bytes)) => Ok(Some(bytes)),
+            Some(Err(pos)) => Err(XRVErr::InvalidBase64 {
+                column: col.to_owned(),
+                pos,
+            }),
+        }
+    }
+
+    /// Bytes of the `size` column `col`: a number, whole or decimal, with
+    /// an optional `k`, `M` or `G` for powers of 1000 or `Ki`, `Mi` or `Gi`
+    /// for powers of 1024, and an optional `B`. A value coming to a fraction
+    /// of a byte fails with `XRVErr::FractionalSize`, anything else that
+    /// does not read as a size with `XRVErr::InvalidTypedValue`.
+    /// `RecordBuilder::field_size` writes sizes in the largest exact unit.
+    ///
+    /// ```
+    /// use xrave::{ColKind, Schema};
+    ///
+    /// let mut document = Schema::new().table("limits", |t| t.col("max", ColKind::Size)).build()?;
+    /// document.add_record("limits", "1", &[("max", "64MiB")])?;
+    /// let record = document.table("limits").unwrap().typed_records().next().unwrap()?;
+    /// assert_eq!(record.get_size("max")?, 64 << 20);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn get_size(&self, col: &str) -> Result<u64, XRVErr> {
+        self.required(col, self.get_opt_size(col)?)
+    }
+
+    pub fn get_opt_size(&self, col: &str) -> Result<Option<u64>, XRVErr> {
+        match self.coerced(col) {
+            None => Ok(None),
+            Some(value) => match parse_size(value) {
+                Err(err) => Err(unit_error(err, self.table, col, value, ColKind::Size)),
+                Ok(bytes) => Ok(Some(bytes)),
+            },
+        }
+    }
+
+    /// Length of time in the `duration` column `col`: a number, whole or
+    /// decimal, followed by `ms`, `s`, `m` or `h`, or by nothing for
+    /// seconds. A value below zero fails with `XRVErr::NegativeDuration`,
+    /// anything else that does not read as a duration, a fraction of a
+    /// nanosecond included, with `XRVErr::InvalidTypedValue`.
+    /// `RecordBuilder::field_duration` writes durations in the largest
+    /// exact unit.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use xrave::{ColKind, Schema};
+    ///
+    /// let mut document = Schema::new().table("jobs", |t| t.col("timeout", ColKind::Duration)).build()?;
+    /// document.add_record("jobs", "1", &[("timeout", "250ms")])?;
+    /// let record = document.table("jobs").unwrap().typed_records().next().unwrap()?;
+    /// assert_eq!(record.get_duration("timeout")?, Duration::from_millis(250));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn get_duration(&self, col: &str) -> Result<Duration, XRVErr> {
+        self.required(col, self.get_opt_duration(col)?)
+    }
+
+    pub fn get_opt_duration(&self, col: &str) -> Result<Option<Duration>, XRVErr> {
+        match self.coerced(col) {
+            None => Ok(None),
+            Some(value) => match parse_duration(value) {
+                Err(err) => Err(unit_error(err, self.table, col, value, ColKind::Duration)),
+                Ok(duration) => Ok(Some(duration)),
+            },
+        }
+    }
+
+    /// Value of `col` after trimming and `empty_as_null`, before any
+    /// number or bool coercion.
+    fn coerced(&self, col: &str) -> Option<&str> {
+        let mut value = self.get_opt_str(col)?;
+        if self.coercion.trim_whitespace {
+            value = value.trim();
+        }
+        if self.coercion.empty_as_null && value.is_empty() {
+            return None;
+        }
+        Some(value)
+    }
+
+    fn get_number<T: FromStr>(
+        &self,
+        col: &str,
+        expected: &'static str,
+    ) -> Result<Option<T>, XRVErr> {
+        let value = match self.coerced(col) {
+            None => return Ok(None),
+            Some(value) => value,
+        };
+        let mut number = value;
+        if self.coercion.quoted_numbers {
+            for quote in ['"', '\''] {
+                if let Some(inner) = number
+                    .strip_prefix(quote)
+                    .and_then(|rest| rest.strip_suffix(quote))
+                {
+                    number = inner;
+                    break;
+                }
+            }
+            if self.coercion.trim_whitespace {
+                number = number.trim();
+            }
+        }
+        let parsed = self
+            .coercion
+            .numeric_locale
+            .normalize(number)
+            .and_then(|number| number.parse::<T>().ok());
+        match parsed {
+            None => Err(self.invalid(col, value, expected)),
+            Some(number) => Ok(Some(number)),
+        }
+    }
+
+    fn required<T>(&self, col: &str, value: Option<T>) -> Result<T, XRVErr> {
+        match value {
+            None => Err(XRVErr::MissingColumn {
+                table: self.table.to_owned(),
+                column: col.to_owned(),
+            }),
+            Some(value) => Ok(value),
+        }
+    }
+
+    fn invalid(&self, col: &str, value: &str, expected: &'static str) -> XRVErr {
+        XRVErr::InvalidTypedValue {
+            table: self.table.to_owned(),
+            column: col.to_owned(),
+            value: value.to_owned(),
+            expected,
+        }
+    }
+}
+
+impl DocumentTable {
+    /// Every record in order, seen through the table's column
+    /// declarations; spilled records are read from the file again.
+    pub fn typed_records(&self) -> impl Iterator<Item = Result<TypedRecord<'_>, XRVErr>> {
+        self.records()
+            .map(|record| Ok(TypedRecord::from_cow(&self.name, &self.cols, record?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn coercions_apply_only_when_asked() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{CoercionOptions, Document, XRVErr};
+
+        let document = Document::read(
+            &b"j:jumps\n\
+            t:1 name:users pos:66 len:54 age:i32 admin:bool note:str?\n\
+            r:1 age:\" 42 \" admin:yes note:\"\"\n\
+            r:2 age:'7' admin:no\n"[..],
+        )?;
+        let table = document.table("users").unwrap();
+        let records = table.typed_records().collect::<Result<Vec<_>, _>>()?;
+
+        assert!(matches!(
+            records[0].get_i32("age"),
+            Err(XRVErr::InvalidTypedValue { .. })
+        ));
+        assert!(records[1].get_i32("age").is_err());
+        assert!(records[0].get_bool("admin").is_err());
+        assert!(records[0].get_opt_i32("note").is_err());
+
+        let trim = CoercionOptions {
+            trim_whitespace: true,
+            ..CoercionOptions::default()
+        };
+        assert_eq!(records[0].with_coercion(trim).get_i32("age")?, 42);
+
+        let quoted = CoercionOptions {
+            quoted_numbers: true,
+            ..CoercionOptions::default()
+        };
+        assert_eq!(records[1].with_coercion(quoted).get_i32("age")?, 7);
+        assert!(records[0].with_coercion(quoted).get_i32("age").is_err());
+        let both = CoercionOptions {
+            trim_whitespace: true,
+            ..quoted
+        };
+        assert_eq!(records[0].with_coercion(both).get_i32("age")?, 42);
+
+        let words = CoercionOptions {
+            bool_words: &[("yes", true), ("no", false)],
+            ..CoercionOptions::default()
+        };
+        assert!(records[0].with_coercion(words).get_bool("admin")?);
+        assert!(!records[1].with_coercion(words).get_bool("admin")?);
+
+        let null = CoercionOptions {
+            empty_as_null: true,
+            ..CoercionOptions::default()
+        };
+        assert_eq!(records[0].with_coercion(null).get_opt_i32("note")?, None);
+        assert_eq!(records[1].with_coercion(null).get_opt_i32("note")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn locales_decide_what_separators_mean() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{CoercionOptions, Col, Document, NumericLocale};
+
+        let dot = NumericLocale {
+            thousands: Some(','),
+            decimal: '.',
+        };
+        let comma = NumericLocale {
+            thousands: Some('.'),
+            decimal: ',',
+        };
+        let space = NumericLocale {
+            thousands: Some(' '),
+            decimal: ',',
+        };
+        let read = |locale: NumericLocale, value: &str| -> Option<f64> {
+            locale.normalize(value)?.parse().ok()
+        };
+
+        assert_eq!(read(dot, "-1,234,567.5"), Some(-1234567.5));
+        assert_eq!(read(comma, "1.234,56"), Some(1234.56));
+        assert_eq!(read(space, "1 234 567,5"), Some(1234567.5));
+        assert_eq!(read(space, "1\u{202f}234"), Some(1234.0));
+
+        // A single separator means what the locale says it means.
+        assert_eq!(read(NumericLocale::default(), "1,234"), None);
+        assert_eq!(read(dot, "1,234"), Some(1234.0));
+        assert_eq!(read(comma, "1,234"), Some(1.234));
+        assert_eq!(read(space, "1,234"), Some(1.234));
+        assert_eq!(read(comma, "1.234"), Some(1234.0));
+        assert_eq!(read(space, "1.234"), None);
+
+        assert_eq!(read(dot, "1,23"), None);
+        assert_eq!(read(dot, "1234,567"), None);
+        assert_eq!(read(dot, ",123"), None);
+        assert_eq!(read(comma, "1,2,3"), None);
+        assert_eq!(read(comma, "1,234.5"), None);
+
+        let document = Document::read(
+            &b"j:jumps\n\
+            t:1 name:prices pos:54 len:27 net:f64 qty:i32\n\
+            r:1 net:1.234,56 qty:2.000\n"[..],
+        )?;
+        let record = document
+            .table("prices")
+            .unwrap()
+            .typed_records()
+            .next()
+            .unwrap()?;
+        assert!(record.get_f64("net").is_err());
+        let options = CoercionOptions {
+            numeric_locale: comma,
+            ..CoercionOptions::default()
+        };
+        assert_eq!(record.with_coercion(options).get_f64("net")?, 1234.56);
+        assert_eq!(record.with_coercion(options).get_i32("qty")?, 2000);
+
+        let bounded = Col::parse("f64[0..2000]")?;
+        assert!(!bounded.accepts("1.234,56"));
+        assert!(bounded.accepts_in("1.234,56", comma));
+        assert!(!bounded.accepts_in("2.000,01", comma));
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_falls_back_to_defaults() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{ColKind, Document, Schema, WriterOptions};
+
+        let mut document = Schema::new()
+            .table("tasks", |t| {
+                t.col("title", ColKind::String)
+                    .col("status", ColKind::String.with_default("in review"))
+            })
+            .build()?;
+        document.add_record("tasks", "1", &[("title", "a"), ("status", "in review")])?;
+        document.add_record("tasks", "2", &[("title", "b"), ("status", "done")])?;
+        document.add_record("tasks", "3", &[("title", "c")])?;
+
+        let mut out = Vec::new();
+        let options = WriterOptions {
+            omit_defaults: true,
+            ..WriterOptions::default()
+        };
+        document.write(&mut out, options)?;
+        let text = String::from_utf8(out)?;
+        assert!(text.contains(" status:\"str=in review\""));
+        assert!(text.contains("r:1 title:a\n"));
+        assert!(text.contains("r:2 title:b status:done\n"));
+
+        let document = Document::read(text.as_bytes())?;
+        let table = document.table("tasks").unwrap();
+        let records = table.typed_records().collect::<Result<Vec<_>, _>>()?;
+        let status: Vec<_> = records.iter().map(|r| r.resolve("status")).collect();
+        assert_eq!(status, [Some("in review"), Some("done"), Some("in review")]);
+        let explicit: Vec<_> = records.iter().map(|r| r.has_explicit("status")).collect();
+        assert_eq!(explicit, [false, true, false]);
+        Ok(())
+    }
+
+    #[test]
+    fn list_columns_repeat_their_field() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{ColKind, Document, Schema, WriterOptions, XRVErr};
+
+        let mut document = Schema::new()
+            .table("posts", |t| {
+                t.col("title", ColKind::String)
+                    .col("tag", ColKind::String.list())
+            })
+            .build()?;
+        document.add_record("posts", "1", &[("title", "a")])?;
+        document.add_record("posts", "2", &[("title", "b"), ("tag", "rust")])?;
+        document.add_record(
+            "posts",
+            "3",
+            &[("tag", "x"), ("title", "c"), ("tag", "y"), ("tag", "z")],
+        )?;
+        assert!(matches!(
+            document.add_record("posts", "4", &[("title", "d"), ("title", "e")]),
+            Err(XRVErr::RepeatedColumn { .. })
+        ));
+
+        let mut out = Vec::new();
+        document.write(&mut out, WriterOptions::default())?;
+        let text = String::from_utf8(out)?;
+        assert!(text.contains(" tag:[str]"));
+
+        let document = Document::read(text.as_bytes())?;
+        let table = document.table("posts").unwrap();
+        let records = table.typed_records().collect::<Result<Vec<_>, _>>()?;
+        let tags: Vec<_> = records.iter().map(|r| r.get_list("tag")).collect();
+        assert_eq!(tags, [vec![], vec!["rust"], vec!["x", "y", "z"]]);
+        Ok(())
+    }
+
+    #[test]
+    fn bytes_round_trip_through_base64() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Document, RecordBuilder, Writer, XRVErr};
+
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let blobs: Vec<Vec<u8>> = (0..=64)
+            .map(|len| {
+                (0..len)
+                    .map(|_| {
+                        state ^= state << 13;
+                        state ^= state >> 7;
+                        state ^= state << 17;
+                        state as u8
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut out = Vec::new();
+        let mut writer = Writer::new(&mut out);
+        writer.add_table("files", &[("blob", "bytes")])?;
+        for (id, blob) in blobs.iter().enumerate() {
+            RecordBuilder::new(&id.to_string())
+                .field_bytes("blob", blob)
+                .write_to(&mut writer, "files")?;
+        }
+        writer.finish()?;
+
+        let text = String::from_utf8(out)?;
+        assert!(text.contains("r:0 blob:\"\"\n"));
+        let document = Document::read(text.as_bytes())?;
+        let table = document.table("files").unwrap();
+        let decoded: Vec<_> = table
+            .typed_records()
+            .map(|r| r?.get_bytes("blob"))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(decoded, blobs);
+
+        let document = Document::read(
+            &b"j:jumps\n\
+            t:1 name:files pos:48 len:41 blob:bytes\n\
+            r:1 blob:aGk=\n\
+            r:2 blob:aG!=\n\
+            r:3 blob:aGk\n"[..],
+        )?;
+        let records = document
+            .table("files")
+            .unwrap()
+            .typed_records()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(records[0].get_bytes("blob")?, b"hi");
+        assert!(matches!(
+            records[1].get_bytes("blob"),
+            Err(XRVErr::InvalidBase64 { pos: 2, .. })
+        ));
+        assert!(matches!(
+            records[2].get_bytes("blob"),
+            Err(XRVErr::InvalidBase64 { pos: 3, .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn sizes_read_and_write_every_unit() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{ColKind, RecordBuilder, Schema, XRVErr};
+
+        let mut document = Schema::new()
+            .table("limits", |t| t.col("max", ColKind::Size))
+            .build()?;
+        let values = [
+            "512", "512B", "3k", "3kB", "64Mi", "64MiB", "2G", "1Gi", "1.5KiB", "0.5k", "0",
+        ];
+        for (id, value) in values.iter().enumerate() {
+            document.add_record("limits", &id.to_string(), &[("max", value)])?;
+        }
+        let table = document.table("limits").unwrap();
+        let sizes: Vec<u64> = table
+            .typed_records()
+            .map(|r| r?.get_size("max"))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(
+            sizes,
+            [
+                512,
+                512,
+                3000,
+                3000,
+                64 << 20,
+                64 << 20,
+                2_000_000_000,
+                1 << 30,
+                1536,
+                500,
+                0
+            ]
+        );
+
+        for value in ["1.5", "0.0001k", "1.0001KiB"] {
+            let result = document.add_record("limits", "x", &[("max", value)]);
+            assert!(
+                matches!(result, Err(XRVErr::FractionalSize { .. })),
+                "{}",
+                value
+            );
+        }
+        for value in [
+            "MiB",
+            "64 MiB",
+            "64mb",
+            "64KB",
+            "-1k",
+            "1e3",
+            ".5k",
+            "5.k",
+            "64MiBB",
+            "99999999999G",
+        ] {
+            let result = document.add_record("limits", "x", &[("max", value)]);
+            assert!(
+                matches!(result, Err(XRVErr::InvalidTypedValue { .. })),
+                "{}",
+                value
+            );
+        }
+
+        let line = |bytes: u64| RecordBuilder::new("1").field_size("max", bytes).build();
+        assert_eq!(line(64 << 20)?, "r:1 max:64MiB\n");
+        assert_eq!(line(3000)?, "r:1 max:3kB\n");
+        assert_eq!(line(2_000_000_000)?, "r:1 max:2GB\n");
+        assert_eq!(line(1000 << 20)?, "r:1 max:1000MiB\n");
+        assert_eq!(line(513)?, "r:1 max:513\n");
+        assert_eq!(line(0)?, "r:1 max:0\n");
+        Ok(())
+    }
+
+    #[test]
+    fn durations_read_and_write_every_unit() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{ColKind, RecordBuilder, Schema, XRVErr};
+        use std::time::Duration;
+
+        let mut document = Schema::new()
+            .table("jobs", |t| t.col("timeout", ColKind::Duration))
+            .build()?;
+        let values = [
+            "30",
+            "30s",
+            "250ms",
+            "1.5",
+            "90m",
+            "2h",
+            "0.25h",
+            "0",
+            "0.000000001s",
+        ];
+        for (id, value) in values.iter().enumerate() {
+            document.add_record("jobs", &id.to_string(), &[("timeout", value)])?;
+        }
+        let table = document.table("jobs").unwrap();
+        let durations: Vec<Duration> = table
+            .typed_records()
+            .map(|r| r?.get_duration("timeout"))
+            .collect::<Result<_, _>>()?;
+        let expected = [30_000, 30_000, 250, 1_500, 5_400_000, 7_200_000, 900_000, 0];
+        assert_eq!(durations[..8], expected.map(Duration::from_millis));
+        assert_eq!(durations[8], Duration::from_nanos(1));
+
+        for value in ["-5s", "-1", "-0.5ms"] {
+            let result = document.add_record("jobs", "x", &[("timeout", value)]);
+            assert!(
+                matches!(result, Err(XRVErr::NegativeDuration { .. })),
+                "{}",
+                value
+            );
+        }
+        for value in [
+            "s",
+            "5 s",
+            "5sec",
+            "5d",
+            "5S",
+            "0.0000000001s",
+            "--5s",
+            "1h30m",
+        ] {
+            let result = document.add_record("jobs", "x", &[("timeout", value)]);
+            assert!(
+                matches!(result, Err(XRVErr::InvalidTypedValue { .. })),
+                "{}",
+                value
+            );
+        }
+
+        let line = |duration: Duration| {
+            RecordBuilder::new("1")
+                .field_duration("t", duration)
+                .build()
+        };
+        assert_eq!(line(Duration::from_secs(30))?, "r:1 t:30s\n");
+        assert_eq!(line(Duration::from_secs(5400))?, "r:1 t:90m\n");
+        assert_eq!(line(Duration::from_secs(7200))?, "r:1 t:2h\n");
+        assert_eq!(line(Duration::from_millis(1500))?, "r:1 t:1500ms\n");
+        assert_eq!(line(Duration::from_micros(1500))?, "r:1 t:0.0015s\n");
+        assert_eq!(line(Duration::ZERO)?, "r:1 t:0s\n");
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+use crate::{error::XRVErr, schema::ColKind};
+
+/// Suffixes of a `size` value and the bytes they stand for, largest first
+/// so that `format_size` picks the largest unit that divides a value.
+const SIZE_UNITS: [(&str, u64); 6] = [
+    ("Gi", 1 << 30),
+    ("G", 1_000_000_000),
+    ("Mi", 1 << 20),
+    ("M", 1_000_000),
+    ("Ki", 1 << 10),
+    ("k", 1_000),
+];
+
+/// Suffixes of a `duration` value and the nanoseconds they stand for,
+/// largest first.
+const DURATION_UNITS: [(&str, u128); 4] = [
+    ("h", 3_600_000_000_000),
+    ("m", 60_000_000_000),
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+];
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+/// Why a `size` or `duration` value was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum UnitError {
+    Invalid,
+    /// A size that comes to a fraction of a byte, such as `1.5` or `0.0001k`.
+    Fractional,
+    /// A duration below zero, such as `-5s`.
+    Negative,
+}
+
+/// The error for `value` of a `size` or `duration` column rejected with
+/// `err`.
+pub(crate) fn unit_error(
+    err: UnitError,
+    table: &str,
+    column: &str,
+    value: &str,
+    kind: ColKind,
+) -> XRVErr {
+    let (table, column, value) = (table.to_owned(), column.to_owned(), value.to_owned());
+    match err {
+        UnitError::Fractional => XRVErr::FractionalSize {
+            table,
+            column,
+            value,
+        },
+        UnitError::Negative => XRVErr::NegativeDuration {
+            table,
+            column,
+            value,
+        },
+        UnitError::Invalid => XRVErr::InvalidTypedValue {
+            table,
+            column,
+            value,
+            expected: kind.as_str(),
+        },
+    }
+}
+
+/// Bytes of a `size` value: a whole or decimal number, then optionally
+/// one of `k`, `M`, `G` (powers of 1000) or `Ki`, `Mi`, `Gi` (powers of
+/// 1024), then optionally `B`. `64MiB`, `64Mi`, `1.5KiB` and `512` are all
+/// sizes; the value has to come to a whole number of bytes.
+pub(crate) fn parse_size(value: &str) -> Result<u64, UnitError> {
+    let value = value.strip_suffix('B').unwrap_or(value);
+    let (number, unit) = split_unit(value);
+    let unit = match unit {
+        "" => 1,
+        unit => match SIZE_UNITS.iter().find(|(suffix, _)| *suffix == unit) {
+            None => return Err(UnitError::Invalid),
+            Some((_, bytes)) => *bytes,
+        },
+    };
+    if number.starts_with('-') {
+        return Err(UnitError::Invalid);
+    }
+    let bytes = scale(number, u128::from(unit))?;
+    u64::try_from(bytes).map_err(|_| UnitError::Invalid)
+}
+
+/// A `duration` value: a whole or decimal number, then one of `ms`, `s`,
+/// `m` or `h`, or no suffix for seconds. It has to come to a whole number
+/// of nanoseconds.
+pub(crate) fn parse_duration(value: &str) -> Result<Duration, UnitError> {
+    let (number, unit) = split_unit(value);
+    let unit = match unit {
+        "" => NANOS_PER_SEC,
+        unit => match DURATION_UNITS.iter().find(|(suffix, _)| *suffix == unit) {
+            None => return Err(UnitError::Invalid),
+            Some((_, nanos)) => *nanos,
+        },
+    };
+    if let Some(number) = number.strip_prefix('-') {
+        return match scale(number, unit) {
+            Err(UnitError::Invalid) => Err(UnitError::Invalid),
+            _ => Err(UnitError::Negative),
+        };
+    }
+    let nanos = match scale(number, unit) {
+        Err(UnitError::Fractional) => return Err(UnitError::Invalid),
+        result => result?,
+    };
+    let secs = u64::try_from(nanos / NANOS_PER_SEC).map_err(|_| UnitError::Invalid)?;
+    Ok(Duration::new(secs, (nanos % NANOS_PER_SEC) as u32))
+}
+
+/// `bytes` in the largest unit that divides it, such as `64MiB` or `3kB`,
+/// and as a bare number when none does.
+pub(crate) fn format_size(bytes: u64) -> String {
+    let unit = SIZE_UNITS
+        .iter()
+        .find(|(_, unit)| bytes != 0 && bytes.is_multiple_of(*unit));
+    match unit {
+        None => bytes.to_string(),
+        Some((suffix, unit)) => format!("{}{}B", bytes / unit, suffix),
+    }
+}
+
+/// `duration` in the largest unit that divides it, such as `30s` or
+/// `90m`, and in seconds with a fraction when it is not whole milliseconds.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let nanos = duration.as_nanos();
+    let unit = DURATION_UNITS
+        .iter()
+        .find(|(_, unit)| nanos != 0 && nanos.is_multiple_of(*unit));
+    match unit {
+        Some((suffix, unit)) => format!("{}{}", nanos / unit, suffix),
+        None if nanos == 0 => "0s".to_owned(),
+        None => {
+            let fraction = format!("{:09}", nanos % NANOS_PER_SEC);
+            format!(
+                "{}.{}s",
+                nanos / NANOS_PER_SEC,
+                fraction.trim_end_matches('0')
+            )
+        }
+    }
+}
+
+/// Splits `value` before its first letter.
+fn split_unit(value: &str) -> (&str, &str) {
+    let at = value
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(value.len());
+    value.split_at(at)
+}
+
+/// `number`, written with ASCII digits and at most one `.`, times `unit`.
+fn scale(number: &str, unit: u128) -> Result<u128, UnitError> {
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    let digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+    if whole.is_empty() || !digits(whole) || !digits(fraction) || number.ends_with('.') {
+        return Err(UnitError::Invalid);
+    }
+    let whole: u128 = whole.parse().map_err(|_| UnitError::Invalid)?;
+    let mut scaled = whole.checked_mul(unit).ok_or(UnitError::Invalid)?;
+    let fraction = fraction.trim_end_matches('0');
+    if !fraction.is_empty() {
+        let denominator = 10u128
+            .checked_pow(fraction.len() as u32)
+            .ok_or(UnitError::Invalid)?;
+        let numerator: u128 = fraction.parse().map_err(|_| UnitError::Invalid)?;
+        let part = numerator.checked_mul(unit).ok_or(UnitError::Invalid)?;
+        if !part.is_multiple_of(denominator) {
+            return Err(UnitError::Fractional);
+        }
+        scaled = scaled
+            .checked_add(part / denominator)
+            .ok_or(UnitError::Invalid)?;
+    }
+    Ok(scaled)
+}
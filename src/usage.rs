@@ -0,0 +1,117 @@
+use std::collections::HashSet;
+
+use crate::{
+    document::Document, error::XRVErr, extract::STYLE_FIELD, ordered::OrderedMap, reader::Reader,
+};
+
+/// How the records of a file use its styles, as found by
+/// `Reader::style_usage`.
+#[derive(Debug, Clone, Default)]
+pub struct StyleUsage {
+    /// Styles no record names, in declaration order.
+    pub unused: Vec<String>,
+    /// Record ids and the undeclared style they name, in file order.
+    pub missing: Vec<(String, String)>,
+    /// Number of records naming each declared style, in declaration order.
+    pub counts: OrderedMap<usize>,
+}
+
+impl Reader {
+    /// Loads the styles, then reads the records of every table once,
+    /// counting the styles they name in a `style:` field.
+    ///
+    /// ```
+    /// use xrave::{Reader, Writer};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-style-usage.xrv");
+    /// let mut writer = Writer::new(std::fs::File::create(&path)?);
+    /// writer.add_style("dim", &[("opacity", "0.5")])?;
+    /// writer.finish()?;
+    ///
+    /// let mut reader = Reader::new(&path)?;
+    /// assert_eq!(reader.style_usage()?.unused, ["dim"]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn style_usage(&mut self) -> Result<StyleUsage, XRVErr> {
+        self.load_headers()?;
+        let mut counts = OrderedMap::new();
+        for id in self.styles.keys() {
+            counts.insert(id.clone(), 0);
+        }
+        let mut missing = Vec::new();
+        let tables: Vec<String> = self.jumps.keys().cloned().collect();
+        for table in tables.iter() {
+            for record in self.records(table)? {
+                let record = record?;
+                for style in record.get_all(STYLE_FIELD) {
+                    match counts.get_mut(style) {
+                        Some(count) => *count += 1,
+                        None => missing.push((record.id.clone(), style.to_owned())),
+                    }
+                }
+            }
+        }
+        let unused = counts
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(id, _)| id.clone())
+            .collect();
+        Ok(StyleUsage {
+            unused,
+            missing,
+            counts,
+        })
+    }
+}
+
+impl Document {
+    /// Drops the styles no record names in a `style:` field and returns
+    /// their ids, in declaration order. Spilled records are read from the
+    /// file again to find the styles they name.
+    pub fn remove_unused_styles(&mut self) -> Result<Vec<String>, XRVErr> {
+        let mut used: HashSet<String> = HashSet::new();
+        for table in self.tables.iter() {
+            for record in table.records() {
+                let record = record?;
+                used.extend(record.get_all(STYLE_FIELD).map(str::to_owned));
+            }
+        }
+        let (kept, removed) = self
+            .styles
+            .drain(..)
+            .partition(|style| used.contains(style.id.as_str()));
+        self.styles = kept;
+        Ok(removed.into_iter().map(|style| style.id).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn style_usage_counts_unused_and_missing_styles() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Document, Reader, Writer};
+
+        let path = std::env::temp_dir().join("xrave-test-style-usage.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("orders", &[("user", "i32"), ("style", "str?")])?;
+        writer.add_style("bold", &[("weight", "700")])?;
+        writer.add_style("dim", &[("opacity", "0.5")])?;
+        writer.add_record("orders", "o1", &[("user", "1"), ("style", "bold")])?;
+        writer.add_record("orders", "o2", &[("user", "1"), ("style", "bold")])?;
+        writer.add_record("orders", "o3", &[("user", "2"), ("style", "italic")])?;
+        writer.add_record("orders", "o4", &[("user", "2")])?;
+        writer.finish()?;
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        let usage = reader.style_usage()?;
+        assert_eq!(usage.unused, ["dim"]);
+        assert_eq!(usage.missing, [("o3".to_owned(), "italic".to_owned())]);
+        assert_eq!(usage.counts.get("bold"), Some(&2));
+        assert_eq!(usage.counts.get("dim"), Some(&0));
+
+        let mut document = Document::open(&path)?;
+        assert_eq!(document.remove_unused_styles()?, ["dim"]);
+        assert!(document.style("bold").is_some() && document.style("dim").is_none());
+        Ok(())
+    }
+}
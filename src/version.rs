@@ -0,0 +1,75 @@
+use std::io::BufRead;
+
+use crate::{
+    error::XRVErr,
+    reader::ParseMode,
+    tokenizer::{XraveBuffer, COLON_CHAR, VERSION_ID},
+};
+
+/// Newest format version this crate reads, and the one `Writer` declares.
+pub(crate) const FORMAT_VERSION: u32 = 3;
+/// Version of a file without a version line.
+pub(crate) const DEFAULT_FORMAT_VERSION: u32 = 1;
+/// First version with escape sequences and line continuations, which
+/// would change the meaning of older files. List columns need no gate:
+/// their `[kind]` declarations were not valid kinds before.
+pub(crate) const EXTENDED_SYNTAX_VERSION: u32 = 2;
+/// First version where a bare `~`, or the null byte of the dialect, is a
+/// null rather than the one-character string it was before.
+pub(crate) const NULL_VALUE_VERSION: u32 = 3;
+
+const VERSION_PREFIX: &[u8] = b"v:xrave ";
+
+/// Parses a `v:xrave N` line, or returns `None` when `line` does not start
+/// with `v:` at all.
+pub(crate) fn parse_version_line(line: &[u8]) -> Option<Result<u32, XRVErr>> {
+    if !line.starts_with(&[VERSION_ID, COLON_CHAR]) {
+        return None;
+    }
+    let version = line
+        .strip_prefix(VERSION_PREFIX)
+        .filter(|digits| !digits.is_empty() && digits.iter().all(u8::is_ascii_digit))
+        .and_then(|digits| std::str::from_utf8(digits).ok()?.parse().ok());
+    Some(
+        version
+            .ok_or_else(|| XRVErr::InvalidVersionLine(String::from_utf8_lossy(line).into_owned())),
+    )
+}
+
+/// Reads the first line of a file into `buffer`. A version line is checked
+/// and consumed, and the buffer switched to the syntax it declares, leaving
+/// the jumps line in the buffer; a file without one is version 1. A version
+/// newer than `FORMAT_VERSION` fails with `XRVErr::UnsupportedVersion`, or
+/// in `ParseMode::Lenient` is recorded in `diagnostics` and read with the
+/// newest syntax known.
+pub(crate) fn read_first_line<R: BufRead>(
+    buffer: &mut XraveBuffer,
+    source: &mut R,
+    max_line_len: usize,
+    mode: ParseMode,
+    diagnostics: &mut Vec<XRVErr>,
+) -> Result<u32, XRVErr> {
+    buffer.read_line(source, max_line_len)?;
+    buffer.strip_bom();
+    let version = match parse_version_line(&buffer.buffer) {
+        None => {
+            buffer.set_version(DEFAULT_FORMAT_VERSION);
+            return Ok(DEFAULT_FORMAT_VERSION);
+        }
+        Some(Err(err)) => return Err(buffer.diagnose(1, err)),
+        Some(Ok(version)) => version,
+    };
+    if version > FORMAT_VERSION {
+        let err = XRVErr::UnsupportedVersion {
+            found: version,
+            max_supported: FORMAT_VERSION,
+        };
+        match mode {
+            ParseMode::Strict => return Err(err),
+            ParseMode::Lenient => diagnostics.push(err),
+        }
+    }
+    buffer.set_version(version);
+    buffer.read_line(source, max_line_len)?;
+    Ok(version)
+}
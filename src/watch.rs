@@ -0,0 +1,320 @@
+use std::io::prelude::*;
+use std::{
+    fs::File,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    checksum::Crc32,
+    error::{open_error, XRVErr},
+    line::OwnedRecordLine,
+    reader::{ParseOptions, Reader},
+};
+
+/// What `watch` saw change in the file since its previous poll.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeEvent {
+    /// Records appended past the region of the last table.
+    Appended(Vec<OwnedRecordLine>),
+    /// The file was emptied.
+    Truncated,
+    /// The file shrank, changed in place or got other headers, and was
+    /// opened again; records are reported from the end of its last table
+    /// region on.
+    Rewritten,
+}
+
+/// Ends a running `watch` from any thread. Clones stop the same watch.
+#[derive(Debug, Clone, Default)]
+pub struct StopHandle(Arc<AtomicBool>);
+
+impl StopHandle {
+    pub fn new() -> StopHandle {
+        StopHandle::default()
+    }
+
+    /// Makes the watch return after its current poll.
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// Time between two looks at the file's length and modification time.
+    pub interval: Duration,
+    pub stop: StopHandle,
+    /// Options the file is opened with, and opened again with after it was
+    /// rewritten.
+    pub parse: ParseOptions,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        WatchOptions {
+            interval: Duration::from_millis(250),
+            stop: StopHandle::new(),
+            parse: ParseOptions::default(),
+        }
+    }
+}
+
+/// Length, modification time and header checksum of the file as last seen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileState {
+    len: u64,
+    modified: Option<SystemTime>,
+    header: u32,
+}
+
+/// An open file being watched: its reader, the table appended records
+/// belong to and the state it was last seen in.
+struct Watched {
+    reader: Reader,
+    table: Option<String>,
+    header_len: u64,
+    state: FileState,
+}
+
+impl Watched {
+    /// Opens `path` and points the reader at the end of the region of its
+    /// last table, the one a file growing by appending grows.
+    fn open(path: &Path, options: &ParseOptions) -> Result<Watched, XRVErr> {
+        let mut reader = Reader::with_options(path, options.clone())?;
+        let last = reader
+            .jumps
+            .iter()
+            .max_by_key(|(_, (seek, len))| seek + len)
+            .map(|(name, (seek, len))| (name.clone(), (seek + len) as u64));
+        let header_len = reader
+            .jumps
+            .values()
+            .map(|(seek, _)| *seek as u64)
+            .min()
+            .unwrap_or(u64::MAX);
+        let table = last.map(|(name, end)| {
+            reader.resume_from(end);
+            name
+        });
+        let state = file_state(path, header_len)?;
+        Ok(Watched {
+            reader,
+            table,
+            header_len,
+            state,
+        })
+    }
+}
+
+/// Reads the length and modification time of `path` and the checksum of
+/// its first `header_len` bytes.
+fn file_state(path: &Path, header_len: u64) -> Result<FileState, XRVErr> {
+    let file = match File::open(path) {
+        Err(err) => return Err(open_error(path, err)),
+        Ok(file) => file,
+    };
+    let metadata = match file.metadata() {
+        Err(err) => return Err(XRVErr::FailToReadLine(err)),
+        Ok(metadata) => metadata,
+    };
+    let mut header = Vec::new();
+    if let Err(err) = file.take(header_len).read_to_end(&mut header) {
+        return Err(XRVErr::FailToReadLine(err));
+    }
+    let mut crc = Crc32::new();
+    crc.update(&header);
+    Ok(FileState {
+        len: metadata.len(),
+        modified: metadata.modified().ok(),
+        header: crc.finish(),
+    })
+}
+
+/// Watches `path` with the default `WatchOptions`, never stopping on its
+/// own; see `watch_with_options`.
+pub fn watch<P, F>(path: P, callback: F) -> Result<(), XRVErr>
+where
+    P: AsRef<Path>,
+    F: FnMut(ChangeEvent),
+{
+    watch_with_options(path, WatchOptions::default(), callback)
+}
+
+/// Polls the length and modification time of `path` every
+/// `options.interval` and hands each change to `callback`, until
+/// `options.stop` is stopped. Records appended past the region of the last
+/// table come as `ChangeEvent::Appended`, those already there when the
+/// watch starts included; a final line without its terminator waits for a
+/// later poll. A file that shrinks, changes without growing or gets a
+/// different header is opened again and reported as
+/// `ChangeEvent::Rewritten`, and an emptied one as `ChangeEvent::Truncated`.
+/// While the file cannot be opened again, as while a writer is still
+/// replacing it, polling goes on. Only failing to open the file at first,
+/// or to read records appended to it, ends the watch with an error.
+///
+/// ```
+/// use std::{io::Write, sync::mpsc, time::Duration};
+/// use xrave::{watch_with_options, ChangeEvent, WatchOptions, Writer};
+///
+/// let path = std::env::temp_dir().join("xrave-doc-watch.xrv");
+/// let mut writer = Writer::new(std::fs::File::create(&path)?);
+/// writer.add_table("events", &[("name", "str")])?;
+/// writer.add_record("events", "1", &[("name", "start")])?;
+/// writer.finish()?;
+/// let mut file = std::fs::OpenOptions::new().append(true).open(&path)?;
+/// file.write_all(b"r:2 name:tick\n")?;
+///
+/// let options = WatchOptions { interval: Duration::from_millis(10), ..WatchOptions::default() };
+/// let stop = options.stop.clone();
+/// let (sender, events) = mpsc::channel();
+/// let watcher = std::thread::spawn({
+///     let path = path.clone();
+///     move || watch_with_options(&path, options, |event| sender.send(event).unwrap())
+/// });
+///
+/// let first = events.recv_timeout(Duration::from_secs(5))?;
+/// assert!(matches!(first, ChangeEvent::Appended(records) if records[0].id == "2"));
+/// stop.stop();
+/// watcher.join().unwrap()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn watch_with_options<P, F>(
+    path: P,
+    options: WatchOptions,
+    mut callback: F,
+) -> Result<(), XRVErr>
+where
+    P: AsRef<Path>,
+    F: FnMut(ChangeEvent),
+{
+    let path = path.as_ref();
+    let mut watched = Some(Watched::open(path, &options.parse)?);
+    let mut appended = true;
+    let mut emptied = false;
+    while !options.stop.is_stopped() {
+        let current = match watched.as_mut() {
+            None => match Watched::open(path, &options.parse) {
+                Err(_) => None,
+                Ok(reopened) => {
+                    callback(ChangeEvent::Rewritten);
+                    appended = true;
+                    emptied = false;
+                    watched = Some(reopened);
+                    watched.as_mut()
+                }
+            },
+            Some(current) => match file_state(path, current.header_len) {
+                Err(_) => None,
+                Ok(state) if state == current.state => Some(current),
+                Ok(state) if state.len == 0 => {
+                    if !emptied {
+                        callback(ChangeEvent::Truncated);
+                        emptied = true;
+                    }
+                    current.state = state;
+                    None
+                }
+                Ok(state)
+                    if emptied
+                        || state.len <= current.state.len
+                        || state.header != current.state.header =>
+                {
+                    watched = None;
+                    continue;
+                }
+                Ok(state) => {
+                    current.state = state;
+                    appended = true;
+                    Some(current)
+                }
+            },
+        };
+
+        if let (Some(current), true) = (current, appended) {
+            appended = false;
+            if let Some(table) = current.table.clone() {
+                match current.reader.poll_new_records(&table) {
+                    Err(XRVErr::FileTruncated { .. }) => {
+                        watched = None;
+                        continue;
+                    }
+                    Err(err) => return Err(err),
+                    Ok(records) if records.is_empty() => {}
+                    Ok(records) => callback(ChangeEvent::Appended(records)),
+                }
+            }
+        }
+        std::thread::sleep(options.interval);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn watch_reports_appends_truncation_and_rewrites() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{watch_with_options, ChangeEvent, WatchOptions, Writer};
+        use std::{io::Write, sync::mpsc, time::Duration};
+
+        let path = std::env::temp_dir().join("xrave-test-watch.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("events", &[("name", "str")])?;
+        writer.add_record("events", "1", &[("name", "start")])?;
+        writer.finish()?;
+
+        let options = WatchOptions {
+            interval: Duration::from_millis(10),
+            ..WatchOptions::default()
+        };
+        let stop = options.stop.clone();
+        let (sender, events) = mpsc::channel();
+        let watcher = std::thread::spawn({
+            let path = path.clone();
+            move || watch_with_options(&path, options, |event| sender.send(event).unwrap())
+        });
+
+        let appender = std::thread::spawn({
+            let path = path.clone();
+            move || -> std::io::Result<()> {
+                let mut file = std::fs::OpenOptions::new().append(true).open(&path)?;
+                file.write_all(b"r:2 name:tick\nr:3 name:tick\n")
+            }
+        });
+        appender.join().unwrap()?;
+
+        let mut ids = Vec::new();
+        while ids.len() < 2 {
+            match events.recv_timeout(Duration::from_secs(5))? {
+                ChangeEvent::Appended(records) => ids.extend(records.into_iter().map(|r| r.id)),
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        assert_eq!(ids, ["2", "3"]);
+
+        std::fs::write(&path, "")?;
+        assert_eq!(
+            events.recv_timeout(Duration::from_secs(5))?,
+            ChangeEvent::Truncated
+        );
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("events", &[("name", "str"), ("at", "i64?")])?;
+        writer.finish()?;
+        assert_eq!(
+            events.recv_timeout(Duration::from_secs(5))?,
+            ChangeEvent::Rewritten
+        );
+
+        stop.stop();
+        watcher.join().unwrap()?;
+        Ok(())
+    }
+}
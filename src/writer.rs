@@ -0,0 +1,1230 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use crate::{
+    checksum::{Crc32, CHECKSUM_ALGORITHM, CHECKSUM_HEADER_SECTION},
+    dictionary::DICTIONARY_MARKER,
+    error::{open_error, XRVErr},
+    ids::{assign_id, IdStrategy, NEXT_ID_FIELD},
+    line::{Field, RecordLine, StyleLine, TableLine, Value},
+    lock::{lock_exclusive, LockMode},
+    ordered::OrderedMap,
+    schema::{split_default, Col, ColKind},
+    tokenizer::{
+        tokenize, Dialect, LineLimits, CHECKSUM_ID, COLON_CHAR, CR_CHAR, DICTIONARY_ID,
+        ESCAPE_CHAR, JUMP_ID, NL_CHAR, QUOTE_CHAR, RECORD_ID, SPACE_CHAR, STYLE_ID, TABLE_ID,
+        TAB_CHAR,
+    },
+    version::FORMAT_VERSION,
+};
+
+/// Names and values of a record's fields, nulls as `None`.
+type PendingFields = Vec<(String, Option<String>)>;
+
+#[derive(Debug, Clone, Default)]
+pub struct WriterOptions {
+    pub checksums: bool,
+    /// Pads record fields with spaces so each field starts at the same
+    /// column on every record of a table, the columns laid out in the
+    /// order of the table header. A record that leaves a column out leaves
+    /// it blank. Padding only ever goes between fields, so the aligned
+    /// output parses to the same values.
+    ///
+    /// ```
+    /// use xrave::{Writer, WriterOptions};
+    ///
+    /// let options = WriterOptions { align_columns: true, ..WriterOptions::default() };
+    /// let mut writer = Writer::with_options(Vec::new(), options);
+    /// writer.add_table("users", &[("name", "str"), ("age", "int")])?;
+    /// writer.add_record("users", "1", &[("name", "Anna Smith"), ("age", "31")])?;
+    /// writer.add_record("users", "20", &[("name", "Bob"), ("age", "4")])?;
+    /// let text = String::from_utf8(writer.finish()?)?;
+    /// assert!(text.contains("r:20 name:Bob          age:4\n"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub align_columns: bool,
+    /// Leaves out record fields whose value equals their column's declared
+    /// default, e.g. `status:str=active`.
+    pub omit_defaults: bool,
+    /// Splits the jumps, table and style lines wider than this many bytes
+    /// at field boundaries, ending each physical line but the last with a
+    /// backslash that readers splice away. A single field wider than the
+    /// limit gets a line of its own. Record lines are never split.
+    ///
+    /// ```
+    /// use xrave::{Writer, WriterOptions};
+    ///
+    /// let options = WriterOptions { max_line_width: Some(24), ..WriterOptions::default() };
+    /// let mut writer = Writer::with_options(Vec::new(), options);
+    /// writer.add_table("users", &[("name", "str"), ("email", "str?"), ("age", "i32")])?;
+    /// let text = String::from_utf8(writer.finish()?)?;
+    /// assert!(text.contains(" \\\nemail:str? age:i32\n"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub max_line_width: Option<usize>,
+    /// Field separator, quote and null byte to write the lines with,
+    /// checked on `finish`.
+    pub dialect: Dialect,
+    /// Tokenizes every line again once it is rendered, failing with the
+    /// tokenizer's error instead of writing a line readers would refuse.
+    /// Debug builds always check and panic on such a line unless this is
+    /// set; release builds only check when it is.
+    ///
+    /// Names are checked either way before anything is rendered: one that
+    /// is empty fails with `XRVErr::InvalidIdentifier`, and one holding the
+    /// quote, a line break or another control byte with
+    /// `XRVErr::InvalidNameByte`. Record and style ids and table names are
+    /// read back as written, so one holding a byte that would be escaped,
+    /// the quote, a backslash, a tab or a line break, fails with
+    /// `XRVErr::InvalidValueByte`. Other values are quoted and escaped as
+    /// needed, so any of them can be written.
+    ///
+    /// ```
+    /// use xrave::{Writer, WriterOptions, XRVErr};
+    ///
+    /// let options = WriterOptions { paranoid: true, ..WriterOptions::default() };
+    /// let mut writer = Writer::with_options(Vec::new(), options);
+    /// writer.add_table("notes", &[("text", "str")])?;
+    /// writer.add_record("notes", "1", &[("text", "line\nbreak")])?;
+    /// assert!(matches!(
+    ///     writer.add_table("a\rb", &[]),
+    ///     Err(XRVErr::InvalidNameByte { byte: b'\r', .. })
+    /// ));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub paranoid: bool,
+    /// Gives a table a dictionary line, `d:users 1:active 2:disabled`,
+    /// when one of its `str` columns other than the key holds at most this
+    /// many distinct values and repeats some of them, and writes that
+    /// column's values as tokens, `status:^2`. Records are held back until
+    /// `finish` to count the values. Readers take every value opening with
+    /// `^` in a table with a dictionary for a token, so such values of any
+    /// column go into the dictionary as well; in a table without one they
+    /// are read as they are. See `Reader::dictionary`.
+    pub dictionary_threshold: Option<usize>,
+    /// How records added with an empty id get one; see `IdStrategy`. Ids
+    /// are only checked to be unique within their table under `Sequential`
+    /// and `Uuid`: the default, `Provided`, writes a repeated id as given.
+    pub ids: IdStrategy,
+}
+
+#[derive(Debug)]
+struct WriterTable {
+    name: String,
+    cols: Vec<u8>,
+    /// Column names in header order, which `align_columns` lines fields
+    /// up by.
+    names: Vec<String>,
+    /// Column defaults declared in the header, by column name.
+    defaults: Vec<(String, String)>,
+    records: Vec<u8>,
+    /// Name and rendered text of the fields of each record, the line head
+    /// first under an empty name, held back until `finish` when
+    /// `align_columns` is set.
+    rows: Vec<Vec<(String, String)>>,
+    /// Columns whose values may be written as dictionary tokens: those
+    /// declared `str`, other than the key.
+    text_cols: Vec<String>,
+    /// Id and fields of each record, held back until `finish` when
+    /// `dictionary_threshold` is set.
+    pending: Vec<(String, PendingFields)>,
+    /// Token and value of each entry of the table's dictionary line.
+    dictionary: Vec<(String, String)>,
+    /// Next id `IdStrategy::Sequential` gives out, written on the header
+    /// as `next_id` when set.
+    next_id: Option<u64>,
+    /// Ids of the records added, kept to check them for duplicates under
+    /// any `IdStrategy` but `Provided`.
+    ids: HashSet<String>,
+}
+
+impl WriterTable {
+    /// Picks the table's dictionary from the held back records and renders
+    /// them, writing the values it holds as tokens.
+    fn write_pending(&mut self, threshold: usize, options: &WriterOptions) {
+        let pending = std::mem::take(&mut self.pending);
+        // Distinct values of each text column, counted up to one past the
+        // threshold, and the number of values it holds.
+        let mut columns: Vec<(&str, HashSet<&str>, usize)> = self
+            .text_cols
+            .iter()
+            .map(|col| (col.as_str(), HashSet::new(), 0))
+            .collect();
+        for (_, fields) in pending.iter() {
+            for (name, value) in fields {
+                let value = match value {
+                    None => continue,
+                    Some(value) => value,
+                };
+                if let Some((_, distinct, count)) = columns.iter_mut().find(|(col, ..)| col == name)
+                {
+                    *count += 1;
+                    if distinct.len() <= threshold {
+                        distinct.insert(value);
+                    }
+                }
+            }
+        }
+        let encoded: Vec<&str> = columns
+            .iter()
+            .filter(|(_, distinct, count)| distinct.len() <= threshold && distinct.len() < *count)
+            .map(|(col, ..)| *col)
+            .collect();
+
+        let mut tokens: HashMap<&str, String> = HashMap::new();
+        for (id, fields) in pending.iter() {
+            let mut values: Vec<Option<Cow<str>>> = Vec::with_capacity(fields.len());
+            for (name, value) in fields {
+                let value = match value {
+                    None => {
+                        values.push(None);
+                        continue;
+                    }
+                    Some(value) => value,
+                };
+                let tokenize = !encoded.is_empty()
+                    && (encoded.contains(&name.as_str()) || value.starts_with(DICTIONARY_MARKER));
+                if !tokenize {
+                    values.push(Some(Cow::Borrowed(value)));
+                    continue;
+                }
+                let token = tokens.entry(value).or_insert_with(|| {
+                    let token = (self.dictionary.len() + 1).to_string();
+                    self.dictionary.push((token.clone(), value.clone()));
+                    token
+                });
+                values.push(Some(Cow::Owned(format!("{}{}", DICTIONARY_MARKER, token))));
+            }
+            let fields: Vec<(&str, Value)> = fields
+                .iter()
+                .zip(values.iter())
+                .map(|((name, _), value)| match value {
+                    None => (name.as_str(), Value::Null),
+                    Some(value) => (name.as_str(), Value::Str(value)),
+                })
+                .collect();
+            if options.align_columns {
+                self.rows
+                    .push(line_pieces(RECORD_ID, id, &fields, options.dialect));
+            } else {
+                write_line(&mut self.records, RECORD_ID, id, &fields, options.dialect);
+            }
+        }
+    }
+}
+
+/// Collects tables, styles and records and lays them out on `finish`, when
+/// the jumps line and every table's `pos`/`len` can be computed.
+#[derive(Debug)]
+pub struct Writer<W: Write> {
+    out: W,
+    pub(crate) options: WriterOptions,
+    tables: Vec<WriterTable>,
+    styles: Vec<u8>,
+}
+
+impl Writer<File> {
+    /// Opens `path` for writing under an exclusive advisory lock and only
+    /// then truncates it. The lock is held until the file returned by
+    /// `finish` is dropped.
+    ///
+    /// ```
+    /// use xrave::{LockMode, Writer, XRVErr};
+    ///
+    /// let path = std::env::temp_dir().join("xrave-doc-lock.xrv");
+    /// let writer = Writer::open_locked(&path, LockMode::Try)?;
+    /// let contender = std::thread::spawn({
+    ///     let path = path.clone();
+    ///     move || Writer::open_locked(&path, LockMode::Try).map(drop)
+    /// });
+    /// assert!(matches!(contender.join().unwrap(), Err(XRVErr::FileLocked)));
+    /// drop(writer.finish()?);
+    /// assert!(Writer::open_locked(&path, LockMode::Try).is_ok());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn open_locked<P: AsRef<Path>>(path: P, lock: LockMode) -> Result<Self, XRVErr> {
+        let file = match OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path.as_ref())
+        {
+            Err(err) => return Err(open_error(path.as_ref(), err)),
+            Ok(file) => file,
+        };
+        lock_exclusive(&file, path.as_ref(), lock)?;
+        if let Err(err) = file.set_len(0) {
+            return Err(XRVErr::FailToWrite(err));
+        }
+        Ok(Writer::new(file))
+    }
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(out: W) -> Self {
+        Writer::with_options(out, WriterOptions::default())
+    }
+
+    pub fn with_options(out: W, options: WriterOptions) -> Self {
+        Writer {
+            out,
+            options,
+            tables: Vec::new(),
+            styles: Vec::new(),
+        }
+    }
+
+    /// Declares a table and its columns as `(name, declaration)` pairs.
+    /// Table and column names holding the separator or a colon are written
+    /// quoted.
+    ///
+    /// ```
+    /// use xrave::Writer;
+    ///
+    /// let mut writer = Writer::new(Vec::new());
+    /// writer.add_table("new hires", &[("first name", "str"), ("start:utc", "str?")])?;
+    /// let text = String::from_utf8(writer.finish()?)?;
+    /// assert!(text.contains(" \"new hires\":"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn add_table(&mut self, name: &str, cols: &[(&str, &str)]) -> Result<(), XRVErr> {
+        if self.tables.iter().any(|table| table.name == name) {
+            return Err(XRVErr::DuplicateTable(name.to_owned()));
+        }
+        check_name(name, self.options.dialect)?;
+        check_id("name", name, self.options.dialect)?;
+        let mut line = Vec::new();
+        let mut defaults = Vec::new();
+        let mut text_cols = Vec::new();
+        for (col, kind) in cols {
+            check_name(col, self.options.dialect)?;
+            write_field(&mut line, col, kind, self.options.dialect);
+            if let (_, Some(default)) = split_default(kind) {
+                defaults.push((col.to_string(), default.to_owned()));
+            }
+            if Col::parse(kind).is_ok_and(|col| col.kind == ColKind::String && !col.key) {
+                text_cols.push(col.to_string());
+            }
+        }
+        if checks_lines(&self.options) {
+            let mut table = vec![TABLE_ID, COLON_CHAR, b'1'];
+            write_field(&mut table, "name", name, self.options.dialect);
+            table.extend_from_slice(&line);
+            check_line(&table, &self.options)?;
+        }
+        self.tables.push(WriterTable {
+            name: name.to_owned(),
+            cols: line,
+            names: cols.iter().map(|(col, _)| col.to_string()).collect(),
+            defaults,
+            records: Vec::new(),
+            rows: Vec::new(),
+            text_cols,
+            pending: Vec::new(),
+            dictionary: Vec::new(),
+            next_id: match self.options.ids {
+                IdStrategy::Sequential(start) => Some(start),
+                _ => None,
+            },
+            ids: HashSet::new(),
+        });
+        Ok(())
+    }
+
+    pub fn add_style(&mut self, id: &str, fields: &[(&str, &str)]) -> Result<(), XRVErr> {
+        check_id("id", id, self.options.dialect)?;
+        for (name, _) in fields {
+            check_name(name, self.options.dialect)?;
+        }
+        let mut line = Vec::new();
+        write_line(&mut line, STYLE_ID, id, fields, self.options.dialect);
+        if checks_lines(&self.options) {
+            check_line(&line, &self.options)?;
+        }
+        write_wrapped(
+            &mut self.styles,
+            &line,
+            self.options.max_line_width,
+            self.options.dialect,
+        );
+        Ok(())
+    }
+
+    pub fn add_record(
+        &mut self,
+        table: &str,
+        id: &str,
+        fields: &[(&str, &str)],
+    ) -> Result<(), XRVErr> {
+        let fields: Vec<(&str, Value)> = fields
+            .iter()
+            .map(|(name, value)| (*name, Value::Str(value)))
+            .collect();
+        self.add_record_values(table, id, &fields)
+    }
+
+    /// Adds a record like `add_record`, with values that may be nulls,
+    /// written as the bare null byte of the dialect. A string that is just
+    /// that byte is quoted so that it reads back as a string.
+    pub fn add_record_values(
+        &mut self,
+        table: &str,
+        id: &str,
+        fields: &[(&str, Value)],
+    ) -> Result<(), XRVErr> {
+        let t = match self.tables.iter_mut().find(|t| t.name == table) {
+            None => return Err(XRVErr::UnknownTable(table.to_owned())),
+            Some(t) => t,
+        };
+        for (name, _) in fields {
+            check_name(name, self.options.dialect)?;
+        }
+        let mut next_id = t.next_id;
+        let id = assign_id(self.options.ids, table, id, &mut next_id)?;
+        let id = &*id;
+        check_id("id", id, self.options.dialect)?;
+        let check_ids = self.options.ids != IdStrategy::Provided;
+        if check_ids && t.ids.contains(id) {
+            return Err(XRVErr::DuplicateRecordId {
+                table: table.to_owned(),
+                id: id.to_owned(),
+            });
+        }
+        let fields: Vec<(&str, Value)> = fields
+            .iter()
+            .filter(|(name, value)| {
+                !self.options.omit_defaults
+                    || !t
+                        .defaults
+                        .iter()
+                        .any(|(col, default)| col == name && Value::Str(default) == *value)
+            })
+            .copied()
+            .collect();
+        if checks_lines(&self.options) {
+            let mut line = Vec::new();
+            write_line(&mut line, RECORD_ID, id, &fields, self.options.dialect);
+            check_line(&line, &self.options)?;
+        }
+        t.next_id = next_id;
+        if check_ids {
+            t.ids.insert(id.to_owned());
+        }
+        if self.options.dictionary_threshold.is_some() {
+            let fields = fields
+                .iter()
+                .map(|(name, value)| match value {
+                    Value::Null => (name.to_string(), None),
+                    Value::Str(value) => (name.to_string(), Some(value.to_string())),
+                })
+                .collect();
+            t.pending.push((id.to_owned(), fields));
+        } else if self.options.align_columns {
+            t.rows
+                .push(line_pieces(RECORD_ID, id, &fields, self.options.dialect));
+        } else {
+            write_line(&mut t.records, RECORD_ID, id, &fields, self.options.dialect);
+        }
+        Ok(())
+    }
+
+    /// Adds a parsed table header. Its `pos` and `len` are recomputed on
+    /// `finish`.
+    pub fn add_table_line(&mut self, table: &TableLine) -> Result<(), XRVErr> {
+        let cols = field_pairs(&table.cols);
+        self.add_table(table.name, &borrow_pairs(&cols))?;
+        if let Some(next_id) = table.next_id {
+            self.set_next_id(table.name, next_id)?;
+        }
+        Ok(())
+    }
+
+    /// Writes `next_id` on the header of `table`, unless records added
+    /// since counted past it.
+    pub(crate) fn set_next_id(&mut self, table: &str, next_id: u64) -> Result<(), XRVErr> {
+        match self.tables.iter_mut().find(|t| t.name == table) {
+            None => Err(XRVErr::UnknownTable(table.to_owned())),
+            Some(t) => {
+                t.next_id = Some(t.next_id.map_or(next_id, |counted| counted.max(next_id)));
+                Ok(())
+            }
+        }
+    }
+
+    pub fn add_style_line(&mut self, style: &StyleLine) -> Result<(), XRVErr> {
+        let fields = field_pairs(&style.cols);
+        self.add_style(style.id, &borrow_pairs(&fields))
+    }
+
+    pub fn add_record_line(&mut self, table: &str, record: &RecordLine) -> Result<(), XRVErr> {
+        let values: Vec<Cow<str>> = record.cols.iter().map(Field::value_unescaped).collect();
+        let fields: Vec<(&str, Value)> = record
+            .cols
+            .iter()
+            .zip(values.iter())
+            .map(|(field, value)| match field.null {
+                true => (field.name, Value::Null),
+                false => (field.name, Value::Str(value)),
+            })
+            .collect();
+        self.add_record_values(table, record.id, &fields)
+    }
+
+    /// Gives `table` the dictionary `entries`, for records added with
+    /// `add_raw_records` that hold its tokens.
+    pub(crate) fn set_dictionary(
+        &mut self,
+        table: &str,
+        entries: &OrderedMap<String>,
+    ) -> Result<(), XRVErr> {
+        match self.tables.iter_mut().find(|t| t.name == table) {
+            None => Err(XRVErr::UnknownTable(table.to_owned())),
+            Some(t) => {
+                t.dictionary = entries
+                    .iter()
+                    .map(|(token, value)| (token.clone(), value.clone()))
+                    .collect();
+                Ok(())
+            }
+        }
+    }
+
+    /// Appends already rendered record lines to `table` as they are.
+    pub(crate) fn add_raw_records(&mut self, table: &str, lines: &[u8]) -> Result<(), XRVErr> {
+        match self.tables.iter_mut().find(|t| t.name == table) {
+            None => Err(XRVErr::UnknownTable(table.to_owned())),
+            Some(t) => {
+                t.records.extend_from_slice(lines);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> Result<W, XRVErr> {
+        self.options.dialect.validate()?;
+        for table in self.tables.iter_mut() {
+            if let Some(threshold) = self.options.dictionary_threshold {
+                table.write_pending(threshold, &self.options);
+            }
+            write_aligned(
+                &mut table.records,
+                &table.names,
+                &table.rows,
+                self.options.dialect.separator,
+            );
+        }
+
+        let mut positions: Vec<usize> = vec![0; self.tables.len()];
+        let header = loop {
+            let header = self.render_header(&positions);
+            let mut pos = header.len();
+            let next: Vec<usize> = self
+                .tables
+                .iter()
+                .map(|table| {
+                    let start = pos;
+                    pos += table.records.len();
+                    start
+                })
+                .collect();
+            if next == positions {
+                break header;
+            }
+            positions = next;
+        };
+
+        let mut output = header;
+        for table in self.tables.iter() {
+            output.extend_from_slice(&table.records);
+        }
+
+        if self.options.checksums {
+            let header_end = positions.first().copied().unwrap_or(output.len());
+            let mut sections = vec![(CHECKSUM_HEADER_SECTION, &output[..header_end])];
+            for (table, pos) in self.tables.iter().zip(positions.iter()) {
+                sections.push((&table.name, &output[*pos..*pos + table.records.len()]));
+            }
+
+            let crcs: Vec<(&str, u32)> = sections
+                .into_iter()
+                .map(|(name, bytes)| {
+                    let mut crc = Crc32::new();
+                    crc.update(bytes);
+                    (name, crc.finish())
+                })
+                .collect();
+            let line = checksum_line(&crcs, self.options.dialect);
+            output.extend_from_slice(&line);
+        }
+
+        match self.out.write_all(&output).and_then(|_| self.out.flush()) {
+            Err(err) => Err(XRVErr::FailToWrite(err)),
+            Ok(_) => Ok(self.out),
+        }
+    }
+
+    fn render_header(&self, positions: &[usize]) -> Vec<u8> {
+        let (width, dialect) = (self.options.max_line_width, self.options.dialect);
+        let mut header = format!("v:xrave {}\n", FORMAT_VERSION).into_bytes();
+        let mut line = vec![JUMP_ID, COLON_CHAR];
+        line.extend_from_slice(b"jumps");
+        for (table, pos) in self.tables.iter().zip(positions) {
+            write_field(
+                &mut line,
+                &table.name,
+                &format!("{}-{}", pos, table.records.len()),
+                dialect,
+            );
+        }
+        line.push(NL_CHAR);
+        write_wrapped(&mut header, &line, width, dialect);
+
+        for (idx, (table, pos)) in self.tables.iter().zip(positions).enumerate() {
+            let fields = [
+                ("name", table.name.clone()),
+                ("pos", pos.to_string()),
+                ("len", table.records.len().to_string()),
+            ];
+            let mut line = vec![TABLE_ID, COLON_CHAR];
+            line.extend_from_slice((idx + 1).to_string().as_bytes());
+            for (name, value) in fields.iter() {
+                write_field(&mut line, name, value, dialect);
+            }
+            if let Some(next_id) = table.next_id {
+                write_field(&mut line, NEXT_ID_FIELD, &next_id.to_string(), dialect);
+            }
+            line.extend_from_slice(&table.cols);
+            line.push(NL_CHAR);
+            write_wrapped(&mut header, &line, width, dialect);
+
+            if !table.dictionary.is_empty() {
+                let entries: Vec<(&str, &str)> = table
+                    .dictionary
+                    .iter()
+                    .map(|(token, value)| (token.as_str(), value.as_str()))
+                    .collect();
+                let mut line = Vec::new();
+                write_line(&mut line, DICTIONARY_ID, &table.name, &entries, dialect);
+                write_wrapped(&mut header, &line, width, dialect);
+            }
+        }
+
+        header.extend_from_slice(&self.styles);
+        header
+    }
+}
+
+/// Fails when `name` cannot be written as a name and read back: when it is
+/// empty or holds the quote, a line break or another control byte.
+fn check_name(name: &str, dialect: Dialect) -> Result<(), XRVErr> {
+    if name.is_empty() {
+        return Err(XRVErr::InvalidIdentifier(name.to_owned()));
+    }
+    match name
+        .bytes()
+        .find(|byte| *byte == dialect.quote || *byte == QUOTE_CHAR || byte.is_ascii_control())
+    {
+        None => Ok(()),
+        Some(byte) => Err(XRVErr::InvalidNameByte {
+            byte,
+            name: name.to_owned(),
+        }),
+    }
+}
+
+/// Fails when `value`, the line id or table name written as `field`, holds
+/// a byte `write_value` would escape: readers take ids and table names as
+/// written, so they would read back the escape sequence.
+fn check_id(field: &str, value: &str, dialect: Dialect) -> Result<(), XRVErr> {
+    match value.bytes().find(|byte| {
+        *byte == dialect.quote || matches!(*byte, ESCAPE_CHAR | CR_CHAR | NL_CHAR | TAB_CHAR)
+    }) {
+        None => Ok(()),
+        Some(byte) => Err(XRVErr::InvalidValueByte {
+            byte,
+            field: field.to_owned(),
+        }),
+    }
+}
+
+/// Whether rendered lines are tokenized again: always in debug builds,
+/// and with `WriterOptions::paranoid` in release builds. A dialect that
+/// `finish` will refuse is left for it to report.
+fn checks_lines(options: &WriterOptions) -> bool {
+    (cfg!(debug_assertions) || options.paranoid) && options.dialect.validate().is_ok()
+}
+
+/// Tokenizes the rendered `line` as readers of the current format version
+/// would, checking only the grammar and not the reader's limits.
+fn check_line(line: &[u8], options: &WriterOptions) -> Result<(), XRVErr> {
+    let limits = LineLimits {
+        max_fields: usize::MAX,
+        max_value_len: usize::MAX,
+        escapes: true,
+        nulls: true,
+        dialect: options.dialect,
+        ..LineLimits::default()
+    };
+    let checked = tokenize(line, 1, limits).map(|_| ());
+    debug_assert!(
+        options.paranoid || checked.is_ok(),
+        "rendered a line readers cannot read: {:?}",
+        String::from_utf8_lossy(line)
+    );
+    checked
+}
+
+/// Names and decoded values of `fields`, so that `write_value` can encode
+/// the values again.
+fn field_pairs<'b>(fields: &[Field<'b>]) -> Vec<(&'b str, Cow<'b, str>)> {
+    fields
+        .iter()
+        .map(|field| (field.name, field.value_unescaped()))
+        .collect()
+}
+
+fn borrow_pairs<'a>(pairs: &'a [(&'a str, Cow<'a, str>)]) -> Vec<(&'a str, &'a str)> {
+    pairs
+        .iter()
+        .map(|(name, value)| (*name, &**value))
+        .collect()
+}
+
+pub(crate) fn write_line<'v, V: Copy + Into<Value<'v>>>(
+    out: &mut Vec<u8>,
+    kind: u8,
+    id: &str,
+    fields: &[(&str, V)],
+    dialect: Dialect,
+) {
+    out.extend_from_slice(&[kind, COLON_CHAR]);
+    write_value(out, id, dialect);
+    for (name, value) in fields {
+        write_field_value(out, name, (*value).into(), dialect);
+    }
+    out.push(NL_CHAR);
+}
+
+/// Renders a line as its `kind:id` head, under an empty name, followed by
+/// one `name:value` piece per field under the field's name, without
+/// separators.
+fn line_pieces(
+    kind: u8,
+    id: &str,
+    fields: &[(&str, Value)],
+    dialect: Dialect,
+) -> Vec<(String, String)> {
+    let mut head = vec![kind, COLON_CHAR];
+    write_value(&mut head, id, dialect);
+    let mut pieces = vec![(String::new(), String::from_utf8_lossy(&head).into_owned())];
+    for (name, value) in fields {
+        let mut piece = Vec::new();
+        write_field_value(&mut piece, name, *value, dialect);
+        pieces.push((
+            name.to_string(),
+            String::from_utf8_lossy(&piece[1..]).into_owned(),
+        ));
+    }
+    pieces
+}
+
+/// Writes `rows` one per line, starting each field at the same column as
+/// the field of its name on every other row. Columns are laid out in the
+/// order of `names`, each as wide as its widest field, and a column a row
+/// leaves out is left blank. A field not named in `names`, or out of their
+/// order, follows the one before it after a single `separator`.
+fn write_aligned(
+    out: &mut Vec<u8>,
+    names: &[String],
+    rows: &[Vec<(String, String)>],
+    separator: u8,
+) {
+    // Column 0 holds the line heads, column `n` the fields named
+    // `names[n - 1]`.
+    let columns = |row: &[(String, String)]| -> Vec<Option<usize>> {
+        let mut last = 0;
+        row.iter()
+            .enumerate()
+            .map(|(idx, (name, _))| {
+                if idx == 0 {
+                    return Some(0);
+                }
+                let column = names.iter().position(|col| col == name)? + 1;
+                if column <= last {
+                    return None;
+                }
+                last = column;
+                Some(column)
+            })
+            .collect()
+    };
+    let mut widths = vec![0; names.len() + 1];
+    for row in rows {
+        for ((_, piece), column) in row.iter().zip(columns(row)) {
+            if let Some(column) = column {
+                widths[column] = widths[column].max(piece.chars().count());
+            }
+        }
+    }
+    let mut starts = Vec::with_capacity(widths.len());
+    let mut start = 0;
+    for width in widths.iter() {
+        starts.push(start);
+        start += width + 1;
+    }
+
+    for row in rows {
+        let mut at = 0;
+        for (idx, ((_, piece), column)) in row.iter().zip(columns(row)).enumerate() {
+            if idx > 0 {
+                let to = column.map_or(at + 1, |column| starts[column].max(at + 1));
+                out.resize(out.len() + (to - at), separator);
+                at = to;
+            }
+            out.extend_from_slice(piece.as_bytes());
+            at += piece.chars().count();
+        }
+        out.push(NL_CHAR);
+    }
+}
+
+/// Appends the rendered `line`, split into physical lines of at most
+/// `width` bytes at field boundaries when a width is given. Each physical
+/// line but the last ends in the separator and a backslash.
+fn write_wrapped(out: &mut Vec<u8>, line: &[u8], width: Option<usize>, dialect: Dialect) {
+    let width = match width {
+        None => return out.extend_from_slice(line),
+        Some(width) => width,
+    };
+    let line = line.strip_suffix(&[NL_CHAR]).unwrap_or(line);
+    let mut pieces = Vec::new();
+    let (mut start, mut quoted, mut escaped) = (0, false, false);
+    for (idx, byte) in line.iter().enumerate() {
+        match *byte {
+            _ if escaped => escaped = false,
+            ESCAPE_CHAR if quoted => escaped = true,
+            byte if byte == dialect.quote => quoted = !quoted,
+            byte if byte == dialect.separator && !quoted => {
+                pieces.push(&line[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    pieces.push(&line[start..]);
+
+    let mut len = 0;
+    for (idx, piece) in pieces.iter().enumerate() {
+        if idx > 0 {
+            let reserve = if idx + 1 < pieces.len() { 2 } else { 0 };
+            if len + 1 + piece.len() + reserve > width {
+                out.extend_from_slice(&[dialect.separator, ESCAPE_CHAR, NL_CHAR]);
+                len = 0;
+            } else {
+                out.push(dialect.separator);
+                len += 1;
+            }
+        }
+        out.extend_from_slice(piece);
+        len += piece.len();
+    }
+    out.push(NL_CHAR);
+}
+
+/// The `c:crc32` line listing the checksum of each section by name.
+pub(crate) fn checksum_line(sections: &[(&str, u32)], dialect: Dialect) -> Vec<u8> {
+    let mut line = vec![CHECKSUM_ID, COLON_CHAR];
+    line.extend_from_slice(CHECKSUM_ALGORITHM.as_bytes());
+    for (name, crc) in sections {
+        write_field(&mut line, name, &format!("{:08X}", crc), dialect);
+    }
+    line.push(NL_CHAR);
+    line
+}
+
+/// Appends the separator and `name:value`, quoting and escaping the value
+/// as `write_value` does.
+fn write_field(out: &mut Vec<u8>, name: &str, value: &str, dialect: Dialect) {
+    write_field_value(out, name, Value::Str(value), dialect);
+}
+
+/// Appends the separator and `name:value` like `write_field`, a null as the
+/// bare null byte of the dialect.
+fn write_field_value(out: &mut Vec<u8>, name: &str, value: Value, dialect: Dialect) {
+    out.push(dialect.separator);
+    write_name(out, name, dialect);
+    out.push(COLON_CHAR);
+    match value {
+        Value::Null => out.push(dialect.null),
+        Value::Str(value) => write_value(out, value, dialect),
+    }
+}
+
+/// Appends `name`, quoted when it contains the separator or a colon.
+/// Names hold no quotes or line breaks, so they are never escaped.
+fn write_name(out: &mut Vec<u8>, name: &str, dialect: Dialect) {
+    let quote = name
+        .bytes()
+        .any(|byte| byte == dialect.separator || byte == COLON_CHAR);
+    if quote {
+        out.push(dialect.quote);
+    }
+    out.extend_from_slice(name.as_bytes());
+    if quote {
+        out.push(dialect.quote);
+    }
+}
+
+/// Appends `value`, quoted when it is empty, starts with a space, contains
+/// the separator or a colon, so that no space ever follows the colon
+/// unquoted, or is just the null byte, and quoted with escape sequences
+/// when it contains a newline, tab, backslash or the quote.
+pub(crate) fn write_value(out: &mut Vec<u8>, value: &str, dialect: Dialect) {
+    let escape = value.bytes().any(|byte| {
+        byte == dialect.quote || matches!(byte, ESCAPE_CHAR | CR_CHAR | NL_CHAR | TAB_CHAR)
+    });
+    let quote = escape
+        || value.is_empty()
+        || value.as_bytes()[0] == SPACE_CHAR
+        || value.as_bytes() == [dialect.null]
+        || value
+            .bytes()
+            .any(|byte| byte == dialect.separator || byte == COLON_CHAR);
+    if !quote {
+        out.extend_from_slice(value.as_bytes());
+        return;
+    }
+    out.push(dialect.quote);
+    if escape {
+        for byte in value.bytes() {
+            match byte {
+                NL_CHAR => out.extend_from_slice(b"\\n"),
+                CR_CHAR => out.extend_from_slice(b"\\r"),
+                TAB_CHAR => out.extend_from_slice(b"\\t"),
+                ESCAPE_CHAR => out.extend_from_slice(&[ESCAPE_CHAR, byte]),
+                byte if byte == dialect.quote => out.extend_from_slice(&[ESCAPE_CHAR, byte]),
+                _ => out.push(byte),
+            }
+        }
+    } else {
+        out.extend_from_slice(value.as_bytes());
+    }
+    out.push(dialect.quote);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::Reader;
+
+    /// A small file touching every line kind the writer emits by default.
+    fn write_sample() -> String {
+        let mut out = Vec::new();
+        let mut writer = Writer::new(&mut out);
+        writer
+            .add_table("users", &[("name", "str"), ("age", "i32")])
+            .unwrap();
+        writer.add_table("events", &[("kind", "str")]).unwrap();
+        writer
+            .add_style("bold", &[("weight", "700"), ("font", "Noto Sans")])
+            .unwrap();
+        writer
+            .add_record("users", "1", &[("name", "Anna Smith"), ("age", "31")])
+            .unwrap();
+        writer
+            .add_record("events", "1", &[("kind", "click")])
+            .unwrap();
+        writer
+            .add_record("users", "2", &[("name", "Bob"), ("age", "40")])
+            .unwrap();
+        writer.finish().unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn sample_is_written_byte_for_byte() {
+        assert_eq!(
+            write_sample(),
+            "v:xrave 3\n\
+             j:jumps users:167-49 events:216-15\n\
+             t:1 name:users pos:167 len:49 name:str age:i32\n\
+             t:2 name:events pos:216 len:15 kind:str\n\
+             s:bold weight:700 font:\"Noto Sans\"\n\
+             r:1 name:\"Anna Smith\" age:31\n\
+             r:2 name:Bob age:40\n\
+             r:1 kind:click\n"
+        );
+    }
+
+    #[test]
+    fn sample_reads_back() {
+        let path = std::env::temp_dir().join("xrave-test-writer-sample.xrv");
+        std::fs::write(&path, write_sample()).unwrap();
+        let mut reader = Reader::new(&path).unwrap();
+        let jumps: Vec<_> = reader
+            .jumps()
+            .map(|jump| (jump.name().to_owned(), jump.seek(), jump.len()))
+            .collect();
+        assert_eq!(
+            jumps,
+            [
+                ("users".to_owned(), 167, 49),
+                ("events".to_owned(), 216, 15)
+            ]
+        );
+        reader.load_headers().unwrap();
+        let tables: Vec<_> = reader.tables().keys().map(String::as_str).collect();
+        assert_eq!(tables, ["users", "events"]);
+        assert_eq!(reader.style("bold").unwrap().get("font"), Some("Noto Sans"));
+
+        let users: Vec<_> = reader
+            .records("users")
+            .unwrap()
+            .map(|record| {
+                let record = record.unwrap();
+                let values: Vec<String> = record.cols.into_iter().map(|col| col.value).collect();
+                (record.id, values)
+            })
+            .collect();
+        assert_eq!(
+            users,
+            [
+                (
+                    "1".to_owned(),
+                    vec!["Anna Smith".to_owned(), "31".to_owned()]
+                ),
+                ("2".to_owned(), vec!["Bob".to_owned(), "40".to_owned()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn align_columns_pads_fields_and_reads_back() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Reader, Writer, WriterOptions};
+
+        let path = std::env::temp_dir().join("xrave-test-align.xrv");
+        let options = WriterOptions {
+            align_columns: true,
+            ..WriterOptions::default()
+        };
+        let mut writer = Writer::with_options(std::fs::File::create(&path)?, options);
+        writer.add_table("users", &[("name", "str"), ("age", "int")])?;
+        writer.add_record("users", "1", &[("name", "Anna Smith"), ("age", "31")])?;
+        writer.add_record("users", "20", &[("name", "Bob"), ("age", "4")])?;
+        writer.finish()?;
+
+        let text = std::fs::read_to_string(&path)?;
+        assert!(text.contains("r:1  name:\"Anna Smith\" age:31\n"));
+        assert!(text.contains("r:20 name:Bob          age:4\n"));
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        let records = reader.records("users")?.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(records[0].cols[0].value, "Anna Smith");
+        assert_eq!(records[1].cols[1].value, "4");
+        Ok(())
+    }
+
+    #[test]
+    fn align_columns_lines_fields_up_by_name() -> Result<(), Box<dyn std::error::Error>> {
+        let options = WriterOptions {
+            align_columns: true,
+            ..WriterOptions::default()
+        };
+        let mut writer = Writer::with_options(Vec::new(), options);
+        writer.add_table(
+            "users",
+            &[("name", "str"), ("email", "str?"), ("age", "int")],
+        )?;
+        let fields = [("name", "Anna"), ("email", "a@example.org"), ("age", "31")];
+        writer.add_record("users", "1", &fields)?;
+        writer.add_record("users", "2", &[("name", "Bob"), ("age", "4")])?;
+        writer.add_record("users", "3", &[("age", "5")])?;
+        let text = String::from_utf8(writer.finish()?)?;
+        assert!(text.ends_with(
+            "r:1 name:Anna email:a@example.org age:31\n\
+             r:2 name:Bob                      age:4\n\
+             r:3                               age:5\n"
+        ));
+
+        let document = crate::Document::read(text.as_bytes())?;
+        let records = document.table("users").unwrap().records();
+        let records = records.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(records[1].cols[1].name.as_ref(), "age");
+        assert_eq!(records[1].cols[1].value, "4");
+        Ok(())
+    }
+
+    #[test]
+    fn max_line_width_splits_header_lines() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Document, Writer, WriterOptions, XRVErr};
+
+        let options = WriterOptions {
+            max_line_width: Some(24),
+            ..WriterOptions::default()
+        };
+        let mut out = Vec::new();
+        let mut writer = Writer::with_options(&mut out, options);
+        writer.add_table(
+            "users",
+            &[("name", "str"), ("email", "str?"), ("age", "i32")],
+        )?;
+        writer.add_record("users", "1", &[("name", "Anna Smith"), ("age", "31")])?;
+        writer.finish()?;
+
+        let text = String::from_utf8(out)?;
+        assert_eq!(
+            text,
+            "v:xrave 3\n\
+             j:jumps users:91-29\n\
+             t:1 name:users pos:91 \\\n\
+             len:29 name:str \\\n\
+             email:str? age:i32\n\
+             r:1 name:\"Anna Smith\" age:31\n",
+        );
+        let document = Document::read(text.as_bytes())?;
+        let table = document.table("users").unwrap();
+        assert_eq!(table.cols.len(), 3);
+        assert_eq!(table.record(0)?.unwrap().cols[1].value, "31");
+
+        let broken = "v:xrave 2\n\
+                      j:jumps\n\
+                      t:1 name:users pos:0 len:0 \\\n\
+                      name:str \\\n\
+                      age:i32:x\n";
+        assert!(matches!(
+            Document::read(broken.as_bytes()),
+            Err(XRVErr::AtLine {
+                line: 5,
+                col: 8,
+                ..
+            })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn paranoid_writer_rejects_bad_names_and_ids() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Document, Writer, WriterOptions, XRVErr};
+
+        let options = WriterOptions {
+            paranoid: true,
+            ..WriterOptions::default()
+        };
+        let mut writer = Writer::with_options(Vec::new(), options);
+        writer.add_table("notes", &[("text", "str"), ("by:who", "str")])?;
+        writer.add_record(
+            "notes",
+            "1",
+            &[("text", "line\nbreak \"quoted\" \\"), ("by:who", "")],
+        )?;
+        writer.add_style("bold", &[("weight", "700")])?;
+
+        let name_byte = |result: Result<(), XRVErr>| match result {
+            Err(XRVErr::InvalidNameByte { byte, name }) => Some((byte, name)),
+            _ => None,
+        };
+        let column = |name: &str| Writer::new(Vec::new()).add_table("t", &[(name, "str")]);
+        assert_eq!(name_byte(column("a\nb")), Some((b'\n', "a\nb".to_owned())));
+        assert_eq!(name_byte(column("a\"b")), Some((b'"', "a\"b".to_owned())));
+        assert!(matches!(column(""), Err(XRVErr::InvalidIdentifier(_))));
+        assert_eq!(
+            name_byte(writer.add_table("a\rb", &[])),
+            Some((b'\r', "a\rb".to_owned()))
+        );
+        assert_eq!(
+            name_byte(writer.add_record("notes", "2", &[("x\ty", "1")])),
+            Some((b'\t', "x\ty".to_owned()))
+        );
+        assert_eq!(
+            name_byte(writer.add_style("s", &[("\"", "1")])),
+            Some((b'"', "\"".to_owned()))
+        );
+        assert!(matches!(
+            writer.add_record("notes", "a\nb", &[]),
+            Err(XRVErr::InvalidValueByte { byte: b'\n', ref field }) if field == "id"
+        ));
+
+        let out = writer.finish()?;
+        let document = Document::read(&out[..])?;
+        let records = document
+            .table("notes")
+            .unwrap()
+            .records()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].cols[0].value, "line\nbreak \"quoted\" \\");
+        Ok(())
+    }
+
+    /// A line no escaper should render: the quote of its value is never
+    /// closed.
+    const BROKEN_LINE: &[u8] = b"r:1 text:\"open";
+
+    #[test]
+    fn paranoid_mode_fails_on_a_line_readers_cannot_read() {
+        let paranoid = WriterOptions {
+            paranoid: true,
+            ..WriterOptions::default()
+        };
+        assert!(checks_lines(&paranoid));
+        assert!(matches!(
+            check_line(BROKEN_LINE, &paranoid),
+            Err(XRVErr::AtLine { line: 1, .. })
+        ));
+        assert!(check_line(b"r:1 text:\"closed\"", &paranoid).is_ok());
+
+        // Left off, release builds do not check lines at all.
+        let options = WriterOptions::default();
+        assert_eq!(checks_lines(&options), cfg!(debug_assertions));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "rendered a line readers cannot read")]
+    fn debug_builds_panic_on_a_line_readers_cannot_read() {
+        let _ = check_line(BROKEN_LINE, &WriterOptions::default());
+    }
+
+    #[test]
+    fn add_table_quotes_names_with_separators() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Reader, Writer};
+
+        let path = std::env::temp_dir().join("xrave-test-quoted-names.xrv");
+        let mut writer = Writer::new(std::fs::File::create(&path)?);
+        writer.add_table("new hires", &[("first name", "str"), ("start:utc", "str?")])?;
+        writer.add_record(
+            "new hires",
+            "1",
+            &[("first name", "Anna"), ("start:utc", "09:00")],
+        )?;
+        writer.finish()?;
+
+        let text = std::fs::read_to_string(&path)?;
+        assert!(text.contains(" \"new hires\":"));
+        assert!(text.contains("\nr:1 \"first name\":Anna \"start:utc\":\"09:00\"\n"));
+
+        let mut reader = Reader::new(path.to_string_lossy().into_owned())?;
+        reader.load_headers()?;
+        let cols: Vec<_> = reader
+            .table("new hires")
+            .unwrap()
+            .cols
+            .iter()
+            .map(|c| c.name.to_string())
+            .collect();
+        assert_eq!(cols, ["first name", "start:utc"]);
+        let record = reader.records("new hires")?.next().unwrap()?;
+        let fields: Vec<_> = record
+            .cols
+            .iter()
+            .map(|f| (&*f.name, f.value.as_str()))
+            .collect();
+        assert_eq!(fields, [("first name", "Anna"), ("start:utc", "09:00")]);
+        Ok(())
+    }
+}